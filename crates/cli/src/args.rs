@@ -30,6 +30,16 @@ pub struct Args {
     #[arg(long, allow_negative_numbers = true, help_heading = "Content Options", num_args(0..))]
     pub timestamps: Option<Vec<String>>,
 
+    /// Collect the blocks listed in a column of a parquet file,
+    /// as FILE[:COLUMN] [default column: block_number]
+    #[arg(
+        long,
+        value_name = "FILE:COLUMN",
+        help_heading = "Content Options",
+        verbatim_doc_comment
+    )]
+    pub blocks_from: Option<String>,
+
     /// Transaction hashes, see syntax below
     #[arg(
         short,
@@ -39,6 +49,13 @@ pub struct Args {
     )]
     pub txs: Option<Vec<String>>,
 
+    /// Collect only the chunks that errored in a prior run, given its report
+    /// as a path, http(s) url, or s3://BUCKET/KEY url,
+    /// other arguments default to those of the prior run.
+    /// s3 requests are not signed, so s3 reports must be publicly readable
+    #[arg(long, value_name = "REPORT", help_heading = "Content Options", verbatim_doc_comment)]
+    pub retry_report: Option<String>,
+
     /// Align chunk boundaries to regular intervals,
     /// e.g. (1000 2000 3000), not (1106 2106 3106)
     #[arg(short, long, help_heading = "Content Options", verbatim_doc_comment)]
@@ -69,6 +86,37 @@ pub struct Args {
     #[arg(long, value_name="COLS", num_args(0..), verbatim_doc_comment, help_heading="Content Options")]
     pub columns: Option<Vec<String>>,
 
+    /// Rename output columns, e.g. contract_address=emitter
+    #[arg(long, value_name = "COLUMN=ALIAS", num_args(1..), help_heading = "Content Options")]
+    pub column_alias: Option<Vec<String>>,
+
+    /// Omit topic columns that are null in every row of a file
+    #[arg(long, help_heading = "Content Options")]
+    pub compact_topics: bool,
+
+    /// Store topics missing from a log as 32 zero bytes rather than null
+    #[arg(long, help_heading = "Content Options")]
+    pub zero_hash_topics: bool,
+
+    /// Store topic0-3 as 0x-prefixed hex strings, other binary columns
+    /// are unchanged
+    #[arg(long, help_heading = "Content Options", verbatim_doc_comment)]
+    pub topics_as_hex: bool,
+
+    /// Add a UInt32 block_bucket column of block_number / SIZE,
+    /// an integer partition key for downstream engines
+    #[arg(long, value_name = "SIZE", help_heading = "Content Options", verbatim_doc_comment)]
+    pub block_bucket_size: Option<u64>,
+
+    /// Add a UInt64 row_id column numbering rows across all
+    /// chunks of the run in block order
+    #[arg(long, help_heading = "Content Options", verbatim_doc_comment)]
+    pub row_id: bool,
+
+    /// Omit the chain_id column, for outputs that only ever hold one chain
+    #[arg(long, help_heading = "Content Options")]
+    pub no_chain_id: bool,
+
     /// Set output datatype(s) of U256 integers
     /// [default: binary, string, f64]
     #[arg(long, num_args(1..), help_heading = "Content Options", verbatim_doc_comment)]
@@ -90,30 +138,132 @@ pub struct Args {
     #[arg(short, long, help_heading = "Source Options")]
     pub rpc: Option<String>,
 
+    /// Collect logs from both --rpc and this RPC, writing logs
+    /// returned by only one of them to logs_diff files
+    #[arg(long, value_name = "RPC", help_heading = "Source Options", verbatim_doc_comment)]
+    pub compare_rpc: Option<String>,
+
+    /// Extra HTTP header(s) sent with every RPC request
+    #[arg(long, value_name = "NAME:VALUE", help_heading = "Source Options", num_args(1..))]
+    pub rpc_header: Option<Vec<String>>,
+
+    /// Bearer token sent as Authorization header with every RPC request
+    #[arg(long, value_name = "TOKEN", help_heading = "Source Options")]
+    pub rpc_bearer: Option<String>,
+
+    /// Basic auth credentials sent as Authorization header with every RPC request
+    #[arg(long, value_name = "USER:PASS", help_heading = "Source Options")]
+    pub rpc_basic_auth: Option<String>,
+
+    /// Extra fields added to eth_getLogs params, as a JSON object,
+    /// support depends on provider (e.g. '{"limit": 1000}')
+    #[arg(long, value_name = "JSON", help_heading = "Source Options", verbatim_doc_comment)]
+    pub log_filter_params: Option<String>,
+
+    /// Split the block range of eth_getLogs requests in half when
+    /// the provider reports too many results, down to single blocks
+    #[arg(long, help_heading = "Source Options", verbatim_doc_comment)]
+    pub bisect_log_ranges: bool,
+
+    /// Record block ranges whose logs a pruned node no longer serves
+    /// as unavailable and continue, rather than failing their chunks.
+    /// chunks with unavailable blocks are not written, so reruns collect them
+    #[arg(long, help_heading = "Source Options", verbatim_doc_comment)]
+    pub skip_unavailable_blocks: bool,
+
+    /// Error messages marking blocks unavailable under --skip-unavailable-blocks,
+    /// matched case-insensitively [default: missing trie node, pruned, historical state]
+    #[arg(
+        long,
+        value_name = "PATTERN",
+        help_heading = "Source Options",
+        num_args(1..),
+        verbatim_doc_comment
+    )]
+    pub unavailable_error_patterns: Option<Vec<String>>,
+
     /// Network name [default: name of eth_getChainId]
     #[arg(long, help_heading = "Source Options")]
     pub network_name: Option<String>,
 
+    /// Collect each chain into {output_dir}/{NAME}/, as NAME=RPC,
+    /// a lone NAME is used as the rpc (e.g. a MESC endpoint)
+    #[arg(long, value_name = "NAME=RPC", help_heading = "Source Options", num_args(1..), verbatim_doc_comment)]
+    pub chains: Option<Vec<String>>,
+
     /// Ratelimit on requests per second
     #[arg(short('l'), long, value_name = "limit", help_heading = "Acquisition Options")]
     pub requests_per_second: Option<u32>,
 
+    /// Ratelimit on blocks collected per second
+    #[arg(long, value_name = "limit", help_heading = "Acquisition Options")]
+    pub blocks_per_second: Option<u64>,
+
     /// Max retries for provider errors
     #[arg(long, default_value_t = 5, value_name = "R", help_heading = "Acquisition Options")]
     pub max_retries: u32,
 
+    /// Max retries across all requests of the run, including retries of
+    /// timeouts and of log ranges split by --bisect-log-ranges [default: no limit]
+    #[arg(long, value_name = "R", help_heading = "Acquisition Options", verbatim_doc_comment)]
+    pub max_total_retries: Option<u64>,
+
     /// Initial retry backoff time (ms)
     #[arg(long, default_value_t = 500, value_name = "B", help_heading = "Acquisition Options")]
     pub initial_backoff: u64,
 
+    /// Stop starting new chunks after this much wall-clock time,
+    /// e.g. 90s, 30m, 2h, in-flight chunks finish
+    #[arg(
+        long,
+        value_name = "DURATION",
+        help_heading = "Acquisition Options",
+        verbatim_doc_comment
+    )]
+    pub max_duration: Option<String>,
+
+    /// Abort the run after this many consecutive chunk failures,
+    /// any successful chunk resets the count
+    #[arg(long, value_name = "N", help_heading = "Acquisition Options", verbatim_doc_comment)]
+    pub max_consecutive_failures: Option<u64>,
+
+    /// Warn about chunks taking longer than MULTIPLE times the mean
+    /// of recent chunks, flagged chunks are listed in the report
+    #[arg(
+        long,
+        value_name = "MULTIPLE",
+        help_heading = "Acquisition Options",
+        verbatim_doc_comment
+    )]
+    pub warn_if_slow: Option<f64>,
+
+    /// Abort the run on the first chunk failure, cancelling in-flight
+    /// chunks, and exit nonzero after writing the report
+    #[arg(long, help_heading = "Acquisition Options", verbatim_doc_comment)]
+    pub fail_fast: bool,
+
     /// Global number of concurrent requests
     #[arg(long, value_name = "M", help_heading = "Acquisition Options")]
     pub max_concurrent_requests: Option<u64>,
 
+    /// Seconds to reduce concurrency for after the provider rate limits a request
+    #[arg(long, default_value_t = 30, value_name = "S", help_heading = "Acquisition Options")]
+    pub throttle_cooldown: u64,
+
     /// Number of chunks processed concurrently
     #[arg(long, value_name = "M", help_heading = "Acquisition Options")]
     pub max_concurrent_chunks: Option<u64>,
 
+    /// Number of collected chunks written concurrently, chunks being
+    /// written no longer count against --max-concurrent-chunks
+    #[arg(long, value_name = "N", help_heading = "Acquisition Options", verbatim_doc_comment)]
+    pub write_concurrency: Option<usize>,
+
+    /// Pause new chunks while this much collected data awaits writing,
+    /// e.g. 512MB or 2GB, each concurrent chunk can overshoot by one chunk
+    #[arg(long, value_name = "SIZE", help_heading = "Acquisition Options", verbatim_doc_comment)]
+    pub max_in_flight_bytes: Option<String>,
+
     /// Chunk collection order (normal, reverse, random)
     #[arg(long, help_heading = "Acquisition Options")]
     pub chunk_order: Option<String>,
@@ -122,6 +272,41 @@ pub struct Args {
     #[arg(short, long, help_heading = "Acquisition Options")]
     pub dry: bool,
 
+    /// Print resolved schemas as JSON and exit
+    #[arg(long, help_heading = "Acquisition Options")]
+    pub print_schema: bool,
+
+    /// Print a CREATE TABLE statement per datatype and exit,
+    /// DIALECT is postgres, bigquery, or clickhouse
+    #[arg(
+        long,
+        value_name = "DIALECT",
+        help_heading = "Acquisition Options",
+        verbatim_doc_comment
+    )]
+    pub print_ddl: Option<String>,
+
+    /// Write a zero-row file per datatype with the columns of a real run and exit,
+    /// named {network}__{datatype}__schema.{format}
+    #[arg(long, help_heading = "Output Options", verbatim_doc_comment)]
+    pub schema_only: bool,
+
+    /// Exit with an error if the run collects zero rows,
+    /// which often means a misconfigured address or topic filter
+    #[arg(long, help_heading = "Output Options", verbatim_doc_comment)]
+    pub fail_fast_on_empty: bool,
+
+    /// Directory for in-progress files, each run writes to its own
+    /// subdirectory that is removed when the run ends or is interrupted
+    /// [default: OUTPUT_DIR/.cryo/tmp]
+    #[arg(long, value_name = "DIR", help_heading = "Output Options", verbatim_doc_comment)]
+    pub temp_dir: Option<String>,
+
+    /// Write a detached signature beside each output file,
+    /// using the hex private key stored in PATH
+    #[arg(long, value_name = "PATH", help_heading = "Output Options", verbatim_doc_comment)]
+    pub sign_key_file: Option<String>,
+
     /// Remember current command for future use
     #[arg(long)]
     pub remember: bool,
@@ -146,8 +331,16 @@ pub struct Args {
     #[arg(long, help_heading = "Output Options")]
     pub partition_by: Option<Vec<String>>,
 
-    /// Directory for output files
-    #[arg(short, long, default_value = ".", help_heading = "Output Options")]
+    /// Directory for output files, or - to stream a single
+    /// datatype to stdout as csv or json lines
+    #[arg(
+        short,
+        long,
+        visible_alias = "output",
+        default_value = ".",
+        help_heading = "Output Options",
+        verbatim_doc_comment
+    )]
     pub output_dir: String,
 
     /// Subdirectories for output files
@@ -163,6 +356,11 @@ pub struct Args {
     #[arg(long, help_heading = "Output Options")]
     pub overwrite: bool,
 
+    /// Collect only the block ranges missing from existing files,
+    /// read from the block range in each file name
+    #[arg(long, help_heading = "Output Options", verbatim_doc_comment)]
+    pub fill_gaps: bool,
+
     /// Save as csv instead of parquet
     #[arg(long, help_heading = "Output Options")]
     pub csv: bool,
@@ -171,6 +369,11 @@ pub struct Args {
     #[arg(long, help_heading = "Output Options")]
     pub json: bool,
 
+    /// Render integer columns scaled down by 10^N in csv and json,
+    /// e.g. value=18, parquet output keeps the raw values
+    #[arg(long, value_name = "COLUMN=N", num_args(1..), help_heading = "Output Options", verbatim_doc_comment)]
+    pub decimals: Option<Vec<String>>,
+
     /// Number of rows per row group in parquet file
     #[arg(long, value_name = "GROUP_SIZE", help_heading = "Output Options")]
     pub row_group_size: Option<usize>,
@@ -192,14 +395,139 @@ pub struct Args {
     #[arg(long, help_heading = "Output Options", verbatim_doc_comment)]
     pub report_dir: Option<PathBuf>,
 
+    /// Number of most recent summary reports to keep
+    #[arg(long, help_heading = "Output Options", value_name = "N")]
+    pub report_retention: Option<usize>,
+
+    /// Update the incomplete summary report during the run, every N
+    /// finished chunks, or after a duration such as 90s or 10m
+    #[arg(long, help_heading = "Output Options", value_name = "N|DURATION", verbatim_doc_comment)]
+    pub report_interval: Option<String>,
+
+    /// Merge all chunks into one file per datatype, named by the
+    /// overall block range, chunks are spooled to disk until the end
+    #[arg(long, help_heading = "Output Options", verbatim_doc_comment)]
+    pub single_file: bool,
+
+    /// Write {prefix}__block_index.json mapping block ranges to the
+    /// files containing them, once every chunk of the run completes
+    #[arg(long, help_heading = "Output Options", verbatim_doc_comment)]
+    pub block_index: bool,
+
+    /// Write the block range, addresses, and topics of each parquet
+    /// file into its key-value metadata under cryo_filter
+    #[arg(long, help_heading = "Output Options", verbatim_doc_comment)]
+    pub embed_filter: bool,
+
+    /// Write an empty _SUCCESS file into the output directory once
+    /// every chunk of the run completes, for Spark and Hadoop readers
+    #[arg(long, help_heading = "Output Options", verbatim_doc_comment)]
+    pub success_marker: bool,
+
+    /// Write a .count file holding the number of rows beside each
+    /// output file, readable without opening the file
+    #[arg(long, help_heading = "Output Options", verbatim_doc_comment)]
+    pub row_count_sidecar: bool,
+
+    /// Route rows into date=YYYY-MM-DD subdirectories by UTC day,
+    /// requires a block_timestamp (logs) or timestamp column
+    #[arg(long, help_heading = "Output Options", verbatim_doc_comment)]
+    pub split_by_day: bool,
+
+    /// Write files into Hive-style block_number_bucket=<n>/ directories,
+    /// where n is block_number / N, the bucket column is not stored in files
+    #[arg(long, help_heading = "Output Options", value_name = "N", verbatim_doc_comment)]
+    pub hive_bucket_size: Option<u64>,
+
+    /// Write files of about SIZE each, e.g. 128MB, instead of one file per chunk,
+    /// merging sparse chunks and splitting dense ones into contiguous block ranges
+    /// that name each file and by which existing output is found
+    #[arg(long, help_heading = "Output Options", value_name = "SIZE", verbatim_doc_comment)]
+    pub target_partition_bytes: Option<String>,
+
+    /// Zero-pad block numbers in file names to N digits,
+    /// existing files are found at any width [default: 8]
+    #[arg(long, help_heading = "Output Options", value_name = "N", verbatim_doc_comment)]
+    pub block_label_width: Option<usize>,
+
     /// Avoid saving a summary report
     #[arg(long, help_heading = "Output Options")]
     pub no_report: bool,
 
+    /// Shell command run once the run finishes, e.g. to start downstream
+    /// jobs, gets the report path as $1 and in CRYO_REPORT_PATH, and
+    /// CRYO_OUTPUT_DIR and CRYO_RUN_STATUS, runs with your privileges
+    /// so only use trusted commands, never loaded from reports or
+    /// remembered commands
+    #[arg(long, value_name = "COMMAND", help_heading = "Output Options", verbatim_doc_comment)]
+    pub post_run_hook: Option<String>,
+
+    /// Run --post-run-hook even if some chunks did not complete
+    #[arg(long, help_heading = "Output Options")]
+    pub post_run_hook_always: bool,
+
+    /// Exit with an error if --post-run-hook fails
+    #[arg(long, help_heading = "Output Options")]
+    pub post_run_hook_strict: bool,
+
+    /// Size that cryo compact fills each merged file up to,
+    /// e.g. 512MB or 1GB [default: 256MiB]
+    #[arg(long, value_name = "SIZE", help_heading = "Output Options", verbatim_doc_comment)]
+    pub target_file_size: Option<String>,
+
     /// Address(es)
     #[arg(long, help_heading = "Dataset-specific Options", num_args(1..))]
     pub address: Option<Vec<String>>,
 
+    /// Collect logs of the addresses in a text file, one per line,
+    /// with or without 0x, text after # is a comment
+    #[arg(
+        long,
+        value_name = "PATH",
+        help_heading = "Dataset-specific Options",
+        verbatim_doc_comment
+    )]
+    pub address_file: Option<String>,
+
+    /// Number of --address-file addresses filtered by each
+    /// eth_getLogs request [default: 1000]
+    #[arg(long, value_name = "N", help_heading = "Dataset-specific Options", verbatim_doc_comment)]
+    pub addresses_per_request: Option<usize>,
+
+    /// Collect logs of the addresses deployed by a factory,
+    /// found in the factory's --factory-event logs
+    #[arg(
+        long,
+        value_name = "ADDRESS",
+        help_heading = "Dataset-specific Options",
+        verbatim_doc_comment
+    )]
+    pub factory: Option<String>,
+
+    /// Creation event emitted by --factory,
+    /// e.g. "PoolCreated(address indexed token0, address indexed token1, address pool)"
+    #[arg(
+        long,
+        value_name = "SIG",
+        help_heading = "Dataset-specific Options",
+        verbatim_doc_comment
+    )]
+    pub factory_event: Option<String>,
+
+    /// Parameter of --factory-event holding the deployed address
+    #[arg(long, value_name = "NAME", help_heading = "Dataset-specific Options")]
+    pub factory_address_arg: Option<String>,
+
+    /// First block searched for --factory deployments
+    /// [default: creation block of factory]
+    #[arg(
+        long,
+        value_name = "BLOCK",
+        help_heading = "Dataset-specific Options",
+        verbatim_doc_comment
+    )]
+    pub factory_start_block: Option<u64>,
+
     /// To Address(es)
     #[arg(long, help_heading = "Dataset-specific Options", value_name="address", num_args(1..))]
     pub to_address: Option<Vec<String>>,
@@ -228,6 +556,14 @@ pub struct Args {
     #[arg(long, help_heading = "Dataset-specific Options", num_args(1..))]
     pub contract: Option<Vec<String>>,
 
+    /// Skip blocks before the creation of --address contract(s)
+    #[arg(long, help_heading = "Dataset-specific Options")]
+    pub contract_creation_block: bool,
+
+    /// Accept mixed-case addresses that fail their EIP-55 checksum
+    #[arg(long, help_heading = "Dataset-specific Options")]
+    pub no_checksum_check: bool,
+
     /// Topic0(s)
     #[arg(long, visible_alias = "event", help_heading = "Dataset-specific Options", num_args(1..))]
     pub topic0: Option<Vec<String>>,
@@ -244,10 +580,199 @@ pub struct Args {
     #[arg(long, help_heading = "Dataset-specific Options", num_args(1..))]
     pub topic3: Option<Vec<String>>,
 
+    /// Collect logs with this value in any of topic1, topic2, or topic3,
+    /// uses one request per position, addresses are left-padded to 32 bytes
+    #[arg(
+        long,
+        value_name = "TOPIC",
+        help_heading = "Dataset-specific Options",
+        verbatim_doc_comment
+    )]
+    pub any_topic: Option<String>,
+
     /// Event signature for log decoding
     #[arg(long, value_name = "SIG", help_heading = "Dataset-specific Options", num_args(1..))]
     pub event_signature: Option<String>,
 
+    /// Event signature for logs in a block range, as START:END=SIG,
+    /// END is exclusive and may be omitted, ranges may not overlap,
+    /// logs outside of every range use --event-signature or are dropped
+    #[arg(
+        long,
+        value_name = "RANGE=SIG",
+        help_heading = "Dataset-specific Options",
+        num_args(1..),
+        verbatim_doc_comment
+    )]
+    pub event_signature_range: Option<Vec<String>>,
+
+    /// Skip decoding logs, output raw log columns even if an event signature is given
+    #[arg(long, help_heading = "Dataset-specific Options")]
+    pub no_decode: bool,
+
+    /// Keep raw topic1-3 and data columns beside the event__ columns of decoded logs
+    #[arg(long, help_heading = "Dataset-specific Options")]
+    pub keep_raw_log_columns: bool,
+
+    /// Store the decoded params of each log as one JSON decoded column
+    /// instead of event__ columns, logs that do not decode are kept as null
+    #[arg(long, help_heading = "Dataset-specific Options", verbatim_doc_comment)]
+    pub decoded_json: bool,
+
+    /// Keep only the first or last log of each block by log_index,
+    /// WHICH is first or last
+    #[arg(
+        long,
+        value_name = "WHICH",
+        help_heading = "Dataset-specific Options",
+        verbatim_doc_comment
+    )]
+    pub per_block: Option<String>,
+
+    /// Only keep logs of the event given by --event-signature or
+    /// --event-signature-range, dropping logs of other events
+    #[arg(long, help_heading = "Dataset-specific Options", verbatim_doc_comment)]
+    pub abi_events_only: bool,
+
+    /// Decode logs on all cores in chunks of N logs,
+    /// speeding up partitions with very many logs
+    #[arg(long, value_name = "N", help_heading = "Dataset-specific Options", verbatim_doc_comment)]
+    pub decode_chunk_size: Option<usize>,
+
+    /// Keep only logs whose 32-byte data word at OFFSET compares to VALUE,
+    /// OP is one of eq, ne, gt, gte, lt, lte (e.g. --log-data-filter 0:gt:1000)
+    #[arg(
+        long,
+        value_name = "OFFSET:OP:VALUE",
+        help_heading = "Dataset-specific Options",
+        verbatim_doc_comment,
+        num_args(1..)
+    )]
+    pub log_data_filter: Option<Vec<String>>,
+
+    /// Names for topic0 values, used to fill the event_name column
+    /// (e.g. --topic0-labels 0xddf2...=Transfer)
+    #[arg(
+        long,
+        value_name = "TOPIC0=NAME",
+        help_heading = "Dataset-specific Options",
+        verbatim_doc_comment,
+        num_args(1..)
+    )]
+    pub topic0_labels: Option<Vec<String>>,
+
+    /// Keep only logs whose event name matches REGEX in full, named by the
+    /// decoded event or else by --topic0-labels (e.g. --event-name-regex '.*Swap.*')
+    #[arg(
+        long,
+        value_name = "REGEX",
+        help_heading = "Dataset-specific Options",
+        verbatim_doc_comment
+    )]
+    pub event_name_regex: Option<String>,
+
+    /// Keep a reproducible FRACTION of logs, chosen by hashing
+    /// block_number and log_index with --seed
+    #[arg(
+        long,
+        value_name = "FRACTION",
+        help_heading = "Dataset-specific Options",
+        verbatim_doc_comment
+    )]
+    pub sample_fraction: Option<f64>,
+
+    /// Seed of the --sample-fraction hash [default: 0]
+    #[arg(long, value_name = "SEED", help_heading = "Dataset-specific Options")]
+    pub seed: Option<u64>,
+
+    /// Collect logs whose topic0 is any of these, requested together in one
+    /// filter, given as event signatures or topic0 hashes. Logs matching
+    /// --exclude-topic0 are still dropped after fetching
+    #[arg(
+        long,
+        value_name = "SIG_OR_TOPIC0",
+        help_heading = "Dataset-specific Options",
+        verbatim_doc_comment,
+        num_args(1..)
+    )]
+    pub include_topic0: Option<Vec<String>>,
+
+    /// Drop logs whose topic0 is one of these, given as event signatures or topic0 hashes
+    /// (e.g. --exclude-topic0 "Approval(address indexed,address indexed,uint256)")
+    #[arg(
+        long,
+        value_name = "SIG_OR_TOPIC0",
+        help_heading = "Dataset-specific Options",
+        verbatim_doc_comment,
+        num_args(1..)
+    )]
+    pub exclude_topic0: Option<Vec<String>>,
+
+    /// Also write a logs_per_block file with the number of logs in each block
+    #[arg(long, help_heading = "Dataset-specific Options")]
+    pub logs_per_block: bool,
+
+    /// Maximum number of logs kept per address across the run
+    #[arg(long, value_name = "N", help_heading = "Dataset-specific Options")]
+    pub max_logs_per_address: Option<u64>,
+
+    /// Only keep logs of transactions at these indices within their block,
+    /// as START:END, END is exclusive and may be omitted
+    #[arg(
+        long,
+        value_name = "START:END",
+        help_heading = "Dataset-specific Options",
+        verbatim_doc_comment
+    )]
+    pub tx_index_range: Option<String>,
+
+    /// Handling of logs with null block_number, transaction_hash,
+    /// transaction_index, or log_index: drop or error [default: drop]
+    #[arg(
+        long,
+        value_name = "POLICY",
+        help_heading = "Dataset-specific Options",
+        verbatim_doc_comment
+    )]
+    pub null_log_policy: Option<String>,
+
+    /// Maximum number of distinct decoded event columns,
+    /// further columns are dropped with a warning [default: 256]
+    #[arg(long, value_name = "N", help_heading = "Dataset-specific Options", verbatim_doc_comment)]
+    pub max_decoded_columns: Option<usize>,
+
+    /// Write log chunks larger than N rows to parquet in batches of N rows,
+    /// requesting the chunk's block ranges one at a time so that only one
+    /// response and one batch per chunk are held in memory
+    #[arg(long, value_name = "N", help_heading = "Dataset-specific Options", verbatim_doc_comment)]
+    pub log_batch_size: Option<usize>,
+
+    /// Decode existing raw log files instead of collecting,
+    /// requires --event-signature
+    #[arg(
+        long,
+        value_name = "PATHS",
+        help_heading = "Dataset-specific Options",
+        verbatim_doc_comment,
+        num_args(1..)
+    )]
+    pub decode_only: Option<Vec<String>>,
+
+    /// Dump raw eth_getLogs responses as json before transformation,
+    /// stored under {report_dir}/log_responses for use with --replay
+    #[arg(long, help_heading = "Dataset-specific Options", verbatim_doc_comment)]
+    pub dump_log_responses: bool,
+
+    /// Transform log responses dumped by --dump-log-responses
+    /// instead of collecting, without connecting to a node
+    #[arg(
+        long,
+        value_name = "DIR",
+        help_heading = "Dataset-specific Options",
+        verbatim_doc_comment
+    )]
+    pub replay: Option<String>,
+
     /// Blocks per request (eth_getLogs)
     #[arg(
         long,
@@ -263,6 +788,43 @@ pub struct Args {
 }
 
 impl Args {
+    /// copy of args with RPC credentials replaced, for use in reports and logs
+    pub(crate) fn redacted(&self) -> Args {
+        let redact = |_: &String| REDACTED.to_string();
+        Args {
+            rpc_header: self
+                .rpc_header
+                .as_ref()
+                .map(|headers| headers.iter().map(redact).collect()),
+            rpc_bearer: self.rpc_bearer.as_ref().map(redact),
+            rpc_basic_auth: self.rpc_basic_auth.as_ref().map(redact),
+            post_run_hook: self.post_run_hook.as_ref().map(redact),
+            ..self.clone()
+        }
+    }
+
+    /// whether args hold RPC credentials that were redacted, e.g. args read from a report
+    pub(crate) fn has_redacted_credentials(&self) -> bool {
+        let is_redacted = |value: &String| value == REDACTED;
+        self.rpc_header.as_ref().map_or(false, |headers| headers.iter().any(is_redacted)) ||
+            self.rpc_bearer.as_ref().map_or(false, is_redacted) ||
+            self.rpc_basic_auth.as_ref().map_or(false, is_redacted)
+    }
+
+    /// whether rows are streamed to stdout instead of written to files, with `--output -`
+    pub(crate) fn stdout_output(&self) -> bool {
+        self.output_dir == "-"
+    }
+
+    /// directory holding output files and the .cryo directory, the working directory when
+    /// streaming to stdout
+    pub(crate) fn output_dir_path(&self) -> PathBuf {
+        match self.stdout_output() {
+            true => PathBuf::from("."),
+            false => PathBuf::from(&self.output_dir),
+        }
+    }
+
     pub(crate) fn merge_with_precedence(self, other: Args) -> Self {
         let default_struct = Args::default();
 
@@ -286,6 +848,32 @@ impl Args {
     }
 }
 
+const REDACTED: &str = "<redacted>";
+
+const SENSITIVE_FLAGS: [&str; 3] = ["--rpc-header", "--rpc-bearer", "--rpc-basic-auth"];
+
+/// replace values of RPC credential flags in a raw command line
+pub(crate) fn redact_cli_command(command: Vec<String>) -> Vec<String> {
+    let mut redacted = Vec::new();
+    let mut in_sensitive = false;
+    for word in command.into_iter() {
+        if word.starts_with('-') {
+            in_sensitive = SENSITIVE_FLAGS.contains(&word.as_str());
+            match word.split_once('=') {
+                Some((flag, _)) if SENSITIVE_FLAGS.contains(&flag) => {
+                    redacted.push(format!("{}={}", flag, REDACTED))
+                }
+                _ => redacted.push(word),
+            }
+        } else if in_sensitive {
+            redacted.push(REDACTED.to_string());
+        } else {
+            redacted.push(word);
+        }
+    }
+    redacted
+}
+
 pub(crate) fn get_styles() -> clap_cryo::builder::Styles {
     let white = anstyle::Color::Rgb(anstyle::RgbColor(255, 255, 255));
     let green = anstyle::Color::Rgb(anstyle::RgbColor(0, 225, 0));
@@ -318,7 +906,11 @@ fn get_after_str() -> String {
       <white><bold>cryo help</bold></white>"#
     );
     let post_subcommands = " <DATASET(S)>         display info about a dataset";
-    format!("{}{}{}", header, subcommands, post_subcommands)
+    let compact = cstr!(
+        r#"
+      <white><bold>cryo compact</bold></white> <DATASET(S)>      merge small output files into larger ones"#
+    );
+    format!("{}{}{}{}", header, subcommands, post_subcommands, compact)
 }
 
 fn get_datatype_help() -> &'static str {
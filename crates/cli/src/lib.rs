@@ -10,6 +10,8 @@
 mod args;
 mod parse;
 mod remember;
+mod report_storage;
+mod retry;
 mod run;
 
 // used in main.rs but not lib.rs
@@ -5,6 +5,8 @@ use clap_cryo::Parser;
 mod args;
 mod parse;
 mod remember;
+mod report_storage;
+mod retry;
 mod run;
 
 pub use args::Args;
@@ -15,7 +17,17 @@ use eyre::Result;
 #[allow(clippy::needless_return)]
 async fn main() -> Result<()> {
     let args = Args::parse();
-    match run::run(args).await {
+
+    // an interrupted run is dropped before exiting, which removes its temp directory
+    let result = tokio::select! {
+        result = run::run(args) => result,
+        _ = tokio::signal::ctrl_c() => {
+            eprintln!("interrupted, removed in-progress files of run");
+            std::process::exit(130)
+        }
+    };
+
+    match result {
         Ok(Some(freeze_summary)) if freeze_summary.errored.is_empty() => Ok(()),
         Ok(Some(_freeze_summary)) => std::process::exit(1),
         Ok(None) => Ok(()),
@@ -29,7 +41,7 @@ async fn main() -> Result<()> {
             // handle release build
             #[cfg(not(debug_assertions))]
             {
-                println!("{}", e);
+                eprintln!("{}", e);
                 std::process::exit(1);
             }
         }
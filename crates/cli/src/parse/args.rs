@@ -13,7 +13,7 @@ pub async fn parse_args(
 ) -> Result<(Query, Source, FileOutput, ExecutionEnv), ParseError> {
     let source = source::parse_source(args).await?;
     let query = query::parse_query(args, Arc::new(source.clone())).await?;
-    let sink = file_output::parse_file_output(args, &source)?;
+    let sink = file_output::parse_file_output(args, &source, &query)?;
     let env = execution::parse_execution_env(args, query.n_tasks() as u64)?;
     Ok((query, source, sink, env))
 }
@@ -9,6 +9,16 @@ pub(crate) async fn parse_blocks(
     args: &Args,
     source: Arc<Source>,
 ) -> Result<(Option<Vec<Option<String>>>, Option<Vec<BlockChunk>>), ParseError> {
+    if let Some(blocks_from) = &args.blocks_from {
+        if args.blocks.is_some() || args.timestamps.is_some() {
+            let message = "cannot use --blocks-from with --blocks or --timestamps";
+            return Err(ParseError::ParseError(message.to_string()))
+        }
+        let block_chunks = vec![read_blocks_from(blocks_from)?];
+        let block_chunks = postprocess_block_chunks(block_chunks, args, source).await?;
+        return Ok((None, Some(block_chunks)))
+    }
+
     let (files, explicit_numbers): (Vec<&String>, Vec<&String>) = match &args.blocks {
         Some(blocks) => blocks.iter().partition(|tx| std::path::Path::new(tx).exists()),
         None => return Ok((None, None)),
@@ -67,6 +77,23 @@ pub(crate) async fn parse_blocks(
     Ok((labels, Some(block_chunks)))
 }
 
+/// read sorted distinct block numbers from FILE[:COLUMN] of --blocks-from
+fn read_blocks_from(blocks_from: &str) -> Result<BlockChunk, ParseError> {
+    let (path, column) = match blocks_from.rsplit_once(':') {
+        Some((path, column)) if !std::path::Path::new(blocks_from).exists() => (path, column),
+        _ => (blocks_from, "block_number"),
+    };
+    let mut block_numbers = read_integer_column(path, column).map_err(|_| {
+        ParseError::ParseError(format!("could not read column {} of {}", column, path))
+    })?;
+    block_numbers.sort_unstable();
+    block_numbers.dedup();
+    if block_numbers.is_empty() {
+        return Err(ParseError::ParseError(format!("no block numbers found in {}", path)))
+    }
+    Ok(BlockChunk::Numbers(block_numbers))
+}
+
 fn read_integer_column(path: &str, column: &str) -> Result<Vec<u64>, ParseError> {
     let file = std::fs::File::open(path)
         .map_err(|_e| ParseError::ParseError("could not open file path".to_string()))?;
@@ -403,16 +430,7 @@ mod tests {
 
     async fn block_token_test_helper(tests: Vec<(BlockTokenTest<'_>, bool)>) {
         let (provider, mock) = Provider::mocked();
-        let source = Source {
-            provider: provider.into(),
-            semaphore: Arc::new(None),
-            rate_limiter: Arc::new(None),
-            chain_id: 1,
-            inner_request_size: 1,
-            max_concurrent_chunks: None,
-            rpc_url: "".to_string(),
-            labels: cryo_freeze::SourceLabels::default(),
-        };
+        let source = Source { ..Source::from_provider(provider) };
         let source = Arc::new(source);
         for (test, res) in tests {
             match test {
@@ -465,16 +483,8 @@ mod tests {
 
     async fn block_input_test_helper(tests: Vec<(BlockInputTest<'_>, bool)>) {
         let (provider, mock) = Provider::mocked();
-        let source = Arc::new(Source {
-            provider: provider.into(),
-            chain_id: 1,
-            rpc_url: "".to_string(),
-            inner_request_size: 1,
-            semaphore: Arc::new(None),
-            max_concurrent_chunks: Some(1),
-            rate_limiter: Arc::new(None),
-            labels: cryo_freeze::SourceLabels::default(),
-        });
+        let source =
+            Arc::new(Source { max_concurrent_chunks: Some(1), ..Source::from_provider(provider) });
         for (test, res) in tests {
             match test {
                 BlockInputTest::WithMock((inputs, expected, latest)) => {
@@ -538,16 +548,7 @@ mod tests {
 
     async fn block_number_test_helper(tests: Vec<(BlockNumberTest<'_>, bool)>) {
         let (provider, mock) = Provider::mocked();
-        let source = Source {
-            provider: provider.into(),
-            semaphore: Arc::new(None),
-            rate_limiter: Arc::new(None),
-            chain_id: 1,
-            inner_request_size: 1,
-            max_concurrent_chunks: Some(1),
-            rpc_url: "".to_string(),
-            labels: cryo_freeze::SourceLabels::default(),
-        };
+        let source = Source { max_concurrent_chunks: Some(1), ..Source::from_provider(provider) };
         let source = Arc::new(source);
         for (test, res) in tests {
             match test {
@@ -590,6 +591,49 @@ mod tests {
         block_number == expected
     }
 
+    #[tokio::test]
+    async fn blocks_from_parquet_column() {
+        let path =
+            std::env::temp_dir().join(format!("cryo_blocks_from_{}.parquet", std::process::id()));
+        let mut df = DataFrame::new(vec![
+            Series::new("trace_block", vec![9u32, 3, 5, 3, 9, 12]),
+            Series::new("n_traces", vec![1u32; 6]),
+        ])
+        .unwrap();
+        let file = std::fs::File::create(&path).unwrap();
+        ParquetWriter::new(file).finish(&mut df).unwrap();
+
+        let (provider, _mock) = Provider::mocked();
+        let source =
+            Arc::new(Source { inner_request_size: 100, ..Source::from_provider(provider) });
+        let args = Args {
+            blocks_from: Some(format!("{}:trace_block", path.to_string_lossy())),
+            chunk_size: 2,
+            ..Default::default()
+        };
+        let (labels, block_chunks) = parse_blocks(&args, source.clone()).await.unwrap();
+        let block_chunks = block_chunks.unwrap();
+        assert!(labels.is_none());
+        let chunk_values: Vec<_> = block_chunks.iter().map(|chunk| chunk.values()).collect();
+        assert_eq!(chunk_values, vec![vec![3, 5], vec![9, 12]]);
+
+        // each block is requested on its own, no blocks between them are collected
+        let partition =
+            cryo_freeze::Partition { block_numbers: Some(block_chunks), ..Default::default() };
+        let block_ranges: Vec<_> = partition
+            .param_sets(Some(source.inner_request_size))
+            .unwrap()
+            .iter()
+            .map(|params| params.block_range)
+            .collect();
+        assert_eq!(block_ranges, vec![Some((3, 3)), Some((5, 5)), Some((9, 9)), Some((12, 12))]);
+
+        // cannot be combined with other block inputs
+        let args = Args { blocks: Some(vec!["1:10".to_string()]), ..args };
+        assert!(parse_blocks(&args, source).await.is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
     #[tokio::test]
     async fn block_token_parsing() {
         // Ranges
@@ -0,0 +1,94 @@
+use super::file_output::{known_network_name, KNOWN_NETWORKS};
+use crate::args::Args;
+use cryo_freeze::ParseError;
+use std::path::Path;
+
+/// a chain collected within a multichain run
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ChainSpec {
+    /// network name, used to namespace output paths
+    pub(crate) name: String,
+    /// rpc url, or MESC endpoint query
+    pub(crate) rpc: String,
+}
+
+/// parse chains from NAME=RPC entries, a lone NAME is used as its own rpc (e.g. a MESC query)
+pub(crate) fn parse_chains(args: &Args) -> Result<Vec<ChainSpec>, ParseError> {
+    let mut chains: Vec<ChainSpec> = Vec::new();
+    for entry in args.chains.iter().flatten() {
+        let (name, rpc) = match entry.split_once('=') {
+            Some((name, rpc)) => (name.trim(), rpc.trim()),
+            None => (entry.trim(), entry.trim()),
+        };
+        if name.is_empty() || rpc.is_empty() {
+            return Err(ParseError::ParseError(format!("invalid chain: {}", entry)))
+        }
+        if chains.iter().any(|chain| chain.name == name) {
+            return Err(ParseError::ParseError(format!("duplicate chain: {}", name)))
+        }
+        chains.push(ChainSpec { name: name.to_string(), rpc: rpc.to_string() });
+    }
+    Ok(chains)
+}
+
+/// args for collecting a single chain, writing into a directory named after the chain
+pub(crate) fn chain_args(args: &Args, chain: &ChainSpec) -> Args {
+    let output_dir = Path::new(&args.output_dir).join(&chain.name);
+    Args {
+        rpc: Some(chain.rpc.clone()),
+        network_name: Some(chain.name.clone()),
+        output_dir: output_dir.to_string_lossy().to_string(),
+        chains: None,
+        ..args.clone()
+    }
+}
+
+/// check that chain_id reported by a chain's rpc matches the chain's configured name
+pub(crate) fn validate_chain_id(name: &str, chain_id: u64) -> Result<(), ParseError> {
+    let expected_id = KNOWN_NETWORKS.iter().find(|(_, known)| *known == name).map(|(id, _)| *id);
+    match (known_network_name(chain_id), expected_id) {
+        (Some(actual), _) if actual != name => Err(ParseError::ParseError(format!(
+            "chain {} has rpc with chain id {}, which is {}",
+            name, chain_id, actual
+        ))),
+        (None, Some(expected_id)) => Err(ParseError::ParseError(format!(
+            "chain {} should have chain id {}, but rpc has chain id {}",
+            name, expected_id, chain_id
+        ))),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_chains() {
+        let args = Args {
+            chains: Some(vec!["ethereum=http://localhost:8545".to_string(), "base".to_string()]),
+            output_dir: "data".to_string(),
+            ..Default::default()
+        };
+        let chains = parse_chains(&args).unwrap();
+        assert_eq!(chains[0].rpc, "http://localhost:8545");
+        assert_eq!(chains[1], ChainSpec { name: "base".to_string(), rpc: "base".to_string() });
+
+        let chain_args = chain_args(&args, &chains[0]);
+        assert_eq!(chain_args.network_name.as_deref(), Some("ethereum"));
+        assert_eq!(Path::new(&chain_args.output_dir), Path::new("data").join("ethereum"));
+        assert!(chain_args.chains.is_none());
+
+        let duplicate = Args { chains: Some(vec!["base".to_string(), "base".to_string()]), ..args };
+        assert!(parse_chains(&duplicate).is_err());
+    }
+
+    #[test]
+    fn test_validate_chain_id() {
+        assert!(validate_chain_id("ethereum", 1).is_ok());
+        assert!(validate_chain_id("my_devnet", 31337).is_ok());
+        assert!(validate_chain_id("ethereum", 8453).is_err());
+        assert!(validate_chain_id("base", 31337).is_err());
+        assert!(validate_chain_id("my_devnet", 1).is_err());
+    }
+}
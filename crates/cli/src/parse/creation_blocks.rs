@@ -0,0 +1,166 @@
+use crate::args::Args;
+use cryo_freeze::{AddressChunk, BlockChunk, ChunkData, ParseError, Source};
+use ethers::prelude::*;
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+type ChunkLabels = Vec<Option<String>>;
+
+const CACHE_FILENAME: &str = "contract_creation_blocks.json";
+
+/// drop blocks before the earliest creation block of the given addresses
+///
+/// if any creation block cannot be resolved, the block chunks are returned unchanged
+pub(crate) async fn skip_blocks_before_creation(
+    args: &Args,
+    source: Arc<Source>,
+    addresses: &Option<Vec<AddressChunk>>,
+    labels: Option<ChunkLabels>,
+    block_numbers: Option<Vec<BlockChunk>>,
+) -> Result<(Option<ChunkLabels>, Option<Vec<BlockChunk>>), ParseError> {
+    let addresses = match addresses {
+        Some(addresses) if !addresses.is_empty() => addresses.values(),
+        _ => {
+            let message = "--contract-creation-block requires --address";
+            return Err(ParseError::ParseError(message.to_string()))
+        }
+    };
+    let block_numbers = match block_numbers {
+        Some(block_numbers) => block_numbers,
+        None => return Ok((labels, None)),
+    };
+
+    let cache_path = args.output_dir_path().join(".cryo").join(CACHE_FILENAME);
+    let mut cache = load_cache(&cache_path);
+    let mut start_block: Option<u64> = None;
+    for address in addresses.iter() {
+        let contract = parse_contract_address(address)?;
+        let key = format!("{}:{}", source.chain_id, address_hex(address));
+        let creation_block = match cache.get(&key) {
+            Some(block) => *block,
+            None => match source.get_contract_creation_block(contract).await {
+                Ok(Some(block)) => {
+                    cache.insert(key, block);
+                    block
+                }
+                Ok(None) | Err(_) => {
+                    eprintln!(
+                        "could not resolve creation block of {}, using specified blocks",
+                        address_hex(address)
+                    );
+                    return Ok((labels, Some(block_numbers)))
+                }
+            },
+        };
+        start_block = Some(start_block.map_or(creation_block, |b| b.min(creation_block)));
+    }
+    save_cache(&cache_path, &cache);
+
+    match start_block {
+        Some(start_block) => {
+            let (labels, block_numbers) = trim_block_chunks(labels, block_numbers, start_block);
+            Ok((labels, Some(block_numbers)))
+        }
+        None => Ok((labels, Some(block_numbers))),
+    }
+}
+
+/// address of a contract, which must be 20 bytes
+fn parse_contract_address(address: &[u8]) -> Result<H160, ParseError> {
+    if address.len() != 20 {
+        let message = format!(
+            "--contract-creation-block requires 20 byte addresses, got {}",
+            address_hex(address)
+        );
+        return Err(ParseError::ParseError(message))
+    }
+    Ok(H160::from_slice(address))
+}
+
+fn address_hex(bytes: &[u8]) -> String {
+    format!("0x{}", hex::encode(bytes))
+}
+
+fn load_cache(path: &PathBuf) -> HashMap<String, u64> {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn save_cache(path: &PathBuf, cache: &HashMap<String, u64>) {
+    let result = path
+        .parent()
+        .map(std::fs::create_dir_all)
+        .transpose()
+        .and_then(|_| std::fs::write(path, serde_json::to_string(cache).unwrap_or_default()));
+    if result.is_err() {
+        eprintln!("could not save contract creation block cache");
+    }
+}
+
+/// remove blocks before start_block, dropping chunks (and their labels) that become empty
+fn trim_block_chunks(
+    labels: Option<ChunkLabels>,
+    block_numbers: Vec<BlockChunk>,
+    start_block: u64,
+) -> (Option<ChunkLabels>, Vec<BlockChunk>) {
+    let mut kept_labels = Vec::new();
+    let mut kept_chunks = Vec::new();
+    for (i, chunk) in block_numbers.into_iter().enumerate() {
+        let chunk = match chunk {
+            BlockChunk::Range(_, end) if end < start_block => continue,
+            BlockChunk::Range(start, end) => BlockChunk::Range(start.max(start_block), end),
+            BlockChunk::Numbers(numbers) => {
+                let numbers: Vec<u64> = numbers.into_iter().filter(|n| *n >= start_block).collect();
+                if numbers.is_empty() {
+                    continue
+                }
+                BlockChunk::Numbers(numbers)
+            }
+        };
+        kept_chunks.push(chunk);
+        if let Some(labels) = &labels {
+            kept_labels.push(labels.get(i).cloned().flatten());
+        }
+    }
+    (labels.map(|_| kept_labels), kept_chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contract_addresses_must_be_20_bytes() {
+        let address = H160::from_low_u64_be(1);
+        assert_eq!(parse_contract_address(address.as_bytes()).unwrap(), address);
+        assert!(parse_contract_address(&[1, 2, 3]).is_err());
+        assert!(parse_contract_address(&[0; 32]).is_err());
+        assert!(parse_contract_address(&[]).is_err());
+    }
+
+    #[test]
+    fn test_trim_block_chunks() {
+        let chunks = vec![
+            BlockChunk::Range(0, 99),
+            BlockChunk::Range(100, 199),
+            BlockChunk::Numbers(vec![140, 160, 180]),
+            BlockChunk::Range(200, 299),
+        ];
+        let labels = Some(vec![
+            Some("a".to_string()),
+            Some("b".to_string()),
+            Some("c".to_string()),
+            Some("d".to_string()),
+        ]);
+        let (labels, chunks) = trim_block_chunks(labels, chunks, 150);
+        assert_eq!(
+            labels,
+            Some(vec![Some("b".to_string()), Some("c".to_string()), Some("d".to_string())])
+        );
+        let values: Vec<Vec<u64>> = chunks.iter().map(|chunk| chunk.values()).collect();
+        assert_eq!(values[0], (150..=199).collect::<Vec<u64>>());
+        assert_eq!(values[1], vec![160, 180]);
+        assert_eq!(values[2], (200..=299).collect::<Vec<u64>>());
+    }
+}
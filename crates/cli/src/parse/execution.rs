@@ -1,9 +1,10 @@
-use crate::args::Args;
-use cryo_freeze::{ExecutionEnv, ExecutionEnvBuilder, ParseError};
+use crate::args::{redact_cli_command, Args};
+use cryo_freeze::{ExecutionEnv, ExecutionEnvBuilder, ParseError, PostRunHook, ReportInterval};
+use std::time::Duration;
 
 pub(crate) fn parse_execution_env(args: &Args, n_tasks: u64) -> Result<ExecutionEnv, ParseError> {
-    let args_str =
-        serde_json::to_string(args).map_err(|e| ParseError::ParseError(e.to_string()))?;
+    let args_str = serde_json::to_string(&args.redacted())
+        .map_err(|e| ParseError::ParseError(e.to_string()))?;
 
     let verbose = match (args.no_verbose, args.verbose) {
         (true, true) => return Err(ParseError::ParseError("".to_string())),
@@ -12,11 +13,55 @@ pub(crate) fn parse_execution_env(args: &Args, n_tasks: u64) -> Result<Execution
         (false, false) => 1,
     };
 
+    if args.max_consecutive_failures == Some(0) {
+        let message = "--max-consecutive-failures must be at least 1";
+        return Err(ParseError::ParseError(message.to_string()))
+    }
+    if args.fail_fast && args.max_consecutive_failures.is_some() {
+        let message = "cannot use --fail-fast with --max-consecutive-failures";
+        return Err(ParseError::ParseError(message.to_string()))
+    }
+
+    if let Some(multiple) = args.warn_if_slow {
+        if !(multiple > 1.0 && multiple.is_finite()) {
+            let message = "--warn-if-slow must be a finite multiple greater than 1";
+            return Err(ParseError::ParseError(message.to_string()))
+        }
+    }
+
+    if args.write_concurrency == Some(0) {
+        return Err(ParseError::ParseError("--write-concurrency must be at least 1".to_string()))
+    }
+
+    if args.post_run_hook.is_none() && (args.post_run_hook_always || args.post_run_hook_strict) {
+        let message = "--post-run-hook-always and --post-run-hook-strict require --post-run-hook";
+        return Err(ParseError::ParseError(message.to_string()))
+    }
+    let post_run_hook = args.post_run_hook.clone().map(|command| PostRunHook {
+        command,
+        always: args.post_run_hook_always,
+        strict: args.post_run_hook_strict,
+    });
+
+    // a run streaming to stdout only writes a report into an explicit --report-dir
+    let report = !args.no_report && (!args.stdout_output() || args.report_dir.is_some());
+
     let builder = ExecutionEnvBuilder::new()
         .dry(args.dry)
         .verbose(verbose)
-        .report(!args.no_report)
+        .report(report)
         .report_dir(args.report_dir.clone())
+        .cli_command(redact_cli_command(std::env::args().collect()))
+        .report_retention(args.report_retention)
+        .report_interval(args.report_interval.as_deref().map(parse_report_interval).transpose()?)
+        .blocks_per_second(args.blocks_per_second)
+        .max_in_flight_bytes(args.max_in_flight_bytes.as_deref().map(parse_byte_size).transpose()?)
+        .max_duration(args.max_duration.as_deref().map(parse_duration).transpose()?)
+        .max_consecutive_failures(args.max_consecutive_failures)
+        .warn_if_slow(args.warn_if_slow)
+        .write_concurrency(args.write_concurrency)
+        .fail_fast(args.fail_fast)
+        .post_run_hook(post_run_hook)
         .args(args_str);
 
     let builder = if !args.no_verbose {
@@ -29,3 +74,99 @@ pub(crate) fn parse_execution_env(args: &Args, n_tasks: u64) -> Result<Execution
 
     Ok(builder.build())
 }
+
+/// parse duration such as 90s, 30m, 2h, or 1d, plain numbers are seconds
+fn parse_duration(input: &str) -> Result<Duration, ParseError> {
+    let invalid = || ParseError::ParseError(format!("invalid duration: {}", input));
+    let (number, scale) = match input.trim() {
+        s if s.ends_with('s') => (&s[..s.len() - 1], 1),
+        s if s.ends_with('m') => (&s[..s.len() - 1], 60),
+        s if s.ends_with('h') => (&s[..s.len() - 1], 3600),
+        s if s.ends_with('d') => (&s[..s.len() - 1], 86400),
+        s => (s, 1),
+    };
+    let number = number.parse::<f64>().map_err(|_| invalid())?;
+    if !number.is_finite() || number < 0.0 {
+        return Err(invalid())
+    }
+    Ok(Duration::from_secs_f64(number * scale as f64))
+}
+
+/// parse byte size such as 512MB, 2GB, or 64KiB, plain numbers are bytes
+pub(crate) fn parse_byte_size(input: &str) -> Result<u64, ParseError> {
+    let invalid = || ParseError::ParseError(format!("invalid byte size: {}", input));
+    let input = input.trim();
+    let split = input.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    let scale: u64 = match unit.to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "kb" => 1_000,
+        "mb" => 1_000_000,
+        "gb" => 1_000_000_000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        _ => return Err(invalid()),
+    };
+    let number = number.trim().parse::<f64>().map_err(|_| invalid())?;
+    if !number.is_finite() || number <= 0.0 {
+        return Err(invalid())
+    }
+    Ok((number * scale as f64) as u64)
+}
+
+/// parse report interval, plain numbers count chunks and suffixed numbers are durations
+fn parse_report_interval(input: &str) -> Result<ReportInterval, ParseError> {
+    let interval = match input.trim().parse::<u64>() {
+        Ok(n_partitions) => ReportInterval::Partitions(n_partitions),
+        Err(_) => ReportInterval::Duration(parse_duration(input)?),
+    };
+    match interval {
+        ReportInterval::Partitions(0) => {
+            Err(ParseError::ParseError("--report-interval must be at least 1 chunk".to_string()))
+        }
+        ReportInterval::Duration(duration) if duration.is_zero() => {
+            Err(ParseError::ParseError("--report-interval must be a positive duration".to_string()))
+        }
+        interval => Ok(interval),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("30m").unwrap(), Duration::from_secs(1800));
+        assert_eq!(parse_duration("1.5h").unwrap(), Duration::from_secs(5400));
+        assert_eq!(parse_duration("1d").unwrap(), Duration::from_secs(86400));
+        assert!(parse_duration("soon").is_err());
+        assert!(parse_duration("-5m").is_err());
+    }
+
+    #[test]
+    fn test_parse_byte_size() {
+        assert_eq!(parse_byte_size("4096").unwrap(), 4096);
+        assert_eq!(parse_byte_size("512MB").unwrap(), 512_000_000);
+        assert_eq!(parse_byte_size("1.5gb").unwrap(), 1_500_000_000);
+        assert_eq!(parse_byte_size("64KiB").unwrap(), 65_536);
+        assert!(parse_byte_size("0").is_err());
+        assert!(parse_byte_size("lots").is_err());
+        assert!(parse_byte_size("5TB").is_err());
+    }
+
+    #[test]
+    fn test_parse_report_interval() {
+        assert_eq!(parse_report_interval("25").unwrap(), ReportInterval::Partitions(25));
+        assert_eq!(
+            parse_report_interval("10m").unwrap(),
+            ReportInterval::Duration(Duration::from_secs(600))
+        );
+        assert!(parse_report_interval("0").is_err());
+        assert!(parse_report_interval("0s").is_err());
+        assert!(parse_report_interval("often").is_err());
+    }
+}
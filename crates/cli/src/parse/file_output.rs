@@ -1,13 +1,59 @@
 use crate::args::Args;
-use cryo_freeze::{FileFormat, FileOutput, ParseError, Source, SubDir};
+use cryo_freeze::{
+    ChunkData, FileFormat, FileOutput, ParseError, Query, Source, SubDir, DEFAULT_BLOCK_LABEL_WIDTH,
+};
+use ethers::signers::LocalWallet;
 use polars::prelude::*;
-use std::fs;
+use std::{collections::HashMap, fs, str::FromStr};
 
-pub(crate) fn parse_file_output(args: &Args, source: &Source) -> Result<FileOutput, ParseError> {
+pub(crate) fn parse_file_output(
+    args: &Args,
+    source: &Source,
+    query: &Query,
+) -> Result<FileOutput, ParseError> {
+    let file_prefix = parse_network_name(args, source.chain_id);
+    let output = parse_file_output_with_prefix(args, file_prefix)?;
+    if output.stdout && query.schemas.len() > 1 {
+        return Err(ParseError::ParseError(
+            "--output - streams a single datatype, collect one datatype at a time".to_string(),
+        ))
+    }
+    let block_label_width = parse_block_label_width(args, query)?;
+    Ok(FileOutput { block_label_width, ..output })
+}
+
+/// width of block numbers in file names, an explicit width must fit every block of query
+///
+/// the width does not depend on the blocks of a run without --block-label-width, so that later
+/// runs name and find files the same way
+fn parse_block_label_width(args: &Args, query: &Query) -> Result<usize, ParseError> {
+    let width = match args.block_label_width {
+        Some(width) => width,
+        None => return Ok(DEFAULT_BLOCK_LABEL_WIDTH),
+    };
+    let max_block = query
+        .partitions
+        .iter()
+        .filter_map(|partition| partition.block_numbers.as_ref())
+        .filter_map(|block_numbers| block_numbers.max_value())
+        .max();
+    match max_block {
+        Some(max_block) if max_block.to_string().len() > width => Err(ParseError::ParseError(
+            format!("--block-label-width {} is too narrow for block {}", width, max_block),
+        )),
+        _ => Ok(width),
+    }
+}
+
+/// parse file output without consulting a source, for modes that do not connect to a node
+pub(crate) fn parse_file_output_with_prefix(
+    args: &Args,
+    file_prefix: String,
+) -> Result<FileOutput, ParseError> {
     // process output directory
-    std::fs::create_dir_all(args.output_dir.clone())
+    std::fs::create_dir_all(args.output_dir_path())
         .map_err(|_| ParseError::ParseError("could not create dir".to_string()))?;
-    let output_dir = std::fs::canonicalize(args.output_dir.clone()).map_err(|_e| {
+    let output_dir = std::fs::canonicalize(args.output_dir_path()).map_err(|_e| {
         ParseError::ParseError("Failed to canonicalize output directory".to_string())
     })?;
     match fs::create_dir_all(&output_dir) {
@@ -26,25 +72,133 @@ pub(crate) fn parse_file_output(args: &Args, source: &Source) -> Result<FileOutp
     );
 
     let format = parse_output_format(args)?;
-    let file_prefix = parse_network_name(args, source.chain_id);
+
+    let column_decimals = parse_column_decimals(args, &format)?;
 
     let subdirs = parse_subdirs(args);
 
+    let hive_bucket_size = match args.hive_bucket_size {
+        Some(0) => {
+            return Err(ParseError::ParseError("--hive-bucket-size must be positive".to_string()))
+        }
+        Some(_) if args.split_by_day => {
+            return Err(ParseError::ParseError(
+                "cannot use both --hive-bucket-size and --split-by-day".to_string(),
+            ))
+        }
+        hive_bucket_size => hive_bucket_size,
+    };
+    if args.single_file && (args.split_by_day || hive_bucket_size.is_some()) {
+        return Err(ParseError::ParseError(
+            "cannot use --single-file with --split-by-day or --hive-bucket-size".to_string(),
+        ))
+    }
+    let target_partition_bytes = args
+        .target_partition_bytes
+        .as_deref()
+        .map(super::execution::parse_byte_size)
+        .transpose()?;
+    if target_partition_bytes.is_some() &&
+        (args.split_by_day || hive_bucket_size.is_some() || args.single_file)
+    {
+        return Err(ParseError::ParseError(
+            "cannot use --target-partition-bytes with --split-by-day, --hive-bucket-size, or --single-file"
+                .to_string(),
+        ))
+    }
+    if args.block_index && (args.split_by_day || target_partition_bytes.is_some()) {
+        return Err(ParseError::ParseError(
+            "cannot use --block-index with --split-by-day or --target-partition-bytes".to_string(),
+        ))
+    }
+    if args.embed_filter && format != FileFormat::Parquet {
+        return Err(ParseError::ParseError("--embed-filter requires parquet output".to_string()))
+    }
+    if args.fill_gaps && (args.overwrite || args.single_file || args.block_index) {
+        return Err(ParseError::ParseError(
+            "cannot use --fill-gaps with --overwrite, --single-file, or --block-index".to_string(),
+        ))
+    }
+
+    if args.stdout_output() {
+        validate_stdout_output(args, &format)?;
+    }
+
+    // a stream to stdout leaves nothing behind in the working directory
+    let temp_base = match &args.temp_dir {
+        Some(temp_dir) => std::path::PathBuf::from(temp_dir),
+        None if args.stdout_output() => std::env::temp_dir().join("cryo"),
+        None => output_dir.join(".cryo/tmp"),
+    };
+    let temp_dir = Some(FileOutput::run_temp_dir(&temp_base));
+
+    let signer = args.sign_key_file.as_deref().map(parse_signer).transpose()?;
+
     let output = FileOutput {
         output_dir,
         subdirs,
         parquet_statistics: !args.no_stats,
         overwrite: args.overwrite,
+        fill_gaps: args.fill_gaps,
         prefix: file_prefix,
         format,
+        custom_format: None,
         suffix: label.clone(),
         parquet_compression,
+        column_decimals,
         row_group_size,
+        split_by_day: args.split_by_day,
+        hive_bucket_size,
+        target_partition_bytes,
+        single_file: args.single_file,
+        stdout: args.stdout_output(),
+        block_index: args.block_index,
+        embed_filter: args.embed_filter,
+        success_marker: args.success_marker,
+        row_count_sidecar: args.row_count_sidecar,
+        temp_dir,
+        signer,
+        block_label_width: args.block_label_width.unwrap_or(DEFAULT_BLOCK_LABEL_WIDTH),
     };
 
     Ok(output)
 }
 
+/// check that options of a run streaming to stdout do not require output files
+fn validate_stdout_output(args: &Args, format: &FileFormat) -> Result<(), ParseError> {
+    if format == &FileFormat::Parquet {
+        return Err(ParseError::ParseError("--output - requires --csv or --json".to_string()))
+    }
+    let file_options = [
+        ("--single-file", args.single_file),
+        ("--split-by-day", args.split_by_day),
+        ("--hive-bucket-size", args.hive_bucket_size.is_some()),
+        ("--target-partition-bytes", args.target_partition_bytes.is_some()),
+        ("--block-index", args.block_index),
+        ("--fill-gaps", args.fill_gaps),
+        ("--success-marker", args.success_marker),
+        ("--row-count-sidecar", args.row_count_sidecar),
+        ("--logs-per-block", args.logs_per_block),
+        ("--compact-topics", args.compact_topics),
+        ("--sign-key-file", args.sign_key_file.is_some()),
+        ("--subdirs", !args.subdirs.is_empty()),
+    ];
+    match file_options.iter().find(|(_, used)| *used) {
+        Some((flag, _)) => {
+            Err(ParseError::ParseError(format!("cannot use {} with --output -", flag)))
+        }
+        None => Ok(()),
+    }
+}
+
+/// parse key used to sign output files, the key file holds a hex private key
+fn parse_signer(path: &str) -> Result<LocalWallet, ParseError> {
+    let key = fs::read_to_string(path)
+        .map_err(|_| ParseError::ParseError(format!("could not read signing key file {}", path)))?;
+    LocalWallet::from_str(key.trim())
+        .map_err(|_| ParseError::ParseError("invalid private key in signing key file".to_string()))
+}
+
 pub(crate) fn parse_subdirs(args: &Args) -> Vec<SubDir> {
     let mut subdirs = Vec::new();
     for arg in args.subdirs.iter() {
@@ -62,32 +216,42 @@ pub(crate) fn parse_subdirs(args: &Args) -> Vec<SubDir> {
 pub(crate) fn parse_network_name(args: &Args, chain_id: u64) -> String {
     match &args.network_name {
         Some(name) => name.clone(),
-        None => match chain_id {
-            1 => "ethereum".to_string(),
-            5 => "goerli".to_string(),
-            10 => "optimism".to_string(),
-            56 => "bnb".to_string(),
-            69 => "optimism_kovan".to_string(),
-            100 => "gnosis".to_string(),
-            137 => "polygon".to_string(),
-            420 => "optimism_goerli".to_string(),
-            1101 => "polygon_zkevm".to_string(),
-            1442 => "polygon_zkevm_testnet".to_string(),
-            8453 => "base".to_string(),
-            10200 => "gnosis_chidao".to_string(),
-            17000 => "holesky".to_string(),
-            42161 => "arbitrum".to_string(),
-            42170 => "arbitrum_nova".to_string(),
-            43114 => "avalanche".to_string(),
-            80001 => "polygon_mumbai".to_string(),
-            84531 => "base_goerli".to_string(),
-            7777777 => "zora".to_string(),
-            11155111 => "sepolia".to_string(),
-            chain_id => "network_".to_string() + chain_id.to_string().as_str(),
+        None => match known_network_name(chain_id) {
+            Some(name) => name.to_string(),
+            None => "network_".to_string() + chain_id.to_string().as_str(),
         },
     }
 }
 
+/// chain ids and names of known networks
+pub(crate) const KNOWN_NETWORKS: [(u64, &str); 20] = [
+    (1, "ethereum"),
+    (5, "goerli"),
+    (10, "optimism"),
+    (56, "bnb"),
+    (69, "optimism_kovan"),
+    (100, "gnosis"),
+    (137, "polygon"),
+    (420, "optimism_goerli"),
+    (1101, "polygon_zkevm"),
+    (1442, "polygon_zkevm_testnet"),
+    (8453, "base"),
+    (10200, "gnosis_chidao"),
+    (17000, "holesky"),
+    (42161, "arbitrum"),
+    (42170, "arbitrum_nova"),
+    (43114, "avalanche"),
+    (80001, "polygon_mumbai"),
+    (84531, "base_goerli"),
+    (7777777, "zora"),
+    (11155111, "sepolia"),
+];
+
+/// name of network with chain_id, if it is a known network
+pub(crate) fn known_network_name(chain_id: u64) -> Option<&'static str> {
+    KNOWN_NETWORKS.iter().find(|(id, _)| *id == chain_id).map(|(_, name)| *name)
+}
+
 pub(crate) fn parse_output_format(args: &Args) -> Result<FileFormat, ParseError> {
     match (args.csv, args.json) {
         (true, true) => {
@@ -99,6 +263,34 @@ pub(crate) fn parse_output_format(args: &Args) -> Result<FileFormat, ParseError>
     }
 }
 
+/// parse decimals of integer columns rendered in text output, given as COLUMN=N
+fn parse_column_decimals(
+    args: &Args,
+    format: &FileFormat,
+) -> Result<Option<HashMap<String, u8>>, ParseError> {
+    let entries = match &args.decimals {
+        Some(entries) => entries,
+        None => return Ok(None),
+    };
+    if *format == FileFormat::Parquet {
+        return Err(ParseError::ParseError("--decimals requires csv or json output".to_string()))
+    }
+    let mut decimals = HashMap::new();
+    for entry in entries.iter() {
+        let (column, n_decimals) = entry.split_once('=').ok_or_else(|| {
+            ParseError::ParseError(format!("decimals must be COLUMN=N, got {}", entry))
+        })?;
+        let n_decimals = n_decimals.parse::<u8>().map_err(|_| {
+            ParseError::ParseError(format!(
+                "invalid decimals for column {}: {}",
+                column, n_decimals
+            ))
+        })?;
+        decimals.insert(column.to_string(), n_decimals);
+    }
+    Ok(Some(decimals))
+}
+
 fn parse_compression(input: &Vec<String>) -> Result<ParquetCompression, ParseError> {
     match input.as_slice() {
         [algorithm] if algorithm.as_str() == "uncompressed" => Ok(ParquetCompression::Uncompressed),
@@ -1,12 +1,14 @@
 mod args;
 mod blocks;
-mod execution;
-mod file_output;
+pub(crate) mod chains;
+mod creation_blocks;
+pub(crate) mod execution;
+pub(crate) mod file_output;
 mod parse_utils;
 mod partitions;
-mod query;
+pub(crate) mod query;
 pub(crate) mod schemas;
-mod source;
+pub(crate) mod source;
 mod timestamps;
 
 pub use args::*;
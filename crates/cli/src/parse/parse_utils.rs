@@ -1,4 +1,5 @@
 use cryo_freeze::ParseError;
+use ethers::prelude::H160;
 use std::collections::HashMap;
 
 pub(crate) fn hex_string_to_binary(hex_string: &String) -> Result<Vec<u8>, ParseError> {
@@ -78,6 +79,75 @@ pub(crate) fn parse_binary_arg(
     Ok(parsed)
 }
 
+/// check that addresses are 20 bytes and, if checksum, that mixed-case hex inputs are valid
+/// EIP-55 checksums, all-lowercase and all-uppercase inputs carry no checksum
+pub(crate) fn validate_addresses(
+    inputs: &[String],
+    parsed: &ParsedBinaryArg,
+    checksum: bool,
+) -> Result<(), ParseError> {
+    if let Some(address) = parsed.values().flatten().find(|address| address.len() != 20) {
+        return Err(ParseError::ParseError(format!(
+            "invalid address 0x{}: {} bytes, expected 20",
+            hex::encode(address),
+            address.len()
+        )))
+    }
+    if !checksum {
+        return Ok(())
+    }
+    for input in inputs.iter() {
+        let hex_string = input.strip_prefix("0x").unwrap_or(input);
+        let is_address =
+            hex_string.len() == 40 && hex_string.chars().all(|c| c.is_ascii_hexdigit());
+        let is_mixed_case = hex_string.chars().any(|c| c.is_ascii_lowercase()) &&
+            hex_string.chars().any(|c| c.is_ascii_uppercase());
+        if !is_address || !is_mixed_case {
+            continue
+        }
+        let address = H160::from_slice(&hex_string_to_binary(input)?);
+        if ethers::utils::to_checksum(&address, None)[2..] != *hex_string {
+            return Err(ParseError::ParseError(format!(
+                "invalid checksum for address {}, use --no-checksum-check to accept it",
+                input
+            )))
+        }
+    }
+    Ok(())
+}
+
+/// read addresses from a text file of one address per line, with or without 0x prefix
+///
+/// text after # is a comment and blank lines are skipped, an empty file is an error since an
+/// empty address filter would match every log
+pub(crate) fn parse_address_file(path: &str, checksum: bool) -> Result<Vec<Vec<u8>>, ParseError> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|_| ParseError::ParseError(format!("could not read address file {}", path)))?;
+    let mut inputs = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let input = line.split('#').next().unwrap_or_default().trim();
+        if input.is_empty() {
+            continue
+        }
+        if hex_string_to_binary(&input.to_string()).is_err() {
+            return Err(ParseError::ParseError(format!(
+                "invalid address on line {} of {}: {}",
+                index + 1,
+                path,
+                input
+            )))
+        }
+        inputs.push(input.to_string());
+    }
+    if inputs.is_empty() {
+        return Err(ParseError::ParseError(format!("no addresses in address file {}", path)))
+    }
+    let addresses = hex_strings_to_binary(&inputs)?;
+    let parsed = HashMap::from([(BinaryInputList::Explicit, addresses)]);
+    validate_addresses(&inputs, &parsed, checksum)?;
+    Ok(parsed.into_values().flatten().collect())
+}
+
 struct FileColumnReference {
     path: String,
     column: String,
@@ -102,3 +172,67 @@ fn parse_file_column_reference(
 
     Ok(parsed)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(address: &str, checksum: bool) -> Result<(), ParseError> {
+        let inputs = vec![address.to_string()];
+        let parsed = parse_binary_arg(&inputs, "address")?;
+        validate_addresses(&inputs, &parsed, checksum)
+    }
+
+    #[test]
+    fn test_validate_addresses() {
+        let checksummed = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+        assert!(validate(checksummed, true).is_ok());
+        assert!(validate(&checksummed.to_lowercase(), true).is_ok());
+        assert!(validate(&checksummed[2..].to_uppercase(), true).is_ok());
+
+        // addresses of the wrong length are rejected even without checksum checks
+        assert!(validate("0x5aaeb6053f3e94c9b9a09f33669435e7ef1bea", true).is_err());
+        assert!(validate("0x5aaeb6053f3e94c9b9a09f33669435e7ef1bea", false).is_err());
+
+        // a single flipped case breaks the checksum
+        let mistyped = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD";
+        assert!(validate(mistyped, true).is_err());
+        assert!(validate(mistyped, false).is_ok());
+    }
+
+    #[test]
+    fn test_parse_address_file() {
+        let path =
+            std::env::temp_dir().join(format!("cryo_test_address_file_{}.txt", std::process::id()));
+        let path_str = path.to_str().unwrap();
+        let contents = "# allowlist\n\
+            0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed\n\
+            \n\
+            fb6916095ca1df60bb79ce92ce3ea74c37c5d359  # bare hex\n\
+            0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed\n";
+        std::fs::write(&path, contents).unwrap();
+        let addresses = parse_address_file(path_str, true).unwrap();
+        let expected = vec![
+            hex::decode("5aaeb6053f3e94c9b9a09f33669435e7ef1beaed").unwrap(),
+            hex::decode("fb6916095ca1df60bb79ce92ce3ea74c37c5d359").unwrap(),
+            hex::decode("5aaeb6053f3e94c9b9a09f33669435e7ef1beaed").unwrap(),
+        ];
+        assert_eq!(addresses, expected);
+
+        // repeated addresses are filtered once, in chunks across requests
+        let address_set = cryo_freeze::LogAddressSet::new(addresses, 1);
+        assert_eq!(address_set.addresses(), &expected[..2]);
+        assert_eq!(address_set.chunks().count(), 2);
+
+        // bad checksums, bad hex, and files without addresses are rejected
+        std::fs::write(&path, "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD\n").unwrap();
+        assert!(parse_address_file(path_str, true).is_err());
+        assert!(parse_address_file(path_str, false).is_ok());
+        std::fs::write(&path, "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaeg\n").unwrap();
+        assert!(parse_address_file(path_str, true).is_err());
+        std::fs::write(&path, "# nothing yet\n\n").unwrap();
+        assert!(parse_address_file(path_str, true).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
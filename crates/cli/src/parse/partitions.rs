@@ -1,6 +1,8 @@
 use super::{
-    blocks,
-    parse_utils::{hex_string_to_binary, hex_strings_to_binary, parse_binary_arg},
+    blocks, creation_blocks,
+    parse_utils::{
+        hex_string_to_binary, hex_strings_to_binary, parse_binary_arg, validate_addresses,
+    },
     timestamps,
 };
 use crate::args::Args;
@@ -33,11 +35,14 @@ pub(crate) async fn parse_partitions(
         parse_transaction_chunks(&args.txs, "transaction_hash")?;
     let call_datas = parse_call_datas(&args.call_data, &args.function, &args.inputs)?;
     let call_data_labels = None;
-    let (address_labels, addresses) = parse_address_chunks(&args.address, "address")?;
-    let (contract_labels, contracts) = parse_address_chunks(&args.contract, "contract_address")?;
+    let checksum = !args.no_checksum_check;
+    let (address_labels, addresses) = parse_address_chunks(&args.address, "address", checksum)?;
+    let (contract_labels, contracts) =
+        parse_address_chunks(&args.contract, "contract_address", checksum)?;
     let (from_address_labels, from_addresses) =
-        parse_address_chunks(&args.from_address, "from_address")?;
-    let (to_address_labels, to_addresses) = parse_address_chunks(&args.to_address, "to_address")?;
+        parse_address_chunks(&args.from_address, "from_address", checksum)?;
+    let (to_address_labels, to_addresses) =
+        parse_address_chunks(&args.to_address, "to_address", checksum)?;
     let (slot_labels, slots) = parse_slot_chunks(&args.slot, "slot")?;
     let (topic0_labels, topic0s) = parse_topic(&args.topic0, "topic0")?;
     let (topic1_labels, topic1s) = parse_topic(&args.topic1, "topic1")?;
@@ -46,11 +51,25 @@ pub(crate) async fn parse_partitions(
 
     // set default blocks
     let block_numbers = if block_numbers.is_none() && transactions.is_none() {
-        Some(blocks::get_default_block_chunks(args, source, schemas).await?)
+        Some(blocks::get_default_block_chunks(args, source.clone(), schemas).await?)
     } else {
         block_numbers
     };
 
+    // skip blocks before contract creation
+    let (block_number_labels, block_numbers) = if args.contract_creation_block {
+        creation_blocks::skip_blocks_before_creation(
+            args,
+            source,
+            &addresses,
+            block_number_labels,
+            block_numbers,
+        )
+        .await?
+    } else {
+        (block_number_labels, block_numbers)
+    };
+
     // aggregate chunk data
     let chunk = Partition {
         label: None,
@@ -191,9 +210,11 @@ pub(crate) fn parse_transaction_chunks(
 pub(crate) fn parse_address_chunks(
     input: &Option<Vec<String>>,
     default_column: &str,
+    checksum: bool,
 ) -> Result<(Option<ChunkLabels>, Option<Vec<AddressChunk>>), ParseError> {
     if let Some(input) = input {
         let parsed = parse_binary_arg(input, default_column)?;
+        validate_addresses(input, &parsed, checksum)?;
         let labels: Vec<Option<String>> = parsed.keys().map(|x| x.clone().to_label()).collect();
         let chunks = parsed.values().map(|a| AddressChunk::Values(a.clone())).collect();
         Ok((Some(labels), Some(chunks)))
@@ -1,7 +1,13 @@
-use super::{parse_schemas, partitions};
+use super::{parse_schemas, parse_utils, partitions};
 use crate::args::Args;
-use cryo_freeze::{Dim, ParseError, Query, QueryLabels, Schemas, Source};
-use std::sync::Arc;
+use cryo_freeze::{
+    AbiEventFilter, AddressLogCap, ChunkData, Datatype, DecodedColumnCap, Dim, EventNameFilter,
+    LogAddressSet, LogBlockCache, LogDataFilter, LogDecoder, LogFactory, LogSample, NullLogPolicy,
+    NullLogs, ParseError, Partition, Query, QueryLabels, Schemas, Source, TimeDimension,
+    Topic0Exclusion, Topic0Inclusion, TransactionIndexRange, DEFAULT_ADDRESSES_PER_REQUEST,
+    DEFAULT_FACTORY_BLOCKS_PER_REQUEST, DEFAULT_MAX_DECODED_COLUMNS,
+};
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
 
 /// parse Query struct from cli Args
 pub async fn parse_query(args: &Args, source: Arc<Source>) -> Result<Query, ParseError> {
@@ -12,22 +18,321 @@ pub async fn parse_query(args: &Args, source: Arc<Source>) -> Result<Query, Pars
         if !arg_aliases.is_empty() { Some(apply_arg_aliases(args, arg_aliases)?) } else { None };
     let args = new_args.as_ref().unwrap_or(args);
 
+    let factory = parse_log_factory(args, &schemas)?;
     let (partitions, partitioned_by, time_dimension) =
-        partitions::parse_partitions(args, source, &schemas).await?;
+        partitions::parse_partitions(args, source.clone(), &schemas).await?;
+    let query = parse_query_options(args, datatypes, schemas)?;
+    let log_address_set = match factory {
+        Some(factory) => {
+            Some(Arc::new(resolve_factory_addresses(args, &factory, &source, &partitions).await?))
+        }
+        None => query.log_address_set,
+    };
+    Ok(Query { partitions, partitioned_by, time_dimension, log_address_set, ..query })
+}
+
+/// parse Query of log responses dumped by an earlier run, without connecting to a node
+pub(crate) fn parse_replay_query(args: &Args) -> Result<Query, ParseError> {
+    let (datatypes, schemas) = parse_schemas(args)?;
+    if !schemas.contains_key(&Datatype::Logs) {
+        return Err(ParseError::ParseError("--replay can only be used with logs".to_string()))
+    }
+    let query = parse_query_options(args, datatypes, schemas)?;
+    Ok(Query { log_response_dir: None, ..query })
+}
+
+/// parse options of Query that do not depend on its partitions
+fn parse_query_options(
+    args: &Args,
+    datatypes: Vec<Datatype>,
+    schemas: Schemas,
+) -> Result<Query, ParseError> {
     let datatypes = cryo_freeze::cluster_datatypes(datatypes);
     let labels = QueryLabels { align: args.align, reorg_buffer: args.reorg_buffer };
     Ok(Query {
         datatypes,
         schemas,
-        time_dimension,
-        partitions,
-        partitioned_by,
+        time_dimension: TimeDimension::Blocks,
+        partitions: vec![],
+        partitioned_by: vec![Dim::BlockNumber],
         exclude_failed: args.exclude_failed,
         js_tracer: args.js_tracer.clone(),
+        logs_per_block: args.logs_per_block,
+        max_logs_per_address: args.max_logs_per_address.map(|n| Arc::new(AddressLogCap::new(n))),
+        log_data_filters: args
+            .log_data_filter
+            .as_ref()
+            .map(|specs| specs.iter().map(|spec| LogDataFilter::parse(spec)).collect())
+            .transpose()?,
+        transaction_index_range: args
+            .tx_index_range
+            .as_deref()
+            .map(TransactionIndexRange::parse)
+            .transpose()?
+            .map(Arc::new),
+        null_logs: Arc::new(NullLogs::new(parse_null_log_policy(args)?)),
+        included_topic0s: parse_included_topic0s(args)?.map(Arc::new),
+        excluded_topic0s: parse_excluded_topic0s(args)?.map(Arc::new),
+        abi_events_only: parse_abi_events_only(args)?.map(Arc::new),
+        topic0_labels: parse_topic0_labels(args)?,
+        event_name_filter: parse_event_name_filter(args)?.map(Arc::new),
+        log_sample: parse_log_sample(args)?.map(Arc::new),
+        any_topic: parse_any_topic(args)?,
+        log_address_set: parse_log_address_set(args)?.map(Arc::new),
+        decoded_column_cap: Arc::new(DecodedColumnCap::new(
+            args.max_decoded_columns.unwrap_or(DEFAULT_MAX_DECODED_COLUMNS),
+        )),
+        log_batch_size: args.log_batch_size,
+        log_block_cache: Arc::new(LogBlockCache::default()),
+        log_response_dir: parse_log_response_dir(args)?,
         labels,
     })
 }
 
+/// directory of this run's log response dumps, within the report directory
+///
+/// only eth_getLogs responses are dumped, logs collected from transaction receipts are not
+fn parse_log_response_dir(args: &Args) -> Result<Option<PathBuf>, ParseError> {
+    if !args.dump_log_responses {
+        return Ok(None)
+    }
+    if args.txs.is_some() {
+        let message = "--dump-log-responses cannot be used with --txs, which collects logs from \
+                       transaction receipts";
+        return Err(ParseError::ParseError(message.to_string()))
+    }
+    let report_dir = match &args.report_dir {
+        Some(report_dir) => report_dir.clone(),
+        None => args.output_dir_path().join(".cryo/reports"),
+    };
+    let timestamp = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S%.6f").to_string();
+    Ok(Some(report_dir.join("log_responses").join(timestamp)))
+}
+
+fn parse_null_log_policy(args: &Args) -> Result<NullLogPolicy, ParseError> {
+    match args.null_log_policy.as_deref() {
+        None | Some("drop") => Ok(NullLogPolicy::Drop),
+        Some("error") => Ok(NullLogPolicy::Error),
+        Some(policy) => Err(ParseError::ParseError(format!(
+            "invalid null log policy: {}, must be drop or error",
+            policy
+        ))),
+    }
+}
+
+fn parse_topic0_labels(args: &Args) -> Result<Option<HashMap<Vec<u8>, String>>, ParseError> {
+    let entries = match &args.topic0_labels {
+        Some(entries) => entries,
+        None => return Ok(None),
+    };
+    let mut labels = HashMap::new();
+    for entry in entries.iter() {
+        let (topic0, name) = entry.split_once('=').ok_or_else(|| {
+            ParseError::ParseError(format!("topic0 label must be TOPIC0=NAME, got {}", entry))
+        })?;
+        let topic0 = hex::decode(topic0.strip_prefix("0x").unwrap_or(topic0))
+            .map_err(|_| ParseError::ParseError(format!("invalid topic0 hex: {}", topic0)))?;
+        if topic0.len() != 32 {
+            return Err(ParseError::ParseError(format!("topic0 must be 32 bytes: {}", entry)))
+        }
+        labels.insert(topic0, name.to_string());
+    }
+    Ok(Some(labels))
+}
+
+fn parse_event_name_filter(args: &Args) -> Result<Option<EventNameFilter>, ParseError> {
+    let pattern = match &args.event_name_regex {
+        Some(pattern) => pattern,
+        None => return Ok(None),
+    };
+    if args.topic0_labels.is_none() &&
+        args.event_signature.is_none() &&
+        args.event_signature_range.is_none()
+    {
+        let message = "--event-name-regex requires --event-signature, --event-signature-range, or \
+                       --topic0-labels to name events";
+        return Err(ParseError::ParseError(message.to_string()))
+    }
+    Ok(Some(EventNameFilter::new(pattern)?))
+}
+
+fn parse_log_sample(args: &Args) -> Result<Option<LogSample>, ParseError> {
+    let fraction = match args.sample_fraction {
+        Some(fraction) => fraction,
+        None if args.seed.is_some() => {
+            return Err(ParseError::ParseError("--seed requires --sample-fraction".to_string()))
+        }
+        None => return Ok(None),
+    };
+    Ok(Some(LogSample::new(fraction, args.seed.unwrap_or(0))?))
+}
+
+fn parse_abi_events_only(args: &Args) -> Result<Option<AbiEventFilter>, ParseError> {
+    if !args.abi_events_only {
+        return Ok(None)
+    }
+    if args.event_signature.is_none() && args.event_signature_range.is_none() {
+        let message = "--abi-events-only requires --event-signature or --event-signature-range";
+        return Err(ParseError::ParseError(message.to_string()))
+    }
+    Ok(Some(AbiEventFilter::default()))
+}
+
+fn parse_included_topic0s(args: &Args) -> Result<Option<Topic0Inclusion>, ParseError> {
+    let entries = match &args.include_topic0 {
+        Some(entries) => entries,
+        None => return Ok(None),
+    };
+    if args.topic0.is_some() {
+        let message = "cannot use --include-topic0 with --topic0";
+        return Err(ParseError::ParseError(message.to_string()))
+    }
+    let topic0s = parse_topic0_entries(entries, "included")?;
+    let excluded = args.exclude_topic0.as_deref().map(|e| parse_topic0_entries(e, "excluded"));
+    if let Some(excluded) = excluded.transpose()? {
+        if topic0s.iter().all(|topic0| excluded.contains(topic0)) {
+            let message = "--exclude-topic0 excludes every topic0 of --include-topic0";
+            return Err(ParseError::ParseError(message.to_string()))
+        }
+    }
+    Ok(Some(Topic0Inclusion::new(topic0s)))
+}
+
+fn parse_excluded_topic0s(args: &Args) -> Result<Option<Topic0Exclusion>, ParseError> {
+    match &args.exclude_topic0 {
+        Some(entries) => Ok(Some(Topic0Exclusion::new(parse_topic0_entries(entries, "excluded")?))),
+        None => Ok(None),
+    }
+}
+
+/// parse topic0s given as event signatures or topic0 hashes
+fn parse_topic0_entries(entries: &[String], kind: &str) -> Result<Vec<Vec<u8>>, ParseError> {
+    let mut topic0s = Vec::new();
+    for entry in entries.iter() {
+        let topic0 = if entry.starts_with("0x") || !entry.contains('(') {
+            let topic0 = hex::decode(entry.strip_prefix("0x").unwrap_or(entry))
+                .map_err(|_| ParseError::ParseError(format!("invalid topic0 hex: {}", entry)))?;
+            if topic0.len() != 32 {
+                return Err(ParseError::ParseError(format!("topic0 must be 32 bytes: {}", entry)))
+            }
+            topic0
+        } else {
+            let decoder = LogDecoder::new(entry.clone()).map_err(|_| {
+                ParseError::ParseError(format!("invalid {} event signature: {}", kind, entry))
+            })?;
+            decoder.event.signature().as_bytes().to_vec()
+        };
+        topic0s.push(topic0);
+    }
+    Ok(topic0s)
+}
+
+fn parse_any_topic(args: &Args) -> Result<Option<Vec<u8>>, ParseError> {
+    let topic = match &args.any_topic {
+        Some(topic) => topic,
+        None => return Ok(None),
+    };
+    if args.topic1.is_some() || args.topic2.is_some() || args.topic3.is_some() {
+        let message = "cannot use --any-topic with --topic1, --topic2, or --topic3";
+        return Err(ParseError::ParseError(message.to_string()))
+    }
+    let bytes = hex::decode(topic.strip_prefix("0x").unwrap_or(topic))
+        .map_err(|_| ParseError::ParseError(format!("invalid topic hex: {}", topic)))?;
+    match bytes.len() {
+        32 => Ok(Some(bytes)),
+        20 => Ok(Some([vec![0u8; 12], bytes].concat())),
+        _ => Err(ParseError::ParseError(format!("topic must be 20 or 32 bytes: {}", topic))),
+    }
+}
+
+fn parse_log_address_set(args: &Args) -> Result<Option<LogAddressSet>, ParseError> {
+    let path = match &args.address_file {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+    if args.address.is_some() || args.contract.is_some() {
+        let message = "cannot use --address-file with --address or --contract";
+        return Err(ParseError::ParseError(message.to_string()))
+    }
+    let chunk_size = args.addresses_per_request.unwrap_or(DEFAULT_ADDRESSES_PER_REQUEST);
+    if chunk_size == 0 {
+        return Err(ParseError::ParseError("--addresses-per-request must be at least 1".to_string()))
+    }
+    let addresses = parse_utils::parse_address_file(path, !args.no_checksum_check)?;
+    Ok(Some(LogAddressSet::new(addresses, chunk_size)))
+}
+
+fn parse_log_factory(args: &Args, schemas: &Schemas) -> Result<Option<LogFactory>, ParseError> {
+    let factory = match &args.factory {
+        Some(factory) => factory,
+        None => {
+            if args.factory_event.is_some() ||
+                args.factory_address_arg.is_some() ||
+                args.factory_start_block.is_some()
+            {
+                let message = "--factory-event, --factory-address-arg, and --factory-start-block \
+                    require --factory";
+                return Err(ParseError::ParseError(message.to_string()))
+            }
+            return Ok(None)
+        }
+    };
+    if !schemas.contains_key(&Datatype::Logs) {
+        return Err(ParseError::ParseError("--factory can only be used with logs".to_string()))
+    }
+    if args.address.is_some() || args.contract.is_some() || args.address_file.is_some() {
+        let message = "cannot use --factory with --address, --contract, or --address-file";
+        return Err(ParseError::ParseError(message.to_string()))
+    }
+    let address = parse_utils::hex_string_to_binary(factory)?;
+    let (signature, address_arg) = match (&args.factory_event, &args.factory_address_arg) {
+        (Some(signature), Some(address_arg)) => (signature.clone(), address_arg.clone()),
+        _ => {
+            let message = "--factory requires --factory-event and --factory-address-arg";
+            return Err(ParseError::ParseError(message.to_string()))
+        }
+    };
+    let decoder = LogDecoder::new(signature.clone()).map_err(|_| {
+        ParseError::ParseError(format!("invalid factory event signature: {}", signature))
+    })?;
+    LogFactory::new(
+        address,
+        decoder,
+        address_arg,
+        args.factory_start_block,
+        DEFAULT_FACTORY_BLOCKS_PER_REQUEST,
+    )
+    .map(Some)
+    .map_err(|e| ParseError::ParseError(e.to_string()))
+}
+
+/// collect the addresses deployed by factory up to the last block of partitions
+async fn resolve_factory_addresses(
+    args: &Args,
+    factory: &LogFactory,
+    source: &Source,
+    partitions: &[Partition],
+) -> Result<LogAddressSet, ParseError> {
+    let end_block = partitions
+        .iter()
+        .filter_map(|partition| partition.block_numbers.as_ref())
+        .filter_map(|block_numbers| block_numbers.max_value())
+        .max()
+        .ok_or_else(|| ParseError::ParseError("--factory requires a block range".to_string()))?;
+    let chunk_size = args.addresses_per_request.unwrap_or(DEFAULT_ADDRESSES_PER_REQUEST);
+    if chunk_size == 0 {
+        return Err(ParseError::ParseError("--addresses-per-request must be at least 1".to_string()))
+    }
+    let addresses = factory.deployed_addresses(source, end_block).await.map_err(|e| {
+        ParseError::ParseError(format!("could not collect factory deployments: {}", e))
+    })?;
+    if addresses.is_empty() {
+        let message = "factory has no deployments within the requested blocks";
+        return Err(ParseError::ParseError(message.to_string()))
+    }
+    Ok(LogAddressSet::new(addresses, chunk_size))
+}
+
 fn find_arg_aliases(args: &Args, schemas: &Schemas) -> Vec<(Dim, Dim)> {
     // does not currently handle optional args, just required args
     let mut swaps = Vec::new();
@@ -1,7 +1,8 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use cryo_freeze::{
-    ColumnEncoding, Datatype, FileFormat, LogDecoder, MultiDatatype, ParseError, Table,
+    ColumnEncoding, Datatype, DecoderRange, FileFormat, LogDecoder, MultiDatatype, ParseError,
+    PerBlockLog, RangedLogDecoder, Table,
 };
 
 use super::file_output;
@@ -9,6 +10,49 @@ use crate::args::Args;
 use cryo_freeze::U256Type;
 use std::str::FromStr;
 
+/// parse event signatures keyed by block range, --event-signature is used outside of the ranges
+fn parse_log_decoder_ranges(
+    args: &Args,
+    fallback: Option<LogDecoder>,
+) -> Result<Option<RangedLogDecoder>, ParseError> {
+    let specs = match &args.event_signature_range {
+        Some(specs) => specs,
+        None => return Ok(None),
+    };
+    let ranges = specs
+        .iter()
+        .map(|spec| DecoderRange::parse(spec))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(ParseError::ParseError)?;
+    RangedLogDecoder::new(ranges, fallback).map(Some).map_err(ParseError::ParseError)
+}
+
+/// parse output names of columns from COLUMN=ALIAS pairs
+fn parse_column_aliases(args: &Args) -> Result<Option<Vec<(String, String)>>, ParseError> {
+    let specs = match &args.column_alias {
+        Some(specs) => specs,
+        None => return Ok(None),
+    };
+    let mut aliases = Vec::new();
+    let mut aliased = HashSet::new();
+    for spec in specs.iter() {
+        let (column, alias) = match spec.split_once('=') {
+            Some((column, alias)) if !column.is_empty() && !alias.is_empty() => (column, alias),
+            _ => {
+                return Err(ParseError::ParseError(format!(
+                    "invalid column alias, use COLUMN=ALIAS: {}",
+                    spec
+                )))
+            }
+        };
+        if !aliased.insert(column) {
+            return Err(ParseError::ParseError(format!("column aliased more than once: {}", column)))
+        }
+        aliases.push((column.to_string(), alias.to_string()));
+    }
+    Ok(Some(aliases))
+}
+
 fn parse_datatypes(raw_inputs: &Vec<String>) -> Result<Vec<Datatype>, ParseError> {
     let mut datatypes = Vec::new();
 
@@ -39,6 +83,15 @@ pub(crate) fn parse_schemas(
         false => ColumnEncoding::Binary,
     };
 
+    if args.zero_hash_topics && args.compact_topics {
+        let message = "--zero-hash-topics cannot be used with --compact-topics";
+        return Err(ParseError::ParseError(message.to_string()))
+    }
+
+    if args.block_bucket_size == Some(0) {
+        return Err(ParseError::ParseError("--block-bucket-size must be at least 1".to_string()))
+    }
+
     let log_decoder = match args.event_signature {
         Some(ref sig) => match LogDecoder::new(sig.clone()) {
             Ok(res) => Some(res),
@@ -46,6 +99,24 @@ pub(crate) fn parse_schemas(
         },
         None => None,
     };
+    let log_decoder_ranges = parse_log_decoder_ranges(args, log_decoder.clone())?;
+    let log_decoder = match &log_decoder_ranges {
+        Some(ranges) => Some(ranges.primary().clone()),
+        None => log_decoder,
+    };
+    if args.decoded_json && log_decoder.is_none() {
+        let message = "--decoded-json requires --event-signature or --event-signature-range";
+        return Err(ParseError::ParseError(message.to_string()))
+    }
+    if args.decoded_json && args.no_decode {
+        let message = "--decoded-json cannot be used with --no-decode";
+        return Err(ParseError::ParseError(message.to_string()))
+    }
+    let column_aliases = parse_column_aliases(args)?;
+    let per_block_log = args.per_block.as_deref().map(PerBlockLog::from_str).transpose()?;
+    if args.no_chain_id {
+        ensure_chain_id_unused(args)?;
+    }
 
     // create schemas
     let schemas: Result<HashMap<Datatype, Table>, ParseError> = datatypes
@@ -61,7 +132,30 @@ pub(crate) fn parse_schemas(
                     sort[datatype].clone(),
                     log_decoder.clone(),
                 )
-                .map(|schema| (*datatype, schema))
+                .and_then(|mut schema| {
+                    schema.log_decoder_ranges = log_decoder_ranges.clone();
+                    schema.compact_topics = args.compact_topics;
+                    schema.zero_hash_topics = args.zero_hash_topics;
+                    schema.block_bucket_size = args.block_bucket_size;
+                    schema.row_id = args.row_id;
+                    schema.skip_decoding = args.no_decode;
+                    schema.decode_chunk_size = args.decode_chunk_size;
+                    schema.keep_raw_log_columns = args.keep_raw_log_columns;
+                    schema.per_block_log = per_block_log;
+                    if args.decoded_json {
+                        schema.set_decoded_json();
+                    }
+                    if args.topics_as_hex {
+                        schema.set_topics_as_hex();
+                    }
+                    if args.no_chain_id {
+                        schema.exclude_chain_id();
+                    }
+                    if let Some(column_aliases) = &column_aliases {
+                        schema.set_column_aliases(column_aliases)?;
+                    }
+                    Ok((*datatype, schema))
+                })
                 .map_err(|e| {
                     ParseError::ParseError(format!(
                         "Failed to get schema for datatype: {:?}, {:?}",
@@ -81,6 +175,41 @@ pub(crate) fn parse_schemas(
         ensure_excluded_columns(exclude_columns, schemas)?
     };
 
+    // block buckets are computed from the block_number column of every output
+    if let (Ok(schemas), Some(_)) = (&schemas, args.block_bucket_size) {
+        if let Some(datatype) = schemas
+            .iter()
+            .find_map(|(datatype, schema)| (!schema.has_column("block_number")).then_some(datatype))
+        {
+            return Err(ParseError::ParseError(format!(
+                "--block-bucket-size requires a block_number column, missing for {}",
+                datatype.name()
+            )))
+        }
+    };
+
+    // the log of each block is picked by grouping logs by block_number and log_index
+    if let (Ok(schemas), Some(_)) = (&schemas, per_block_log) {
+        let schema = schemas.get(&Datatype::Logs).ok_or_else(|| {
+            ParseError::ParseError("--per-block can only be used with logs".to_string())
+        })?;
+        if !schema.has_column("block_number") || !schema.has_column("log_index") {
+            let message = "--per-block requires block_number and log_index columns";
+            return Err(ParseError::ParseError(message.to_string()))
+        }
+    };
+
+    // make sure all aliased columns are in at least one schema, decoded event columns are only
+    // known once logs are decoded
+    if let (Ok(schemas), Some(column_aliases)) = (&schemas, &column_aliases) {
+        let columns: Vec<String> = column_aliases
+            .iter()
+            .map(|(column, _)| column.clone())
+            .filter(|column| !column.starts_with(cryo_freeze::DECODED_COLUMN_PREFIX))
+            .collect();
+        ensure_included_columns(&columns, schemas)?
+    };
+
     Ok((datatypes, schemas?))
 }
 
@@ -108,6 +237,17 @@ fn parse_u256_types(args: &Args) -> Result<Vec<U256Type>, ParseError> {
     )
 }
 
+/// chain_id cannot be excluded while other options ask for it
+fn ensure_chain_id_unused(args: &Args) -> Result<(), ParseError> {
+    let requested = [&args.include_columns, &args.columns, &args.sort];
+    if requested.iter().any(|columns| columns.iter().flatten().any(|column| column == "chain_id")) {
+        let message = "--no-chain-id cannot be used with chain_id in --include-columns, \
+                       --columns, or --sort";
+        return Err(ParseError::ParseError(message.to_string()))
+    }
+    Ok(())
+}
+
 fn ensure_included_columns(
     include_columns: &[String],
     schemas: &cryo_freeze::Schemas,
@@ -1,38 +1,79 @@
 use std::env;
 
 use crate::args::Args;
-use cryo_freeze::{sources::ProviderWrapper, ParseError, Source, SourceLabels};
+use cryo_freeze::{
+    sources::ProviderWrapper, ConcurrencyThrottle, LatencyTracker, ParseError, RetryBudget, Source,
+    SourceLabels, SourceRetryPolicy, UnavailableLogRanges, DEFAULT_UNAVAILABLE_LOG_PATTERNS,
+};
 use ethers::prelude::*;
 use governor::{Quota, RateLimiter};
 use polars::prelude::*;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION},
+    Url,
+};
 use std::num::NonZeroU32;
 
 pub(crate) async fn parse_source(args: &Args) -> Result<Source, ParseError> {
     // parse network info
     let rpc_url = parse_rpc_url(args)?;
-    let (provider, chain_id): (ProviderWrapper, u64) = if rpc_url.starts_with("http") {
-        let provider = Provider::<RetryClient<Http>>::new_client(
-            &rpc_url,
-            args.max_retries,
-            args.initial_backoff,
-        )
-        .map_err(|_e| ParseError::ParseError("could not connect to provider".to_string()))?;
-        let chain_id = provider.get_chainid().await.map_err(ParseError::ProviderError)?.as_u64();
-        (provider.into(), chain_id)
-    } else if rpc_url.starts_with("ws") {
-        let provider = Provider::<Ws>::connect(&rpc_url).await.map_err(|_| {
-            ParseError::ParseError("could not instantiate HTTP Provider".to_string())
-        })?;
-        let chain_id = provider.get_chainid().await.map_err(ParseError::ProviderError)?.as_u64();
-        (provider.into(), chain_id)
-    } else if rpc_url.ends_with(".ipc") {
-        let provider: Provider<Ipc> = Provider::connect_ipc(&rpc_url).await.map_err(|_| {
-            ParseError::ParseError("could not instantiate HTTP Provider".to_string())
-        })?;
-        let chain_id = provider.get_chainid().await.map_err(ParseError::ProviderError)?.as_u64();
-        (provider.into(), chain_id)
-    } else {
-        return Err(ParseError::ParseError(format!("invalid rpc url: {}", rpc_url)));
+    let retry_budget = args.max_total_retries.map(|n| Arc::new(RetryBudget::new(n)));
+    let log_filter_params = parse_log_filter_params(args)?;
+    let unavailable_log_ranges = parse_unavailable_log_ranges(args)?.map(Arc::new);
+
+    // process concurrency info
+    let max_concurrent_requests = args.max_concurrent_requests.unwrap_or(100);
+    let max_concurrent_chunks = match args.max_concurrent_chunks {
+        Some(0) => None,
+        Some(max) => Some(max),
+        None => Some(4),
+    };
+
+    let semaphore = tokio::sync::Semaphore::new(max_concurrent_requests as usize);
+    let semaphore = Arc::new(Some(semaphore));
+    let concurrency_throttle = Arc::new(ConcurrencyThrottle::new(
+        semaphore.clone(),
+        max_concurrent_requests,
+        std::time::Duration::from_secs(args.throttle_cooldown),
+    ));
+
+    let transport = rpc_transport(&rpc_url);
+    if let Some(transport) = &transport {
+        validate_rpc_credentials(args, transport)?;
+    }
+    let (provider, chain_id): (ProviderWrapper, u64) = match transport {
+        Some(RpcTransport::Http) => {
+            let headers = parse_rpc_headers(args)?;
+            let provider = new_http_provider(
+                &rpc_url,
+                headers,
+                args.max_retries,
+                args.initial_backoff,
+                retry_budget.clone(),
+                Some(concurrency_throttle.clone()),
+            )?;
+            let chain_id =
+                provider.get_chainid().await.map_err(ParseError::ProviderError)?.as_u64();
+            (provider.into(), chain_id)
+        }
+        Some(RpcTransport::Ws) => {
+            let provider = Provider::<Ws>::connect(&rpc_url).await.map_err(|_| {
+                ParseError::ParseError("could not instantiate WS Provider".to_string())
+            })?;
+            let chain_id =
+                provider.get_chainid().await.map_err(ParseError::ProviderError)?.as_u64();
+            (provider.into(), chain_id)
+        }
+        Some(RpcTransport::Ipc) => {
+            let path = rpc_url.strip_prefix("ipc://").unwrap_or(&rpc_url);
+            let provider: Provider<Ipc> = Provider::connect_ipc(path).await.map_err(|_| {
+                ParseError::ParseError("could not instantiate IPC Provider".to_string())
+            })?;
+            let chain_id =
+                provider.get_chainid().await.map_err(ParseError::ProviderError)?.as_u64();
+            (provider.into(), chain_id)
+        }
+        None => return Err(ParseError::ParseError(format!("invalid rpc url: {}", rpc_url))),
     };
 
     let rate_limiter = match args.requests_per_second {
@@ -46,29 +87,25 @@ pub(crate) async fn parse_source(args: &Args) -> Result<Source, ParseError> {
         None => None,
     };
 
-    // process concurrency info
-    let max_concurrent_requests = args.max_concurrent_requests.unwrap_or(100);
-    let max_concurrent_chunks = match args.max_concurrent_chunks {
-        Some(0) => None,
-        Some(max) => Some(max),
-        None => Some(4),
-    };
-
-    let semaphore = tokio::sync::Semaphore::new(max_concurrent_requests as usize);
-    let semaphore = Arc::new(Some(semaphore));
-
     let output = Source {
         chain_id,
         inner_request_size: args.inner_request_size,
         max_concurrent_chunks,
         semaphore,
         rate_limiter: rate_limiter.into(),
+        retry_budget,
+        concurrency_throttle: Some(concurrency_throttle),
+        rpc_latency: Some(Arc::new(LatencyTracker::default())),
+        log_filter_params,
+        bisect_log_ranges: args.bisect_log_ranges,
+        unavailable_log_ranges,
         rpc_url,
         provider,
         labels: SourceLabels {
             max_concurrent_requests: args.requests_per_second.map(|x| x as u64),
             max_requests_per_second: args.requests_per_second.map(|x| x as u64),
             max_retries: Some(args.max_retries),
+            max_total_retries: args.max_total_retries,
             initial_backoff: Some(args.initial_backoff),
         },
     };
@@ -76,6 +113,136 @@ pub(crate) async fn parse_source(args: &Args) -> Result<Source, ParseError> {
     Ok(output)
 }
 
+/// parse custom headers and auth credentials to send with every RPC request
+fn parse_rpc_headers(args: &Args) -> Result<HeaderMap, ParseError> {
+    let mut headers = HeaderMap::new();
+    for header in args.rpc_header.iter().flatten() {
+        let (name, value) = header.split_once(':').ok_or_else(|| {
+            ParseError::ParseError("rpc header must be formatted as NAME:VALUE".to_string())
+        })?;
+        let name = HeaderName::from_bytes(name.trim().as_bytes())
+            .map_err(|_| ParseError::ParseError("invalid rpc header name".to_string()))?;
+        headers.insert(name, sensitive_header_value(value.trim())?);
+    }
+
+    let authorization = match (&args.rpc_bearer, &args.rpc_basic_auth) {
+        (Some(_), Some(_)) => {
+            let message = "cannot use both --rpc-bearer and --rpc-basic-auth";
+            return Err(ParseError::ParseError(message.to_string()))
+        }
+        (Some(token), None) => Some(Authorization::bearer(token)),
+        (None, Some(credentials)) => {
+            let (username, password) = credentials.split_once(':').ok_or_else(|| {
+                ParseError::ParseError("rpc basic auth must be formatted as USER:PASS".to_string())
+            })?;
+            Some(Authorization::basic(username, password))
+        }
+        (None, None) => None,
+    };
+    if let Some(authorization) = authorization {
+        headers.insert(AUTHORIZATION, sensitive_header_value(&authorization.to_string())?);
+    }
+
+    Ok(headers)
+}
+
+/// rpc headers and auth are only sent by http providers, so other transports reject them rather
+/// than connecting without them
+fn validate_rpc_credentials(args: &Args, transport: &RpcTransport) -> Result<(), ParseError> {
+    if transport == &RpcTransport::Http {
+        return Ok(())
+    }
+    let credential_flags = [
+        ("--rpc-header", args.rpc_header.is_some()),
+        ("--rpc-bearer", args.rpc_bearer.is_some()),
+        ("--rpc-basic-auth", args.rpc_basic_auth.is_some()),
+    ];
+    match credential_flags.iter().find(|(_, used)| *used) {
+        Some((flag, _)) => {
+            Err(ParseError::ParseError(format!("{} requires an http(s) rpc url", flag)))
+        }
+        None => Ok(()),
+    }
+}
+
+/// parse patterns of errors marking blocks unavailable, if unavailable blocks are skipped
+fn parse_unavailable_log_ranges(args: &Args) -> Result<Option<UnavailableLogRanges>, ParseError> {
+    if !args.skip_unavailable_blocks {
+        if args.unavailable_error_patterns.is_some() {
+            let message = "--unavailable-error-patterns requires --skip-unavailable-blocks";
+            return Err(ParseError::ParseError(message.to_string()))
+        }
+        return Ok(None)
+    }
+    let patterns = match &args.unavailable_error_patterns {
+        Some(patterns) => patterns.clone(),
+        None => {
+            DEFAULT_UNAVAILABLE_LOG_PATTERNS.iter().map(|pattern| pattern.to_string()).collect()
+        }
+    };
+    if patterns.iter().any(|pattern| pattern.is_empty()) {
+        let message = "--unavailable-error-patterns cannot be empty";
+        return Err(ParseError::ParseError(message.to_string()))
+    }
+    Ok(Some(UnavailableLogRanges::new(patterns)))
+}
+
+/// parse extra fields added to eth_getLogs params, these cannot replace cryo's own filter fields
+fn parse_log_filter_params(
+    args: &Args,
+) -> Result<Option<serde_json::Map<String, serde_json::Value>>, ParseError> {
+    let params = match &args.log_filter_params {
+        Some(params) => params,
+        None => return Ok(None),
+    };
+    let params = match serde_json::from_str(params) {
+        Ok(serde_json::Value::Object(params)) => params,
+        _ => {
+            let message = "--log-filter-params must be a JSON object";
+            return Err(ParseError::ParseError(message.to_string()))
+        }
+    };
+    let reserved = ["fromBlock", "toBlock", "blockHash", "address", "topics"];
+    if let Some(key) = params.keys().find(|key| reserved.contains(&key.as_str())) {
+        return Err(ParseError::ParseError(format!("--log-filter-params cannot set {}", key)))
+    }
+    Ok(Some(params))
+}
+
+/// header value that is marked sensitive so that it is never logged
+fn sensitive_header_value(value: &str) -> Result<HeaderValue, ParseError> {
+    let mut value = HeaderValue::from_str(value)
+        .map_err(|_| ParseError::ParseError("invalid rpc header value".to_string()))?;
+    value.set_sensitive(true);
+    Ok(value)
+}
+
+/// create http provider whose client attaches headers to every request, including retries
+fn new_http_provider(
+    rpc_url: &str,
+    headers: HeaderMap,
+    max_retries: u32,
+    initial_backoff: u64,
+    retry_budget: Option<Arc<RetryBudget>>,
+    concurrency_throttle: Option<Arc<ConcurrencyThrottle>>,
+) -> Result<Provider<RetryClient<Http>>, ParseError> {
+    let url = Url::parse(rpc_url)
+        .map_err(|_| ParseError::ParseError(format!("invalid rpc url: {}", rpc_url)))?;
+    let client = reqwest::Client::builder()
+        .default_headers(headers)
+        .build()
+        .map_err(|_e| ParseError::ParseError("could not connect to provider".to_string()))?;
+    let http = Http::new_with_client(url, client);
+    // timeouts are retried by the policy rather than the client, so they count against the budget
+    let retry_policy = Box::new(SourceRetryPolicy::new(retry_budget, concurrency_throttle));
+    let client = RetryClientBuilder::default()
+        .rate_limit_retries(max_retries)
+        .timeout_retries(0)
+        .initial_backoff(std::time::Duration::from_millis(initial_backoff))
+        .build(http, retry_policy);
+    Ok(Provider::new(client))
+}
+
 pub(crate) fn parse_rpc_url(args: &Args) -> Result<String, ParseError> {
     // get MESC url
     let mesc_url = if mesc::is_mesc_enabled() {
@@ -107,9 +274,236 @@ pub(crate) fn parse_rpc_url(args: &Args) -> Result<String, ParseError> {
     };
 
     // prepend http or https if need be
-    if !url.starts_with("http") & !url.starts_with("ws") & !url.ends_with(".ipc") {
+    if rpc_transport(&url).is_none() {
         Ok("http://".to_string() + url.as_str())
     } else {
         Ok(url)
     }
 }
+
+/// transport used to connect to rpc endpoint
+#[derive(Debug, PartialEq)]
+enum RpcTransport {
+    Http,
+    Ws,
+    Ipc,
+}
+
+/// detect transport from connection string, ipc sockets are given as paths or as ipc://PATH
+fn rpc_transport(rpc_url: &str) -> Option<RpcTransport> {
+    if rpc_url.starts_with("http") {
+        Some(RpcTransport::Http)
+    } else if rpc_url.starts_with("ws") {
+        Some(RpcTransport::Ws)
+    } else if rpc_url.starts_with("ipc://") ||
+        rpc_url.ends_with(".ipc") ||
+        rpc_url.starts_with(r"\\.\pipe\") ||
+        is_socket(rpc_url)
+    {
+        Some(RpcTransport::Ipc)
+    } else {
+        None
+    }
+}
+
+#[cfg(unix)]
+fn is_socket(path: &str) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    std::fs::metadata(path).map(|metadata| metadata.file_type().is_socket()).unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_socket(_path: &str) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        sync::atomic::{AtomicU64, Ordering},
+    };
+
+    /// read a whole http request from stream
+    fn read_request(stream: &mut std::net::TcpStream) -> String {
+        let mut request = Vec::new();
+        let mut buffer = [0u8; 4096];
+        loop {
+            let n = stream.read(&mut buffer).unwrap();
+            request.extend_from_slice(&buffer[..n]);
+            let text = String::from_utf8_lossy(&request);
+            if let Some(end) = text.find("\r\n\r\n") {
+                let content_length = text[..end]
+                    .lines()
+                    .find_map(|line| {
+                        line.to_lowercase()
+                            .strip_prefix("content-length:")
+                            .map(|v| v.trim().parse::<usize>().unwrap())
+                    })
+                    .unwrap_or(0);
+                if request.len() >= end + 4 + content_length {
+                    break
+                }
+            }
+            if n == 0 {
+                break
+            }
+        }
+        String::from_utf8_lossy(&request).to_lowercase()
+    }
+
+    /// serve a single JSON-RPC response and return the raw request that was received
+    fn serve_once(listener: TcpListener) -> std::thread::JoinHandle<String> {
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let request = read_request(&mut stream);
+            let body = r#"{"jsonrpc":"2.0","id":1,"result":"0x1"}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+            request
+        })
+    }
+
+    /// answer every request with an empty response of status, counting the requests received
+    fn serve_status(listener: TcpListener, status: &'static str) -> Arc<AtomicU64> {
+        let n_requests = Arc::new(AtomicU64::new(0));
+        let counter = n_requests.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                read_request(&mut stream);
+                counter.fetch_add(1, Ordering::SeqCst);
+                let response = format!(
+                    "HTTP/1.1 {}\r\nconnection: close\r\ncontent-length: 0\r\n\r\n",
+                    status
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        n_requests
+    }
+
+    #[tokio::test]
+    async fn test_retries_stop_once_the_retry_budget_is_used_up() {
+        for status in ["503 Service Unavailable", "429 Too Many Requests"] {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let rpc_url = format!("http://{}", listener.local_addr().unwrap());
+            let n_requests = serve_status(listener, status);
+            let budget = Arc::new(RetryBudget::new(2));
+            let provider =
+                new_http_provider(&rpc_url, HeaderMap::new(), 10, 0, Some(budget.clone()), None)
+                    .unwrap();
+
+            // the first request is retried twice, then the budget is used up
+            assert!(provider.get_chainid().await.is_err());
+            assert_eq!(n_requests.load(Ordering::SeqCst), 3);
+            assert_eq!(budget.used(), 2);
+
+            // later requests are not retried at all
+            assert!(provider.get_chainid().await.is_err());
+            assert_eq!(n_requests.load(Ordering::SeqCst), 4);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rpc_headers_are_sent() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let rpc_url = format!("http://{}", listener.local_addr().unwrap());
+        let server = serve_once(listener);
+
+        let args = Args {
+            rpc_header: Some(vec!["X-Api-Key: abc123".to_string()]),
+            rpc_bearer: Some("secret".to_string()),
+            ..Default::default()
+        };
+        let headers = parse_rpc_headers(&args).unwrap();
+        let provider = new_http_provider(&rpc_url, headers, 0, 0, None, None).unwrap();
+        let chain_id = provider.get_chainid().await.unwrap();
+        assert_eq!(chain_id.as_u64(), 1);
+
+        let request = server.join().unwrap();
+        assert!(request.contains("x-api-key: abc123"));
+        assert!(request.contains("authorization: bearer secret"));
+    }
+
+    #[test]
+    fn test_rpc_transport_detected_from_connection_string() {
+        assert_eq!(rpc_transport("http://localhost:8545"), Some(RpcTransport::Http));
+        assert_eq!(rpc_transport("https://eth.example.com"), Some(RpcTransport::Http));
+        assert_eq!(rpc_transport("ws://localhost:8546"), Some(RpcTransport::Ws));
+        assert_eq!(rpc_transport("wss://eth.example.com"), Some(RpcTransport::Ws));
+        assert_eq!(rpc_transport("/data/geth/geth.ipc"), Some(RpcTransport::Ipc));
+        assert_eq!(rpc_transport("ipc:///data/reth/reth.sock"), Some(RpcTransport::Ipc));
+        assert_eq!(rpc_transport(r"\\.\pipe\geth.ipc"), Some(RpcTransport::Ipc));
+        assert_eq!(rpc_transport("localhost:8545"), None);
+        assert_eq!(rpc_transport("/data/reth/reth.sock"), None);
+
+        #[cfg(unix)]
+        {
+            let path = std::env::temp_dir().join(format!("cryo_rpc_{}.sock", std::process::id()));
+            let _ = std::fs::remove_file(&path);
+            let _listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+            assert_eq!(rpc_transport(path.to_str().unwrap()), Some(RpcTransport::Ipc));
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_rpc_credentials_require_http_transport() {
+        let args = Args { rpc_bearer: Some("secret".to_string()), ..Default::default() };
+        assert!(validate_rpc_credentials(&args, &RpcTransport::Http).is_ok());
+        assert!(validate_rpc_credentials(&args, &RpcTransport::Ws).is_err());
+        assert!(validate_rpc_credentials(&args, &RpcTransport::Ipc).is_err());
+        assert!(validate_rpc_credentials(&Args::default(), &RpcTransport::Ws).is_ok());
+    }
+
+    #[test]
+    fn test_log_filter_params_must_be_object() {
+        let parse = |params: &str| {
+            let args = Args { log_filter_params: Some(params.to_string()), ..Default::default() };
+            parse_log_filter_params(&args)
+        };
+        let params = parse(r#"{"limit": 1000}"#).unwrap().unwrap();
+        assert_eq!(params["limit"], 1000);
+        assert!(parse("[1000]").is_err());
+        assert!(parse("limit=1000").is_err());
+        assert!(parse(r#"{"toBlock": "0x1"}"#).is_err());
+        assert_eq!(parse_log_filter_params(&Args::default()).unwrap(), None);
+    }
+
+    #[test]
+    fn test_rpc_credentials_redacted() {
+        let args = Args {
+            rpc_header: Some(vec!["X-Api-Key: abc123".to_string()]),
+            rpc_basic_auth: Some("user:pass".to_string()),
+            ..Default::default()
+        };
+        let serialized = serde_json::to_string(&args.redacted()).unwrap();
+        assert!(!serialized.contains("abc123"));
+        assert!(!serialized.contains("user:pass"));
+
+        let command =
+            ["cryo", "logs", "--rpc-bearer", "secret", "--rpc-header=X-Key:abc", "-b", "1"]
+                .iter()
+                .map(|word| word.to_string())
+                .collect();
+        assert_eq!(
+            crate::args::redact_cli_command(command),
+            vec![
+                "cryo",
+                "logs",
+                "--rpc-bearer",
+                "<redacted>",
+                "--rpc-header=<redacted>",
+                "-b",
+                "1"
+            ]
+        );
+    }
+}
@@ -326,7 +326,6 @@ mod tests {
     use governor::{Quota, RateLimiter};
 
     use super::*;
-    use cryo_freeze::SourceLabels;
     use ethers::prelude::*;
 
     async fn setup_source() -> Source {
@@ -348,14 +347,9 @@ mod tests {
         let semaphore = tokio::sync::Semaphore::new(max_concurrent_requests as usize);
 
         Source {
-            provider: provider.into(),
             semaphore: Arc::new(Some(semaphore)),
             rate_limiter: Arc::new(rate_limiter),
-            chain_id: 1,
-            inner_request_size: 1,
-            max_concurrent_chunks: None,
-            rpc_url: "".to_string(),
-            labels: SourceLabels::default(),
+            ..Source::from_provider(provider)
         }
     }
 
@@ -3,6 +3,7 @@
 // - only one default command is remembered for each directory
 // - remembered commands are only activated when datatypes are omitted
 // - can add `--dry` or any other additional arguments to override remembered arguments
+// - rpc credentials are redacted in remembered commands, so they must be given again
 
 use crate::args::Args;
 use cryo_freeze::ParseError;
@@ -24,8 +25,9 @@ pub(crate) struct RememberedCommand {
 
 pub(crate) fn save_remembered_command(cryo_dir: PathBuf, args: &Args) -> Result<(), ParseError> {
     let cryo_version = cryo_freeze::CRYO_VERSION.to_string();
-    let args = Args { remember: false, ..args.clone() };
+    let args = Args { remember: false, ..args.redacted() };
     let command = std::env::args().filter(|w| w != "--remember").collect::<Vec<_>>();
+    let command = crate::args::redact_cli_command(command);
 
     let remembered = RememberedCommand { cryo_version, command, args };
 
@@ -52,6 +54,61 @@ pub(crate) fn load_remembered_command(cryo_dir: PathBuf) -> Result<RememberedCom
     Ok(remembered)
 }
 
+/// merge remembered args into args, remembered args taking precedence except for the rpc
+/// credentials that were redacted when they were remembered
+pub(crate) fn merge_remembered_args(args: Args, remembered: Args) -> Result<Args, ParseError> {
+    let remembered = if remembered.has_redacted_credentials() {
+        let has_credentials =
+            args.rpc_header.is_some() || args.rpc_bearer.is_some() || args.rpc_basic_auth.is_some();
+        if !has_credentials {
+            return Err(ParseError::ParseError(
+                "rpc credentials are redacted in remembered commands, specify them again"
+                    .to_string(),
+            ))
+        }
+        Args { rpc_header: None, rpc_bearer: None, rpc_basic_auth: None, ..remembered }
+    } else {
+        remembered
+    };
+    Ok(args.merge_with_precedence(remembered))
+}
+
 pub(crate) fn get_remembered_command_path(cryo_dir: PathBuf) -> Result<PathBuf, ParseError> {
     Ok(cryo_dir.join(REMEMBER_FILENAME))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remembered_command_does_not_store_credentials() {
+        let cryo_dir =
+            std::env::temp_dir().join(format!("cryo_test_remember_{}", std::process::id()));
+        std::fs::create_dir_all(&cryo_dir).unwrap();
+        let args = Args {
+            datatype: vec!["logs".to_string()],
+            rpc_bearer: Some("secret-token".to_string()),
+            rpc_basic_auth: Some("user:hunter2".to_string()),
+            remember: true,
+            ..Default::default()
+        };
+        save_remembered_command(cryo_dir.clone(), &args).unwrap();
+        let contents =
+            std::fs::read_to_string(get_remembered_command_path(cryo_dir.clone()).unwrap())
+                .unwrap();
+        assert!(!contents.contains("secret-token"));
+        assert!(!contents.contains("hunter2"));
+
+        // credentials of the current command replace the redacted ones
+        let remembered = load_remembered_command(cryo_dir.clone()).unwrap();
+        assert!(merge_remembered_args(Args::default(), remembered.args.clone()).is_err());
+        let current = Args { rpc_bearer: Some("new-token".to_string()), ..Default::default() };
+        let merged = merge_remembered_args(current, remembered.args).unwrap();
+        assert_eq!(merged.datatype, vec!["logs".to_string()]);
+        assert_eq!(merged.rpc_bearer.as_deref(), Some("new-token"));
+        assert_eq!(merged.rpc_basic_auth, None);
+
+        std::fs::remove_dir_all(&cryo_dir).unwrap();
+    }
+}
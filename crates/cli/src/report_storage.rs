@@ -0,0 +1,159 @@
+// reports of prior runs can be read from remote storage, so that ci jobs can retry from state
+// kept in object storage
+// - local paths are read and written on the filesystem
+// - http(s) urls are read with GET
+// - s3://BUCKET/KEY urls are read with GET from the bucket's https endpoint, or from the
+//   AWS_ENDPOINT_URL endpoint if set, requests are not signed so objects must be readable without
+//   credentials, and objects of private buckets are rejected with an error saying so
+// - reports are only written locally, remote locations are read-only
+
+use cryo_freeze::{err, CollectError, LocalReportStorage, ReportStorage};
+
+/// storage holding the report at location, chosen by the scheme of location
+pub(crate) fn report_storage(location: &str) -> Box<dyn ReportStorage> {
+    if location.starts_with("s3://") {
+        Box::new(S3ReportStorage::new(endpoint_from_env()))
+    } else if location.starts_with("http://") || location.starts_with("https://") {
+        Box::new(HttpReportStorage::default())
+    } else {
+        Box::new(LocalReportStorage)
+    }
+}
+
+/// reports read from http(s) urls
+#[derive(Default)]
+pub(crate) struct HttpReportStorage {
+    client: reqwest::Client,
+}
+
+#[async_trait::async_trait]
+impl ReportStorage for HttpReportStorage {
+    async fn read_report(&self, location: &str) -> Result<String, CollectError> {
+        http_get(&self.client, location, None).await
+    }
+
+    async fn write_report(&self, location: &str, _contents: &str) -> Result<(), CollectError> {
+        Err(
+            err(format!("cannot write report to {}, http reports are read-only", location).as_str()),
+        )
+    }
+}
+
+/// reports read from s3://BUCKET/KEY urls
+pub(crate) struct S3ReportStorage {
+    client: reqwest::Client,
+    endpoint: Option<String>,
+}
+
+impl S3ReportStorage {
+    /// create storage reading from endpoint, or from the https endpoint of each bucket if None
+    pub(crate) fn new(endpoint: Option<String>) -> Self {
+        S3ReportStorage { client: reqwest::Client::new(), endpoint }
+    }
+
+    /// https url of the object at an s3://BUCKET/KEY location
+    pub(crate) fn object_url(&self, location: &str) -> Result<String, CollectError> {
+        let path = location.strip_prefix("s3://").unwrap_or(location);
+        let (bucket, key) = match path.split_once('/') {
+            Some((bucket, key)) if !bucket.is_empty() && !key.is_empty() => (bucket, key),
+            _ => return Err(err(format!("invalid s3 location: {}", location).as_str())),
+        };
+        Ok(match &self.endpoint {
+            Some(endpoint) => format!("{}/{}/{}", endpoint.trim_end_matches('/'), bucket, key),
+            None => format!("https://{}.s3.amazonaws.com/{}", bucket, key),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl ReportStorage for S3ReportStorage {
+    async fn read_report(&self, location: &str) -> Result<String, CollectError> {
+        let private = "s3 requests are not signed, so reports in private buckets cannot be read. \
+                       make the report publicly readable, or download it and pass its path";
+        http_get(&self.client, &self.object_url(location)?, Some(private)).await
+    }
+
+    async fn write_report(&self, location: &str, _contents: &str) -> Result<(), CollectError> {
+        Err(err(format!("cannot write report to {}, s3 reports are read-only", location).as_str()))
+    }
+}
+
+fn endpoint_from_env() -> Option<String> {
+    ["AWS_ENDPOINT_URL_S3", "AWS_ENDPOINT_URL"]
+        .iter()
+        .find_map(|name| std::env::var(name).ok().filter(|value| !value.is_empty()))
+}
+
+/// fetch report at url, denied_reason explains responses refusing access if given
+async fn http_get(
+    client: &reqwest::Client,
+    url: &str,
+    denied_reason: Option<&str>,
+) -> Result<String, CollectError> {
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| err(format!("could not fetch report {}: {}", url, e).as_str()))?;
+    let status = response.status();
+    if let (true, Some(reason)) = (status == 401 || status == 403, denied_reason) {
+        let message = format!("could not fetch report {}: status {}, {}", url, status, reason);
+        return Err(err(message.as_str()))
+    }
+    if !status.is_success() {
+        let message = format!("could not fetch report {}: status {}", url, status);
+        return Err(err(message.as_str()))
+    }
+    response.text().await.map_err(|_| err(format!("could not read report {}", url).as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+
+    #[test]
+    fn test_s3_object_urls() {
+        let storage = S3ReportStorage::new(None);
+        assert_eq!(
+            storage.object_url("s3://ci-state/cryo/report.json").unwrap(),
+            "https://ci-state.s3.amazonaws.com/cryo/report.json"
+        );
+        assert!(storage.object_url("s3://ci-state").is_err());
+        assert!(storage.object_url("s3:///report.json").is_err());
+
+        let storage = S3ReportStorage::new(Some("http://localhost:9000/".to_string()));
+        assert_eq!(
+            storage.object_url("s3://ci-state/report.json").unwrap(),
+            "http://localhost:9000/ci-state/report.json"
+        );
+    }
+
+    /// serve each response to one connection on a local port, returning the endpoint
+    fn serve(responses: Vec<&'static str>) -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = [0u8; 4096];
+                let _ = stream.read(&mut request);
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        endpoint
+    }
+
+    #[tokio::test]
+    async fn test_s3_reports_of_public_buckets_are_read_and_private_buckets_rejected() {
+        let public = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{}";
+        let private = "HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+        let storage = S3ReportStorage::new(Some(serve(vec![public, private])));
+
+        let location = "s3://ci-state/report.json";
+        assert_eq!(storage.read_report(location).await.unwrap(), "{}");
+        let error = storage.read_report(location).await.unwrap_err().to_string();
+        assert!(error.contains("private buckets cannot be read"));
+        assert!(storage.write_report(location, "{}").await.is_err());
+    }
+}
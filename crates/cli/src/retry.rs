@@ -0,0 +1,115 @@
+// using --retry-report collects only the chunks that errored in a prior run
+// - block ranges of errored chunks are read from the report of the prior run
+// - the report can be a local path, an http(s) url, or an s3://BUCKET/KEY url
+// - other arguments come from the prior run, overridden by arguments of the current command
+// - rpc credentials are redacted in reports, so they must be given again
+// - the retry is a normal run and writes its own report
+
+use crate::{args::Args, report_storage::report_storage};
+use cryo_freeze::{ParseError, ReportStorage};
+
+pub(crate) async fn retry_args(args: Args, report_location: &str) -> Result<Args, ParseError> {
+    let storage = report_storage(report_location);
+    retry_args_from_storage(args, storage.as_ref(), report_location).await
+}
+
+async fn retry_args_from_storage(
+    args: Args,
+    storage: &dyn ReportStorage,
+    report_location: &str,
+) -> Result<Args, ParseError> {
+    let retry = cryo_freeze::load_report_retry(storage, report_location)
+        .await
+        .map_err(|e| ParseError::ParseError(format!("cannot retry report: {}", e)))?;
+    if retry.errored_block_ranges.is_empty() {
+        return Err(ParseError::ParseError("report has no errored chunks to retry".to_string()))
+    }
+
+    let prior_args: Args = match &retry.args {
+        Some(prior_args) => serde_json::from_str(prior_args)
+            .map_err(|_| ParseError::ParseError("could not parse args of report".to_string()))?,
+        None => Args::default(),
+    };
+    // hooks recorded in a report are never run, only one given to this command
+    let post_run_hook = args.post_run_hook.clone();
+    let args = prior_args.merge_with_precedence(args);
+    if args.has_redacted_credentials() {
+        return Err(ParseError::ParseError(
+            "rpc credentials are redacted in reports, specify them again to retry".to_string(),
+        ))
+    }
+
+    // block ranges of reports are inclusive, block arguments exclude their end
+    let blocks = retry
+        .errored_block_ranges
+        .iter()
+        .map(|(start, end)| format!("{}:{}", start, end + 1))
+        .collect();
+    Ok(Args {
+        blocks: Some(blocks),
+        blocks_from: None,
+        timestamps: None,
+        txs: None,
+        retry_report: None,
+        post_run_hook,
+        ..args
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cryo_freeze::{err, CollectError, CRYO_VERSION};
+    use std::{collections::HashMap, sync::Mutex};
+
+    /// remote storage holding reports in memory
+    #[derive(Default)]
+    struct MockStorage {
+        reports: Mutex<HashMap<String, String>>,
+    }
+
+    #[async_trait::async_trait]
+    impl ReportStorage for MockStorage {
+        async fn read_report(&self, location: &str) -> Result<String, CollectError> {
+            let reports = self.reports.lock().unwrap();
+            reports.get(location).cloned().ok_or_else(|| err("report not found"))
+        }
+
+        async fn write_report(&self, location: &str, contents: &str) -> Result<(), CollectError> {
+            self.reports.lock().unwrap().insert(location.to_string(), contents.to_string());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_from_remote_report() {
+        let prior_args = Args {
+            datatype: vec!["logs".to_string()],
+            blocks: Some(vec!["0:3000".to_string()]),
+            rpc: Some("https://rpc.example".to_string()),
+            ..Default::default()
+        };
+        let report = serde_json::json!({
+            "cryo_version": CRYO_VERSION,
+            "cli_command": null,
+            "args": serde_json::to_string(&prior_args).unwrap(),
+            "results": {
+                "errored_paths": ["a.parquet"],
+                "errored_block_ranges": [[1000, 1999]],
+            },
+        });
+        let storage = MockStorage::default();
+        let location = "s3://ci-state/cryo/report.json";
+        storage.write_report(location, &report.to_string()).await.unwrap();
+
+        let args = Args { retry_report: Some(location.to_string()), ..Default::default() };
+        let args = retry_args_from_storage(args, &storage, location).await.unwrap();
+        assert_eq!(args.datatype, vec!["logs".to_string()]);
+        assert_eq!(args.blocks, Some(vec!["1000:2000".to_string()]));
+        assert_eq!(args.rpc.as_deref(), Some("https://rpc.example"));
+        assert_eq!(args.retry_report, None);
+
+        let missing = "https://ci.example/missing.json";
+        assert!(retry_args_from_storage(Args::default(), &storage, missing).await.is_err());
+    }
+}
@@ -1,9 +1,12 @@
-use crate::{args, parse, remember};
+use crate::{args, parse, remember, retry};
 use clap_cryo::Parser;
 use color_print::cstr;
 use colored::Colorize;
-use cryo_freeze::{err, CollectError, ExecutionEnv, FreezeSummary};
-use std::{sync::Arc, time::SystemTime};
+use cryo_freeze::{
+    err, ChainReport, CollectError, Datatype, ExecutionEnv, FreezeSummary, SqlDialect,
+    DEFAULT_COMPACT_TARGET_BYTES,
+};
+use std::{path::PathBuf, sync::Arc, time::SystemTime};
 
 /// run cli
 pub async fn run(args: args::Args) -> Result<Option<FreezeSummary>, CollectError> {
@@ -11,9 +14,17 @@ pub async fn run(args: args::Args) -> Result<Option<FreezeSummary>, CollectError
     if args.datatype.first() == Some(&"help".to_string()) {
         return handle_help_subcommands(args).await
     }
+    if args.datatype.first() == Some(&"compact".to_string()) {
+        return handle_compact(args)
+    }
+
+    let cryo_dir = args.output_dir_path().join(".cryo");
 
-    let cryo_dir: std::path::PathBuf = args.output_dir.clone().into();
-    let cryo_dir = cryo_dir.join(".cryo");
+    // retry errored chunks of a prior run
+    let args = match args.retry_report.clone() {
+        Some(report_location) => retry::retry_args(args, &report_location).await?,
+        None => args,
+    };
 
     // remember previous command
     let args = if args.datatype.is_empty() {
@@ -22,25 +33,75 @@ pub async fn run(args: args::Args) -> Result<Option<FreezeSummary>, CollectError
             eprintln!("remembered command comes from different cryo version, proceed with caution");
             eprintln!();
         };
-        println!(
+        eprintln!(
             "{} {} {}",
             "remembering previous command:".truecolor(170, 170, 170),
             "cryo".bold().white(),
             remembered.command.into_iter().skip(1).collect::<Vec<_>>().join(" ").white().bold()
         );
-        println!();
-        args.merge_with_precedence(remembered.args)
+        eprintln!();
+        // hooks are only run when given to this command, never from a remembered command
+        let post_run_hook = args.post_run_hook.clone();
+        args::Args { post_run_hook, ..remember::merge_remembered_args(args, remembered.args)? }
     } else {
         args
     };
 
     // remember current command
     if args.remember {
-        println!("remembering this command for future use");
-        println!();
+        eprintln!("remembering this command for future use");
+        eprintln!();
         remember::save_remembered_command(cryo_dir, &args)?;
     }
 
+    // only collection can be streamed to stdout
+    let other_modes = args.decode_only.is_some() ||
+        args.replay.is_some() ||
+        args.schema_only ||
+        args.chains.is_some() ||
+        args.compare_rpc.is_some();
+    if args.stdout_output() && other_modes {
+        let message =
+            "--output - cannot be used with --decode-only, --replay, --schema-only, --chains, or \
+             --compare-rpc";
+        return Err(err(message))
+    }
+
+    // handle decode-only mode
+    if let Some(paths) = &args.decode_only {
+        return handle_decode_only(&args, paths)
+    }
+
+    // handle replay of dumped log responses
+    if let Some(dir) = &args.replay {
+        return handle_replay(&args, dir)
+    }
+
+    // handle schema printing
+    if args.print_schema {
+        return handle_print_schema(&args)
+    }
+
+    // handle ddl printing
+    if let Some(dialect) = &args.print_ddl {
+        return handle_print_ddl(&args, dialect)
+    }
+
+    // handle schema-only output
+    if args.schema_only {
+        return handle_schema_only(&args).await
+    }
+
+    // handle multichain runs
+    if args.chains.is_some() {
+        return handle_multichain(&args).await
+    }
+
+    // handle comparison of two rpcs
+    if let Some(compare_rpc) = &args.compare_rpc {
+        return handle_compare_rpc(&args, compare_rpc).await
+    }
+
     // handle regular flow
     let t_start_parse = Some(SystemTime::now());
     let (query, source, sink, env) = match parse::parse_args(&args).await {
@@ -51,7 +112,287 @@ pub async fn run(args: args::Args) -> Result<Option<FreezeSummary>, CollectError
     let source = Arc::new(source);
     let env = ExecutionEnv { t_start_parse, ..env };
     let env = env.set_start_time();
-    cryo_freeze::freeze(&query, &source, &sink, &env).await
+    let summary = cryo_freeze::freeze(&query, &source, &sink, &env).await?;
+    ensure_nonempty(&args, summary)
+}
+
+/// with --fail-fast-on-empty, turn a run that collected zero rows into an error
+///
+/// runs that skip chunks because their output already exists are not treated as empty
+fn ensure_nonempty(
+    args: &args::Args,
+    summary: Option<FreezeSummary>,
+) -> Result<Option<FreezeSummary>, CollectError> {
+    match &summary {
+        Some(summary)
+            if args.fail_fast_on_empty &&
+                summary.n_rows == 0 &&
+                !summary.completed.is_empty() &&
+                summary.skipped.is_empty() &&
+                summary.errored.is_empty() =>
+        {
+            Err(err(format!(
+                "no rows collected, check the filters used: {}",
+                describe_filters(args)
+            )
+            .as_str()))
+        }
+        _ => Ok(summary),
+    }
+}
+
+/// describe the datasets and filters of a run for diagnostics
+fn describe_filters(args: &args::Args) -> String {
+    let mut filters = vec![format!("datatypes={}", args.datatype.join(","))];
+    let lists = [
+        ("blocks", &args.blocks),
+        ("timestamps", &args.timestamps),
+        ("txs", &args.txs),
+        ("address", &args.address),
+        ("contract", &args.contract),
+        ("from-address", &args.from_address),
+        ("to-address", &args.to_address),
+        ("topic0", &args.topic0),
+        ("topic1", &args.topic1),
+        ("topic2", &args.topic2),
+        ("topic3", &args.topic3),
+        ("log-data-filter", &args.log_data_filter),
+    ];
+    for (name, values) in lists.iter() {
+        if let Some(values) = values {
+            filters.push(format!("{}={}", name, values.join(",")));
+        }
+    }
+    if let Some(blocks_from) = &args.blocks_from {
+        filters.push(format!("blocks-from={}", blocks_from));
+    }
+    if let Some(topic) = &args.any_topic {
+        filters.push(format!("any-topic={}", topic));
+    }
+    if let Some(signature) = &args.event_signature {
+        filters.push(format!("event-signature={}", signature));
+    }
+    filters.join(" ")
+}
+
+fn handle_print_schema(args: &args::Args) -> Result<Option<FreezeSummary>, CollectError> {
+    let (datatypes, schemas) = parse::schemas::parse_schemas(args)?;
+    let mut descriptions = Vec::new();
+    for datatype in datatypes.iter() {
+        let schema = schemas
+            .get(datatype)
+            .ok_or_else(|| err(format!("missing schema for datatype: {:?}", datatype).as_str()))?;
+        descriptions.push(schema.describe());
+    }
+    let serialized = serde_json::to_string_pretty(&descriptions)
+        .map_err(|_| err("could not serialize schemas"))?;
+    println!("{}", serialized);
+    Ok(None)
+}
+
+fn handle_print_ddl(
+    args: &args::Args,
+    dialect: &str,
+) -> Result<Option<FreezeSummary>, CollectError> {
+    let dialect: SqlDialect = dialect.parse()?;
+    let (datatypes, schemas) = parse::schemas::parse_schemas(args)?;
+    let mut statements = Vec::new();
+    for datatype in datatypes.iter() {
+        let schema = schemas
+            .get(datatype)
+            .ok_or_else(|| err(format!("missing schema for datatype: {:?}", datatype).as_str()))?;
+        statements.push(schema.create_table_sql(dialect, &datatype.name()));
+    }
+    println!("{}", statements.join("\n\n"));
+    Ok(None)
+}
+
+/// collect the same query on each chain, continuing past chains that fail
+async fn handle_multichain(args: &args::Args) -> Result<Option<FreezeSummary>, CollectError> {
+    let t_start = SystemTime::now();
+    let chains = parse::chains::parse_chains(args)?;
+    let mut aggregate = FreezeSummary::default();
+    let mut chain_reports = Vec::new();
+    for chain in chains.iter() {
+        let chain_args = parse::chains::chain_args(args, chain);
+        let (chain_id, result) = freeze_chain(&chain_args, chain).await;
+        chain_reports.push(ChainReport::new(
+            chain.name.clone(),
+            chain_id,
+            PathBuf::from(&chain_args.output_dir),
+            &result,
+        ));
+        match result {
+            Ok(Some(summary)) => {
+                aggregate.completed.extend(summary.completed);
+                aggregate.completed_empty.extend(summary.completed_empty);
+                aggregate.skipped.extend(summary.skipped);
+                aggregate.errored.extend(summary.errored);
+                aggregate.n_rows += summary.n_rows;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                eprintln!("could not collect chain {}: {}", chain.name, e);
+                aggregate.errored.push((None, e));
+            }
+        }
+    }
+
+    if !args.no_report {
+        let report_dir = match &args.report_dir {
+            Some(report_dir) => report_dir.clone(),
+            None => args.output_dir_path().join(".cryo/reports"),
+        };
+        let cli_command = args::redact_cli_command(std::env::args().collect());
+        cryo_freeze::write_multichain_report(
+            &report_dir,
+            t_start,
+            Some(cli_command),
+            &chain_reports,
+        )
+        .await?;
+    }
+    ensure_nonempty(args, Some(aggregate))
+}
+
+/// collect a single chain of a multichain run, returning its chain id if the rpc was reached
+async fn freeze_chain(
+    args: &args::Args,
+    chain: &parse::chains::ChainSpec,
+) -> (Option<u64>, Result<Option<FreezeSummary>, CollectError>) {
+    let (query, source, sink, env) = match parse::parse_args(args).await {
+        Ok(opts) => opts,
+        Err(e) => return (None, Err(e.into())),
+    };
+    let chain_id = source.chain_id;
+    if let Err(e) = parse::chains::validate_chain_id(&chain.name, chain_id) {
+        return (Some(chain_id), Err(e.into()))
+    }
+    let source = Arc::new(source);
+    let env = env.set_start_time();
+    (Some(chain_id), cryo_freeze::freeze(&query, &source, &sink, &env).await)
+}
+
+/// collect logs from both rpcs, writing the logs that only one of them returned
+async fn handle_compare_rpc(
+    args: &args::Args,
+    compare_rpc: &str,
+) -> Result<Option<FreezeSummary>, CollectError> {
+    let (query, source, sink, _env) = parse::parse_args(args).await?;
+    let compare_args = args::Args { rpc: Some(compare_rpc.to_string()), ..args.clone() };
+    let compare_source = parse::source::parse_source(&compare_args).await?;
+    if compare_source.chain_id != source.chain_id {
+        let message = format!(
+            "--compare-rpc is on chain {} but --rpc is on chain {}",
+            compare_source.chain_id, source.chain_id
+        );
+        return Err(err(message.as_str()))
+    }
+    let outputs = cryo_freeze::compare_logs(
+        Arc::new(query),
+        Arc::new(source),
+        Arc::new(compare_source),
+        &sink,
+    )
+    .await?;
+    if !args.no_verbose {
+        for (path, n_differing) in outputs.iter() {
+            println!("{} differing logs in {}", n_differing, path.to_string_lossy());
+        }
+    }
+    Ok(None)
+}
+
+async fn handle_schema_only(args: &args::Args) -> Result<Option<FreezeSummary>, CollectError> {
+    let (datatypes, schemas) = parse::schemas::parse_schemas(args)?;
+    let prefix = match &args.network_name {
+        Some(network_name) => network_name.clone(),
+        None => {
+            let source = parse::source::parse_source(args).await?;
+            parse::file_output::parse_network_name(args, source.chain_id)
+        }
+    };
+    let sink = parse::file_output::parse_file_output_with_prefix(args, prefix)?;
+    let outputs = cryo_freeze::write_schema_files(&datatypes, &schemas, &sink)?;
+    if !args.no_verbose {
+        for output in outputs.iter() {
+            println!("wrote schema {}", output.to_string_lossy());
+        }
+    }
+    Ok(None)
+}
+
+fn handle_decode_only(
+    args: &args::Args,
+    paths: &[String],
+) -> Result<Option<FreezeSummary>, CollectError> {
+    let (_, schemas) = parse::schemas::parse_schemas(args)?;
+    let schema = schemas
+        .get(&Datatype::Logs)
+        .ok_or_else(|| err("--decode-only can only be used with the logs datatype"))?;
+    let prefix = args.network_name.clone().unwrap_or_default();
+    let sink = parse::file_output::parse_file_output_with_prefix(args, prefix)?;
+    let paths: Vec<PathBuf> = paths.iter().map(PathBuf::from).collect();
+    let outputs = cryo_freeze::decode_logs(&paths, schema, &sink)?;
+    if !args.no_verbose {
+        for output in outputs.iter() {
+            println!("decoded {}", output.to_string_lossy());
+        }
+    }
+    Ok(None)
+}
+
+/// merge the small files of each datatype in the output dir into files of --target-file-size
+fn handle_compact(args: args::Args) -> Result<Option<FreezeSummary>, CollectError> {
+    let args = args::Args { datatype: args.datatype[1..].to_vec(), ..args };
+    if args.datatype.is_empty() {
+        return Err(err("cryo compact requires the dataset(s) to compact"))
+    }
+    let prefix = args
+        .network_name
+        .clone()
+        .ok_or_else(|| err("cryo compact requires --network-name, the prefix of its files"))?;
+    let target_bytes = match &args.target_file_size {
+        Some(size) => parse::execution::parse_byte_size(size)?,
+        None => DEFAULT_COMPACT_TARGET_BYTES,
+    };
+    let (datatypes, schemas) = parse::schemas::parse_schemas(&args)?;
+    let sink = parse::file_output::parse_file_output_with_prefix(&args, prefix)?;
+    for datatype in datatypes.iter() {
+        let schema = schemas
+            .get(datatype)
+            .ok_or_else(|| err(format!("missing schema for datatype: {:?}", datatype).as_str()))?;
+        let summary = cryo_freeze::compact_files(*datatype, schema, &sink, target_bytes)?;
+        if !args.no_verbose {
+            println!(
+                "compacted {}: {} files ({:.1} MB) into {} files ({:.1} MB)",
+                datatype.name(),
+                summary.n_files_before,
+                summary.n_bytes_before as f64 / 1e6,
+                summary.n_files_after,
+                summary.n_bytes_after as f64 / 1e6,
+            );
+        }
+    }
+    Ok(None)
+}
+
+fn handle_replay(args: &args::Args, dir: &str) -> Result<Option<FreezeSummary>, CollectError> {
+    let query = parse::query::parse_replay_query(args)?;
+    let dumps = cryo_freeze::read_log_responses(&PathBuf::from(dir))?;
+    let chain_id = dumps[0].chain_id;
+    if dumps.iter().any(|dump| dump.chain_id != chain_id) {
+        return Err(err("log responses of several chains cannot be replayed together"))
+    }
+    let prefix = parse::file_output::parse_network_name(args, chain_id);
+    let sink = parse::file_output::parse_file_output_with_prefix(args, prefix)?;
+    let outputs = cryo_freeze::replay_logs(&dumps, &query, &sink, args.chunk_size)?;
+    if !args.no_verbose {
+        for output in outputs.iter() {
+            println!("replayed {}", output.to_string_lossy());
+        }
+    }
+    Ok(None)
 }
 
 async fn handle_help_subcommands(args: args::Args) -> Result<Option<FreezeSummary>, CollectError> {
@@ -0,0 +1,196 @@
+use crate::{err, BlockChunk, CollectError, FileOutput, FreezeSummary, Partition, Query};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+/// block ranges of a freeze mapped to the files containing them, per datatype
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct BlockIndex {
+    /// entries of each datatype, sorted by first block
+    pub datatypes: BTreeMap<String, Vec<BlockIndexEntry>>,
+}
+
+/// file holding an inclusive range of blocks
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct BlockIndexEntry {
+    /// first block of file
+    pub start_block: u64,
+    /// last block of file
+    pub end_block: u64,
+    /// path of file, relative to the directory of the index
+    pub path: PathBuf,
+}
+
+impl BlockIndex {
+    /// add file holding the blocks of chunks, path is relative to the directory of the index
+    fn insert(&mut self, datatype: String, chunks: &[BlockChunk], path: &Path) {
+        let entries = self.datatypes.entry(datatype).or_default();
+        for chunk in chunks.iter() {
+            let ranges = match chunk {
+                BlockChunk::Range(start, end) => vec![(*start, *end)],
+                BlockChunk::Numbers(numbers) => numbers.iter().map(|n| (*n, *n)).collect(),
+            };
+            for (start_block, end_block) in ranges {
+                entries.push(BlockIndexEntry { start_block, end_block, path: path.to_path_buf() });
+            }
+        }
+    }
+
+    fn sort(&mut self) {
+        for entries in self.datatypes.values_mut() {
+            entries.sort_by_key(|entry| entry.start_block);
+        }
+    }
+
+    /// find file of datatype containing block, by binary search over the sorted entries
+    pub fn lookup(&self, datatype: &str, block_number: u64) -> Option<&Path> {
+        let entries = self.datatypes.get(datatype)?;
+        let n_before = entries.partition_point(|entry| entry.start_block <= block_number);
+        let entry = entries.get(n_before.checked_sub(1)?)?;
+        (block_number <= entry.end_block).then_some(entry.path.as_path())
+    }
+}
+
+/// path of the block index of sink
+pub fn block_index_path(sink: &FileOutput) -> PathBuf {
+    sink.output_dir.join(format!("{}__block_index.json", sink.prefix))
+}
+
+/// index the files of completed and skipped partitions, which together cover the whole query
+pub(crate) fn build_block_index(
+    query: &Query,
+    sink: &FileOutput,
+    summary: &FreezeSummary,
+) -> Result<BlockIndex, CollectError> {
+    let mut index = BlockIndex::default();
+    let relative = |path: &Path| path.strip_prefix(&sink.output_dir).unwrap_or(path).to_path_buf();
+
+    // partitions of single-file runs are merged into one file spanning every partition
+    if sink.single_file {
+        let chunks: Vec<BlockChunk> = query
+            .partitions
+            .iter()
+            .filter_map(|partition| partition.block_numbers.clone())
+            .flatten()
+            .collect();
+        for (datatype, path) in crate::single_file::single_file_paths(query, sink)? {
+            index.insert(datatype.name(), &chunks, &relative(&path));
+        }
+        index.sort();
+        return Ok(index)
+    }
+
+    let partitions: Vec<&Partition> =
+        summary.completed.iter().chain(summary.skipped.iter()).collect();
+    for partition in partitions {
+        let chunks = partition
+            .block_numbers
+            .as_ref()
+            .ok_or_else(|| err("block index requires partitions of block ranges"))?;
+        for (datatype, path) in sink.output_paths(query, partition)? {
+            index.insert(datatype.name(), chunks, &relative(&path));
+        }
+    }
+    index.sort();
+    Ok(index)
+}
+
+/// write index of the files of a complete freeze, returning its path
+pub(crate) fn write_block_index(
+    query: &Query,
+    sink: &FileOutput,
+    summary: &FreezeSummary,
+) -> Result<PathBuf, CollectError> {
+    let index = build_block_index(query, sink, summary)?;
+    let path = block_index_path(sink);
+    let serialized =
+        serde_json::to_vec_pretty(&index).map_err(|_| err("could not serialize block index"))?;
+    std::fs::write(&path, serialized).map_err(|_| err("could not write block index"))?;
+    Ok(path)
+}
+
+/// read block index written by a freeze
+pub fn read_block_index(path: &Path) -> Result<BlockIndex, CollectError> {
+    let contents = std::fs::read(path).map_err(|_| err("could not read block index"))?;
+    serde_json::from_slice(&contents).map_err(|_| err("invalid block index"))
+}
+
+/// find file of datatype containing block using the block index at index_path
+pub fn lookup_block_file(
+    index_path: &Path,
+    datatype: &str,
+    block_number: u64,
+) -> Result<Option<PathBuf>, CollectError> {
+    let index = read_block_index(index_path)?;
+    let dir = index_path.parent().unwrap_or(Path::new(""));
+    Ok(index.lookup(datatype, block_number).map(|path| dir.join(path)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ColumnEncoding, Datatype, Dim, MetaDatatype, U256Type};
+    use std::collections::HashMap;
+
+    fn get_query() -> Query {
+        let schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &None,
+                None,
+                None,
+            )
+            .unwrap();
+        Query {
+            datatypes: vec![MetaDatatype::Scalar(Datatype::Logs)],
+            schemas: HashMap::from([(Datatype::Logs, schema)]),
+            partitioned_by: vec![Dim::BlockNumber],
+            ..Default::default()
+        }
+    }
+
+    fn partition(chunk: BlockChunk) -> Partition {
+        Partition { block_numbers: Some(vec![chunk]), ..Default::default() }
+    }
+
+    #[test]
+    fn test_block_resolves_to_file_via_index() {
+        let output_dir =
+            std::env::temp_dir().join(format!("cryo_test_block_index_{}", std::process::id()));
+        std::fs::create_dir_all(&output_dir).unwrap();
+        let sink =
+            FileOutput { output_dir: output_dir.clone(), block_index: true, ..Default::default() };
+        // skipped partitions already exist on disk and are indexed alongside completed ones
+        let summary = FreezeSummary {
+            completed: vec![
+                partition(BlockChunk::Range(1000, 1999)),
+                partition(BlockChunk::Numbers(vec![5000, 5002])),
+            ],
+            skipped: vec![partition(BlockChunk::Range(0, 999))],
+            ..Default::default()
+        };
+
+        let index_path = write_block_index(&get_query(), &sink, &summary).unwrap();
+        assert_eq!(index_path, block_index_path(&sink));
+        let lookup = |block_number| lookup_block_file(&index_path, "logs", block_number).unwrap();
+        assert_eq!(
+            lookup(1500),
+            Some(output_dir.join("ethereum__logs__00001000_to_00001999.parquet"))
+        );
+        assert_eq!(
+            lookup(0),
+            Some(output_dir.join("ethereum__logs__00000000_to_00000999.parquet"))
+        );
+        assert_eq!(lookup(5002), lookup(5000));
+        assert!(lookup(5000).is_some());
+        assert_eq!(lookup(2500), None);
+        assert_eq!(lookup(5001), None);
+        assert_eq!(lookup_block_file(&index_path, "blocks", 1500).unwrap(), None);
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+}
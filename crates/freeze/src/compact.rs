@@ -0,0 +1,400 @@
+use crate::{
+    dataframes, err, gaps, provenance, CollectError, ColumnType, Datatype, FileFilter, FileOutput,
+    Table,
+};
+use polars::prelude::*;
+use std::path::{Path, PathBuf};
+
+/// default size in bytes that compaction fills each merged file up to
+pub const DEFAULT_COMPACT_TARGET_BYTES: u64 = 256 * 1024 * 1024;
+
+/// file counts and sizes of a datatype's files before and after compaction
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompactionSummary {
+    /// number of files before compaction
+    pub n_files_before: u64,
+    /// total bytes of files before compaction
+    pub n_bytes_before: u64,
+    /// number of files after compaction
+    pub n_files_after: u64,
+    /// total bytes of files after compaction
+    pub n_bytes_after: u64,
+    /// merged files written by compaction
+    pub outputs: Vec<PathBuf>,
+}
+
+/// merge the block range files of datatype in the output dir of sink into files of about
+/// target_bytes each
+///
+/// adjacent files are merged in block order into a file named by their overall block range, with
+/// rows sorted by the sort columns of schema. only files of contiguous blocks are merged, so that
+/// a merged file never covers blocks missing from the output. every file must have the same
+/// columns, column types, and embedded filter apart from its blocks, which is checked before
+/// anything is written. merged files carry the filter over for their blocks, and are signed if
+/// sink has a signer. a merged file is moved into place before its inputs are removed, so an
+/// interrupted compaction is resumed by running it again: files whose blocks are covered by a
+/// merged file are leftovers and are removed first
+pub fn compact_files(
+    datatype: Datatype,
+    schema: &Table,
+    sink: &FileOutput,
+    target_bytes: u64,
+) -> Result<CompactionSummary, CollectError> {
+    if !sink.writes_parquet() {
+        return Err(err("only parquet files can be compacted"))
+    }
+    if sink.hive_bucket_size.is_some() || sink.split_by_day {
+        return Err(err("files in hive bucket or day directories cannot be compacted"))
+    }
+    let _temp_dir_cleanup = sink.temp_dir_cleanup();
+
+    let mut files = datatype_files(datatype, sink)?;
+    let n_files_before = files.len() as u64;
+    let n_bytes_before = files.iter().map(|file| file.n_bytes).sum();
+
+    // remove inputs left behind by an interrupted compaction
+    let (leftovers, kept): (Vec<_>, Vec<_>) =
+        files.iter().cloned().partition(|file| files.iter().any(|other| other.covers(file)));
+    for leftover in leftovers.iter() {
+        remove_output_file(&leftover.path)?;
+    }
+    files = kept;
+    if let Some(pair) = files.windows(2).find(|pair| pair[1].start <= pair[0].end) {
+        let message = format!(
+            "files {:?} and {:?} overlap",
+            pair[0].path.file_name(),
+            pair[1].path.file_name()
+        );
+        return Err(err(message.as_str()))
+    }
+
+    // signed files would lose their signatures unless merged files are signed too
+    if sink.signer.is_none() &&
+        files.iter().any(|file| dataframes::signature_path(&file.path).exists())
+    {
+        return Err(err("signed files can only be compacted with a signing key"))
+    }
+
+    // check that every file has the same column types and filter before any file is merged
+    let mut column_types: Option<Vec<(String, ColumnType)>> = None;
+    let mut filter: Option<Option<FileFilter>> = None;
+    for file in files.iter() {
+        let file_filter = provenance::read_file_filter(&file.path)?
+            .map(|file_filter| FileFilter { block_range: None, ..file_filter });
+        match &filter {
+            Some(filter) if *filter != file_filter => {
+                let message =
+                    format!("filter of {:?} differs from other files", file.path.file_name());
+                return Err(err(message.as_str()))
+            }
+            Some(_) => {}
+            None => filter = Some(file_filter),
+        }
+
+        let file_types = file_column_types(&file.path)?;
+        match &column_types {
+            Some(column_types) if *column_types != file_types => {
+                let message =
+                    format!("schema of {:?} differs from other files", file.path.file_name());
+                return Err(err(message.as_str()))
+            }
+            Some(_) => {}
+            None => column_types = Some(file_types),
+        }
+    }
+
+    let mut outputs = Vec::new();
+    for group in group_files(files, target_bytes) {
+        if group.len() > 1 {
+            outputs.push(merge_files(&group, datatype, schema, sink, filter.as_ref())?);
+        }
+    }
+
+    let files_after = datatype_files(datatype, sink)?;
+    Ok(CompactionSummary {
+        n_files_before,
+        n_bytes_before,
+        n_files_after: files_after.len() as u64,
+        n_bytes_after: files_after.iter().map(|file| file.n_bytes).sum(),
+        outputs,
+    })
+}
+
+/// block range file of a datatype
+#[derive(Debug, Clone)]
+struct BlockRangeFile {
+    path: PathBuf,
+    start: u64,
+    end: u64,
+    n_bytes: u64,
+}
+
+impl BlockRangeFile {
+    /// whether the blocks of other are within the blocks of this file, and this file is larger
+    fn covers(&self, other: &BlockRangeFile) -> bool {
+        self.start <= other.start &&
+            other.end <= self.end &&
+            (self.start, self.end) != (other.start, other.end)
+    }
+}
+
+/// block range files of datatype in the output dir of sink, in block order
+fn datatype_files(
+    datatype: Datatype,
+    sink: &FileOutput,
+) -> Result<Vec<BlockRangeFile>, CollectError> {
+    let dir = sink.get_schema_path(datatype)?.parent().map(Path::to_path_buf).unwrap_or_default();
+    let mut paths = Vec::new();
+    let entries = std::fs::read_dir(&dir).map_err(|_| err("could not read output dir"))?;
+    for entry in entries {
+        let path = entry.map_err(|_| err("could not read output dir"))?.path();
+        if path.is_file() {
+            paths.push(path);
+        }
+    }
+    let mut files = gaps::block_range_files(&paths, sink, datatype)
+        .into_iter()
+        .map(|((start, end), path)| {
+            let n_bytes =
+                std::fs::metadata(&path).map_err(|_| err("could not read file size"))?.len();
+            Ok(BlockRangeFile { path, start, end, n_bytes })
+        })
+        .collect::<Result<Vec<_>, CollectError>>()?;
+    files.sort_by_key(|file| (file.start, file.end));
+    Ok(files)
+}
+
+/// names and types of the columns of a parquet file, without reading its rows
+fn file_column_types(path: &Path) -> Result<Vec<(String, ColumnType)>, CollectError> {
+    let file = std::fs::File::open(path).map_err(|_| err("could not open file"))?;
+    let df = ParquetReader::new(file).with_n_rows(Some(0)).finish()?;
+    df.get_columns()
+        .iter()
+        .map(|series| {
+            let column_type = ColumnType::from_dtype(series.dtype()).ok_or_else(|| {
+                let message =
+                    format!("column {} of {:?} has an unsupported type", series.name(), path);
+                err(message.as_str())
+            })?;
+            Ok((series.name().to_string(), column_type))
+        })
+        .collect()
+}
+
+/// split files in block order into runs of files of contiguous blocks whose sizes add up to about
+/// target_bytes, files on either side of missing blocks are never in the same run
+fn group_files(files: Vec<BlockRangeFile>, target_bytes: u64) -> Vec<Vec<BlockRangeFile>> {
+    let mut groups: Vec<Vec<BlockRangeFile>> = Vec::new();
+    let mut group_bytes = 0;
+    for file in files.into_iter() {
+        match groups.last_mut() {
+            Some(group)
+                if group_bytes + file.n_bytes <= target_bytes &&
+                    group.last().map_or(false, |last| file.start == last.end + 1) =>
+            {
+                group_bytes += file.n_bytes;
+                group.push(file);
+            }
+            _ => {
+                group_bytes = file.n_bytes;
+                groups.push(vec![file]);
+            }
+        }
+    }
+    groups
+}
+
+/// write the rows of group to a file named by its overall block range, then remove the group
+///
+/// filter is the filter shared by the files of group apart from their blocks, if they have one
+fn merge_files(
+    group: &[BlockRangeFile],
+    datatype: Datatype,
+    schema: &Table,
+    sink: &FileOutput,
+    filter: Option<&Option<FileFilter>>,
+) -> Result<PathBuf, CollectError> {
+    let mut df: Option<DataFrame> = None;
+    for file in group.iter() {
+        let reader = std::fs::File::open(&file.path).map_err(|_| err("could not open file"))?;
+        let file_df = ParquetReader::new(reader).finish()?;
+        match &mut df {
+            Some(df) => {
+                df.vstack_mut(&file_df)?;
+            }
+            None => df = Some(file_df),
+        }
+    }
+    let mut df = df.ok_or_else(|| err("no files to merge"))?;
+    df.rechunk();
+
+    // files are stacked in block order, rows are then sorted by the schema's sort columns
+    let sort_columns: Vec<String> = schema
+        .sort_columns
+        .iter()
+        .flatten()
+        .map(|column| schema.output_name(column).to_string())
+        .filter(|column| df.column(column).is_ok())
+        .collect();
+    let mut df = if sort_columns.is_empty() { df } else { df.sort(sort_columns, false, false)? };
+
+    let (start, end) = (group[0].start, group[group.len() - 1].end);
+    let path = sink.block_range_path(datatype, start, end)?;
+    let metadata = match filter.cloned().flatten() {
+        Some(filter) => {
+            let filter = FileFilter { block_range: Some((start, end)), ..filter };
+            let serialized = serde_json::to_string(&filter)
+                .map_err(|_| err("could not serialize file filter"))?;
+            vec![(provenance::FILE_FILTER_KEY.to_string(), serialized)]
+        }
+        None => Vec::new(),
+    };
+    dataframes::df_to_file_with_metadata(&mut df, &path, sink, &metadata)
+        .map_err(|_| err("error writing merged file"))?;
+    for file in group.iter() {
+        remove_output_file(&file.path)?;
+    }
+    Ok(path)
+}
+
+/// remove output file along with its row count and signature sidecars
+fn remove_output_file(path: &Path) -> Result<(), CollectError> {
+    std::fs::remove_file(path).map_err(|_| err("could not remove compacted file"))?;
+    for sidecar in [dataframes::row_count_path(path), dataframes::signature_path(path)] {
+        if sidecar.exists() {
+            std::fs::remove_file(sidecar).map_err(|_| err("could not remove compacted file"))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ColumnEncoding, U256Type};
+
+    fn get_sink(output_dir: PathBuf) -> FileOutput {
+        FileOutput { output_dir, ..Default::default() }
+    }
+
+    fn write_file(sink: &FileOutput, start: u64, end: u64) {
+        let block_numbers: Vec<u32> = (start as u32..=end as u32).rev().collect();
+        let log_indices = vec![0u32; block_numbers.len()];
+        let mut df = df!("block_number" => block_numbers, "log_index" => log_indices).unwrap();
+        let path = sink.block_range_path(Datatype::Logs, start, end).unwrap();
+        dataframes::df_to_file(&mut df, &path, sink).unwrap();
+    }
+
+    #[test]
+    fn test_compaction_merges_files_in_block_order_and_resumes() {
+        let output_dir =
+            std::env::temp_dir().join(format!("cryo_test_compact_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&output_dir);
+        let sink = get_sink(output_dir.clone());
+        let mut schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &None,
+                None,
+                None,
+            )
+            .unwrap();
+        schema.sort_columns = Some(vec!["block_number".to_string(), "log_index".to_string()]);
+        for start in [0, 10, 20, 30] {
+            write_file(&sink, start, start + 9);
+        }
+        let n_bytes = datatype_files(Datatype::Logs, &sink).unwrap()[0].n_bytes;
+
+        // files are merged in pairs, sorted by block
+        let target_bytes = 2 * n_bytes + n_bytes / 2;
+        let summary = compact_files(Datatype::Logs, &schema, &sink, target_bytes).unwrap();
+        assert_eq!((summary.n_files_before, summary.n_files_after), (4, 2));
+        assert_eq!(summary.n_bytes_before, 4 * n_bytes);
+        let first = sink.block_range_path(Datatype::Logs, 0, 19).unwrap();
+        let second = sink.block_range_path(Datatype::Logs, 20, 39).unwrap();
+        assert_eq!(summary.outputs, vec![first.clone(), second.clone()]);
+        let df = ParquetReader::new(std::fs::File::open(&first).unwrap()).finish().unwrap();
+        let block_numbers: Vec<Option<u32>> =
+            df.column("block_number").unwrap().u32().unwrap().into_iter().collect();
+        assert_eq!(block_numbers, (0..20).map(Some).collect::<Vec<_>>());
+
+        // inputs left behind by an interrupted compaction are removed when it is run again
+        write_file(&sink, 20, 29);
+        let summary = compact_files(Datatype::Logs, &schema, &sink, 1).unwrap();
+        assert_eq!((summary.n_files_before, summary.n_files_after), (3, 2));
+        assert!(summary.outputs.is_empty());
+
+        // files with different column types are not merged
+        let mut df = df!("block_number" => [40u64, 41], "log_index" => [0u32, 0]).unwrap();
+        let path = sink.block_range_path(Datatype::Logs, 40, 41).unwrap();
+        dataframes::df_to_file(&mut df, &path, &sink).unwrap();
+        assert!(compact_files(Datatype::Logs, &schema, &sink, u64::MAX).is_err());
+        assert_eq!(datatype_files(Datatype::Logs, &sink).unwrap().len(), 3);
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[test]
+    fn test_compaction_keeps_gaps_filters_and_signatures() {
+        use ethers::signers::{LocalWallet, Signer};
+        use std::str::FromStr;
+
+        let output_dir =
+            std::env::temp_dir().join(format!("cryo_test_compact_gaps_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&output_dir);
+        let signer = LocalWallet::from_str(
+            "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318",
+        )
+        .unwrap();
+        let sink = FileOutput { signer: Some(signer.clone()), ..get_sink(output_dir.clone()) };
+        let schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &None,
+                None,
+                None,
+            )
+            .unwrap();
+        let filter = FileFilter {
+            datatype: "logs".to_string(),
+            topic0s: Some(vec!["0x01".to_string()]),
+            ..Default::default()
+        };
+        for (start, end) in [(0, 9), (10, 19), (30, 39)] {
+            let block_numbers: Vec<u32> = (start as u32..=end as u32).collect();
+            let log_indices = vec![0u32; block_numbers.len()];
+            let mut df = df!("block_number" => block_numbers, "log_index" => log_indices).unwrap();
+            let file_filter = FileFilter { block_range: Some((start, end)), ..filter.clone() };
+            let metadata = vec![(
+                provenance::FILE_FILTER_KEY.to_string(),
+                serde_json::to_string(&file_filter).unwrap(),
+            )];
+            let path = sink.block_range_path(Datatype::Logs, start, end).unwrap();
+            dataframes::df_to_file_with_metadata(&mut df, &path, &sink, &metadata).unwrap();
+        }
+
+        // signed files are not compacted without a signing key
+        let unsigned = get_sink(output_dir.clone());
+        assert!(compact_files(Datatype::Logs, &schema, &unsigned, u64::MAX).is_err());
+
+        // files on either side of the missing blocks 20-29 are not merged
+        let summary = compact_files(Datatype::Logs, &schema, &sink, u64::MAX).unwrap();
+        let merged = sink.block_range_path(Datatype::Logs, 0, 19).unwrap();
+        assert_eq!(summary.outputs, vec![merged.clone()]);
+        assert_eq!(summary.n_files_after, 2);
+        assert!(sink.block_range_path(Datatype::Logs, 30, 39).unwrap().exists());
+
+        // the merged file carries the filter over for its blocks and is signed
+        let merged_filter = provenance::read_file_filter(&merged).unwrap().unwrap();
+        assert_eq!(merged_filter, FileFilter { block_range: Some((0, 19)), ..filter });
+        assert!(dataframes::verify_file_signature(&merged, signer.address()).unwrap());
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+}
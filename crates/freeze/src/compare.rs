@@ -0,0 +1,48 @@
+use crate::{
+    collect_partition, dataframes, err, CollectError, Datatype, FileOutput, MetaDatatype, Query,
+    SchemaFunctions, Source,
+};
+use polars::prelude::*;
+use std::path::PathBuf;
+
+/// collect logs of each partition from two sources, writing the logs returned by only one of them
+///
+/// returns the path and number of differing logs of each partition
+pub async fn compare_logs(
+    query: Arc<Query>,
+    source: Arc<Source>,
+    compare_source: Arc<Source>,
+    sink: &FileOutput,
+) -> Result<Vec<(PathBuf, usize)>, CollectError> {
+    query.is_valid()?;
+    if !matches!(query.datatypes.as_slice(), [MetaDatatype::Scalar(Datatype::Logs)]) {
+        return Err(err("only logs can be compared between sources"))
+    }
+    let schema = query.schemas.get_schema(&Datatype::Logs)?;
+
+    let _temp_dir_cleanup = sink.temp_dir_cleanup();
+    let mut outputs = Vec::new();
+    for partition in query.partitions.iter() {
+        let datatype = MetaDatatype::Scalar(Datatype::Logs);
+        let (mut primary, mut compare) = futures::try_join!(
+            collect_partition(datatype.clone(), partition.clone(), query.clone(), source.clone()),
+            collect_partition(datatype, partition.clone(), query.clone(), compare_source.clone()),
+        )?;
+        let (primary, compare) =
+            match (primary.remove(&Datatype::Logs), compare.remove(&Datatype::Logs)) {
+                (Some(primary), Some(compare)) => (primary, compare),
+                _ => return Err(err("no logs dataframe returned")),
+            };
+
+        let mut diff = dataframes::diff_logs(&primary, &compare)?;
+        dataframes::alias_columns(&mut diff, schema)?;
+        let path = sink.get_named_path(&query, partition, "logs_diff".to_string())?;
+        if path.exists() && !sink.overwrite {
+            return Err(err(format!("output file already exists: {:?}", path).as_str()))
+        }
+        dataframes::df_to_file(&mut diff, &path, sink)
+            .map_err(|_| err("error writing logs diff file"))?;
+        outputs.push((path, diff.height()));
+    }
+    Ok(outputs)
+}
@@ -1,6 +1,8 @@
 use crate::*;
 use ethers::prelude::*;
+use futures::StreamExt;
 use polars::prelude::*;
+use std::collections::{HashMap, HashSet};
 
 /// columns for transactions
 #[cryo_to_df::to_df(Datatype::Logs)]
@@ -17,8 +19,22 @@ pub struct Logs {
     topic1: Vec<Option<Vec<u8>>>,
     topic2: Vec<Option<Vec<u8>>>,
     topic3: Vec<Option<Vec<u8>>>,
+    topic1_is_address: Vec<Option<bool>>,
+    topic2_is_address: Vec<Option<bool>>,
+    topic3_is_address: Vec<Option<bool>>,
     data: Vec<Vec<u8>>,
     n_data_bytes: Vec<u32>,
+    data_selector: Vec<Option<Vec<u8>>>,
+    n_topics: Vec<u32>,
+    raw_log_rlp: Vec<Vec<u8>>,
+    tx_from: Vec<Option<Vec<u8>>>,
+    tx_to: Vec<Option<Vec<u8>>>,
+    block_timestamp: Vec<Option<u32>>,
+    base_fee_per_gas: Vec<Option<Vec<u8>>>,
+    event_name: Vec<Option<String>>,
+    decoded: Vec<Option<String>>,
+    log_type: Vec<String>,
+    global_log_seq: Vec<u64>,
     event_cols: indexmap::IndexMap<String, Vec<ethers_core::abi::Token>>,
     chain_id: Vec<u64>,
 }
@@ -59,39 +75,381 @@ impl Dataset for Logs {
     fn arg_aliases() -> Option<std::collections::HashMap<Dim, Dim>> {
         Some([(Dim::Contract, Dim::Address)].into_iter().collect())
     }
+
+    fn post_process(df: DataFrame, schema: &Table) -> Result<DataFrame, CollectError> {
+        let log_index = match schema.per_block_log {
+            Some(PerBlockLog::First) => col("log_index").min(),
+            Some(PerBlockLog::Last) => col("log_index").max(),
+            None => return Ok(df),
+        };
+        // rows keep their order and every column, including decoded event columns
+        df.lazy()
+            .filter(col("log_index").eq(log_index.over([col("block_number")])))
+            .collect()
+            .map_err(CollectError::PolarsError)
+    }
+}
+
+/// data fetched alongside logs to fill optional columns
+#[derive(Default)]
+pub struct LogContext {
+    /// originating transactions of logs, by transaction hash
+    pub transactions: HashMap<H256, Transaction>,
+    /// timestamps of blocks containing logs, by block number
+    pub block_timestamps: HashMap<u64, u32>,
+    /// base fees of blocks containing logs, by block number, None before London
+    pub block_base_fees: HashMap<u64, Option<U256>>,
 }
 
 #[async_trait::async_trait]
 impl CollectByBlock for Logs {
-    type Response = Vec<Log>;
+    type Response = (Vec<Log>, LogContext);
 
-    async fn extract(request: Params, source: Arc<Source>, _: Arc<Query>) -> R<Self::Response> {
-        source.get_logs(&request.ethers_log_filter()?).await
+    async fn extract(request: Params, source: Arc<Source>, query: Arc<Query>) -> R<Self::Response> {
+        let request = match &query.included_topic0s {
+            Some(included) => Params { topic0s: Some(included.topic0s().to_vec()), ..request },
+            None => request,
+        };
+        let filters = match &query.log_address_set {
+            Some(address_set) => {
+                let addresses = Some(address_set.addresses().to_vec());
+                let filters = Params { addresses, ..request.clone() }
+                    .ethers_log_filters(address_set.chunk_size)?;
+                address_set.record_requests(request.block_range()?, filters.len() as u64);
+                filters
+            }
+            None => vec![request.ethers_log_filter()?],
+        };
+        let requests = filters.iter().map(|filter| get_filter_logs(filter, &source, &query));
+        let mut responses = futures::future::try_join_all(requests).await?;
+        if let Some(dir) = &query.log_response_dir {
+            for (filter, logs) in filters.into_iter().zip(responses.iter()) {
+                let dump = LogResponseDump {
+                    chain_id: source.chain_id,
+                    block_range: request.block_range()?,
+                    filter,
+                    logs: logs.clone(),
+                };
+                crate::replay::dump_log_response(dir, &dump)?;
+            }
+        }
+        let logs = match responses.len() {
+            1 => responses.remove(0),
+            _ => merge_logs(responses),
+        };
+        if let Some(included) = &query.included_topic0s {
+            included.record(logs.len());
+        }
+        let context = get_log_context(&logs, &source, &query).await?;
+        Ok((logs, context))
     }
 
     fn transform(response: Self::Response, columns: &mut Self, query: &Arc<Query>) -> R<()> {
-        let schema = query.schemas.get_schema(&Datatype::Logs)?;
-        process_logs(response, columns, schema)
+        let (logs, context) = response;
+        transform_logs(logs, &context, columns, query)
     }
 }
 
 #[async_trait::async_trait]
 impl CollectByTransaction for Logs {
-    type Response = Vec<Log>;
+    type Response = (Vec<Log>, LogContext);
 
-    async fn extract(request: Params, source: Arc<Source>, _: Arc<Query>) -> R<Self::Response> {
-        source.get_transaction_logs(request.transaction_hash()?).await
+    async fn extract(request: Params, source: Arc<Source>, query: Arc<Query>) -> R<Self::Response> {
+        let mut logs = source.get_transaction_logs(request.transaction_hash()?).await?;
+        if let Some(topic) = &query.any_topic {
+            logs.retain(|log| log.topics.iter().skip(1).any(|t| t.as_bytes() == topic.as_slice()));
+        }
+        if let Some(address_set) = &query.log_address_set {
+            logs.retain(|log| address_set.contains(log.address.as_bytes()));
+        }
+        if let Some(included) = &query.included_topic0s {
+            logs.retain(|log| included.includes(log));
+            included.record(logs.len());
+        }
+        let context = get_log_context(&logs, &source, &query).await?;
+        Ok((logs, context))
     }
 
     fn transform(response: Self::Response, columns: &mut Self, query: &Arc<Query>) -> R<()> {
-        let schema = query.schemas.get_schema(&Datatype::Logs)?;
-        process_logs(response, columns, schema)
+        let (logs, context) = response;
+        transform_logs(logs, &context, columns, query)
+    }
+}
+
+/// process logs into columns using the schema and filters of query
+fn transform_logs(
+    logs: Vec<Log>,
+    context: &LogContext,
+    columns: &mut Logs,
+    query: &Query,
+) -> R<()> {
+    let schema = query.schemas.get_schema(&Datatype::Logs)?;
+    process_logs(logs, context, columns, schema, &LogFilters::new(query))
+}
+
+/// filters and caps of a query applied to logs as they are processed into columns
+#[derive(Clone, Copy, Default)]
+struct LogFilters<'a> {
+    topic0_labels: Option<&'a HashMap<Vec<u8>, String>>,
+    data_filters: Option<&'a [LogDataFilter]>,
+    column_cap: Option<&'a DecodedColumnCap>,
+    transaction_index_range: Option<&'a TransactionIndexRange>,
+    null_logs: Option<&'a NullLogs>,
+    excluded_topic0s: Option<&'a Topic0Exclusion>,
+    abi_events_only: Option<&'a AbiEventFilter>,
+    event_name_filter: Option<&'a EventNameFilter>,
+    log_sample: Option<&'a LogSample>,
+}
+
+impl<'a> LogFilters<'a> {
+    fn new(query: &'a Query) -> Self {
+        LogFilters {
+            topic0_labels: query.topic0_labels.as_ref(),
+            data_filters: query.log_data_filters.as_deref(),
+            column_cap: Some(query.decoded_column_cap.as_ref()),
+            transaction_index_range: query.transaction_index_range.as_deref(),
+            null_logs: Some(query.null_logs.as_ref()),
+            excluded_topic0s: query.excluded_topic0s.as_deref(),
+            abi_events_only: query.abi_events_only.as_deref(),
+            event_name_filter: query.event_name_filter.as_deref(),
+            log_sample: query.log_sample.as_deref(),
+        }
+    }
+}
+
+/// collect logs of partition, handing them to on_batch as dataframes of at most batch_size rows
+/// once the partition grows past batch_size. partitions that stay within batch_size are returned
+/// whole so that they take the regular in-memory path
+///
+/// sub-ranges of the partition are requested one at a time and each response is handed on as it
+/// arrives, so that at most one response and one batch of the partition are held in memory
+pub(crate) async fn collect_logs_in_batches<F>(
+    partition: Partition,
+    source: Arc<Source>,
+    query: Arc<Query>,
+    batch_size: usize,
+    on_batch: F,
+) -> R<Option<DataFrame>>
+where
+    F: FnMut(DataFrame) -> R<()> + Send,
+{
+    let mut batcher = LogBatcher::new(batch_size, source.chain_id, on_batch);
+    for params in partition.param_sets(Some(source.inner_request_size))? {
+        let (logs, context) =
+            <Logs as CollectByBlock>::extract(params, source.clone(), query.clone()).await?;
+        batcher.push(logs, &context, &query)?;
+    }
+    batcher.finish(&query)
+}
+
+/// accumulates log columns, flushing them as a dataframe whenever batch_size rows accumulate
+pub(crate) struct LogBatcher<F> {
+    columns: Logs,
+    batch_size: usize,
+    chain_id: u64,
+    on_batch: F,
+    flushed: bool,
+}
+
+impl<F: FnMut(DataFrame) -> R<()>> LogBatcher<F> {
+    pub(crate) fn new(batch_size: usize, chain_id: u64, on_batch: F) -> Self {
+        let batch_size = batch_size.max(1);
+        LogBatcher { columns: Logs::default(), batch_size, chain_id, on_batch, flushed: false }
+    }
+
+    /// process a response in slices of batch_size logs, flushing full batches
+    pub(crate) fn push(&mut self, logs: Vec<Log>, context: &LogContext, query: &Query) -> R<()> {
+        let mut logs = logs.into_iter();
+        loop {
+            let slice: Vec<Log> = logs.by_ref().take(self.batch_size).collect();
+            if slice.is_empty() {
+                return Ok(())
+            }
+            transform_logs(slice, context, &mut self.columns, query)?;
+            if self.columns.n_rows as usize >= self.batch_size {
+                self.flush(query)?;
+            }
+        }
+    }
+
+    /// flush remaining rows, returning all rows instead if no batch was ever flushed
+    pub(crate) fn finish(mut self, query: &Query) -> R<Option<DataFrame>> {
+        if self.flushed {
+            if self.columns.n_rows > 0 {
+                self.flush(query)?;
+            }
+            Ok(None)
+        } else {
+            self.take_df(query).map(Some)
+        }
+    }
+
+    fn flush(&mut self, query: &Query) -> R<()> {
+        let df = self.take_df(query)?;
+        self.flushed = true;
+        (self.on_batch)(df)
+    }
+
+    fn take_df(&mut self, query: &Query) -> R<DataFrame> {
+        let columns = std::mem::take(&mut self.columns);
+        columns
+            .create_dfs(&query.schemas, self.chain_id)?
+            .remove(&Datatype::Logs)
+            .ok_or_else(|| err("missing logs dataframe"))
+    }
+}
+
+/// logs matching filter, fanned out over topic positions 1-3 if query has an any-topic
+async fn get_filter_logs(filter: &Filter, source: &Source, query: &Query) -> R<Vec<Log>> {
+    match &query.any_topic {
+        Some(topic) => {
+            let filters = any_topic_filters(filter, H256::from_slice(topic));
+            let requests = filters.iter().map(|filter| source.get_logs(filter));
+            Ok(merge_logs(futures::future::try_join_all(requests).await?))
+        }
+        None => source.get_logs(filter).await,
+    }
+}
+
+/// one filter per topic position 1-3, together matching logs with topic at any position
+fn any_topic_filters(filter: &Filter, topic: H256) -> Vec<Filter> {
+    (1..4)
+        .map(|position| {
+            let mut filter = filter.clone();
+            filter.topics[position] = Some(ValueOrArray::Value(Some(topic)));
+            filter
+        })
+        .collect()
+}
+
+/// merge logs of several filters, dropping logs returned by more than one filter
+pub(crate) fn merge_logs(responses: Vec<Vec<Log>>) -> Vec<Log> {
+    let mut seen = HashSet::new();
+    let mut logs: Vec<Log> = responses
+        .into_iter()
+        .flatten()
+        .filter(|log| seen.insert((log.block_number, log.log_index)))
+        .collect();
+    logs.sort_by_key(|log| (log.block_number, log.log_index));
+    logs
+}
+
+/// fetch data needed by optional columns of schema
+async fn get_log_context(logs: &[Log], source: &Arc<Source>, query: &Query) -> R<LogContext> {
+    let schema = query.schemas.get_schema(&Datatype::Logs)?;
+    let headers = get_log_blocks(logs, source, schema, &query.log_block_cache).await?;
+    Ok(LogContext {
+        transactions: get_log_transactions(logs, source, schema).await?,
+        block_timestamps: headers
+            .iter()
+            .map(|(block_number, (timestamp, _))| (*block_number, *timestamp))
+            .collect(),
+        block_base_fees: headers
+            .iter()
+            .map(|(block_number, (_, base_fee))| (*block_number, *base_fee))
+            .collect(),
+    })
+}
+
+/// fetch headers of blocks containing logs, one request per distinct block not yet in cache
+async fn get_log_blocks(
+    logs: &[Log],
+    source: &Arc<Source>,
+    schema: &Table,
+    cache: &LogBlockCache,
+) -> R<HashMap<u64, LogBlockHeader>> {
+    if !schema.has_column("block_timestamp") && !schema.has_column("base_fee_per_gas") {
+        return Ok(HashMap::new())
+    }
+
+    let block_numbers: HashSet<u64> =
+        logs.iter().filter_map(|log| log.block_number.map(|bn| bn.as_u64())).collect();
+    let mut headers = HashMap::new();
+    let mut missing = Vec::new();
+    for block_number in block_numbers.into_iter() {
+        match cache.get(block_number) {
+            Some(header) => {
+                headers.insert(block_number, header);
+            }
+            None => missing.push(block_number),
+        }
+    }
+
+    let mut requests = futures::stream::iter(missing)
+        .map(|block_number| {
+            let source = source.clone();
+            async move { (block_number, source.get_block(block_number).await) }
+        })
+        .buffer_unordered(MAX_CONCURRENT_CONTEXT_REQUESTS);
+    while let Some((block_number, result)) = requests.next().await {
+        match result {
+            Ok(Some(block)) => {
+                let header = (block.timestamp.as_u32(), block.base_fee_per_gas);
+                cache.insert(block_number, header);
+                headers.insert(block_number, header);
+            }
+            Ok(None) => return Err(err("could not find block of log")),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(headers)
+}
+
+/// most requests for the transactions or blocks of a response of logs in flight at once
+const MAX_CONCURRENT_CONTEXT_REQUESTS: usize = 64;
+
+/// fetch originating transactions of logs, one request per distinct transaction hash
+async fn get_log_transactions(
+    logs: &[Log],
+    source: &Arc<Source>,
+    schema: &Table,
+) -> R<HashMap<H256, Transaction>> {
+    if !schema.has_column("tx_from") && !schema.has_column("tx_to") {
+        return Ok(HashMap::new())
+    }
+
+    let tx_hashes: HashSet<H256> = logs.iter().filter_map(|log| log.transaction_hash).collect();
+    let mut requests = futures::stream::iter(tx_hashes)
+        .map(|tx_hash| {
+            let source = source.clone();
+            async move { (tx_hash, source.get_transaction(tx_hash).await) }
+        })
+        .buffer_unordered(MAX_CONCURRENT_CONTEXT_REQUESTS);
+
+    let mut transactions = HashMap::new();
+    while let Some((tx_hash, result)) = requests.next().await {
+        match result {
+            Ok(Some(transaction)) => {
+                transactions.insert(tx_hash, transaction);
+            }
+            Ok(None) => return Err(err("could not find transaction of log")),
+            Err(e) => return Err(e),
+        }
     }
+    Ok(transactions)
 }
 
 /// process block into columns
-fn process_logs(logs: Vec<Log>, columns: &mut Logs, schema: &Table) -> R<()> {
+fn process_logs(
+    logs: Vec<Log>,
+    context: &LogContext,
+    columns: &mut Logs,
+    schema: &Table,
+    filters: &LogFilters<'_>,
+) -> R<()> {
+    let LogFilters {
+        topic0_labels,
+        data_filters,
+        column_cap,
+        transaction_index_range,
+        null_logs,
+        excluded_topic0s,
+        abi_events_only,
+        event_name_filter,
+        log_sample,
+    } = *filters;
     let decode_keys = match &schema.log_decoder {
+        _ if schema.skip_decoding => None,
         None => None,
         Some(decoder) => {
             let keys = decoder
@@ -105,23 +463,111 @@ fn process_logs(logs: Vec<Log>, columns: &mut Logs, schema: &Table) -> R<()> {
         }
     };
 
-    for log in logs.iter() {
+    // decode events in parallel up front, rows are still added one log at a time in order
+    let mut predecoded = match (schema.decode_chunk_size, &decode_keys) {
+        (Some(chunk_size), Some(_)) => Some(decode_logs_in_chunks(&logs, schema, chunk_size)),
+        _ => None,
+    };
+
+    for (i, log) in logs.iter().enumerate() {
         if let (Some(bn), Some(tx), Some(ti), Some(li)) =
             (log.block_number, log.transaction_hash, log.transaction_index, log.log_index)
         {
-            // decode event
-            if let (Some(decoder), Some(decode_keys)) = (&schema.log_decoder, &decode_keys) {
-                match decoder.event.parse_log(log.clone().into()) {
-                    Ok(log) => {
-                        for param in log.params {
-                            if decode_keys.contains(param.name.as_str()) {
-                                columns.event_cols.entry(param.name).or_default().push(param.value);
-                            }
+            // drop excluded events, eth_getLogs cannot exclude topics so this is done here
+            if let Some(excluded_topic0s) = excluded_topic0s {
+                if excluded_topic0s.excludes(log) {
+                    continue
+                }
+            }
+
+            // filter by position of transaction within block
+            if let Some(transaction_index_range) = transaction_index_range {
+                if !transaction_index_range.admit(ti.as_u64()) {
+                    continue
+                }
+            }
+
+            // select decoder by block, logs outside of every decoder range cannot be decoded
+            let decoder = schema.log_decoder_at(bn.as_u64());
+            if schema.log_decoder_ranges.is_some() && decoder.is_none() {
+                continue
+            }
+
+            // keep only logs of the decoder's event
+            if let (Some(abi_events_only), Some(decoder)) = (abi_events_only, decoder) {
+                if !abi_events_only.admit(log, decoder) {
+                    continue
+                }
+            }
+
+            // filter by data content
+            if let Some(data_filters) = data_filters {
+                if !data_filters.iter().all(|filter| filter.matches(&log.data)) {
+                    continue
+                }
+            }
+
+            // keep the logs of the sample
+            if let Some(log_sample) = log_sample {
+                if !log_sample.admit(bn.as_u64(), li.as_u64()) {
+                    continue
+                }
+            }
+
+            // decode event, logs that do not decode are kept as null when decoded to json
+            let parsed = match (decoder, &decode_keys) {
+                (Some(decoder), Some(_)) => Some(
+                    predecoded
+                        .as_mut()
+                        .and_then(|predecoded| predecoded[i].take())
+                        .unwrap_or_else(|| decoder.event.parse_log(log.clone().into())),
+                ),
+                _ => None,
+            };
+            let parsed = match parsed {
+                Some(Ok(parsed)) => Some(parsed),
+                Some(Err(_)) if !schema.decoded_json => continue,
+                _ => None,
+            };
+
+            // name of event, given by the decoder if it decodes the log and by the topic0 labels
+            // otherwise
+            let event_name = match (decoder, &parsed) {
+                (Some(decoder), Some(_)) => Some(decoder.event.name.as_str()),
+                _ => match (topic0_labels, log.topics.first()) {
+                    (Some(labels), Some(topic0)) => {
+                        labels.get(topic0.as_bytes()).map(|name| name.as_str())
+                    }
+                    _ => None,
+                },
+            };
+            if let Some(event_name_filter) = event_name_filter {
+                if !event_name_filter.admit(event_name) {
+                    continue
+                }
+            }
+
+            // add decoded params once the log is known to be kept
+            let mut decoded = None;
+            if let (Some(parsed), Some(decode_keys)) = (parsed, &decode_keys) {
+                if schema.decoded_json || schema.has_column("decoded") {
+                    decoded = Some(decoded_params_json(&parsed.params));
+                }
+                if !schema.decoded_json {
+                    for param in parsed.params {
+                        if !decode_keys.contains(param.name.as_str()) {
+                            continue
+                        }
+                        if let Some(values) = columns.event_cols.get_mut(&param.name) {
+                            values.push(param.value);
+                        } else if column_cap
+                            .map_or(true, |cap| cap.admit(&param.name, columns.event_cols.len()))
+                        {
+                            columns.event_cols.insert(param.name, vec![param.value]);
                         }
                     }
-                    Err(_) => continue,
                 }
-            };
+            }
 
             columns.n_rows += 1;
             store!(schema, columns, block_number, bn.as_u32());
@@ -132,24 +578,1652 @@ fn process_logs(logs: Vec<Log>, columns: &mut Logs, schema: &Table) -> R<()> {
             store!(schema, columns, address, log.address.as_bytes().to_vec());
             store!(schema, columns, data, log.data.to_vec());
             store!(schema, columns, n_data_bytes, log.data.len() as u32);
+            store!(
+                schema,
+                columns,
+                data_selector,
+                log.data.get(..4).map(|selector| selector.to_vec())
+            );
+            store!(schema, columns, n_topics, log.topics.len() as u32);
+            store!(schema, columns, raw_log_rlp, ethers::utils::rlp::encode(log).to_vec());
+
+            // originating transaction
+            let transaction = context.transactions.get(&tx);
+            store!(schema, columns, tx_from, transaction.map(|t| t.from.as_bytes().to_vec()));
+            store!(
+                schema,
+                columns,
+                tx_to,
+                transaction.and_then(|t| t.to).map(|to| to.as_bytes().to_vec())
+            );
 
-            // topics
+            store!(
+                schema,
+                columns,
+                block_timestamp,
+                context.block_timestamps.get(&bn.as_u64()).copied()
+            );
+            store!(
+                schema,
+                columns,
+                base_fee_per_gas,
+                context
+                    .block_base_fees
+                    .get(&bn.as_u64())
+                    .copied()
+                    .flatten()
+                    .map(|fee| fee.to_vec_u8())
+            );
+
+            // event name
+            if schema.has_column("event_name") {
+                columns.event_name.push(event_name.map(|name| name.to_string()));
+            }
+            store!(schema, columns, decoded, decoded);
+            store!(schema, columns, log_type, classify_log(log).to_string());
+            store!(schema, columns, global_log_seq, canonical_log_key(bn.as_u64(), li.as_u64()));
+
+            // topics, slots holding addresses are given by the event abi if a decoder is used
+            let abi_address_topics = decoder.map(|decoder| decoder.address_topics());
             for i in 0..4 {
-                let topic = if i < log.topics.len() {
-                    Some(log.topics[i].as_bytes().to_vec())
-                } else {
-                    None
+                let topic = log_topic(log, i);
+                let is_address = topic.as_ref().map(|topic| match &abi_address_topics {
+                    Some(address_topics) => address_topics[i],
+                    None => looks_like_address(topic),
+                });
+                let topic = match topic {
+                    None if schema.zero_hash_topics => Some(vec![0u8; 32]),
+                    topic => topic,
                 };
                 match i {
                     0 => store!(schema, columns, topic0, topic),
-                    1 => store!(schema, columns, topic1, topic),
-                    2 => store!(schema, columns, topic2, topic),
-                    3 => store!(schema, columns, topic3, topic),
+                    1 => {
+                        store!(schema, columns, topic1, topic);
+                        store!(schema, columns, topic1_is_address, is_address);
+                    }
+                    2 => {
+                        store!(schema, columns, topic2, topic);
+                        store!(schema, columns, topic2_is_address, is_address);
+                    }
+                    3 => {
+                        store!(schema, columns, topic3, topic);
+                        store!(schema, columns, topic3_is_address, is_address);
+                    }
                     _ => {}
                 }
             }
+        } else if let Some(null_logs) = null_logs {
+            null_logs.handle(log)?;
         }
     }
 
     Ok(())
 }
+
+/// decode events of logs in parallel chunks of chunk_size logs, returned in the order of logs
+///
+/// logs without a decoder for their block are left undecoded
+fn decode_logs_in_chunks(
+    logs: &[Log],
+    schema: &Table,
+    chunk_size: usize,
+) -> Vec<Option<ethers_core::abi::Result<ethers_core::abi::Log>>> {
+    use rayon::prelude::*;
+    let decode = |log: &Log| {
+        let decoder = schema.log_decoder_at(log.block_number?.as_u64())?;
+        Some(decoder.event.parse_log(log.clone().into()))
+    };
+    logs.par_chunks(chunk_size.max(1))
+        .map(|chunk| chunk.iter().map(decode).collect::<Vec<_>>())
+        .collect::<Vec<_>>()
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+/// get topic at position i of log
+///
+/// a null topic always means the log genuinely had fewer than i + 1 topics, topics are never
+/// nulled for any other reason (e.g. decoding failures or provider omissions)
+fn log_topic(log: &Log, i: usize) -> Option<Vec<u8>> {
+    log.topics.get(i).map(|topic| topic.as_bytes().to_vec())
+}
+
+/// whether a topic looks like an abi-encoded address, i.e. 12 zero bytes followed by 20 bytes
+/// that are not all zero
+///
+/// this heuristic is only used when no event decoder is configured, otherwise the event abi
+/// decides. it has false positives: any value whose upper 12 bytes are zero, such as a small
+/// uint256 amount or id, is reported as an address. the zero address is a false negative
+fn looks_like_address(topic: &[u8]) -> bool {
+    topic.len() == 32 && topic[..12].iter().all(|b| *b == 0) && topic[12..].iter().any(|b| *b != 0)
+}
+
+/// key ordering logs by (block_number, log_index), held in global_log_seq until the run is
+/// numbered by number_global_log_seq
+fn canonical_log_key(block_number: u64, log_index: u64) -> u64 {
+    (block_number << 32) | (log_index & 0xffff_ffff)
+}
+
+/// replace the canonical keys of the global_log_seq column with the position of each log in
+/// (block_number, log_index) order, numbering the logs of df from first_seq
+///
+/// df must hold every log of the run, or of a partition following the first_seq logs before it
+/// in block order, so the sequence is only stable across runs that collect the same block range
+/// with the same filters. it does not depend on the row order or sort of df
+pub(crate) fn number_global_log_seq(
+    df: &mut DataFrame,
+    first_seq: u64,
+) -> Result<(), CollectError> {
+    let keys: Vec<Option<u64>> = df.column("global_log_seq")?.u64()?.into_iter().collect();
+    let mut order: Vec<usize> = (0..keys.len()).collect();
+    order.sort_by_key(|i| keys[*i]);
+    let mut seq = vec![0u64; keys.len()];
+    for (position, i) in order.into_iter().enumerate() {
+        seq[i] = first_seq + position as u64;
+    }
+    df.replace("global_log_seq", Series::new("global_log_seq", seq))?;
+    Ok(())
+}
+
+/// coarse classification of log by topic0 and number of topics
+///
+/// erc20 and erc721 transfers share a topic0, erc721 transfers index the token id as a 4th topic
+fn classify_log(log: &Log) -> &'static str {
+    let topic0 = match log.topics.first() {
+        Some(topic0) => topic0,
+        None => return "other",
+    };
+    if *topic0 == *EVENT_ERC20_TRANSFER {
+        match log.topics.len() {
+            3 => "erc20_transfer",
+            4 => "erc721_transfer",
+            _ => "other",
+        }
+    } else if *topic0 == *EVENT_ERC1155_TRANSFER_SINGLE || *topic0 == *EVENT_ERC1155_TRANSFER_BATCH
+    {
+        "erc1155_transfer"
+    } else if *topic0 == *EVENT_ERC20_APPROVAL || *topic0 == *EVENT_ERC721_APPROVAL_FOR_ALL {
+        "approval"
+    } else {
+        "other"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers_core::abi::Token;
+
+    fn get_schema(columns: &[&str]) -> Table {
+        let columns = Some(columns.iter().map(|c| c.to_string()).collect());
+        Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &columns,
+                None,
+                None,
+            )
+            .unwrap()
+    }
+
+    fn get_log(tx_hash: H256, topics: Vec<H256>) -> Log {
+        Log {
+            address: H160::from_low_u64_be(1),
+            topics,
+            block_number: Some(U64::from(1u64)),
+            transaction_hash: Some(tx_hash),
+            transaction_index: Some(U64::from(0u64)),
+            log_index: Some(U256::from(0u64)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_tx_from_tx_to_columns() {
+        let tx_hash = H256::from_low_u64_be(7);
+        let transaction = Transaction {
+            hash: tx_hash,
+            from: H160::from_low_u64_be(2),
+            to: Some(H160::from_low_u64_be(3)),
+            ..Default::default()
+        };
+        let context = LogContext {
+            transactions: HashMap::from([(tx_hash, transaction)]),
+            ..Default::default()
+        };
+        let schema = get_schema(&["transaction_hash", "tx_from", "tx_to"]);
+
+        let mut columns = Logs::default();
+        process_logs(
+            vec![get_log(tx_hash, vec![])],
+            &context,
+            &mut columns,
+            &schema,
+            &LogFilters::default(),
+        )
+        .unwrap();
+        assert_eq!(columns.tx_from, vec![Some(H160::from_low_u64_be(2).as_bytes().to_vec())]);
+        assert_eq!(columns.tx_to, vec![Some(H160::from_low_u64_be(3).as_bytes().to_vec())]);
+    }
+
+    #[test]
+    fn test_log_type_classification() {
+        let address = H256::from(H160::from_low_u64_be(0xabc));
+        let log = |topic0: H256, n_topics: usize| {
+            let mut topics = vec![topic0];
+            topics.extend(vec![address; n_topics - 1]);
+            get_log(H256::from_low_u64_be(1), topics)
+        };
+        let logs = vec![
+            log(*EVENT_ERC20_TRANSFER, 3),
+            log(*EVENT_ERC20_TRANSFER, 4),
+            log(*EVENT_ERC1155_TRANSFER_SINGLE, 4),
+            log(*EVENT_ERC1155_TRANSFER_BATCH, 4),
+            log(*EVENT_ERC20_APPROVAL, 3),
+            log(*EVENT_ERC20_APPROVAL, 4),
+            log(*EVENT_ERC721_APPROVAL_FOR_ALL, 3),
+            log(*EVENT_ERC20_TRANSFER, 1),
+            log(H256::from_low_u64_be(7), 2),
+            get_log(H256::from_low_u64_be(1), vec![]),
+        ];
+        let schema = get_schema(&["block_number", "log_type"]);
+        let mut columns = Logs::default();
+        process_logs(logs, &LogContext::default(), &mut columns, &schema, &LogFilters::default())
+            .unwrap();
+        assert_eq!(
+            columns.log_type,
+            vec![
+                "erc20_transfer",
+                "erc721_transfer",
+                "erc1155_transfer",
+                "erc1155_transfer",
+                "approval",
+                "approval",
+                "approval",
+                "other",
+                "other",
+                "other",
+            ]
+        );
+        assert_eq!(Logs::column_types()["log_type"], ColumnType::String);
+        assert!(!Logs::default_columns().unwrap().contains(&"log_type"));
+    }
+
+    #[test]
+    fn test_data_selector_column() {
+        let log = |data: Vec<u8>| Log {
+            data: Bytes::from(data),
+            ..get_log(H256::from_low_u64_be(1), vec![])
+        };
+        let logs = vec![
+            log(vec![0xa9, 0x05, 0x9c, 0xbb, 0x01, 0x02]),
+            log(vec![0xa9, 0x05, 0x9c]),
+            log(vec![]),
+        ];
+        let schema = get_schema(&["block_number", "data_selector"]);
+        let mut columns = Logs::default();
+        process_logs(logs, &LogContext::default(), &mut columns, &schema, &LogFilters::default())
+            .unwrap();
+        // data shorter than a selector has none
+        assert_eq!(columns.data_selector, vec![Some(vec![0xa9, 0x05, 0x9c, 0xbb]), None, None]);
+        assert_eq!(Logs::column_types()["data_selector"], ColumnType::Binary);
+        assert!(!Logs::default_columns().unwrap().contains(&"data_selector"));
+    }
+
+    #[test]
+    fn test_n_topics_column() {
+        // one log of each shape, from an anonymous event without topics to four topics
+        let logs: Vec<Log> = (0..=4u64)
+            .map(|n| {
+                get_log(H256::from_low_u64_be(n), vec![H256::from_low_u64_be(100); n as usize])
+            })
+            .collect();
+        let schema = get_schema(&["block_number", "n_topics"]);
+        let mut columns = Logs::default();
+        process_logs(logs, &LogContext::default(), &mut columns, &schema, &LogFilters::default())
+            .unwrap();
+        assert_eq!(columns.n_topics, vec![0, 1, 2, 3, 4]);
+        assert_eq!(Logs::column_types()["n_topics"], ColumnType::UInt32);
+        assert!(!Logs::default_columns().unwrap().contains(&"n_topics"));
+    }
+
+    #[test]
+    fn test_global_log_seq_numbers_run_in_canonical_order() {
+        let log = |block_number: u64, log_index: u64| Log {
+            block_number: Some(U64::from(block_number)),
+            log_index: Some(U256::from(log_index)),
+            ..get_log(H256::from_low_u64_be(block_number * 10 + log_index), vec![])
+        };
+        let schema = get_schema(&["block_number", "log_index", "global_log_seq"]);
+        let schemas = HashMap::from([(Datatype::Logs, schema.clone())]);
+        let partition_df = |logs: Vec<Log>| {
+            let mut columns = Logs::default();
+            process_logs(
+                logs,
+                &LogContext::default(),
+                &mut columns,
+                &schema,
+                &LogFilters::default(),
+            )
+            .unwrap();
+            columns.create_dfs(&schemas, 1).unwrap().remove(&Datatype::Logs).unwrap()
+        };
+
+        // partitions finish out of order and the merged rows are sorted by another column
+        let late = partition_df(vec![log(20, 0), log(20, 3)]);
+        let early = partition_df(vec![log(3, 7), log(3, 1), log(5, 0)]);
+        let mut df = late.vstack(&early).unwrap().sort(["log_index"], false, false).unwrap();
+        number_global_log_seq(&mut df, 0).unwrap();
+
+        let column = |name: &str| -> Vec<u64> {
+            let values = df.column(name).unwrap().cast(&DataType::UInt64).unwrap();
+            values.u64().unwrap().into_no_null_iter().collect()
+        };
+        let mut numbered: Vec<(u64, u64, u64)> = column("global_log_seq")
+            .into_iter()
+            .zip(column("block_number"))
+            .zip(column("log_index"))
+            .map(|((seq, block_number), log_index)| (seq, block_number, log_index))
+            .collect();
+        numbered.sort();
+        assert_eq!(numbered, vec![(0, 3, 1), (1, 3, 7), (2, 5, 0), (3, 20, 0), (4, 20, 3)]);
+        assert_eq!(Logs::column_types()["global_log_seq"], ColumnType::UInt64);
+        assert!(!Logs::default_columns().unwrap().contains(&"global_log_seq"));
+    }
+
+    #[test]
+    fn test_raw_log_rlp_round_trips() {
+        let topics = vec![H256::from_low_u64_be(100), H256::from_low_u64_be(200)];
+        let log = Log {
+            address: H160::from_low_u64_be(0xaaaa),
+            data: vec![1u8, 2, 3].into(),
+            ..get_log(H256::from_low_u64_be(1), topics.clone())
+        };
+        let schema = get_schema(&["block_number", "raw_log_rlp"]);
+        let mut columns = Logs::default();
+        process_logs(
+            vec![log.clone()],
+            &LogContext::default(),
+            &mut columns,
+            &schema,
+            &LogFilters::default(),
+        )
+        .unwrap();
+        assert_eq!(Logs::column_types()["raw_log_rlp"], ColumnType::Binary);
+        assert!(!Logs::default_columns().unwrap().contains(&"raw_log_rlp"));
+
+        let rlp = ethers::utils::rlp::Rlp::new(&columns.raw_log_rlp[0]);
+        assert_eq!(rlp.item_count().unwrap(), 3);
+        assert_eq!(rlp.val_at::<H160>(0).unwrap(), log.address);
+        assert_eq!(rlp.list_at::<H256>(1).unwrap(), topics);
+        assert_eq!(rlp.val_at::<Vec<u8>>(2).unwrap(), vec![1u8, 2, 3]);
+    }
+
+    #[test]
+    fn test_base_fee_per_gas_column() {
+        let context = LogContext {
+            block_base_fees: HashMap::from([(1, None), (2, Some(U256::from(7u64)))]),
+            ..Default::default()
+        };
+        let schema = get_schema(&["block_number", "base_fee_per_gas"]);
+        let pre_london = get_log(H256::from_low_u64_be(1), vec![]);
+        let post_london = Log {
+            block_number: Some(U64::from(2u64)),
+            ..get_log(H256::from_low_u64_be(2), vec![])
+        };
+
+        let mut columns = Logs::default();
+        process_logs(
+            vec![pre_london, post_london],
+            &context,
+            &mut columns,
+            &schema,
+            &LogFilters::default(),
+        )
+        .unwrap();
+        assert_eq!(columns.base_fee_per_gas, vec![None, Some(U256::from(7u64).to_vec_u8())]);
+        assert_eq!(Logs::column_types()["base_fee_per_gas"], ColumnType::Binary);
+    }
+
+    #[test]
+    fn test_topic_nullity_matches_topic_count() {
+        let schema = get_schema(&["topic0", "topic1", "topic2", "topic3"]);
+        let mut columns = Logs::default();
+        let logs: Vec<Log> = (0..=4)
+            .map(|n_topics| {
+                let topics = (0..n_topics).map(H256::from_low_u64_be).collect();
+                get_log(H256::from_low_u64_be(n_topics), topics)
+            })
+            .collect();
+        process_logs(logs, &LogContext::default(), &mut columns, &schema, &LogFilters::default())
+            .unwrap();
+
+        let topic_columns = [&columns.topic0, &columns.topic1, &columns.topic2, &columns.topic3];
+        for (i, topic_column) in topic_columns.iter().enumerate() {
+            for (n_topics, topic) in topic_column.iter().enumerate() {
+                assert_eq!(topic.is_none(), i >= n_topics);
+                if let Some(topic) = topic {
+                    assert_eq!(topic, &H256::from_low_u64_be(i as u64).as_bytes().to_vec());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_topic_is_address_heuristic_and_abi() {
+        let from = H160::from_low_u64_be(0xaaaa);
+        let to = H160::from_low_u64_be(0xbbbb);
+        let mut data = [0u8; 32];
+        U256::from(5u64).to_big_endian(&mut data);
+        let topic0 = H256::from_low_u64_be(100);
+        let topics = vec![topic0, H256::from(from), H256::from_low_u64_be(5)];
+
+        // without a decoder, small integers are flagged too (known false positive)
+        let column_names = ["topic1_is_address", "topic2_is_address", "topic3_is_address"];
+        let schema = get_schema(&column_names);
+        let mut columns = Logs::default();
+        let logs = vec![
+            get_log(H256::from_low_u64_be(1), topics),
+            get_log(H256::from_low_u64_be(2), vec![topic0, H256::repeat_byte(0xff)]),
+        ];
+        process_logs(logs, &LogContext::default(), &mut columns, &schema, &LogFilters::default())
+            .unwrap();
+        assert_eq!(columns.topic1_is_address, vec![Some(true), Some(false)]);
+        assert_eq!(columns.topic2_is_address, vec![Some(true), None]);
+        assert_eq!(columns.topic3_is_address, vec![None, None]);
+
+        // with a decoder, the event abi decides
+        let signature = "event Deposit(address indexed from, uint256 indexed id, uint256 amount)";
+        let decoder = LogDecoder::new(signature.to_string()).unwrap();
+        let topic0 = decoder.event.signature();
+        let column_names = column_names.iter().map(|c| c.to_string()).collect();
+        let schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &Some(column_names),
+                None,
+                Some(decoder),
+            )
+            .unwrap();
+        let log = Log {
+            data: data.to_vec().into(),
+            ..get_log(
+                H256::from_low_u64_be(3),
+                vec![topic0, H256::from(to), H256::from_low_u64_be(5)],
+            )
+        };
+        let mut columns = Logs::default();
+        process_logs(
+            vec![log],
+            &LogContext::default(),
+            &mut columns,
+            &schema,
+            &LogFilters::default(),
+        )
+        .unwrap();
+        assert_eq!(columns.topic1_is_address, vec![Some(true)]);
+        assert_eq!(columns.topic2_is_address, vec![Some(false)]);
+        assert_eq!(columns.topic3_is_address, vec![None]);
+    }
+
+    #[test]
+    fn test_decode_across_proxy_upgrade() {
+        let old = "0:100=event Transfer(address from, address to, uint256 value)";
+        let new = "100:=event Transfer(address indexed from, address indexed to, uint256 value)";
+        let ranges = vec![DecoderRange::parse(old).unwrap(), DecoderRange::parse(new).unwrap()];
+        let ranges = RangedLogDecoder::new(ranges, None).unwrap();
+        let mut schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &Some(vec!["block_number".to_string()]),
+                None,
+                Some(ranges.primary().clone()),
+            )
+            .unwrap();
+        schema.log_decoder_ranges = Some(ranges);
+
+        let topic0 = LogDecoder::new("event Transfer(address,address,uint256)".to_string())
+            .unwrap()
+            .event
+            .signature();
+        let (from, to) = (H160::from_low_u64_be(0xaaaa), H160::from_low_u64_be(0xbbbb));
+        let encoded = |tokens: Vec<Token>| ethers_core::abi::encode(&tokens);
+        let at_block = |log: Log, block_number: u64| Log {
+            block_number: Some(U64::from(block_number)),
+            ..log
+        };
+
+        // before upgrade everything is in data, after upgrade from and to are indexed
+        let before = Log {
+            data: encoded(vec![
+                Token::Address(from),
+                Token::Address(to),
+                Token::Uint(U256::from(1u64)),
+            ])
+            .into(),
+            ..get_log(H256::from_low_u64_be(1), vec![topic0])
+        };
+        let after = Log {
+            data: encoded(vec![Token::Uint(U256::from(2u64))]).into(),
+            ..get_log(H256::from_low_u64_be(2), vec![topic0, H256::from(from), H256::from(to)])
+        };
+        let logs = vec![at_block(before.clone(), 99), at_block(after.clone(), 100)];
+
+        let mut columns = Logs::default();
+        process_logs(logs, &LogContext::default(), &mut columns, &schema, &LogFilters::default())
+            .unwrap();
+        assert_eq!(columns.block_number, vec![99, 100]);
+        assert_eq!(columns.event_cols["from"], vec![Token::Address(from), Token::Address(from)]);
+        assert_eq!(columns.event_cols["to"], vec![Token::Address(to), Token::Address(to)]);
+        assert_eq!(
+            columns.event_cols["value"],
+            vec![Token::Uint(U256::from(1u64)), Token::Uint(U256::from(2u64))]
+        );
+
+        // each encoding only decodes with the abi of its own range
+        let logs = vec![at_block(before, 100), at_block(after, 99)];
+        let mut columns = Logs::default();
+        process_logs(logs, &LogContext::default(), &mut columns, &schema, &LogFilters::default())
+            .unwrap();
+        assert_eq!(columns.n_rows, 0);
+    }
+
+    #[test]
+    fn test_decoded_column_cap_stops_new_columns() {
+        let signature = "event Transfer(address indexed from, address indexed to, uint256 value)";
+        let decoder = LogDecoder::new(signature.to_string()).unwrap();
+        let topic0 = decoder.event.signature();
+        let schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &None,
+                None,
+                Some(decoder),
+            )
+            .unwrap();
+        let (from, to) = (H160::from_low_u64_be(0xaaaa), H160::from_low_u64_be(0xbbbb));
+        let logs: Vec<Log> = (1..3)
+            .map(|i| Log {
+                data: ethers_core::abi::encode(&[Token::Uint(U256::from(i))]).into(),
+                ..get_log(H256::from_low_u64_be(i), vec![topic0, H256::from(from), H256::from(to)])
+            })
+            .collect();
+
+        let cap = DecodedColumnCap::new(2);
+        let mut columns = Logs::default();
+        process_logs(
+            logs,
+            &LogContext::default(),
+            &mut columns,
+            &schema,
+            &LogFilters { column_cap: Some(&cap), ..Default::default() },
+        )
+        .unwrap();
+        assert_eq!(columns.n_rows, 2);
+        assert_eq!(columns.event_cols.keys().collect::<Vec<_>>(), vec!["from", "to"]);
+        assert_eq!(columns.event_cols["to"], vec![Token::Address(to), Token::Address(to)]);
+        assert_eq!(cap.dropped(), vec!["value".to_string()]);
+
+        let schemas = HashMap::from([(Datatype::Logs, schema)]);
+        let df = columns.create_dfs(&schemas, 1).unwrap().remove(&Datatype::Logs).unwrap();
+        assert_eq!(df.height(), 2);
+        let names = df.get_column_names();
+        assert!(names.contains(&"event__to"));
+        assert!(!names.iter().any(|name| name.starts_with("event__value")));
+    }
+
+    #[tokio::test]
+    async fn test_log_block_headers_fetched_once_per_run() {
+        let (provider, mock) = Provider::mocked();
+        let source = Arc::new(Source { ..Source::from_provider(provider) });
+        let schema = get_schema(&["block_number", "block_timestamp", "base_fee_per_gas"]);
+        let cache = LogBlockCache::default();
+
+        // block 1 was fetched by an earlier partition before London, block 2 is a 1559 block
+        cache.insert(1, (100, None));
+        let london_block = Block::<TxHash> {
+            number: Some(U64::from(2u64)),
+            timestamp: U256::from(112u64),
+            base_fee_per_gas: Some(U256::from(7_000_000_000u64)),
+            ..Default::default()
+        };
+        mock.push(london_block).unwrap();
+
+        let logs = vec![
+            get_log(H256::from_low_u64_be(1), vec![]),
+            Log {
+                block_number: Some(U64::from(2u64)),
+                ..get_log(H256::from_low_u64_be(2), vec![])
+            },
+        ];
+        let headers = get_log_blocks(&logs, &source, &schema, &cache).await.unwrap();
+        assert_eq!(headers[&1], (100, None));
+        assert_eq!(headers[&2], (112, Some(U256::from(7_000_000_000u64))));
+
+        // later partitions hit the cache, the mock has no responses left
+        assert_eq!(cache.get(2), Some(headers[&2]));
+        let headers = get_log_blocks(&logs[1..], &source, &schema, &cache).await.unwrap();
+        assert_eq!(headers[&2].1, Some(U256::from(7_000_000_000u64)));
+    }
+
+    #[test]
+    fn test_skip_decoding_omits_event_columns() {
+        let signature = "event Transfer(address indexed from, address indexed to, uint256 value)";
+        let decoder = LogDecoder::new(signature.to_string()).unwrap();
+        let topic0 = decoder.event.signature();
+        let mut schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &None,
+                None,
+                Some(decoder),
+            )
+            .unwrap();
+        schema.skip_decoding = true;
+        let log = Log {
+            data: ethers_core::abi::encode(&[Token::Uint(U256::from(5u64))]).into(),
+            ..get_log(
+                H256::from_low_u64_be(1),
+                vec![topic0, H256::from_low_u64_be(2), H256::from_low_u64_be(3)],
+            )
+        };
+
+        let mut columns = Logs::default();
+        process_logs(
+            vec![log],
+            &LogContext::default(),
+            &mut columns,
+            &schema,
+            &LogFilters::default(),
+        )
+        .unwrap();
+        assert!(columns.event_cols.is_empty());
+
+        let schemas = HashMap::from([(Datatype::Logs, schema)]);
+        let df = columns.create_dfs(&schemas, 1).unwrap().remove(&Datatype::Logs).unwrap();
+        assert_eq!(df.height(), 1);
+        let names = df.get_column_names();
+        assert!(names.iter().all(|name| !name.starts_with("event__")));
+        assert!(names.contains(&"topic1") && names.contains(&"data"));
+    }
+
+    #[test]
+    fn test_topics_as_hex_strings() {
+        let mut schema = get_schema(&["topic0", "topic1", "topic2", "topic3", "data"]);
+        schema.set_topics_as_hex();
+        let topics = vec![H256::from_low_u64_be(0xabc), H256::from_low_u64_be(1)];
+        let log = get_log(H256::from_low_u64_be(1), topics.clone());
+
+        let mut columns = Logs::default();
+        process_logs(
+            vec![log],
+            &LogContext::default(),
+            &mut columns,
+            &schema,
+            &LogFilters::default(),
+        )
+        .unwrap();
+        let schemas = HashMap::from([(Datatype::Logs, schema)]);
+        let df = columns.create_dfs(&schemas, 1).unwrap().remove(&Datatype::Logs).unwrap();
+
+        // topics are 0x-prefixed hex of 32 bytes, absent topics stay null
+        for (i, topic) in topics.iter().enumerate() {
+            let column = df.column(&format!("topic{}", i)).unwrap();
+            let value = column.utf8().unwrap().get(0).unwrap();
+            assert_eq!(value.len(), 66);
+            assert_eq!(value, format!("{:?}", topic));
+        }
+        assert_eq!(df.column("topic2").unwrap().utf8().unwrap().get(0), None);
+        assert_eq!(df.column("topic3").unwrap().null_count(), 1);
+        assert_eq!(df.column("data").unwrap().dtype(), &DataType::Binary);
+    }
+
+    #[test]
+    fn test_decoded_json_replaces_event_columns() {
+        let signature = "event Batch(address indexed sender, int256 delta, uint256[] amounts)";
+        let decoder = LogDecoder::new(signature.to_string()).unwrap();
+        let topic0 = decoder.event.signature();
+        let mut schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &None,
+                None,
+                Some(decoder),
+            )
+            .unwrap();
+        schema.set_decoded_json();
+        let batch = Log {
+            data: ethers_core::abi::encode(&[
+                Token::Int(I256::from(-5).into_raw()),
+                Token::Array(vec![Token::Uint(U256::from(1u64)), Token::Uint(U256::from(2u64))]),
+            ])
+            .into(),
+            ..get_log(H256::from_low_u64_be(1), vec![topic0, H256::from_low_u64_be(1)])
+        };
+        // logs of other events are kept, with a null decoded value
+        let other = get_log(H256::from_low_u64_be(2), vec![H256::from_low_u64_be(9)]);
+
+        let mut columns = Logs::default();
+        process_logs(
+            vec![batch, other],
+            &LogContext::default(),
+            &mut columns,
+            &schema,
+            &LogFilters::default(),
+        )
+        .unwrap();
+        assert!(columns.event_cols.is_empty());
+        let expected = r#"{"sender":"0x0000000000000000000000000000000000000001","delta":"-5","amounts":["1","2"]}"#;
+        assert_eq!(columns.decoded, vec![Some(expected.to_string()), None]);
+
+        let schemas = HashMap::from([(Datatype::Logs, schema)]);
+        let df = columns.create_dfs(&schemas, 1).unwrap().remove(&Datatype::Logs).unwrap();
+        assert_eq!(df.height(), 2);
+        let names = df.get_column_names();
+        assert!(names.iter().all(|name| !name.starts_with("event__")));
+        assert!(names.contains(&"decoded"));
+
+        // tuples are arrays of their fields
+        let info = ethers_core::abi::LogParam {
+            name: "info".to_string(),
+            value: Token::Tuple(vec![Token::Int(I256::from(-1).into_raw()), Token::Bool(true)]),
+        };
+        assert_eq!(decoded_params_json(&[info]), r#"{"info":["-1",true]}"#);
+    }
+
+    #[test]
+    fn test_decoded_columns_never_shadow_base_columns() {
+        let decoder = LogDecoder::new("event Payload(bytes data)".to_string()).unwrap();
+        let topic0 = decoder.event.signature();
+        let columns = Some(vec!["block_number".to_string(), "data".to_string()]);
+        let mut schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &columns,
+                None,
+                Some(decoder),
+            )
+            .unwrap();
+        schema.keep_raw_log_columns = true;
+        let raw_data = ethers_core::abi::encode(&[Token::Bytes(vec![0xde, 0xad])]);
+        let log = Log {
+            data: raw_data.clone().into(),
+            ..get_log(H256::from_low_u64_be(1), vec![topic0])
+        };
+
+        let mut columns = Logs::default();
+        process_logs(
+            vec![log],
+            &LogContext::default(),
+            &mut columns,
+            &schema,
+            &LogFilters::default(),
+        )
+        .unwrap();
+        let schemas = HashMap::from([(Datatype::Logs, schema)]);
+        let df = columns.create_dfs(&schemas, 1).unwrap().remove(&Datatype::Logs).unwrap();
+        let names = df.get_column_names();
+        assert_eq!(names.iter().filter(|name| **name == "data").count(), 1);
+        assert_eq!(names.iter().filter(|name| **name == "event__data").count(), 1);
+        assert!(df.column("data").unwrap().equals(&Series::new("data", vec![raw_data])));
+        assert!(df
+            .column("event__data")
+            .unwrap()
+            .equals(&Series::new("event__data", vec![vec![0xdeu8, 0xad]])));
+    }
+
+    #[test]
+    fn test_per_block_log_keeps_first_or_last_log() {
+        let signature = "event Transfer(address indexed from, address indexed to, uint256 value)";
+        let decoder = LogDecoder::new(signature.to_string()).unwrap();
+        let topic0 = decoder.event.signature();
+        let columns = Some(vec!["block_number".to_string(), "log_index".to_string()]);
+        let sort = Some(vec!["block_number".to_string(), "log_index".to_string()]);
+        let schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &columns,
+                sort,
+                Some(decoder),
+            )
+            .unwrap();
+        let (from, to) = (H160::from_low_u64_be(0xaaaa), H160::from_low_u64_be(0xbbbb));
+        // block 1 has three logs, block 2 has one
+        let logs: Vec<Log> = [(1u64, 4u64), (1, 2), (1, 3), (2, 0)]
+            .into_iter()
+            .map(|(block_number, log_index)| Log {
+                data: ethers_core::abi::encode(&[Token::Uint(U256::from(log_index))]).into(),
+                block_number: Some(U64::from(block_number)),
+                log_index: Some(U256::from(log_index)),
+                ..get_log(H256::from_low_u64_be(log_index), vec![topic0, from.into(), to.into()])
+            })
+            .collect();
+
+        let per_block = |per_block_log: Option<PerBlockLog>| {
+            let mut schema = schema.clone();
+            schema.per_block_log = per_block_log;
+            let mut columns = Logs::default();
+            process_logs(
+                logs.clone(),
+                &LogContext::default(),
+                &mut columns,
+                &schema,
+                &LogFilters::default(),
+            )
+            .unwrap();
+            let schemas = HashMap::from([(Datatype::Logs, schema)]);
+            columns.create_dfs(&schemas, 1).unwrap().remove(&Datatype::Logs).unwrap()
+        };
+
+        assert_eq!(per_block(None).height(), 4);
+        for (per_block_log, log_index) in [(PerBlockLog::First, 2u32), (PerBlockLog::Last, 4u32)] {
+            let df = per_block(Some(per_block_log));
+            assert!(df
+                .column("block_number")
+                .unwrap()
+                .equals(&Series::new("block_number", [1u32, 2])));
+            assert!(df
+                .column("log_index")
+                .unwrap()
+                .equals(&Series::new("log_index", [log_index, 0])));
+            // decoded columns are kept with the row of their log
+            let value = ethers_core::abi::encode(&[Token::Uint(U256::from(log_index))]);
+            let zero = ethers_core::abi::encode(&[Token::Uint(U256::zero())]);
+            assert!(df
+                .column("event__value_binary")
+                .unwrap()
+                .equals(&Series::new("event__value_binary", vec![value, zero])));
+        }
+    }
+
+    #[test]
+    fn test_batched_logs_match_in_memory_output() {
+        let signature = "event Transfer(address indexed from, address indexed to, uint256 value)";
+        let decoder = LogDecoder::new(signature.to_string()).unwrap();
+        let topic0 = decoder.event.signature();
+        let schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &None,
+                None,
+                Some(decoder),
+            )
+            .unwrap();
+        let query = Query {
+            datatypes: vec![MetaDatatype::Scalar(Datatype::Logs)],
+            schemas: HashMap::from([(Datatype::Logs, schema)]),
+            log_batch_size: Some(1_000),
+            ..Default::default()
+        };
+
+        // synthetic response of a single block with 10,000 transfers
+        let logs: Vec<Log> = (0..10_000u64)
+            .map(|i| {
+                let (from, to) = (H160::from_low_u64_be(i), H160::from_low_u64_be(i + 1));
+                Log {
+                    log_index: Some(U256::from(i)),
+                    data: ethers_core::abi::encode(&[Token::Uint(U256::from(i))]).into(),
+                    ..get_log(
+                        H256::from_low_u64_be(i),
+                        vec![topic0, H256::from(from), H256::from(to)],
+                    )
+                }
+            })
+            .collect();
+        let small_logs = logs[..10].to_vec();
+
+        let mut columns = Logs::default();
+        transform_logs(logs.clone(), &LogContext::default(), &mut columns, &query).unwrap();
+        let in_memory =
+            columns.create_dfs(&query.schemas, 1).unwrap().remove(&Datatype::Logs).unwrap();
+
+        let mut batches = Vec::new();
+        let mut batcher = LogBatcher::new(1_000, 1, |df| {
+            batches.push(df);
+            Ok(())
+        });
+        batcher.push(logs, &LogContext::default(), &query).unwrap();
+        assert!(batcher.finish(&query).unwrap().is_none());
+        assert_eq!(batches.len(), 10);
+        assert!(batches.iter().all(|batch| batch.height() == 1_000));
+
+        // batches appended as row groups read back as the in-memory output
+        let output_dir =
+            std::env::temp_dir().join(format!("cryo_test_batched_logs_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&output_dir);
+        std::fs::create_dir_all(&output_dir).unwrap();
+        let sink =
+            FileOutput { output_dir: output_dir.clone(), overwrite: true, ..Default::default() };
+        let path = output_dir.join("batched.parquet");
+        let mut writer =
+            crate::dataframes::BatchedParquetWriter::new(&path, &batches[0].schema(), &sink)
+                .unwrap();
+        for batch in batches.iter() {
+            writer.write_batch(batch).unwrap();
+        }
+        writer.finish().unwrap();
+        let written = ParquetReader::new(std::fs::File::open(&path).unwrap()).finish().unwrap();
+        assert!(written.equals(&in_memory));
+        std::fs::remove_dir_all(&output_dir).unwrap();
+
+        // responses within the batch size stay on the in-memory path
+        let mut batcher = LogBatcher::new(1_000, 1, |_| Err(err("unexpected batch")));
+        batcher.push(small_logs, &LogContext::default(), &query).unwrap();
+        assert_eq!(batcher.finish(&query).unwrap().unwrap().height(), 10);
+    }
+
+    #[test]
+    fn test_schema_only_file_matches_populated_run() {
+        let signature = "event Transfer(address indexed from, address indexed to, uint256 value)";
+        let decoder = LogDecoder::new(signature.to_string()).unwrap();
+        let topic0 = decoder.event.signature();
+        let schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary, U256Type::String, U256Type::F64],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &Some(vec!["all".to_string()]),
+                None,
+                Some(decoder),
+            )
+            .unwrap();
+        let schemas = HashMap::from([(Datatype::Logs, schema.clone())]);
+
+        let output_dir =
+            std::env::temp_dir().join(format!("cryo_test_schema_only_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&output_dir);
+        std::fs::create_dir_all(&output_dir).unwrap();
+        let sink =
+            FileOutput { output_dir: output_dir.clone(), overwrite: true, ..Default::default() };
+
+        // populated run
+        let (from, to) = (H160::from_low_u64_be(0xaaaa), H160::from_low_u64_be(0xbbbb));
+        let log = Log {
+            data: ethers_core::abi::encode(&[Token::Uint(U256::from(7u64))]).into(),
+            ..get_log(H256::from_low_u64_be(1), vec![topic0, H256::from(from), H256::from(to)])
+        };
+        let mut columns = Logs::default();
+        process_logs(
+            vec![log],
+            &LogContext::default(),
+            &mut columns,
+            &schema,
+            &LogFilters::default(),
+        )
+        .unwrap();
+        let mut dfs = columns.create_dfs(&schemas, 1).unwrap();
+        let mut populated = dfs.remove(&Datatype::Logs).unwrap();
+        assert_eq!(populated.height(), 1);
+        let populated_path = output_dir.join("populated.parquet");
+        crate::dataframes::df_to_file(&mut populated, &populated_path, &sink).unwrap();
+
+        // schema only run
+        let paths = write_schema_files(&[Datatype::Logs], &schemas, &sink).unwrap();
+        assert_eq!(paths, vec![output_dir.join("ethereum__logs__schema.parquet")]);
+
+        let read = |path: &std::path::Path| {
+            ParquetReader::new(std::fs::File::open(path).unwrap()).finish().unwrap()
+        };
+        let empty = read(&paths[0]);
+        assert_eq!(empty.height(), 0);
+        assert!(empty.get_column_names().iter().any(|name| name.starts_with("event__")));
+        assert_eq!(empty.schema(), read(&populated_path).schema());
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[test]
+    fn test_any_topic_fan_out_collects_each_log_once() {
+        let topic0 = H256::from_low_u64_be(100);
+        let target = H256::from(H160::from_low_u64_be(0xaaaa));
+        let other = H256::from(H160::from_low_u64_be(0xbbbb));
+        let filters = any_topic_filters(&Filter::new(), target);
+        assert_eq!(filters.len(), 3);
+        for (i, filter) in filters.iter().enumerate() {
+            for position in 0..4 {
+                let expected = (position == i + 1).then_some(ValueOrArray::Value(Some(target)));
+                assert_eq!(filter.topics[position], expected);
+            }
+        }
+
+        let at = |log_index: u64, topics: Vec<H256>| Log {
+            log_index: Some(U256::from(log_index)),
+            ..get_log(H256::from_low_u64_be(log_index), topics)
+        };
+        let sender = at(0, vec![topic0, target, other]);
+        let recipient = at(1, vec![topic0, other, target]);
+        let self_transfer = at(2, vec![topic0, target, target]);
+
+        // simulated responses of the topic1, topic2, and topic3 filters
+        let responses = vec![
+            vec![sender.clone(), self_transfer.clone()],
+            vec![recipient.clone(), self_transfer.clone()],
+            vec![],
+        ];
+        assert_eq!(merge_logs(responses), vec![sender, recipient, self_transfer]);
+    }
+
+    #[tokio::test]
+    async fn test_address_set_requested_in_chunks() {
+        let (provider, mock) = Provider::mocked();
+        let source = Arc::new(Source { ..Source::from_provider(provider) });
+        let address = |i: u64| H160::from_low_u64_be(i);
+        let addresses: Vec<Vec<u8>> = (1..=3).map(|i| address(i).as_bytes().to_vec()).collect();
+
+        // repeated addresses are requested once
+        let address_set =
+            LogAddressSet::new([addresses.clone(), vec![addresses[0].clone()]].concat(), 2);
+        assert_eq!(address_set.addresses(), addresses.as_slice());
+        let query = Arc::new(Query {
+            datatypes: vec![MetaDatatype::Scalar(Datatype::Logs)],
+            schemas: HashMap::from([(Datatype::Logs, get_schema(&["block_number", "address"]))]),
+            log_address_set: Some(Arc::new(address_set)),
+            ..Default::default()
+        });
+
+        // simulated responses of the two chunks of addresses
+        let log = |block_number: u64, emitter: u64| Log {
+            address: address(emitter),
+            block_number: Some(U64::from(block_number)),
+            ..get_log(H256::from_low_u64_be(block_number), vec![])
+        };
+        mock.push(vec![log(7, 3)]).unwrap();
+        mock.push(vec![log(5, 1), log(9, 2)]).unwrap();
+        let request = Params { block_range: Some((0, 9)), ..Default::default() };
+        let (logs, _context) =
+            <Logs as CollectByBlock>::extract(request, source, query.clone()).await.unwrap();
+        let emitters: Vec<H160> = logs.iter().map(|log| log.address).collect();
+        assert_eq!(emitters, vec![address(1), address(3), address(2)]);
+        let address_set = query.log_address_set.as_ref().unwrap();
+        assert_eq!(address_set.n_requests(&[(0, 9)]), 2);
+        assert_eq!(address_set.n_requests(&[(10, 19)]), 0);
+
+        let filter = Filter::new().from_block(0).to_block(9);
+        for filter in
+            [filter.clone().address(vec![address(1), address(2)]), filter.address(address(3))]
+        {
+            mock.assert_request("eth_getLogs", [serde_json::to_value(filter).unwrap()]).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_included_and_excluded_topic0s_compose() {
+        let (provider, mock) = Provider::mocked();
+        let source = Arc::new(Source { ..Source::from_provider(provider) });
+        let transfer = H256::from_low_u64_be(100);
+        let approval = H256::from_low_u64_be(200);
+        let included = Arc::new(Topic0Inclusion::new(vec![
+            transfer.as_bytes().to_vec(),
+            approval.as_bytes().to_vec(),
+            transfer.as_bytes().to_vec(),
+        ]));
+        let excluded = Arc::new(Topic0Exclusion::new(vec![approval.as_bytes().to_vec()]));
+        let query = Arc::new(Query {
+            datatypes: vec![MetaDatatype::Scalar(Datatype::Logs)],
+            schemas: HashMap::from([(Datatype::Logs, get_schema(&["transaction_hash"]))]),
+            included_topic0s: Some(included.clone()),
+            excluded_topic0s: Some(excluded.clone()),
+            ..Default::default()
+        });
+
+        // the node matches either topic0, the excluded one is dropped after fetching
+        let log = |tx: u64, topic0: H256| Log {
+            block_number: Some(U64::from(tx)),
+            log_index: Some(U256::zero()),
+            ..get_log(H256::from_low_u64_be(tx), vec![topic0])
+        };
+        mock.push(vec![log(1, transfer), log(2, approval), log(3, transfer)]).unwrap();
+        let request = Params { block_range: Some((0, 9)), ..Default::default() };
+        let response =
+            <Logs as CollectByBlock>::extract(request, source, query.clone()).await.unwrap();
+        let filter = Filter::new()
+            .from_block(0)
+            .to_block(9)
+            .topic0(ValueOrArray::Array(vec![transfer, approval]));
+        mock.assert_request("eth_getLogs", [serde_json::to_value(filter).unwrap()]).unwrap();
+
+        let mut columns = Logs::default();
+        <Logs as CollectByBlock>::transform(response, &mut columns, &query).unwrap();
+        let kept: Vec<Vec<u8>> =
+            [1, 3].iter().map(|tx| H256::from_low_u64_be(*tx).as_bytes().to_vec()).collect();
+        assert_eq!(columns.transaction_hash, kept);
+
+        // node-side and client-side filtering are counted separately
+        assert_eq!(included.n_matched(), 3);
+        assert_eq!(excluded.n_excluded(), 1);
+    }
+
+    #[test]
+    fn test_absent_topics_stored_as_null_or_zero_hash() {
+        let transfer = H256::from_low_u64_be(100);
+        let sender = H256::from_low_u64_be(7);
+        let logs = vec![
+            get_log(H256::from_low_u64_be(1), vec![transfer, sender]),
+            get_log(H256::from_low_u64_be(2), vec![]),
+        ];
+        let output_dir =
+            std::env::temp_dir().join(format!("cryo_test_zero_hash_topics_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&output_dir);
+        std::fs::create_dir_all(&output_dir).unwrap();
+
+        // topics as read back from a parquet file written with zero_hash_topics
+        let round_trip = |zero_hash_topics: bool| {
+            let mut schema = get_schema(&["topic0", "topic1", "topic2", "topic1_is_address"]);
+            schema.zero_hash_topics = zero_hash_topics;
+            let mut columns = Logs::default();
+            process_logs(
+                logs.clone(),
+                &LogContext::default(),
+                &mut columns,
+                &schema,
+                &LogFilters::default(),
+            )
+            .unwrap();
+            let schemas = HashMap::from([(Datatype::Logs, schema)]);
+            let mut df = columns.create_dfs(&schemas, 1).unwrap().remove(&Datatype::Logs).unwrap();
+            let path = output_dir.join(format!("zero_hash_topics_{}.parquet", zero_hash_topics));
+            ParquetWriter::new(std::fs::File::create(&path).unwrap()).finish(&mut df).unwrap();
+            let df = ParquetReader::new(std::fs::File::open(&path).unwrap()).finish().unwrap();
+            let topic = |name: &str| -> Vec<Option<Vec<u8>>> {
+                let column = df.column(name).unwrap().binary().unwrap();
+                column.into_iter().map(|value| value.map(|value| value.to_vec())).collect()
+            };
+            let is_address: Vec<Option<bool>> =
+                df.column("topic1_is_address").unwrap().bool().unwrap().into_iter().collect();
+            (topic("topic0"), topic("topic1"), topic("topic2"), is_address)
+        };
+
+        // absent topics are null by default
+        let (topic0, topic1, topic2, is_address) = round_trip(false);
+        assert_eq!(topic0, vec![Some(transfer.as_bytes().to_vec()), None]);
+        assert_eq!(topic1, vec![Some(sender.as_bytes().to_vec()), None]);
+        assert_eq!(topic2, vec![None, None]);
+        assert_eq!(is_address, vec![Some(true), None]);
+
+        // with zero_hash_topics every topic is present, absent ones are 32 zero bytes
+        let zero = Some(vec![0u8; 32]);
+        let (topic0, topic1, topic2, is_address) = round_trip(true);
+        assert_eq!(topic0, vec![Some(transfer.as_bytes().to_vec()), zero.clone()]);
+        assert_eq!(topic1, vec![Some(sender.as_bytes().to_vec()), zero.clone()]);
+        assert_eq!(topic2, vec![zero.clone(), zero]);
+        assert_eq!(is_address, vec![Some(true), None]);
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[test]
+    fn test_event_name_from_topic0_labels() {
+        let transfer = H256::from_low_u64_be(100);
+        let approval = H256::from_low_u64_be(200);
+        let unknown = H256::from_low_u64_be(300);
+        let labels = HashMap::from([
+            (transfer.as_bytes().to_vec(), "Transfer".to_string()),
+            (approval.as_bytes().to_vec(), "Approval".to_string()),
+        ]);
+        let schema = get_schema(&["topic0", "event_name"]);
+        let logs = vec![
+            get_log(H256::from_low_u64_be(1), vec![transfer]),
+            get_log(H256::from_low_u64_be(2), vec![approval]),
+            get_log(H256::from_low_u64_be(3), vec![unknown]),
+            get_log(H256::from_low_u64_be(4), vec![]),
+        ];
+
+        let mut columns = Logs::default();
+        process_logs(
+            logs,
+            &LogContext::default(),
+            &mut columns,
+            &schema,
+            &LogFilters { topic0_labels: Some(&labels), ..Default::default() },
+        )
+        .unwrap();
+        assert_eq!(
+            columns.event_name,
+            vec![Some("Transfer".to_string()), Some("Approval".to_string()), None, None]
+        );
+    }
+
+    #[test]
+    fn test_filter_transfers_by_value() {
+        let transfer = H256::from_low_u64_be(100);
+        let logs: Vec<Log> = [5u64, 1_000, 2_000_000, 999]
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                let mut data = [0u8; 32];
+                U256::from(*value).to_big_endian(&mut data);
+                Log {
+                    data: data.to_vec().into(),
+                    ..get_log(H256::from_low_u64_be(i as u64), vec![transfer])
+                }
+            })
+            .collect();
+        let filters = vec![LogDataFilter::parse("0:gte:1000").unwrap()];
+        let schema = get_schema(&["data"]);
+
+        let mut columns = Logs::default();
+        process_logs(
+            logs,
+            &LogContext::default(),
+            &mut columns,
+            &schema,
+            &LogFilters { data_filters: Some(&filters), ..Default::default() },
+        )
+        .unwrap();
+        assert_eq!(columns.n_rows, 2);
+        let values: Vec<U256> =
+            columns.data.iter().map(|data| U256::from_big_endian(&data[0..32])).collect();
+        assert_eq!(values, vec![U256::from(1_000u64), U256::from(2_000_000u64)]);
+    }
+
+    #[test]
+    fn test_filter_logs_by_transaction_index_range() {
+        assert!(TransactionIndexRange::parse("100").is_err());
+        assert!(TransactionIndexRange::parse("a:100").is_err());
+        assert!(TransactionIndexRange::parse("100:100").is_err());
+        assert!(TransactionIndexRange::parse("100:").unwrap().end.is_none());
+
+        let logs: Vec<Log> = [0u64, 1, 2, 3, 2]
+            .iter()
+            .enumerate()
+            .map(|(i, transaction_index)| Log {
+                transaction_index: Some(U64::from(*transaction_index)),
+                log_index: Some(U256::from(i)),
+                ..get_log(H256::from_low_u64_be(*transaction_index), vec![])
+            })
+            .collect();
+        let range = TransactionIndexRange::parse("1:3").unwrap();
+        let schema = get_schema(&["transaction_index"]);
+
+        let mut columns = Logs::default();
+        process_logs(
+            logs,
+            &LogContext::default(),
+            &mut columns,
+            &schema,
+            &LogFilters { transaction_index_range: Some(&range), ..Default::default() },
+        )
+        .unwrap();
+        assert_eq!(columns.transaction_index, vec![1, 2, 2]);
+        assert_eq!((range.n_kept(), range.n_dropped()), (3, 2));
+    }
+
+    #[test]
+    fn test_null_log_policy() {
+        let logs = vec![
+            get_log(H256::from_low_u64_be(1), vec![]),
+            Log { log_index: None, ..get_log(H256::from_low_u64_be(2), vec![]) },
+        ];
+        let schema = get_schema(&["block_number"]);
+        let process = |null_logs: &NullLogs| {
+            let mut columns = Logs::default();
+            let result = process_logs(
+                logs.clone(),
+                &LogContext::default(),
+                &mut columns,
+                &schema,
+                &LogFilters { null_logs: Some(null_logs), ..Default::default() },
+            );
+            result.map(|_| columns.n_rows)
+        };
+
+        // logs with null fields are dropped and counted by default
+        let null_logs = NullLogs::default();
+        assert_eq!(process(&null_logs).unwrap(), 1);
+        assert_eq!(null_logs.n_dropped(), 1);
+
+        // strict policy fails the partition and names the null field
+        let null_logs = NullLogs::new(NullLogPolicy::Error);
+        match process(&null_logs) {
+            Err(CollectError::CollectError(message)) => assert!(message.contains("null log_index")),
+            other => panic!("expected null log_index error, got {:?}", other),
+        }
+        assert_eq!(null_logs.n_dropped(), 0);
+    }
+
+    #[test]
+    fn test_exclude_logs_by_topic0() {
+        let approval = LogDecoder::new("Approval(address indexed,address indexed,uint256)".into())
+            .unwrap()
+            .event
+            .signature();
+        let transfer = H256::from_low_u64_be(2);
+        let logs = vec![
+            get_log(H256::from_low_u64_be(1), vec![approval]),
+            get_log(H256::from_low_u64_be(2), vec![transfer]),
+            get_log(H256::from_low_u64_be(3), vec![approval]),
+            get_log(H256::from_low_u64_be(4), vec![]),
+        ];
+        let excluded = Topic0Exclusion::new(vec![approval.as_bytes().to_vec()]);
+        let schema = get_schema(&["transaction_hash"]);
+
+        let mut columns = Logs::default();
+        process_logs(
+            logs,
+            &LogContext::default(),
+            &mut columns,
+            &schema,
+            &LogFilters { excluded_topic0s: Some(&excluded), ..Default::default() },
+        )
+        .unwrap();
+        let kept = vec![H256::from_low_u64_be(2), H256::from_low_u64_be(4)];
+        assert_eq!(
+            columns.transaction_hash,
+            kept.iter().map(|h| h.as_bytes().to_vec()).collect::<Vec<_>>()
+        );
+        assert_eq!(excluded.n_excluded(), 2);
+        assert_eq!(excluded.counts(), HashMap::from([(approval.as_bytes().to_vec(), 2)]));
+    }
+
+    #[test]
+    fn test_chain_id_column_excluded() {
+        let mut schema = get_schema(&["block_number", "chain_id"]);
+        schema.exclude_chain_id();
+        assert_eq!(schema.columns(), vec!["block_number"]);
+
+        let mut columns = Logs::default();
+        let logs = vec![get_log(H256::from_low_u64_be(1), vec![])];
+        process_logs(logs, &LogContext::default(), &mut columns, &schema, &LogFilters::default())
+            .unwrap();
+        let schemas = HashMap::from([(Datatype::Logs, schema)]);
+        let df = columns.create_dfs(&schemas, 1).unwrap().remove(&Datatype::Logs).unwrap();
+        assert_eq!(df.get_column_names(), vec!["block_number"]);
+        assert_eq!(df.height(), 1);
+    }
+
+    #[test]
+    fn test_abi_events_only_skips_other_events() {
+        let signature = "event Transfer(address indexed from, address indexed to, uint256 value)";
+        let decoder = LogDecoder::new(signature.to_string()).unwrap();
+        let transfer = decoder.event.signature();
+        let approval = LogDecoder::new("Approval(address indexed,address indexed,uint256)".into())
+            .unwrap()
+            .event
+            .signature();
+        let mut schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &None,
+                None,
+                Some(decoder),
+            )
+            .unwrap();
+        // raw columns are kept so that logs of other events are not dropped by decoding
+        schema.skip_decoding = true;
+        let logs: Vec<Log> = [transfer, approval, transfer]
+            .into_iter()
+            .enumerate()
+            .map(|(i, topic0)| Log {
+                log_index: Some(U256::from(i)),
+                ..get_log(H256::from_low_u64_be(i as u64), vec![topic0])
+            })
+            .collect();
+        let process = |abi_events_only: Option<&AbiEventFilter>| {
+            let mut columns = Logs::default();
+            process_logs(
+                logs.clone(),
+                &LogContext::default(),
+                &mut columns,
+                &schema,
+                &LogFilters { abi_events_only, ..Default::default() },
+            )
+            .unwrap();
+            columns
+        };
+
+        // all logs are kept by default
+        assert_eq!(process(None).n_rows, 3);
+
+        let abi_events_only = AbiEventFilter::default();
+        let columns = process(Some(&abi_events_only));
+        assert_eq!(columns.log_index, vec![0, 2]);
+        assert_eq!(abi_events_only.n_skipped(), 1);
+    }
+
+    #[test]
+    fn test_event_name_filter_keeps_matching_events() {
+        let swap = H256::from_low_u64_be(100);
+        let swap_v3 = H256::from_low_u64_be(200);
+        let sync = H256::from_low_u64_be(300);
+        let labels = HashMap::from([
+            (swap.as_bytes().to_vec(), "Swap".to_string()),
+            (swap_v3.as_bytes().to_vec(), "SwapV3".to_string()),
+            (sync.as_bytes().to_vec(), "Sync".to_string()),
+        ]);
+        let schema = get_schema(&["log_index", "event_name"]);
+        let logs: Vec<Log> = [swap, sync, swap_v3, H256::from_low_u64_be(400)]
+            .into_iter()
+            .enumerate()
+            .map(|(i, topic0)| Log {
+                log_index: Some(U256::from(i)),
+                ..get_log(H256::from_low_u64_be(i as u64), vec![topic0])
+            })
+            .collect();
+
+        let filter = EventNameFilter::new(".*Swap.*").unwrap();
+        let mut columns = Logs::default();
+        process_logs(
+            logs,
+            &LogContext::default(),
+            &mut columns,
+            &schema,
+            &LogFilters {
+                topic0_labels: Some(&labels),
+                event_name_filter: Some(&filter),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(columns.log_index, vec![0, 2]);
+        assert_eq!(columns.event_name, vec![Some("Swap".to_string()), Some("SwapV3".to_string())]);
+
+        // unlabeled logs have no event name and are dropped
+        assert_eq!((filter.n_kept(), filter.n_dropped()), (2, 2));
+
+        // patterns match whole event names
+        assert!(!EventNameFilter::new("Swap").unwrap().admit(Some("SwapV3")));
+        assert!(EventNameFilter::new("Swap(").is_err());
+    }
+
+    #[test]
+    fn test_event_name_filter_uses_decoded_event_names() {
+        let signature = "event Transfer(address indexed from, address indexed to, uint256 value)";
+        let decoder = LogDecoder::new(signature.to_string()).unwrap();
+        let transfer = decoder.event.signature();
+        let approval = H256::from_low_u64_be(100);
+        let columns = Some(vec!["log_index".to_string(), "event_name".to_string()]);
+        let mut schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &columns,
+                None,
+                Some(decoder),
+            )
+            .unwrap();
+        // logs that do not decode are kept, so that they can be named by their labels
+        schema.decoded_json = true;
+        let address = |n: u64| H256::from(H160::from_low_u64_be(n));
+        let logs: Vec<Log> = [transfer, approval, H256::from_low_u64_be(200)]
+            .into_iter()
+            .enumerate()
+            .map(|(i, topic0)| Log {
+                log_index: Some(U256::from(i)),
+                data: ethers_core::abi::encode(&[Token::Uint(U256::from(5))]).into(),
+                ..get_log(H256::from_low_u64_be(i as u64), vec![topic0, address(1), address(2)])
+            })
+            .collect();
+        let labels = HashMap::from([(approval.as_bytes().to_vec(), "Approval".to_string())]);
+
+        let process = |pattern: &str| {
+            let filter = EventNameFilter::new(pattern).unwrap();
+            let mut columns = Logs::default();
+            process_logs(
+                logs.clone(),
+                &LogContext::default(),
+                &mut columns,
+                &schema,
+                &LogFilters {
+                    topic0_labels: Some(&labels),
+                    event_name_filter: Some(&filter),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            columns
+        };
+
+        // decoded logs are named by the decoder's event without needing a label
+        let columns = process("Transfer");
+        assert_eq!(columns.log_index, vec![0]);
+        assert_eq!(columns.event_name, vec![Some("Transfer".to_string())]);
+
+        // other logs fall back to their labels
+        let columns = process("Transfer|Approval");
+        assert_eq!(columns.log_index, vec![0, 1]);
+        assert_eq!(
+            columns.event_name,
+            vec![Some("Transfer".to_string()), Some("Approval".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_log_sample_is_reproducible_by_seed() {
+        let schema = get_schema(&["block_number", "log_index"]);
+        let logs: Vec<Log> = (0..1000u64)
+            .map(|i| Log {
+                block_number: Some(U64::from(i / 10)),
+                log_index: Some(U256::from(i % 10)),
+                ..get_log(H256::from_low_u64_be(i), vec![])
+            })
+            .collect();
+        let sample = |log_sample: &LogSample| {
+            let mut columns = Logs::default();
+            process_logs(
+                logs.clone(),
+                &LogContext::default(),
+                &mut columns,
+                &schema,
+                &LogFilters { log_sample: Some(log_sample), ..Default::default() },
+            )
+            .unwrap();
+            columns.block_number.into_iter().zip(columns.log_index).collect::<Vec<_>>()
+        };
+
+        // the same seed selects the same logs
+        let log_sample = LogSample::new(0.25, 7).unwrap();
+        let kept = sample(&log_sample);
+        assert_eq!(kept, sample(&LogSample::new(0.25, 7).unwrap()));
+        assert_eq!(
+            (log_sample.n_kept(), log_sample.n_dropped()),
+            (kept.len() as u64, 1000 - kept.len() as u64)
+        );
+        let kept_fraction = log_sample.kept_fraction().unwrap();
+        assert!((0.2..0.3).contains(&kept_fraction), "kept fraction {}", kept_fraction);
+
+        // another seed selects another subset of about the same size
+        let other = sample(&LogSample::new(0.25, 8).unwrap());
+        assert_ne!(kept, other);
+        assert!((200..300).contains(&other.len()));
+
+        // a fraction of 1 keeps every log, fractions outside (0, 1] are rejected
+        assert_eq!(sample(&LogSample::new(1.0, 7).unwrap()).len(), 1000);
+        assert!(LogSample::new(0.0, 7).is_err());
+        assert!(LogSample::new(1.5, 7).is_err());
+        assert!(LogSample::new(f64::NAN, 7).is_err());
+        assert_eq!(LogSample::new(0.5, 7).unwrap().kept_fraction(), None);
+    }
+
+    #[test]
+    fn test_parallel_decode_matches_serial_decode() {
+        let signature = "event Transfer(address indexed from, address indexed to, uint256 value)";
+        let decoder = LogDecoder::new(signature.to_string()).unwrap();
+        let topic0 = decoder.event.signature();
+        let schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &None,
+                None,
+                Some(decoder),
+            )
+            .unwrap();
+        // every fifth log is missing a topic and fails to decode, which must not misalign rows
+        let logs: Vec<Log> = (0..100u64)
+            .map(|i| {
+                let mut topics =
+                    vec![topic0, H256::from_low_u64_be(i), H256::from_low_u64_be(i + 1)];
+                if i % 5 == 0 {
+                    topics.pop();
+                }
+                Log {
+                    data: ethers_core::abi::encode(&[Token::Uint(U256::from(i))]).into(),
+                    log_index: Some(U256::from(i)),
+                    ..get_log(H256::from_low_u64_be(i), topics)
+                }
+            })
+            .collect();
+
+        let decode = |decode_chunk_size| {
+            let mut schema = schema.clone();
+            schema.decode_chunk_size = decode_chunk_size;
+            let mut columns = Logs::default();
+            process_logs(
+                logs.clone(),
+                &LogContext::default(),
+                &mut columns,
+                &schema,
+                &LogFilters::default(),
+            )
+            .unwrap();
+            columns
+        };
+        let serial = decode(None);
+        assert_eq!(serial.n_rows, 80);
+        for chunk_size in [1, 7, 1000] {
+            let parallel = decode(Some(chunk_size));
+            assert_eq!(parallel.n_rows, serial.n_rows);
+            assert_eq!(parallel.log_index, serial.log_index);
+            assert_eq!(
+                parallel.event_cols.keys().collect::<Vec<_>>(),
+                serial.event_cols.keys().collect::<Vec<_>>()
+            );
+            assert_eq!(parallel.event_cols, serial.event_cols);
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use crate::{dataframes, err, CollectError, ColumnEncoding, FileOutput, LogDecoder, Table};
+use ethers::prelude::*;
+use ethers_core::abi::RawLog;
+use polars::prelude::*;
+use std::path::{Path, PathBuf};
+
+const RAW_LOG_COLUMNS: [&str; 5] = ["topic0", "topic1", "topic2", "topic3", "data"];
+
+/// decode previously collected raw log files without refetching, writing new files to sink
+pub fn decode_logs(
+    paths: &[PathBuf],
+    schema: &Table,
+    sink: &FileOutput,
+) -> Result<Vec<PathBuf>, CollectError> {
+    let decoder = schema
+        .log_decoder
+        .as_ref()
+        .ok_or_else(|| err("decoding raw logs requires an event signature"))?;
+    if !sink.writes_parquet() {
+        return Err(err("decoded logs can only be written as parquet"))
+    }
+
+    // inputs are checked before any file is written
+    for path in paths.iter() {
+        if path.extension().and_then(|extension| extension.to_str()) != Some("parquet") {
+            let message = format!("can only decode raw logs from parquet files, got {:?}", path);
+            return Err(err(message.as_str()))
+        }
+    }
+
+    let _temp_dir_cleanup = sink.temp_dir_cleanup();
+    let mut outputs = Vec::new();
+    for path in paths.iter() {
+        let mut df = decode_log_file(path, decoder, schema)?;
+        let output_path = get_decoded_path(path, sink)?;
+        if output_path.exists() && !sink.overwrite {
+            return Err(err(format!("output file already exists: {:?}", output_path).as_str()))
+        }
+        dataframes::df_to_file(&mut df, &output_path, sink)
+            .map_err(|_| err("error writing decoded file"))?;
+        outputs.push(output_path);
+    }
+    Ok(outputs)
+}
+
+fn get_decoded_path(path: &Path, sink: &FileOutput) -> Result<PathBuf, CollectError> {
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or_else(|| err("could not determine name of input file"))?;
+    let filename = format!("{}__decoded.{}", stem, sink.output_format().extension());
+    Ok(sink.output_dir.join(filename))
+}
+
+/// read raw log file and append decoded event columns, dropping logs that do not match event
+fn decode_log_file(
+    path: &Path,
+    decoder: &LogDecoder,
+    schema: &Table,
+) -> Result<DataFrame, CollectError> {
+    let file = std::fs::File::open(path).map_err(|_| err("could not open file path"))?;
+    let df = ParquetReader::new(file).finish().map_err(|_| {
+        let message = format!("could not read {:?}, raw logs must be a parquet file", path);
+        err(message.as_str())
+    })?;
+
+    // validate that raw topics and data are present
+    for column in RAW_LOG_COLUMNS.iter() {
+        match df.column(column) {
+            Ok(series) if series.dtype() == &DataType::Binary => {}
+            Ok(_) => {
+                let message = format!("column {} of {:?} must be binary", column, path);
+                return Err(err(message.as_str()))
+            }
+            Err(_) => {
+                let message = format!("column {} missing from {:?}", column, path);
+                return Err(err(message.as_str()))
+            }
+        }
+    }
+    let raw_columns = RAW_LOG_COLUMNS
+        .iter()
+        .map(|column| Ok(df.column(column)?.binary()?.into_iter().collect::<Vec<_>>()))
+        .collect::<Result<Vec<_>, CollectError>>()?;
+
+    // reconstruct logs
+    let mut logs = Vec::new();
+    let mut mask = Vec::new();
+    for row in 0..df.height() {
+        let mut topics = Vec::new();
+        for topic in raw_columns[..4].iter().filter_map(|column| column[row]) {
+            if topic.len() != 32 {
+                return Err(err(format!("invalid topic length in {:?}", path).as_str()))
+            }
+            topics.push(H256::from_slice(topic));
+        }
+        let data = raw_columns[4][row].unwrap_or_default().to_vec();
+        let log = Log { topics, data: data.into(), ..Default::default() };
+        let matches = decoder.event.parse_log(RawLog::from(log.clone())).is_ok();
+        if matches {
+            logs.push(log);
+        }
+        mask.push(matches);
+    }
+
+    // append decoded columns
+    let mask = Series::new("mask", mask);
+    let df = df.filter(mask.bool()?)?;
+    let mut columns = df.get_columns().to_vec();
+    for (name, tokens) in decoder.parse_log_from_event(logs) {
+        columns.extend(decoder.make_series(
+            name,
+            tokens,
+            df.height(),
+            &schema.u256_types,
+            &ColumnEncoding::Binary,
+        )?);
+    }
+    Ok(DataFrame::new(columns)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Datatype, U256Type};
+    use ethers_core::abi::Token;
+
+    #[test]
+    fn test_decoded_logs_round_trip_raw_log_files() {
+        let output_dir =
+            std::env::temp_dir().join(format!("cryo_test_decode_logs_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&output_dir);
+        std::fs::create_dir_all(&output_dir).unwrap();
+        let decoder = LogDecoder::new(
+            "event Transfer(address indexed from, address indexed to, uint256 value)".into(),
+        )
+        .unwrap();
+        let schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::String],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &None,
+                None,
+                Some(decoder.clone()),
+            )
+            .unwrap();
+        let sink = FileOutput { output_dir: output_dir.clone(), ..Default::default() };
+
+        // raw transfers, and a log of another event that is dropped
+        let (from, to) = (H160::from_low_u64_be(1), H160::from_low_u64_be(2));
+        let topic = |address: H160| Some(H256::from(address).as_bytes().to_vec());
+        let signature = Some(decoder.event.signature().as_bytes().to_vec());
+        let other = Some(H256::from_low_u64_be(7).as_bytes().to_vec());
+        let value = |value: u64| Some(ethers_core::abi::encode(&[Token::Uint(value.into())]));
+        let mut raw = df!(
+            "block_number" => [1u32, 2, 3],
+            "topic0" => [signature.clone(), other, signature],
+            "topic1" => [topic(from), None, topic(to)],
+            "topic2" => [topic(to), None, topic(from)],
+            "topic3" => [None::<Vec<u8>>, None, None],
+            "data" => [value(1000), value(1), value(5)],
+        )
+        .unwrap();
+        let path = output_dir.join("ethereum__logs__00000000_to_00000009.parquet");
+        dataframes::df_to_file(&mut raw, &path, &sink).unwrap();
+
+        let outputs = decode_logs(&[path.clone()], &schema, &sink).unwrap();
+        let file = std::fs::File::open(&outputs[0]).unwrap();
+        let decoded = ParquetReader::new(file).finish().unwrap();
+        let column = |name: &str| decoded.column(name).unwrap().clone();
+        let block_numbers: Vec<_> = column("block_number").u32().unwrap().into_iter().collect();
+        assert_eq!(block_numbers, vec![Some(1), Some(3)]);
+        let froms: Vec<_> = column("event__from").binary().unwrap().into_iter().collect();
+        assert_eq!(froms, vec![Some(from.as_bytes()), Some(to.as_bytes())]);
+        let tos: Vec<_> = column("event__to").binary().unwrap().into_iter().collect();
+        assert_eq!(tos, vec![Some(to.as_bytes()), Some(from.as_bytes())]);
+        let values: Vec<_> = column("event__value_string").utf8().unwrap().into_iter().collect();
+        assert_eq!(values, vec![Some("1000"), Some("5")]);
+
+        // raw topics and data are kept as they were collected
+        let kept = BooleanChunked::from_slice("kept", &[true, false, true]);
+        for name in RAW_LOG_COLUMNS {
+            assert!(column(name).equals_missing(&raw.column(name).unwrap().filter(&kept).unwrap()));
+        }
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[test]
+    fn test_decoding_rejects_files_that_are_not_parquet() {
+        let output_dir =
+            std::env::temp_dir().join(format!("cryo_test_decode_csv_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&output_dir);
+        std::fs::create_dir_all(&output_dir).unwrap();
+        let decoder = LogDecoder::new(
+            "event Transfer(address indexed from, address indexed to, uint256 value)".into(),
+        )
+        .unwrap();
+        let schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::String],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &None,
+                None,
+                Some(decoder),
+            )
+            .unwrap();
+        let sink = FileOutput { output_dir: output_dir.clone(), ..Default::default() };
+
+        let csv_path = output_dir.join("ethereum__logs__00000000_to_00000009.csv");
+        std::fs::write(&csv_path, "block_number,topic0\n1,0x00\n").unwrap();
+        let error = decode_logs(&[csv_path], &schema, &sink).unwrap_err();
+        assert!(error.to_string().contains("parquet"));
+
+        // files named like parquet files must hold parquet data
+        let fake_path = output_dir.join("ethereum__logs__00000010_to_00000019.parquet");
+        std::fs::write(&fake_path, "block_number,topic0\n1,0x00\n").unwrap();
+        let error = decode_logs(&[fake_path], &schema, &sink).unwrap_err();
+        assert!(error.to_string().contains("must be a parquet file"));
+        assert!(!output_dir.join("ethereum__logs__00000010_to_00000019__decoded.parquet").exists());
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+}
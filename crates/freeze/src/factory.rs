@@ -0,0 +1,215 @@
+use crate::{err, CollectError, LogDecoder, Source};
+use ethers::prelude::*;
+use ethers_core::abi::{ParamType, RawLog, Token};
+
+/// default number of blocks of each eth_getLogs request for the deployments of a factory
+pub const DEFAULT_FACTORY_BLOCKS_PER_REQUEST: u64 = 10_000;
+
+/// factory whose deployments define the addresses whose logs are collected
+///
+/// the deployed addresses are read from an address parameter of the factory's creation event,
+/// e.g. the pool of a Uniswap PoolCreated event
+#[derive(Clone, Debug)]
+pub struct LogFactory {
+    /// address of factory
+    pub address: Vec<u8>,
+    /// decoder of the creation event emitted by factory
+    pub decoder: LogDecoder,
+    /// name of the event parameter holding the deployed address
+    pub address_arg: String,
+    /// first block searched for deployments, None searches from the creation of factory
+    pub start_block: Option<u64>,
+    /// number of blocks of each eth_getLogs request
+    pub blocks_per_request: u64,
+}
+
+impl LogFactory {
+    /// create new factory, checking that address_arg is an address parameter of the event
+    pub fn new(
+        address: Vec<u8>,
+        decoder: LogDecoder,
+        address_arg: String,
+        start_block: Option<u64>,
+        blocks_per_request: u64,
+    ) -> Result<Self, CollectError> {
+        if address.len() != 20 {
+            return Err(err("factory must be a 20 byte address"))
+        }
+        match decoder.event.inputs.iter().find(|input| input.name == address_arg) {
+            Some(input) if input.kind == ParamType::Address => {}
+            Some(_) => {
+                let message = format!("factory event parameter {} is not an address", address_arg);
+                return Err(err(message.as_str()))
+            }
+            None => {
+                let message = format!("factory event has no parameter {}", address_arg);
+                return Err(err(message.as_str()))
+            }
+        }
+        let blocks_per_request = blocks_per_request.max(1);
+        Ok(LogFactory { address, decoder, address_arg, start_block, blocks_per_request })
+    }
+
+    /// addresses deployed by factory up to end_block, in order of deployment
+    ///
+    /// this is the first stage of a factory collection, its addresses are then collected as an
+    /// address set
+    pub async fn deployed_addresses(
+        &self,
+        source: &Source,
+        end_block: u64,
+    ) -> Result<Vec<Vec<u8>>, CollectError> {
+        let factory = H160::from_slice(&self.address);
+        let start_block = match self.start_block {
+            Some(start_block) => start_block,
+            None => source
+                .get_contract_creation_block(factory)
+                .await?
+                .ok_or_else(|| err("factory has no code at the latest block"))?,
+        };
+        if start_block > end_block {
+            return Ok(Vec::new())
+        }
+
+        let mut filters = Vec::new();
+        let mut start = start_block;
+        while start <= end_block {
+            let end = start.saturating_add(self.blocks_per_request - 1).min(end_block);
+            let filter = Filter::new()
+                .address(factory)
+                .topic0(self.decoder.event.signature())
+                .from_block(start)
+                .to_block(end);
+            filters.push(filter);
+            start = match end.checked_add(1) {
+                Some(next) => next,
+                None => break,
+            };
+        }
+        let requests = filters.iter().map(|filter| source.get_logs(filter));
+        let mut logs: Vec<Log> =
+            futures::future::try_join_all(requests).await?.into_iter().flatten().collect();
+        logs.sort_by_key(|log| (log.block_number, log.log_index));
+
+        logs.into_iter().map(|log| self.deployed_address(log)).collect()
+    }
+
+    /// address deployed by a creation event log
+    fn deployed_address(&self, log: Log) -> Result<Vec<u8>, CollectError> {
+        let decoded = self
+            .decoder
+            .event
+            .parse_log(RawLog::from(log))
+            .map_err(|_| err("could not decode factory creation event"))?;
+        match decoded.params.into_iter().find(|param| param.name == self.address_arg) {
+            Some(param) => match param.value {
+                Token::Address(address) => Ok(address.as_bytes().to_vec()),
+                _ => Err(err("factory event parameter is not an address")),
+            },
+            None => Err(err("factory event is missing its address parameter")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        CollectByBlock, ColumnEncoding, Datatype, LogAddressSet, Logs, MetaDatatype, Params, Query,
+        U256Type,
+    };
+    use std::{collections::HashMap, sync::Arc};
+
+    #[tokio::test]
+    async fn test_factory_deployments_collected_in_two_stages() {
+        let (provider, mock) = Provider::mocked();
+        let source = Arc::new(Source { ..Source::from_provider(provider) });
+        let factory_address = H160::from_low_u64_be(0xfac);
+        let decoder = LogDecoder::new(
+            "event PairCreated(address indexed token0, address indexed token1, address pair, \
+             uint256 n_pairs)"
+                .to_string(),
+        )
+        .unwrap();
+        assert!(LogFactory::new(
+            factory_address.as_bytes().to_vec(),
+            decoder.clone(),
+            "n_pairs".to_string(),
+            Some(0),
+            10,
+        )
+        .is_err());
+        let factory = LogFactory::new(
+            factory_address.as_bytes().to_vec(),
+            decoder.clone(),
+            "pair".to_string(),
+            Some(0),
+            10,
+        )
+        .unwrap();
+
+        // stage one: two pairs are created within the run's blocks
+        let (pair_a, pair_b) = (H160::from_low_u64_be(0xa), H160::from_low_u64_be(0xb));
+        let creation = |block_number: u64, pair: H160| Log {
+            address: factory_address,
+            topics: vec![
+                decoder.event.signature(),
+                H256::from_low_u64_be(1),
+                H256::from_low_u64_be(block_number),
+            ],
+            data: ethers_core::abi::encode(&[
+                Token::Address(pair),
+                Token::Uint(U256::from(block_number)),
+            ])
+            .into(),
+            block_number: Some(U64::from(block_number)),
+            log_index: Some(U256::zero()),
+            ..Default::default()
+        };
+        let pair_log = |block_number: u64, pair: H160| Log {
+            address: pair,
+            block_number: Some(U64::from(block_number)),
+            transaction_hash: Some(H256::from_low_u64_be(block_number)),
+            transaction_index: Some(U64::zero()),
+            log_index: Some(U256::zero()),
+            ..Default::default()
+        };
+        mock.push(vec![pair_log(6, pair_b), pair_log(8, pair_a)]).unwrap();
+        mock.push(vec![creation(2, pair_a), creation(4, pair_b)]).unwrap();
+
+        let addresses = factory.deployed_addresses(&source, 9).await.unwrap();
+        assert_eq!(addresses, vec![pair_a.as_bytes().to_vec(), pair_b.as_bytes().to_vec()]);
+        let creations = Filter::new()
+            .address(factory_address)
+            .topic0(decoder.event.signature())
+            .from_block(0)
+            .to_block(9);
+        mock.assert_request("eth_getLogs", [serde_json::to_value(creations).unwrap()]).unwrap();
+
+        // stage two: logs of the deployed pairs are collected as an address set
+        let schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &Some(vec!["block_number".to_string(), "address".to_string()]),
+                None,
+                None,
+            )
+            .unwrap();
+        let query = Arc::new(Query {
+            datatypes: vec![MetaDatatype::Scalar(Datatype::Logs)],
+            schemas: HashMap::from([(Datatype::Logs, schema)]),
+            log_address_set: Some(Arc::new(LogAddressSet::new(addresses, 1000))),
+            ..Default::default()
+        });
+        let request = Params { block_range: Some((0, 9)), ..Default::default() };
+        let (logs, _context) =
+            <Logs as CollectByBlock>::extract(request, source, query).await.unwrap();
+        let emitters: Vec<H160> = logs.iter().map(|log| log.address).collect();
+        assert_eq!(emitters, vec![pair_b, pair_a]);
+        let pairs = Filter::new().from_block(0).to_block(9).address(vec![pair_a, pair_b]);
+        mock.assert_request("eth_getLogs", [serde_json::to_value(pairs).unwrap()]).unwrap();
+    }
+}
@@ -1,15 +1,30 @@
 use crate::{
-    collect_partition, dataframes, err, reports, summaries, CollectError, Datatype, ExecutionEnv,
-    FileOutput, FreezeSummary, MetaDatatype, Partition, Query, Source,
+    block_index, collect_partition, dataframes, err, gaps, provenance, reports,
+    row_ids::{self, RowIdTurn},
+    single_file,
+    stdout::{StdoutStream, StdoutTurn},
+    summaries, ChunkData, CollectError, ColumnStats, Datatype, Dim, ExecutionEnv, FileOutput,
+    FreezeSummary, MetaDatatype, Partition, PostRunHook, Query, ReportInterval, SchemaFunctions,
+    SlowPartition, Source, Table, TimeDimension,
 };
 use chrono::{DateTime, Local};
 use futures::{stream::FuturesUnordered, StreamExt};
+use polars::prelude::DataFrame;
 use std::{
     collections::{HashMap, HashSet},
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::Arc,
+    time::{Duration, Instant},
 };
-use tokio::sync::Semaphore;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+type FileColumnStats = HashMap<PathBuf, HashMap<String, ColumnStats>>;
+
+/// name of the marker file that complete runs write into their output directory
+pub const SUCCESS_MARKER: &str = "_SUCCESS";
+
+/// name used in place of the datatype name in paths of the counts of logs per block
+const LOGS_PER_BLOCK: &str = "logs_per_block";
 
 type PartitionPayload = (
     Partition,
@@ -31,9 +46,60 @@ pub async fn freeze(
 ) -> Result<Option<FreezeSummary>, CollectError> {
     // check validity of query
     query.is_valid()?;
+    let numbers_logs = query
+        .schemas
+        .get(&Datatype::Logs)
+        .map_or(false, |schema| schema.has_column("global_log_seq"));
+    if numbers_logs && !(sink.single_file || sink.stdout) {
+        return Err(err("global_log_seq requires a single file or stdout output, which number \
+             logs across the whole run"))
+    }
+    let logs_have_address =
+        query.schemas.get(&Datatype::Logs).map_or(true, |schema| schema.has_column("address"));
+    if query.max_logs_per_address.is_some() && !logs_have_address {
+        return Err(err("max logs per address requires the address column"))
+    }
+
+    // files of a target size span or split partitions, so they are named by the blocks they cover
+    // and existing output is found by those block ranges, as when filling gaps
+    let sized_files = match sink.target_partition_bytes {
+        Some(_) if query.partitioned_by != vec![Dim::BlockNumber] => {
+            return Err(err("target partition bytes require partitions of block ranges only"))
+        }
+        Some(target_bytes) => Some(Arc::new(dataframes::SizedFiles::new(target_bytes)?)),
+        None => None,
+    };
+
+    // restrict partitions to blocks missing from existing files, skipping all if there are none
+    let gap_fill = if sink.fill_gaps || (sized_files.is_some() && !sink.overwrite) {
+        Some(gaps::fill_gaps(query, sink)?)
+    } else {
+        None
+    };
+    let (query, filled_gaps) = match &gap_fill {
+        Some((gap_query, gaps)) if !gaps.is_empty() => (gap_query, gaps.clone()),
+        _ => (query, Vec::new()),
+    };
 
-    // get partitions
-    let (payloads, skipping) = get_payloads(query, source, sink, env)?;
+    // summaries of a run streaming to stdout would interleave with its rows, the progress bar and
+    // warnings are drawn to stderr
+    let stdout_env;
+    let env = if sink.stdout && !env.dry {
+        stdout_env = ExecutionEnv { verbose: 0, ..env.clone() };
+        &stdout_env
+    } else {
+        env
+    };
+
+    // get partitions, single-file runs collect partitions into a spool that is merged at the end
+    let spool =
+        if sink.single_file { Some(single_file::SingleFileSpool::new(query, sink)?) } else { None };
+    let (payloads, skipping) = match &spool {
+        _ if gap_fill.is_some() && filled_gaps.is_empty() => (Vec::new(), query.partitions.clone()),
+        Some(spool) if spool.output_exists(query, sink)? => (Vec::new(), query.partitions.clone()),
+        Some(spool) => get_payloads(&spool.query, source, &spool.sink, env)?,
+        None => get_payloads(query, source, sink, env)?,
+    };
 
     // print summary
     if env.verbose >= 1 {
@@ -47,20 +113,53 @@ pub async fn freeze(
 
     // check if empty
     if payloads.is_empty() {
-        let results = FreezeSummary { skipped: skipping, ..Default::default() };
+        let mut results = FreezeSummary { skipped: skipping, ..Default::default() };
         if env.verbose >= 1 {
             summaries::print_cryo_conclusion(&results, query, env)
         }
+        if sink.success_marker {
+            write_success_marker(sink, &results)?;
+        }
+        if let Some(hook) = &env.post_run_hook {
+            run_post_run_hook(hook, &sink.output_dir, None, &mut results);
+        }
         return Ok(Some(results))
     }
 
+    // a marker left by an earlier run no longer describes the output once new files are written
+    if sink.success_marker {
+        remove_success_marker(sink)?;
+    }
+
     // create initial report
     if env.report {
-        reports::write_report(env, query, sink, None)?;
+        reports::write_report(env, query, sink, None).await?;
     };
 
     // perform collection
-    let results = freeze_partitions(env, payloads, skipping).await;
+    let _temp_dir_cleanup = sink.temp_dir_cleanup();
+    let mut results = freeze_partitions(env, query, sink, payloads, skipping, sized_files).await;
+    if sink.fill_gaps {
+        results.filled_gaps = gaps::completed_gaps(filled_gaps, &results);
+    }
+    results.retries_used = source.retry_budget.as_ref().map(|budget| budget.used());
+    results.throttle_events =
+        source.concurrency_throttle.as_ref().map(|throttle| throttle.n_events());
+    results.rpc_latency = source.rpc_latency.as_ref().and_then(|tracker| tracker.summary());
+    results.in_flight_pauses = env.in_flight_bytes.as_ref().map(|in_flight| in_flight.n_pauses());
+    results.slow_partitions =
+        env.slow_partitions.as_ref().map(|slow| slow.flagged()).unwrap_or_default();
+    results.unavailable_ranges =
+        source.unavailable_log_ranges.as_ref().map(|ranges| ranges.ranges()).unwrap_or_default();
+    if let Some(spool) = &spool {
+        write_single_file(spool, query, sink, &mut results)?;
+    }
+    if sink.stdout {
+        warn_incomplete_stream(&results);
+    }
+    if sink.block_index {
+        write_block_index(query, sink, &results, env)?;
+    }
 
     // create summary
     if env.verbose >= 1 {
@@ -68,10 +167,22 @@ pub async fn freeze(
     }
 
     // create final report
-    if env.report {
-        reports::write_report(env, query, sink, Some(&results))?;
+    let report_path = if env.report {
+        Some(reports::write_report(env, query, sink, Some(&results)).await?)
+    } else {
+        None
     };
 
+    // mark output as complete
+    if sink.success_marker {
+        write_success_marker(sink, &results)?;
+    }
+
+    // hand off to downstream jobs
+    if let Some(hook) = &env.post_run_hook {
+        run_post_run_hook(hook, &sink.output_dir, report_path.as_deref(), &mut results);
+    }
+
     // return
     Ok(Some(results))
 }
@@ -90,10 +201,41 @@ fn get_payloads(
     let mut payloads = Vec::new();
     let mut skipping = Vec::new();
     let mut all_paths = HashSet::new();
+
+    // files of block ranges are also found under names padded to another block label width
+    let skip_existing = !sink.overwrite && !sink.stdout;
+    let existing_ranges = if skip_existing && query.partitioned_by == vec![Dim::BlockNumber] {
+        gaps::existing_block_ranges(query, sink)?
+    } else {
+        HashSet::new()
+    };
     for datatype in query.datatypes.clone().into_iter() {
         for partition in query.partitions.clone().into_iter() {
             let paths = sink.get_paths(query, &partition, Some(vec![datatype.clone()]))?;
-            if !sink.overwrite && paths.values().all(|path| path.exists()) {
+            let logs_per_block_path = if query.logs_per_block && paths.contains_key(&Datatype::Logs)
+            {
+                Some(sink.get_named_path(query, &partition, LOGS_PER_BLOCK.to_string())?)
+            } else {
+                None
+            };
+            // day files are only known once written, so their manifests are checked instead
+            let block_range = partition
+                .block_numbers
+                .as_ref()
+                .and_then(|chunks| Some((chunks.min_value()?, chunks.max_value()?)));
+            let datatype_files_exist = if sink.split_by_day {
+                paths.values().all(|path| dataframes::day_files_exist(&sink.output_dir, path))
+            } else {
+                paths.iter().all(|(datatype, path)| {
+                    path.exists() ||
+                        block_range
+                            .map_or(false, |range| existing_ranges.contains(&(*datatype, range)))
+                })
+            };
+            if skip_existing &&
+                datatype_files_exist &&
+                logs_per_block_path.iter().all(|path| path.exists())
+            {
                 skipping.push(partition);
                 continue
             }
@@ -126,8 +268,11 @@ fn get_payloads(
 
 async fn freeze_partitions(
     env: &ExecutionEnv,
+    query: &Query,
+    sink: &FileOutput,
     payloads: Vec<PartitionPayload>,
     skipped: Vec<Partition>,
+    sized_files: Option<Arc<dataframes::SizedFiles>>,
 ) -> FreezeSummary {
     if let Some(bar) = &env.bar {
         bar.set_length(payloads.len() as u64);
@@ -138,26 +283,108 @@ async fn freeze_partitions(
         }
     }
 
-    // spawn task for each partition
+    // row ids and address caps are applied in block order, whichever order partitions run in
+    let in_block_order =
+        query.schemas.values().any(|schema| schema.row_id) || query.max_logs_per_address.is_some();
+    let mut row_id_turns = if in_block_order {
+        let partitions: Vec<_> = payloads.iter().map(|payload| (&payload.0, &payload.1)).collect();
+        row_ids::row_id_turns(&partitions).into_iter().map(Some).collect()
+    } else {
+        Vec::new()
+    };
+    row_id_turns.resize_with(payloads.len(), || None);
+
+    // rows are added to files of a target size in block order, taking turns like row ids
+    let mut size_turns: Vec<_> = match &sized_files {
+        Some(sized_files) => {
+            let partitions: Vec<_> =
+                payloads.iter().map(|payload| (&payload.0, &payload.1)).collect();
+            row_ids::row_id_turns(&partitions)
+                .into_iter()
+                .map(|turn| Some((sized_files.clone(), turn)))
+                .collect()
+        }
+        None => Vec::new(),
+    };
+    size_turns.resize_with(payloads.len(), || None);
+
+    // rows are streamed to stdout in block order, taking turns like row ids
+    let mut stdout_turns: Vec<_> = if sink.stdout {
+        let stream = Arc::new(StdoutStream::default());
+        let partitions: Vec<_> = payloads.iter().map(|payload| (&payload.0, &payload.1)).collect();
+        row_ids::row_id_turns(&partitions).into_iter().map(|turn| Some(stream.turn(turn))).collect()
+    } else {
+        Vec::new()
+    };
+    stdout_turns.resize_with(payloads.len(), || None);
+
+    // spawn task for each partition, tasks check for an abort before starting
     let mut futures = FuturesUnordered::new();
-    for payload in payloads.into_iter() {
-        futures.push(tokio::spawn(
-            async move { (payload.0.clone(), freeze_partition(payload).await) },
-        ));
+    let mut abort_handles = Vec::new();
+    for (((payload, row_id_turn), size_turn), stdout_turn) in
+        payloads.into_iter().zip(row_id_turns).zip(size_turns).zip(stdout_turns)
+    {
+        let partition = payload.0.clone();
+        let handle = tokio::spawn(freeze_partition(payload, row_id_turn, size_turn, stdout_turn));
+        abort_handles.push(handle.abort_handle());
+        futures.push(async move { (partition, handle.await) });
     }
 
-    // aggregate results
-    let mut completed = Vec::new();
-    let mut errored = Vec::new();
-    let mut n_rows = 0;
+    // aggregate results, periodically updating the incomplete report
+    let mut summary = FreezeSummary { skipped, ..Default::default() };
+    let mut cadence = env.report_interval.filter(|_| env.report).map(ReportCadence::new);
     while let Some(result) = futures.next().await {
         match result {
-            Ok((partition, Ok(chunk_n_rows))) => {
-                n_rows += chunk_n_rows;
-                completed.push(partition)
+            (partition, Ok(Ok(PartitionOutcome::Written(n_rows, column_stats)))) => {
+                record_completed(&mut summary, partition, n_rows, column_stats);
+                record_partition_outcome(env, true);
+            }
+            (_, Ok(Ok(outcome @ PartitionOutcome::Sized(..)))) |
+            (_, Ok(Ok(outcome @ PartitionOutcome::SizedFailed(..)))) => {
+                record_partition_outcome(env, matches!(outcome, PartitionOutcome::Sized(..)));
+                record_sized_outcome(&mut summary, outcome);
+            }
+            (partition, Ok(Ok(PartitionOutcome::Unavailable))) => {
+                summary.unavailable.push(partition);
+                record_partition_outcome(env, true);
+            }
+            (partition, Ok(Ok(PartitionOutcome::NotStarted))) if env.aborted() => {
+                summary.abort_skipped.push(partition)
+            }
+            (partition, Ok(Ok(PartitionOutcome::NotStarted))) => {
+                summary.budget_skipped.push(partition)
+            }
+            (partition, Ok(Err(e))) => {
+                summary.errored.push((Some(partition), e));
+                record_partition_outcome(env, false);
+            }
+            (partition, Err(e)) if e.is_cancelled() => summary.abort_skipped.push(partition),
+            (_, Err(e)) => {
+                let e = err(format!("error joining chunks: {:?}", e).as_str());
+                summary.errored.push((None, e));
+                record_partition_outcome(env, false);
+            }
+        }
+
+        // a fail-fast run cancels in-flight partitions once it aborts
+        if env.fail_fast && env.aborted() {
+            abort_handles.drain(..).for_each(|handle| handle.abort());
+        }
+        if cadence.as_mut().map_or(false, |cadence| cadence.record()) {
+            if let Err(e) = reports::write_report_snapshot(env, query, sink, &summary).await {
+                eprintln!("could not update incomplete report: {}", e);
+            }
+        }
+    }
+
+    // write the rows still held for files of a target size, completing the partitions they hold
+    if let Some(sized_files) = &sized_files {
+        match sized_files.finish() {
+            Ok(batch) => {
+                let outcome = write_sized_batch(batch, sized_files, query, sink);
+                record_sized_outcome(&mut summary, outcome);
             }
-            Ok((partition, Err(e))) => errored.push((Some(partition), e)),
-            Err(e) => errored.push((None, err(format!("error joining chunks: {:?}", e).as_str()))),
+            Err(e) => summary.errored.push((None, e)),
         }
     }
 
@@ -165,36 +392,1643 @@ async fn freeze_partitions(
         bar.finish_and_clear();
     }
 
-    FreezeSummary { completed, errored, skipped, n_rows }
+    FreezeSummary { aborted: env.aborted(), ..summary }
+}
+
+/// tracks when the next report snapshot of a run is due
+struct ReportCadence {
+    interval: ReportInterval,
+    n_partitions: u64,
+    last_snapshot: Instant,
+}
+
+impl ReportCadence {
+    fn new(interval: ReportInterval) -> Self {
+        ReportCadence { interval, n_partitions: 0, last_snapshot: Instant::now() }
+    }
+
+    /// record a finished partition, returning whether a snapshot is due
+    fn record(&mut self) -> bool {
+        self.n_partitions += 1;
+        if self.interval.is_due(self.n_partitions, self.last_snapshot.elapsed()) {
+            self.n_partitions = 0;
+            self.last_snapshot = Instant::now();
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// record a completed partition, partitions whose filters matched no rows are also recorded as
+/// completed empty rather than skipped or errored
+fn record_completed(
+    summary: &mut FreezeSummary,
+    partition: Partition,
+    n_rows: u64,
+    column_stats: FileColumnStats,
+) {
+    if n_rows == 0 {
+        summary.completed_empty.push(partition.clone());
+    }
+    summary.n_rows += n_rows;
+    summary.column_stats.extend(column_stats);
+    summary.completed.push(partition);
+}
+
+/// record the partitions completed or errored by writing files of a target size
+fn record_sized_outcome(summary: &mut FreezeSummary, outcome: PartitionOutcome) {
+    match outcome {
+        PartitionOutcome::Sized(partitions, column_stats) => {
+            summary.column_stats.extend(column_stats);
+            for (partition, n_rows) in partitions.into_iter() {
+                record_completed(summary, partition, n_rows, HashMap::new());
+            }
+        }
+        PartitionOutcome::SizedFailed(partitions, e) => {
+            let message = e.to_string();
+            let mut e = Some(e);
+            for partition in partitions.into_iter() {
+                let e = e.take().unwrap_or_else(|| err(&message));
+                summary.errored.push((Some(partition), e));
+            }
+            if let Some(e) = e {
+                summary.errored.push((None, e));
+            }
+        }
+        _ => {}
+    }
+}
+
+/// merge the spooled partitions of a single-file run into its output files
+///
+/// nothing is written unless every partition completed, since the file is named by the overall
+/// block range of the run
+fn write_single_file(
+    spool: &single_file::SingleFileSpool,
+    query: &Query,
+    sink: &FileOutput,
+    summary: &mut FreezeSummary,
+) -> Result<(), CollectError> {
+    let n_incomplete =
+        summary.errored.len() + summary.budget_skipped.len() + summary.abort_skipped.len();
+    if n_incomplete > 0 {
+        eprintln!("not writing single file because {} chunks did not complete", n_incomplete);
+        return Ok(())
+    }
+    let mut column_stats = HashMap::new();
+    for (datatype, path, mut df) in spool.merge(query, sink, &summary.completed)? {
+        let schema = query.schemas.get_schema(&datatype)?;
+        if datatype == Datatype::Logs && schema.has_column("global_log_seq") {
+            crate::datasets::logs::number_global_log_seq(&mut df, 0)?;
+        }
+        let metadata = provenance::filter_metadata(query, &query.partitions, datatype, sink)?;
+        write_df(&mut df, schema, &path, sink, &metadata, &mut column_stats)?;
+    }
+    summary.column_stats = column_stats;
+    Ok(())
+}
+
+/// warn that the rows streamed to stdout end before the end of the run, since the stream stops at
+/// the first partition that did not complete
+fn warn_incomplete_stream(summary: &FreezeSummary) {
+    let n_incomplete = summary.errored.len() +
+        summary.unavailable.len() +
+        summary.budget_skipped.len() +
+        summary.abort_skipped.len();
+    if n_incomplete > 0 {
+        eprintln!(
+            "stdout stream ended early because {} chunks did not complete, rows of blocks after \
+             the first of them were not streamed",
+            n_incomplete
+        );
+    }
+}
+
+/// index the files of the run, unless some chunks did not complete
+fn write_block_index(
+    query: &Query,
+    sink: &FileOutput,
+    summary: &FreezeSummary,
+    env: &ExecutionEnv,
+) -> Result<(), CollectError> {
+    let n_incomplete =
+        summary.errored.len() + summary.budget_skipped.len() + summary.abort_skipped.len();
+    if n_incomplete > 0 {
+        eprintln!("not writing block index because {} chunks did not complete", n_incomplete);
+        return Ok(())
+    }
+    let path = block_index::write_block_index(query, sink, summary)?;
+    if env.verbose >= 1 {
+        println!("wrote block index to {}", path.display());
+    }
+    Ok(())
 }
 
-async fn freeze_partition(payload: PartitionPayload) -> Result<u64, CollectError> {
+/// write marker signaling that the output of the run is complete, unless some chunks did not
+/// complete
+fn write_success_marker(sink: &FileOutput, summary: &FreezeSummary) -> Result<(), CollectError> {
+    let n_incomplete = n_incomplete_chunks(summary);
+    if n_incomplete > 0 {
+        eprintln!(
+            "not writing {} because {} chunks did not complete",
+            SUCCESS_MARKER, n_incomplete
+        );
+        return Ok(())
+    }
+    std::fs::create_dir_all(&sink.output_dir).map_err(|_| err("could not create output dir"))?;
+    std::fs::write(sink.output_dir.join(SUCCESS_MARKER), "")
+        .map_err(|_| err("could not write success marker"))
+}
+
+fn n_incomplete_chunks(summary: &FreezeSummary) -> usize {
+    summary.errored.len() +
+        summary.budget_skipped.len() +
+        summary.abort_skipped.len() +
+        summary.unavailable.len()
+}
+
+/// run hook once the run is finished, unless some chunks did not complete and the hook only runs
+/// after complete runs. failures of the hook are recorded as run errors only if the hook is strict
+fn run_post_run_hook(
+    hook: &PostRunHook,
+    output_dir: &Path,
+    report_path: Option<&Path>,
+    summary: &mut FreezeSummary,
+) {
+    let n_incomplete = n_incomplete_chunks(summary);
+    if n_incomplete > 0 && !hook.always {
+        eprintln!("not running post-run hook because {} chunks did not complete", n_incomplete);
+        return
+    }
+    let status = if n_incomplete == 0 { "complete" } else { "incomplete" };
+    match hook.run(output_dir, report_path, status) {
+        Ok(()) => {}
+        Err(e) if hook.strict => summary.errored.push((None, e)),
+        Err(e) => eprintln!("warning: {}", e),
+    }
+}
+
+fn remove_success_marker(sink: &FileOutput) -> Result<(), CollectError> {
+    let path = sink.output_dir.join(SUCCESS_MARKER);
+    if path.exists() {
+        std::fs::remove_file(path).map_err(|_| err("could not remove success marker"))?;
+    }
+    Ok(())
+}
+
+/// record outcome of a finished partition, warning when it trips the failure streak
+fn record_partition_outcome(env: &ExecutionEnv, success: bool) {
+    if let Some(streak) = &env.failure_streak {
+        let was_tripped = streak.tripped();
+        if streak.record(success) && !was_tripped {
+            warn_run_aborted(env);
+        }
+    }
+}
+
+/// warn that a partition took much longer than the partitions before it
+fn warn_slow_partition(slow: &SlowPartition) {
+    let ranges: Vec<String> =
+        slow.block_ranges.iter().map(|(start, end)| format!("{}-{}", start, end)).collect();
+    eprintln!(
+        "slow chunk: blocks {} took {:.1}s, recent chunks averaged {:.1}s",
+        ranges.join(", "),
+        slow.seconds,
+        slow.mean_seconds
+    );
+}
+
+/// warn that the run is aborting, describing what happens to in-flight partitions
+fn warn_run_aborted(env: &ExecutionEnv) {
+    match &env.failure_streak {
+        _ if env.fail_fast => {
+            eprintln!("aborting after first chunk failure, cancelling in-flight chunks")
+        }
+        Some(streak) => eprintln!(
+            "aborting after {} consecutive chunk failures, in-flight chunks will finish",
+            streak.max_failures
+        ),
+        None => {}
+    }
+}
+
+fn write_df(
+    df: &mut DataFrame,
+    schema: &Table,
+    path: &Path,
+    sink: &FileOutput,
+    metadata: &[(String, String)],
+    column_stats: &mut FileColumnStats,
+) -> Result<(), CollectError> {
+    let stats = dataframes::compute_column_stats(df, &schema.datatype.default_sort())?;
+    column_stats.insert(path.to_path_buf(), stats);
+    dataframes::drop_null_topic_columns(df, schema)?;
+    dataframes::add_block_bucket_column(df, schema)?;
+    dataframes::alias_columns(df, schema)?;
+    dataframes::df_to_file_with_metadata(df, path, sink, metadata)
+        .map_err(|_| CollectError::CollectError("error writing file".to_string()))
+}
+
+/// write counts of logs per block of partition beside its logs file, like any other output file
+fn write_logs_per_block(
+    df: &DataFrame,
+    partition: &Partition,
+    query: &Query,
+    sink: &FileOutput,
+    metadata: &[(String, String)],
+    column_stats: &mut FileColumnStats,
+) -> Result<(), CollectError> {
+    let block_numbers = partition.block_numbers.as_ref().map(|chunks| chunks.values());
+    let mut counts = dataframes::count_rows_per_block(df, block_numbers.as_deref())?;
+    let path = sink.get_named_path(query, partition, LOGS_PER_BLOCK.to_string())?;
+    let schema = query.schemas.get_schema(&Datatype::Logs)?;
+    write_df(&mut counts, schema, &path, sink, metadata, column_stats)
+}
+
+/// batch size for writing logs of partition in batches, None if it must be collected in memory
+///
+/// batches are only used when each partition maps to a single parquet file whose columns are
+/// known before collection, and whose rows do not depend on logs of other batches
+fn log_batch_size(datatype: &MetaDatatype, query: &Query, sink: &FileOutput) -> Option<usize> {
+    let compact_topics =
+        query.schemas.get(&Datatype::Logs).map_or(false, |schema| schema.compact_topics);
+    let per_block_log =
+        query.schemas.get(&Datatype::Logs).map_or(false, |schema| schema.per_block_log.is_some());
+    let row_id = query.schemas.get(&Datatype::Logs).map_or(false, |schema| schema.row_id);
+    let batchable = matches!(datatype, MetaDatatype::Scalar(Datatype::Logs)) &&
+        !compact_topics &&
+        !per_block_log &&
+        !row_id &&
+        query.max_logs_per_address.is_none() &&
+        matches!(query.time_dimension, TimeDimension::Blocks) &&
+        sink.writes_parquet() &&
+        !sink.split_by_day &&
+        sink.hive_bucket_size.is_none() &&
+        sink.target_partition_bytes.is_none() &&
+        !query.logs_per_block;
+    query.log_batch_size.filter(|_| batchable)
+}
+
+/// logs of a partition, either held in memory or already written to disk in batches
+enum CollectedLogs {
+    InMemory(DataFrame),
+    Written(u64, FileColumnStats),
+}
+
+async fn freeze_logs_in_batches(
+    partition: &Partition,
+    query: &Arc<Query>,
+    source: Arc<Source>,
+    path: &Path,
+    sink: &FileOutput,
+    batch_size: usize,
+) -> Result<CollectedLogs, CollectError> {
+    let schema = query.schemas.get_schema(&Datatype::Logs)?;
+    let sort = schema.datatype.default_sort();
+    let metadata =
+        provenance::filter_metadata(query, std::slice::from_ref(partition), Datatype::Logs, sink)?;
+    let mut writer: Option<dataframes::BatchedParquetWriter> = None;
+    let mut n_rows = 0;
+    let mut stats: HashMap<String, ColumnStats> = HashMap::new();
+    let write_batch = |mut df: DataFrame| -> Result<(), CollectError> {
+        n_rows += df.height() as u64;
+        for (column, batch_stats) in dataframes::compute_column_stats(&df, &sort)? {
+            match stats.get_mut(&column) {
+                Some(column_stats) => column_stats.merge(batch_stats),
+                None => {
+                    stats.insert(column, batch_stats);
+                }
+            }
+        }
+        dataframes::add_block_bucket_column(&mut df, schema)?;
+        dataframes::alias_columns(&mut df, schema)?;
+        if writer.is_none() {
+            let batched_writer = dataframes::BatchedParquetWriter::new(path, &df.schema(), sink)
+                .map_err(|_| err("error writing file"))?;
+            writer = Some(batched_writer.with_metadata(metadata.clone()));
+        }
+        match &mut writer {
+            Some(writer) => writer.write_batch(&df).map_err(|_| err("error writing file")),
+            None => Err(err("could not create batched writer")),
+        }
+    };
+    let remainder = crate::datasets::logs::collect_logs_in_batches(
+        partition.clone(),
+        source,
+        query.clone(),
+        batch_size,
+        write_batch,
+    )
+    .await?;
+
+    match (remainder, writer) {
+        (Some(df), _) => Ok(CollectedLogs::InMemory(df)),
+        (None, Some(writer)) => {
+            writer.finish().map_err(|_| err("error writing file"))?;
+            Ok(CollectedLogs::Written(n_rows, HashMap::from([(path.to_path_buf(), stats)])))
+        }
+        (None, None) => Err(err("no log batches were written")),
+    }
+}
+
+/// turn of a partition to add its rows to the files of a target size being filled
+type SizeTurn = (Arc<dataframes::SizedFiles>, RowIdTurn);
+
+/// outcome of a partition that did not fail
+enum PartitionOutcome {
+    /// partition was written, with the number of rows written and the stats of its files
+    Written(u64, FileColumnStats),
+    /// rows of partition were added to files of a target size, with the partitions whose rows
+    /// are all in written files as a result, their number of rows, and the stats of the files
+    Sized(Vec<(Partition, u64)>, FileColumnStats),
+    /// writing files of a target size failed, leaving the partitions whose rows they held
+    /// unwritten
+    SizedFailed(Vec<Partition>, CollectError),
+    /// partition was not written because the provider no longer serves some of its blocks
+    Unavailable,
+    /// partition was not started because the time budget of the run elapsed or the run aborted
+    NotStarted,
+}
+
+/// collect and write partition
+async fn freeze_partition(
+    payload: PartitionPayload,
+    row_id_turn: Option<RowIdTurn>,
+    size_turn: Option<SizeTurn>,
+    stdout_turn: Option<StdoutTurn>,
+) -> Result<PartitionOutcome, CollectError> {
     let (partition, datatype, paths, query, source, sink, env, semaphore) = payload;
 
+    // partitions taking turns in block order hold their rows until their turn, so they are not
+    // started more than a window of turns ahead of the next turn. the window is waited for before
+    // acquiring a chunk permit, so that permits are never held by partitions that cannot start
+    if let Some(max_concurrent_chunks) = source.max_concurrent_chunks {
+        let window = 2 * (max_concurrent_chunks.max(1) as usize);
+        if let Some(row_id_turn) = &row_id_turn {
+            row_id_turn.wait_within(window).await;
+        }
+        if let Some((_, size_turn)) = &size_turn {
+            size_turn.wait_within(window).await;
+        }
+        if let Some(stdout_turn) = &stdout_turn {
+            stdout_turn.wait_within(window).await;
+        }
+    }
+
     // acquire chunk semaphore
-    let _permit = match &semaphore {
-        Some(semaphore) => Some(semaphore.acquire().await),
+    let mut permit = match &semaphore {
+        Some(semaphore) => semaphore.acquire().await.ok(),
         None => None,
     };
 
-    // collect data
-    let dfs = collect_partition(datatype, partition, query, source).await?;
+    // wait for collected partitions to be written once too many bytes are in flight
+    if let Some(in_flight) = &env.in_flight_bytes {
+        in_flight.wait().await;
+    }
+
+    // stop starting new partitions once time budget elapses or run aborts, in-flight partitions
+    // finish
+    if env.time_budget_elapsed() || env.aborted() {
+        return Ok(PartitionOutcome::NotStarted)
+    }
+
+    // throttle block consumption
+    if let (Some(limiter), Some(block_numbers)) =
+        (&env.block_rate_limiter, &partition.block_numbers)
+    {
+        limiter.acquire(block_numbers.size()).await;
+    }
 
-    // write dataframes to disk
+    // flag partitions that take much longer than the partitions before them
+    let block_ranges = env.slow_partitions.as_ref().map(|_| reports::block_ranges(&partition));
+    let result = write_partition(
+        partition,
+        datatype,
+        paths,
+        query,
+        source,
+        sink,
+        &env,
+        &mut permit,
+        row_id_turn,
+        size_turn,
+        stdout_turn,
+    )
+    .await;
+    let result = result.map(|written| match written {
+        Some((outcome, duration)) => {
+            if let (Some(slow_partitions), Some(block_ranges)) =
+                (&env.slow_partitions, block_ranges)
+            {
+                if let Some(slow) = slow_partitions.record(block_ranges, duration) {
+                    warn_slow_partition(&slow);
+                }
+            }
+            outcome
+        }
+        None => PartitionOutcome::Unavailable,
+    });
+
+    // a fail-fast run aborts before the permit is released, so queued partitions never start
+    if result.is_err() && env.fail_fast {
+        if let Some(streak) = &env.failure_streak {
+            if streak.trip() {
+                warn_run_aborted(&env);
+            }
+        }
+    }
+    result
+}
+
+/// collect partition and write its files, returning the outcome and the time spent collecting
+/// and writing, excluding waits for other partitions. returns None without writing files if the
+/// provider no longer serves some blocks of the partition
+#[allow(clippy::too_many_arguments)]
+async fn write_partition(
+    partition: Partition,
+    datatype: MetaDatatype,
+    paths: HashMap<Datatype, PathBuf>,
+    query: Arc<Query>,
+    source: Arc<Source>,
+    sink: FileOutput,
+    env: &ExecutionEnv,
+    chunk_permit: &mut Option<SemaphorePermit<'_>>,
+    row_id_turn: Option<RowIdTurn>,
+    size_turn: Option<SizeTurn>,
+    stdout_turn: Option<StdoutTurn>,
+) -> Result<Option<(PartitionOutcome, Duration)>, CollectError> {
+    // collect data, large log partitions are written in batches while they are collected. batches
+    // are not used when blocks may be unavailable, since those partitions are not written
+    let unavailable_log_ranges = source.unavailable_log_ranges.clone();
+    let batch_size =
+        log_batch_size(&datatype, &query, &sink).filter(|_| unavailable_log_ranges.is_none());
+    let t_collect = Instant::now();
+    let dfs = match batch_size {
+        Some(batch_size) => {
+            let path = paths.get(&Datatype::Logs).ok_or_else(|| {
+                CollectError::CollectError("could not get path for datatype".to_string())
+            })?;
+            match freeze_logs_in_batches(&partition, &query, source, path, &sink, batch_size)
+                .await?
+            {
+                CollectedLogs::InMemory(df) => HashMap::from([(Datatype::Logs, df)]),
+                CollectedLogs::Written(n_rows, column_stats) => {
+                    if let Some(bar) = &env.bar {
+                        bar.inc(1);
+                    }
+                    let outcome = PartitionOutcome::Written(n_rows, column_stats);
+                    return Ok(Some((outcome, t_collect.elapsed())))
+                }
+            }
+        }
+        None => collect_partition(datatype, partition.clone(), query.clone(), source).await?,
+    };
+    let collect_duration = t_collect.elapsed();
+
+    // partitions missing unavailable blocks are left unwritten so that later runs collect them
+    if let Some(unavailable_log_ranges) = &unavailable_log_ranges {
+        let block_ranges = reports::block_ranges(&partition);
+        if block_ranges.iter().any(|(start, end)| unavailable_log_ranges.overlaps(*start, *end)) {
+            if let Some(bar) = &env.bar {
+                bar.inc(1);
+            }
+            return Ok(None)
+        }
+    }
+
+    // cap logs per address and number rows once the partitions before this one in block order
+    // are done, the chunk permit is freed first so that waiting partitions never hold up the
+    // partitions before them
+    let dfs = match row_id_turn {
+        Some(row_id_turn) => {
+            *chunk_permit = None;
+            row_id_turn.wait().await;
+            let mut dfs = dfs;
+            if let (Some(address_cap), Some(df)) =
+                (&query.max_logs_per_address, dfs.get_mut(&Datatype::Logs))
+            {
+                *df = address_cap.apply(df)?;
+            }
+            let n_rows = dfs.iter().map(|(datatype, df)| (*datatype, df.height() as u64)).collect();
+            let first_ids = row_id_turn.take(&n_rows).await;
+            for (datatype, df) in dfs.iter_mut() {
+                if query.schemas.get_schema(datatype)?.row_id {
+                    row_ids::add_row_id_column(df, first_ids[datatype])?;
+                }
+            }
+            dfs
+        }
+        None => dfs,
+    };
+
+    // with a target file size, rows are added to the files being filled once the partitions
+    // before this one in block order are added. the files that are full are written before the
+    // turn passes on, so that the partitions whose rows they hold complete in block order
+    let sized_outcome = match size_turn {
+        Some((sized_files, size_turn)) => {
+            *chunk_permit = None;
+            size_turn.wait().await;
+            let t_write = Instant::now();
+            let outcome = sized_files
+                .push(&partition, &dfs)
+                .map(|batch| write_sized_batch(batch, &sized_files, &query, &sink));
+            size_turn.take(&HashMap::new()).await;
+            Some((outcome?, t_write.elapsed()))
+        }
+        None => None,
+    };
+
+    // rows streamed to stdout are written once the partitions before this one in block order are
+    // written, so that the stream holds the rows of the run in block order
+    if let Some(stdout_turn) = stdout_turn {
+        *chunk_permit = None;
+        stdout_turn.wait().await;
+        let t_write = Instant::now();
+        let n_rows = stdout_turn.write(dfs, &query, &sink).await?;
+        if let Some(bar) = &env.bar {
+            bar.inc(1);
+        }
+        let outcome = PartitionOutcome::Written(n_rows, HashMap::new());
+        return Ok(Some((outcome, collect_duration + t_write.elapsed())))
+    }
+
+    // collected dataframes count against the in-flight bound until they are written
+    let _reservation = env.in_flight_bytes.as_ref().map(|in_flight| {
+        in_flight.reserve(dfs.values().map(|df| df.estimated_size() as u64).sum())
+    });
+
+    // with a write concurrency, the next partition is collected while this one is written, and
+    // files are written on blocking threads so that writes proceed in parallel. the chunk permit
+    // is held until a write permit is acquired, so that at most as many collected partitions wait
+    // to be written as there are chunk permits
+    let (n_rows, column_stats, write_duration) = match &env.write_semaphore {
+        Some(write_semaphore) => {
+            let _write_permit = write_semaphore.acquire().await.ok();
+            *chunk_permit = None;
+            let t_write = Instant::now();
+            let (n_rows, column_stats) = tokio::task::spawn_blocking(move || {
+                write_dfs(dfs, &partition, &paths, &query, &sink)
+            })
+            .await
+            .map_err(CollectError::TaskFailed)??;
+            (n_rows, column_stats, t_write.elapsed())
+        }
+        None => {
+            let t_write = Instant::now();
+            let (n_rows, column_stats) = write_dfs(dfs, &partition, &paths, &query, &sink)?;
+            (n_rows, column_stats, t_write.elapsed())
+        }
+    };
+
+    // update progress bar
+    if let Some(bar) = &env.bar {
+        bar.inc(1);
+    }
+
+    let duration = collect_duration + write_duration;
+    Ok(Some(match sized_outcome {
+        Some((PartitionOutcome::Sized(partitions, mut sized_stats), sized_duration)) => {
+            sized_stats.extend(column_stats);
+            (PartitionOutcome::Sized(partitions, sized_stats), duration + sized_duration)
+        }
+        Some((outcome, sized_duration)) => (outcome, duration + sized_duration),
+        None => (PartitionOutcome::Written(n_rows, column_stats), duration),
+    }))
+}
+
+/// write collected dataframes of partition to disk, returning the number of rows written
+///
+/// with a target file size, the rows of partition are written to the files of a target size
+/// instead, see write_sized_batch
+fn write_dfs(
+    dfs: HashMap<Datatype, DataFrame>,
+    partition: &Partition,
+    paths: &HashMap<Datatype, PathBuf>,
+    query: &Query,
+    sink: &FileOutput,
+) -> Result<(u64, FileColumnStats), CollectError> {
     let mut n_rows = 0;
+    let mut column_stats = HashMap::new();
     for (datatype, mut df) in dfs {
         n_rows += df.height() as u64;
         let path = paths.get(&datatype).ok_or_else(|| {
             CollectError::CollectError("could not get path for datatype".to_string())
         })?;
-        let result = dataframes::df_to_file(&mut df, path, &sink);
-        result.map_err(|_| CollectError::CollectError("error writing file".to_string()))?
+        let schema = query.schemas.get_schema(&datatype)?;
+        let partitions = std::slice::from_ref(partition);
+        let metadata = provenance::filter_metadata(query, partitions, datatype, sink)?;
+        if query.logs_per_block && datatype == Datatype::Logs {
+            write_logs_per_block(&df, partition, query, sink, &metadata, &mut column_stats)?;
+        }
+        // bucket and day files embed the block range of their own rows
+        if let Some(bucket_size) = sink.hive_bucket_size {
+            for (bucket, mut bucket_df) in dataframes::split_by_block_bucket(&df, bucket_size)? {
+                let bucket_path = dataframes::bucket_path(path, bucket)?;
+                let start = bucket.saturating_mul(bucket_size);
+                let bucket_range = (start, start.saturating_add(bucket_size - 1));
+                let metadata = provenance::filter_metadata_within(
+                    query,
+                    partitions,
+                    datatype,
+                    sink,
+                    bucket_range,
+                )?;
+                write_df(&mut bucket_df, schema, &bucket_path, sink, &metadata, &mut column_stats)?;
+            }
+        } else if sink.split_by_day {
+            let mut day_paths = Vec::new();
+            for (day, mut day_df) in dataframes::split_by_day(&df)? {
+                let day_path = dataframes::day_path(path, &day)?;
+                let metadata = match dataframes::rows_block_range(&day_df)? {
+                    Some(day_range) => provenance::filter_metadata_within(
+                        query, partitions, datatype, sink, day_range,
+                    )?,
+                    None => metadata.clone(),
+                };
+                write_df(&mut day_df, schema, &day_path, sink, &metadata, &mut column_stats)?;
+                day_paths.push(day_path);
+            }
+            dataframes::write_day_manifest(&sink.output_dir, path, &day_paths)?;
+        } else if sink.target_partition_bytes.is_none() {
+            write_df(&mut df, schema, path, sink, &metadata, &mut column_stats)?;
+        }
     }
+    Ok((n_rows, column_stats))
+}
 
-    // update progress bar
-    if let Some(bar) = env.bar {
-        bar.inc(1);
+/// write a batch of files of a target size, completing the partitions of the batch only if every
+/// file is written. if a file fails, the rows still held are dropped and their partitions fail
+/// too, since some of their rows may have been in the failed file
+fn write_sized_batch(
+    batch: dataframes::SizedBatch,
+    sized_files: &dataframes::SizedFiles,
+    query: &Query,
+    sink: &FileOutput,
+) -> PartitionOutcome {
+    match write_sized_files(batch.files, query, sink) {
+        Ok(column_stats) => PartitionOutcome::Sized(batch.partitions, column_stats),
+        Err(e) => {
+            let mut partitions: Vec<_> =
+                batch.partitions.into_iter().map(|(partition, _)| partition).collect();
+            partitions.extend(sized_files.abandon());
+            PartitionOutcome::SizedFailed(partitions, e)
+        }
+    }
+}
+
+/// write files of a target size, each named by the blocks it covers
+fn write_sized_files(
+    sized_files: Vec<dataframes::SizedFile>,
+    query: &Query,
+    sink: &FileOutput,
+) -> Result<FileColumnStats, CollectError> {
+    let mut column_stats = HashMap::new();
+    for (datatype, range_partition, mut df) in sized_files {
+        let schema = query.schemas.get_schema(&datatype)?;
+        let path = sink.get_path(query, &range_partition, datatype)?;
+        let metadata = provenance::filter_metadata(
+            query,
+            std::slice::from_ref(&range_partition),
+            datatype,
+            sink,
+        )?;
+        write_df(&mut df, schema, &path, sink, &metadata, &mut column_stats)?;
+    }
+    Ok(column_stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AddressLogCap, BlockChunk, ColumnEncoding, Dim, ExecutionEnvBuilder, U256Type};
+    use ethers::prelude::{Log, Provider, H160, H256, U256, U64};
+    use polars::prelude::{ParquetReader, SerReader};
+
+    #[test]
+    fn test_partition_with_no_matching_rows_is_completed_empty() {
+        let partition = |start, end| Partition {
+            block_numbers: Some(vec![BlockChunk::Range(start, end)]),
+            ..Default::default()
+        };
+        let mut summary = FreezeSummary::default();
+        let stats = |path: &str| [(PathBuf::from(path), HashMap::new())].into_iter().collect();
+        record_completed(&mut summary, partition(0, 999), 12, stats("a.parquet"));
+        record_completed(&mut summary, partition(1000, 1999), 0, stats("b.parquet"));
+
+        assert_eq!(summary.completed.len(), 2);
+        assert_eq!(summary.completed_empty.len(), 1);
+        assert_eq!(summary.completed_empty[0].block_numbers.as_ref().unwrap()[0].values()[0], 1000);
+        assert!(summary.errored.is_empty());
+        assert!(summary.skipped.is_empty() && summary.budget_skipped.is_empty());
+        assert_eq!(summary.n_rows, 12);
+        assert_eq!(summary.column_stats.len(), 2);
     }
 
-    Ok(n_rows)
+    #[tokio::test]
+    async fn test_report_snapshots_are_written_during_run() {
+        let report_dir =
+            std::env::temp_dir().join(format!("cryo_test_report_snapshots_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&report_dir);
+        let sink = FileOutput { output_dir: report_dir.clone(), ..Default::default() };
+        let query = Query { partitioned_by: vec![Dim::BlockNumber], ..Default::default() };
+        let env = ExecutionEnvBuilder::new()
+            .report_dir(Some(report_dir.clone()))
+            .report_interval(Some(ReportInterval::Partitions(2)))
+            .build();
+        reports::write_report(&env, &query, &sink, None).await.unwrap();
+        let incomplete_path = reports::get_report_path(&env, &sink, false).unwrap();
+        let n_completed = || {
+            let report: serde_json::Value =
+                serde_json::from_str(&std::fs::read_to_string(&incomplete_path).unwrap()).unwrap();
+            report["results"]["n_completed_with_rows"].as_u64()
+        };
+        assert_eq!(n_completed(), None);
+
+        // snapshots follow the run every two partitions
+        let mut summary = FreezeSummary::default();
+        let mut cadence = ReportCadence::new(env.report_interval.unwrap());
+        let mut snapshots = Vec::new();
+        for start in (0..5000).step_by(1000) {
+            let partition = Partition {
+                block_numbers: Some(vec![BlockChunk::Range(start, start + 999)]),
+                ..Default::default()
+            };
+            record_completed(&mut summary, partition, 10, HashMap::new());
+            if cadence.record() {
+                reports::write_report_snapshot(&env, &query, &sink, &summary).await.unwrap();
+                snapshots.push(n_completed());
+            }
+        }
+        assert_eq!(snapshots, vec![Some(2), Some(4)]);
+        assert!(!reports::get_report_path(&env, &sink, true).unwrap().exists());
+
+        // final report replaces the snapshot
+        let final_path = reports::write_report(&env, &query, &sink, Some(&summary)).await.unwrap();
+        assert!(final_path.exists());
+        assert!(!incomplete_path.exists());
+
+        std::fs::remove_dir_all(&report_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_single_file_run_writes_one_sorted_file() {
+        let output_dir =
+            std::env::temp_dir().join(format!("cryo_test_single_file_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&output_dir);
+        let (provider, mock) = Provider::mocked();
+        let source = Source { inner_request_size: 10, ..Source::from_provider(provider) };
+        let schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &None,
+                None,
+                None,
+            )
+            .unwrap();
+        let partition = |start| Partition {
+            block_numbers: Some(vec![BlockChunk::Range(start, start + 9)]),
+            ..Default::default()
+        };
+        let query = Query {
+            datatypes: vec![MetaDatatype::Scalar(Datatype::Logs)],
+            schemas: HashMap::from([(Datatype::Logs, schema)]),
+            partitions: vec![partition(0), partition(10), partition(20)],
+            partitioned_by: vec![Dim::BlockNumber],
+            ..Default::default()
+        };
+        let sink =
+            FileOutput { output_dir: output_dir.clone(), single_file: true, ..Default::default() };
+        let env = ExecutionEnvBuilder::new().verbose(0).report(false).build();
+
+        // one eth_getLogs response per partition, in whichever order partitions run
+        for block_number in [25u64, 5, 15] {
+            let log = Log {
+                address: H160::from_low_u64_be(1),
+                block_number: Some(U64::from(block_number)),
+                transaction_hash: Some(H256::from_low_u64_be(block_number)),
+                transaction_index: Some(U64::from(0u64)),
+                log_index: Some(U256::from(0u64)),
+                ..Default::default()
+            };
+            mock.push(vec![log]).unwrap();
+        }
+        let summary = freeze(&query, &source, &sink, &env).await.unwrap().unwrap();
+        assert_eq!(summary.completed.len(), 3);
+        assert!(summary.errored.is_empty());
+
+        let files: Vec<PathBuf> = std::fs::read_dir(&output_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.is_file())
+            .collect();
+        assert_eq!(files, vec![output_dir.join("ethereum__logs__00000000_to_00000029.parquet")]);
+        assert_eq!(summary.column_stats.keys().collect::<Vec<_>>(), vec![&files[0]]);
+        let df = ParquetReader::new(std::fs::File::open(&files[0]).unwrap()).finish().unwrap();
+        let block_numbers: Vec<_> =
+            df.column("block_number").unwrap().u32().unwrap().into_iter().collect();
+        assert_eq!(block_numbers, vec![Some(5), Some(15), Some(25)]);
+
+        // spooled partition files are removed
+        let spool_base = output_dir.join(".cryo/tmp");
+        assert!(!spool_base.exists() || std::fs::read_dir(&spool_base).unwrap().next().is_none());
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_writes_complete_every_file() {
+        let output_dir = std::env::temp_dir()
+            .join(format!("cryo_test_write_concurrency_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&output_dir);
+        let (provider, mock) = Provider::mocked();
+        let source = Source {
+            inner_request_size: 10,
+            max_concurrent_chunks: Some(1),
+            ..Source::from_provider(provider)
+        };
+        let schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &None,
+                None,
+                None,
+            )
+            .unwrap();
+        let partition = |start| Partition {
+            block_numbers: Some(vec![BlockChunk::Range(start, start + 9)]),
+            ..Default::default()
+        };
+        let starts = [0u64, 10, 20, 30, 40, 50];
+        let query = Query {
+            datatypes: vec![MetaDatatype::Scalar(Datatype::Logs)],
+            schemas: HashMap::from([(Datatype::Logs, schema)]),
+            partitions: starts.iter().map(|start| partition(*start)).collect(),
+            partitioned_by: vec![Dim::BlockNumber],
+            ..Default::default()
+        };
+        let sink = FileOutput { output_dir: output_dir.clone(), ..Default::default() };
+        let env = ExecutionEnvBuilder::new()
+            .verbose(0)
+            .report_dir(Some(output_dir.join("reports")))
+            .write_concurrency(Some(3))
+            .build();
+
+        // one eth_getLogs response of 100 logs per partition, in whichever order partitions run
+        for start in starts {
+            let logs: Vec<Log> = (0..100u64)
+                .map(|i| Log {
+                    address: H160::from_low_u64_be(1),
+                    block_number: Some(U64::from(start)),
+                    transaction_hash: Some(H256::from_low_u64_be(start * 100 + i)),
+                    transaction_index: Some(U64::from(0u64)),
+                    log_index: Some(U256::from(i)),
+                    ..Default::default()
+                })
+                .collect();
+            mock.push(logs).unwrap();
+        }
+        let summary = freeze(&query, &source, &sink, &env).await.unwrap().unwrap();
+        assert_eq!(summary.completed.len(), starts.len());
+        assert!(summary.errored.is_empty());
+        assert_eq!(summary.n_rows, 100 * starts.len() as u64);
+
+        // every file is complete and listed in the report
+        let report_path = reports::get_report_path(&env, &sink, true).unwrap();
+        let report: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(report_path).unwrap()).unwrap();
+        let mut completed_paths: Vec<PathBuf> = report["results"]["completed_paths"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|path| PathBuf::from(path.as_str().unwrap()))
+            .collect();
+        completed_paths.sort();
+        let expected: Vec<PathBuf> = starts
+            .iter()
+            .map(|start| {
+                output_dir.join(format!("ethereum__logs__{:08}_to_{:08}.parquet", start, start + 9))
+            })
+            .collect();
+        assert_eq!(completed_paths, expected);
+        for path in completed_paths.iter() {
+            let df = ParquetReader::new(std::fs::File::open(path).unwrap()).finish().unwrap();
+            assert_eq!(df.height(), 100);
+        }
+        assert_eq!(summary.column_stats.len(), starts.len());
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_partition_with_unavailable_blocks_is_not_written() {
+        let output_dir =
+            std::env::temp_dir().join(format!("cryo_test_unavailable_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&output_dir);
+        let (provider, mock) = Provider::mocked();
+        let patterns = crate::DEFAULT_UNAVAILABLE_LOG_PATTERNS.iter().map(|p| p.to_string());
+        let source = Source {
+            inner_request_size: 10,
+            unavailable_log_ranges: Some(Arc::new(crate::UnavailableLogRanges::new(
+                patterns.collect(),
+            ))),
+            ..Source::from_provider(provider)
+        };
+        let schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &None,
+                None,
+                None,
+            )
+            .unwrap();
+        let query = Query {
+            datatypes: vec![MetaDatatype::Scalar(Datatype::Logs)],
+            schemas: HashMap::from([(Datatype::Logs, schema)]),
+            partitions: vec![Partition {
+                block_numbers: Some(vec![BlockChunk::Range(0, 9)]),
+                ..Default::default()
+            }],
+            partitioned_by: vec![Dim::BlockNumber],
+            ..Default::default()
+        };
+        let sink = FileOutput { output_dir: output_dir.clone(), ..Default::default() };
+        let env = ExecutionEnvBuilder::new().verbose(0).report(false).build();
+        let error = ethers::prelude::JsonRpcError {
+            code: -32000,
+            message: "missing trie node 3a4f (path )".to_string(),
+            data: None,
+        };
+        mock.push_response(ethers::prelude::MockResponse::Error(error));
+
+        let summary = freeze(&query, &source, &sink, &env).await.unwrap().unwrap();
+        assert_eq!(summary.unavailable.len(), 1);
+        assert!(summary.completed.is_empty() && summary.errored.is_empty());
+        assert_eq!(summary.unavailable_ranges, vec![(0, 9)]);
+        assert_eq!(n_incomplete_chunks(&summary), 1);
+
+        // nothing is written, so the partition is collected again by the next run
+        let path = output_dir.join("ethereum__logs__00000000_to_00000009.parquet");
+        assert!(!path.exists());
+        let (payloads, skipped) = get_payloads(&query, &source, &sink, &env).unwrap();
+        assert!(payloads.len() == 1 && skipped.is_empty());
+
+        let _ = std::fs::remove_dir_all(&output_dir);
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_run_removes_temp_dir() {
+        let output_dir =
+            std::env::temp_dir().join(format!("cryo_test_interrupted_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&output_dir);
+
+        // an rpc endpoint that accepts requests but never responds
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let rpc_url = format!("http://{}", listener.local_addr().unwrap());
+        let http: ethers::prelude::Http = rpc_url.parse().unwrap();
+        let policy = Box::new(ethers::prelude::HttpRateLimitRetryPolicy);
+        let provider = Provider::new(ethers::prelude::RetryClient::new(http, policy, 0, 0));
+        let source = Source::from_provider(provider);
+        let schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &None,
+                None,
+                None,
+            )
+            .unwrap();
+        let query = Query {
+            datatypes: vec![MetaDatatype::Scalar(Datatype::Logs)],
+            schemas: HashMap::from([(Datatype::Logs, schema)]),
+            partitions: vec![Partition {
+                block_numbers: Some(vec![BlockChunk::Range(0, 9)]),
+                ..Default::default()
+            }],
+            partitioned_by: vec![Dim::BlockNumber],
+            ..Default::default()
+        };
+        let temp_dir = FileOutput::run_temp_dir(&output_dir.join(".cryo/tmp"));
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        std::fs::write(temp_dir.join("0_ethereum__logs.parquet"), "").unwrap();
+        let sink = FileOutput {
+            output_dir: output_dir.clone(),
+            temp_dir: Some(temp_dir.clone()),
+            ..Default::default()
+        };
+        let env = ExecutionEnvBuilder::new().verbose(0).report(false).build();
+
+        // interrupting the run drops it mid-collection
+        let run = freeze(&query, &source, &sink, &env);
+        let interrupted = tokio::time::timeout(std::time::Duration::from_millis(200), run).await;
+        assert!(interrupted.is_err());
+        assert!(!temp_dir.exists());
+
+        drop(listener);
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_logs_per_block_file_is_reported_and_checked_for_skipping() {
+        let output_dir =
+            std::env::temp_dir().join(format!("cryo_test_logs_per_block_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&output_dir);
+        let (provider, mock) = Provider::mocked();
+        let source = Source { inner_request_size: 10, ..Source::from_provider(provider) };
+        let schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &None,
+                None,
+                None,
+            )
+            .unwrap();
+        let query = Query {
+            datatypes: vec![MetaDatatype::Scalar(Datatype::Logs)],
+            schemas: HashMap::from([(Datatype::Logs, schema)]),
+            partitions: vec![Partition {
+                block_numbers: Some(vec![BlockChunk::Range(0, 9)]),
+                ..Default::default()
+            }],
+            partitioned_by: vec![Dim::BlockNumber],
+            logs_per_block: true,
+            ..Default::default()
+        };
+        let sink = FileOutput {
+            output_dir: output_dir.clone(),
+            row_count_sidecar: true,
+            ..Default::default()
+        };
+        let env = ExecutionEnvBuilder::new()
+            .verbose(0)
+            .report_dir(Some(output_dir.join("reports")))
+            .build();
+        let log = Log {
+            address: H160::from_low_u64_be(1),
+            block_number: Some(U64::from(5u64)),
+            transaction_hash: Some(H256::from_low_u64_be(5)),
+            transaction_index: Some(U64::from(0u64)),
+            log_index: Some(U256::from(0u64)),
+            ..Default::default()
+        };
+        mock.push(vec![log]).unwrap();
+        let summary = freeze(&query, &source, &sink, &env).await.unwrap().unwrap();
+        assert_eq!(summary.completed.len(), 1);
+
+        // the counts file is reported and gets a sidecar like the logs file
+        let report_path = reports::get_report_path(&env, &sink, true).unwrap();
+        let report: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(report_path).unwrap()).unwrap();
+        let completed_paths: Vec<PathBuf> = report["results"]["completed_paths"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|path| PathBuf::from(path.as_str().unwrap()))
+            .collect();
+        let counts_path = output_dir.join("ethereum__logs_per_block__00000000_to_00000009.parquet");
+        assert_eq!(
+            completed_paths,
+            vec![
+                output_dir.join("ethereum__logs__00000000_to_00000009.parquet"),
+                counts_path.clone()
+            ]
+        );
+        assert!(dataframes::row_count_path(&counts_path).exists());
+
+        // a partition missing its counts file is collected again
+        let (payloads, skipped) = get_payloads(&query, &source, &sink, &env).unwrap();
+        assert!(payloads.is_empty() && skipped.len() == 1);
+        std::fs::remove_file(&counts_path).unwrap();
+        let (payloads, skipped) = get_payloads(&query, &source, &sink, &env).unwrap();
+        assert!(payloads.len() == 1 && skipped.is_empty());
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[test]
+    fn test_partitions_are_skipped_by_files_of_another_label_width() {
+        let output_dir =
+            std::env::temp_dir().join(format!("cryo_test_label_width_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&output_dir);
+        std::fs::create_dir_all(&output_dir).unwrap();
+        let (provider, _mock) = Provider::mocked();
+        let source = Source::from_provider(provider);
+        let schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &None,
+                None,
+                None,
+            )
+            .unwrap();
+        let partition = |start| Partition {
+            block_numbers: Some(vec![BlockChunk::Range(start, start + 9)]),
+            ..Default::default()
+        };
+        let query = Query {
+            datatypes: vec![MetaDatatype::Scalar(Datatype::Logs)],
+            schemas: HashMap::from([(Datatype::Logs, schema)]),
+            partitions: vec![partition(0), partition(10)],
+            partitioned_by: vec![Dim::BlockNumber],
+            ..Default::default()
+        };
+        let sink = FileOutput { output_dir: output_dir.clone(), ..Default::default() };
+        let env = ExecutionEnvBuilder::new().verbose(0).build();
+
+        // a file written at the default width is found by a run with a wider width
+        let (payloads, _) = get_payloads(&query, &source, &sink, &env).unwrap();
+        let path = &payloads[0].2[&Datatype::Logs];
+        assert!(path.ends_with("ethereum__logs__00000000_to_00000009.parquet"));
+        std::fs::write(path, b"").unwrap();
+        let sink = FileOutput { block_label_width: 12, ..sink };
+        let (payloads, skipped) = get_payloads(&query, &source, &sink, &env).unwrap();
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(payloads.len(), 1);
+        assert!(payloads[0].2[&Datatype::Logs]
+            .ends_with("ethereum__logs__000000000010_to_000000000019.parquet"));
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[test]
+    fn test_split_by_day_partitions_are_skipped_by_their_day_manifests() {
+        let output_dir =
+            std::env::temp_dir().join(format!("cryo_test_day_skipping_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&output_dir);
+        let (provider, _mock) = Provider::mocked();
+        let source = Source::from_provider(provider);
+        let schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &None,
+                None,
+                None,
+            )
+            .unwrap();
+        let query = Query {
+            datatypes: vec![MetaDatatype::Scalar(Datatype::Logs)],
+            schemas: HashMap::from([(Datatype::Logs, schema)]),
+            partitions: vec![Partition {
+                block_numbers: Some(vec![BlockChunk::Range(0, 9)]),
+                ..Default::default()
+            }],
+            partitioned_by: vec![Dim::BlockNumber],
+            ..Default::default()
+        };
+        let sink =
+            FileOutput { output_dir: output_dir.clone(), split_by_day: true, ..Default::default() };
+        let env = ExecutionEnvBuilder::new().verbose(0).build();
+        let (payloads, skipped) = get_payloads(&query, &source, &sink, &env).unwrap();
+        assert!(payloads.len() == 1 && skipped.is_empty());
+
+        // a partition without rows writes no day files, but is complete once recorded
+        let path = &payloads[0].2[&Datatype::Logs];
+        dataframes::write_day_manifest(&output_dir, path, &[]).unwrap();
+        let (payloads, skipped) = get_payloads(&query, &source, &sink, &env).unwrap();
+        assert!(payloads.is_empty() && skipped.len() == 1);
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_row_ids_are_contiguous_in_block_order_across_partitions() {
+        let output_dir =
+            std::env::temp_dir().join(format!("cryo_test_row_ids_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&output_dir);
+        let (provider, mock) = Provider::mocked();
+        let source = Source {
+            inner_request_size: 10,
+            max_concurrent_chunks: Some(1),
+            ..Source::from_provider(provider)
+        };
+        let mut schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &None,
+                None,
+                None,
+            )
+            .unwrap();
+        schema.row_id = true;
+        let partition = |start| Partition {
+            block_numbers: Some(vec![BlockChunk::Range(start, start + 9)]),
+            ..Default::default()
+        };
+        // partitions run in reverse block order, the last partition is collected first
+        let query = Query {
+            datatypes: vec![MetaDatatype::Scalar(Datatype::Logs)],
+            schemas: HashMap::from([(Datatype::Logs, schema)]),
+            partitions: vec![partition(20), partition(10), partition(0)],
+            partitioned_by: vec![Dim::BlockNumber],
+            ..Default::default()
+        };
+        let sink = FileOutput { output_dir: output_dir.clone(), ..Default::default() };
+        let env = ExecutionEnvBuilder::new().verbose(0).report(false).build();
+
+        // responses are served last in first out, partitions request in order 20, 10, 0
+        let logs = |block_number: u64, n_logs: u64| -> Vec<Log> {
+            (0..n_logs)
+                .map(|i| Log {
+                    address: H160::from_low_u64_be(1),
+                    block_number: Some(U64::from(block_number)),
+                    transaction_hash: Some(H256::from_low_u64_be(block_number * 100 + i)),
+                    transaction_index: Some(U64::from(0u64)),
+                    log_index: Some(U256::from(i)),
+                    ..Default::default()
+                })
+                .collect()
+        };
+        mock.push(logs(5, 3)).unwrap();
+        mock.push(logs(15, 2)).unwrap();
+        mock.push(logs(25, 4)).unwrap();
+        let summary = freeze(&query, &source, &sink, &env).await.unwrap().unwrap();
+        assert_eq!(summary.completed.len(), 3);
+        assert!(summary.errored.is_empty());
+
+        let row_ids = |start: u64| -> Vec<Option<u64>> {
+            let path = output_dir.join(format!(
+                "ethereum__logs__{:08}_to_{:08}.parquet",
+                start,
+                start + 9
+            ));
+            let df = ParquetReader::new(std::fs::File::open(path).unwrap()).finish().unwrap();
+            df.column("row_id").unwrap().u64().unwrap().into_iter().collect()
+        };
+        let row_ids: Vec<u64> = [0, 10, 20].into_iter().flat_map(row_ids).flatten().collect();
+        assert_eq!(row_ids, (0..9).collect::<Vec<u64>>());
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_address_cap_keeps_the_same_logs_whichever_order_partitions_run_in() {
+        let partition = |start| Partition {
+            block_numbers: Some(vec![BlockChunk::Range(start, start + 9)]),
+            ..Default::default()
+        };
+        let logs = |block_number: u64| -> Vec<Log> {
+            [(1, 0), (1, 1), (2, 2)]
+                .into_iter()
+                .map(|(address, i)| Log {
+                    address: H160::from_low_u64_be(address),
+                    block_number: Some(U64::from(block_number)),
+                    transaction_hash: Some(H256::from_low_u64_be(block_number * 100 + i)),
+                    transaction_index: Some(U64::from(0u64)),
+                    log_index: Some(U256::from(i)),
+                    ..Default::default()
+                })
+                .collect()
+        };
+
+        // kept logs as (block_number, log_index), collecting partitions in the given order
+        let kept_logs = |run: &'static str, starts: [u64; 3]| async move {
+            let output_dir = std::env::temp_dir().join(format!(
+                "cryo_test_address_cap_{}_{}",
+                run,
+                std::process::id()
+            ));
+            let _ = std::fs::remove_dir_all(&output_dir);
+            let (provider, mock) = Provider::mocked();
+            let source = Source {
+                inner_request_size: 10,
+                max_concurrent_chunks: Some(1),
+                ..Source::from_provider(provider)
+            };
+            let schema = Datatype::Logs
+                .table_schema(
+                    &[U256Type::Binary],
+                    &ColumnEncoding::Binary,
+                    &None,
+                    &None,
+                    &None,
+                    None,
+                    None,
+                )
+                .unwrap();
+            let query = Query {
+                datatypes: vec![MetaDatatype::Scalar(Datatype::Logs)],
+                schemas: HashMap::from([(Datatype::Logs, schema)]),
+                partitions: starts.iter().map(|start| partition(*start)).collect(),
+                partitioned_by: vec![Dim::BlockNumber],
+                max_logs_per_address: Some(Arc::new(AddressLogCap::new(3))),
+                ..Default::default()
+            };
+            let sink = FileOutput { output_dir: output_dir.clone(), ..Default::default() };
+            let env = ExecutionEnvBuilder::new().verbose(0).report(false).build();
+
+            // responses are served last in first out
+            for start in starts.iter().rev() {
+                mock.push(logs(start + 5)).unwrap();
+            }
+            let summary = freeze(&query, &source, &sink, &env).await.unwrap().unwrap();
+            assert_eq!(summary.completed.len(), 3);
+            assert!(summary.errored.is_empty());
+
+            let mut kept = Vec::new();
+            for start in [0u64, 10, 20] {
+                let path = output_dir.join(format!(
+                    "ethereum__logs__{:08}_to_{:08}.parquet",
+                    start,
+                    start + 9
+                ));
+                let Ok(file) = std::fs::File::open(path) else { continue };
+                let df = ParquetReader::new(file).finish().unwrap();
+                let block_numbers = df.column("block_number").unwrap().u32().unwrap().clone();
+                let log_indices = df.column("log_index").unwrap().u32().unwrap().clone();
+                kept.extend(block_numbers.into_no_null_iter().zip(log_indices.into_no_null_iter()));
+            }
+            std::fs::remove_dir_all(&output_dir).unwrap();
+            kept
+        };
+
+        // the first logs of each address in block order are kept, even when the last partition
+        // is collected first
+        let expected = vec![(5, 0), (5, 1), (5, 2), (15, 0), (15, 2), (25, 2)];
+        assert_eq!(kept_logs("reverse", [20, 10, 0]).await, expected);
+        assert_eq!(kept_logs("forward", [0, 10, 20]).await, expected);
+        assert_eq!(kept_logs("mixed", [10, 20, 0]).await, expected);
+    }
+
+    #[tokio::test]
+    async fn test_sparse_partitions_are_merged_into_files_of_target_size() {
+        let output_dir =
+            std::env::temp_dir().join(format!("cryo_test_target_bytes_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&output_dir);
+        let (provider, mock) = Provider::mocked();
+        let source = Source {
+            inner_request_size: 10,
+            max_concurrent_chunks: Some(1),
+            ..Source::from_provider(provider)
+        };
+        let schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &None,
+                None,
+                None,
+            )
+            .unwrap();
+        let partition = |start| Partition {
+            block_numbers: Some(vec![BlockChunk::Range(start, start + 9)]),
+            ..Default::default()
+        };
+        let query = Query {
+            datatypes: vec![MetaDatatype::Scalar(Datatype::Logs)],
+            schemas: HashMap::from([(Datatype::Logs, schema)]),
+            partitions: vec![partition(0), partition(10), partition(20)],
+            partitioned_by: vec![Dim::BlockNumber],
+            ..Default::default()
+        };
+        let sink = FileOutput {
+            output_dir: output_dir.clone(),
+            target_partition_bytes: Some(1 << 30),
+            ..Default::default()
+        };
+        let env = ExecutionEnvBuilder::new().verbose(0).report(false).build();
+
+        // responses are served last in first out, partitions request in block order
+        for block_number in [25u64, 15, 5] {
+            let log = Log {
+                address: H160::from_low_u64_be(1),
+                block_number: Some(U64::from(block_number)),
+                transaction_hash: Some(H256::from_low_u64_be(block_number)),
+                transaction_index: Some(U64::from(0u64)),
+                log_index: Some(U256::from(0u64)),
+                ..Default::default()
+            };
+            mock.push(vec![log]).unwrap();
+        }
+        let summary = freeze(&query, &source, &sink, &env).await.unwrap().unwrap();
+        assert_eq!(summary.completed.len(), 3);
+        assert!(summary.errored.is_empty());
+
+        // the three sparse partitions fill one file named by the blocks it covers
+        let path = output_dir.join("ethereum__logs__00000000_to_00000029.parquet");
+        assert_eq!(summary.column_stats.keys().collect::<Vec<_>>(), vec![&path]);
+        let df = ParquetReader::new(std::fs::File::open(&path).unwrap()).finish().unwrap();
+        assert_eq!(df.height(), 3);
+
+        // a later run finds the partitions within the block range of the file
+        let summary = freeze(&query, &source, &sink, &env).await.unwrap().unwrap();
+        assert_eq!(summary.skipped.len(), 3);
+        assert!(summary.completed.is_empty());
+
+        // partitions whose file cannot be written are errored rather than completed
+        for block_number in [25u64, 15, 5] {
+            let log = Log {
+                address: H160::from_low_u64_be(1),
+                block_number: Some(U64::from(block_number)),
+                transaction_hash: Some(H256::from_low_u64_be(block_number)),
+                transaction_index: Some(U64::from(0u64)),
+                log_index: Some(U256::from(0u64)),
+                ..Default::default()
+            };
+            mock.push(vec![log]).unwrap();
+        }
+        std::fs::remove_file(&path).unwrap();
+        std::fs::create_dir_all(path.join("blocked")).unwrap();
+        let sink = FileOutput { overwrite: true, ..sink };
+        let summary = freeze(&query, &source, &sink, &env).await.unwrap().unwrap();
+        assert!(summary.completed.is_empty());
+        assert_eq!(summary.errored.len(), 3);
+        assert!(summary.errored.iter().all(|(partition, _)| partition.is_some()));
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_fail_fast_halts_and_reports_after_first_error() {
+        let output_dir =
+            std::env::temp_dir().join(format!("cryo_test_fail_fast_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&output_dir);
+
+        // no responses are queued, so every eth_getLogs request fails
+        let (provider, _mock) = Provider::mocked();
+        let source = Source {
+            inner_request_size: 10,
+            max_concurrent_chunks: Some(1),
+            ..Source::from_provider(provider)
+        };
+        let schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &None,
+                None,
+                None,
+            )
+            .unwrap();
+        let partition = |start| Partition {
+            block_numbers: Some(vec![BlockChunk::Range(start, start + 9)]),
+            ..Default::default()
+        };
+        let query = Query {
+            datatypes: vec![MetaDatatype::Scalar(Datatype::Logs)],
+            schemas: HashMap::from([(Datatype::Logs, schema)]),
+            partitions: vec![partition(0), partition(10), partition(20)],
+            partitioned_by: vec![Dim::BlockNumber],
+            ..Default::default()
+        };
+        let sink = FileOutput { output_dir: output_dir.clone(), ..Default::default() };
+
+        // tolerant runs attempt every partition
+        let env = ExecutionEnvBuilder::new().verbose(0).report(false).build();
+        let summary = freeze(&query, &source, &sink, &env).await.unwrap().unwrap();
+        assert_eq!(summary.errored.len(), 3);
+        assert!(!summary.aborted);
+
+        // fail-fast runs stop after the first error and still write a report recording it
+        let env = ExecutionEnvBuilder::new()
+            .verbose(0)
+            .report_dir(Some(output_dir.join("reports")))
+            .fail_fast(true)
+            .build();
+        let summary = freeze(&query, &source, &sink, &env).await.unwrap().unwrap();
+        assert_eq!(summary.errored.len(), 1);
+        assert_eq!(summary.abort_skipped.len(), 2);
+        assert!(summary.completed.is_empty());
+        assert!(summary.aborted);
+
+        let report_path = reports::get_report_path(&env, &sink, true).unwrap();
+        let report: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(report_path).unwrap()).unwrap();
+        assert_eq!(report["results"]["errored_block_ranges"].as_array().unwrap().len(), 1);
+        assert_eq!(report["results"]["aborted_after_consecutive_failures"], true);
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_success_marker_written_only_for_complete_runs() {
+        let output_dir =
+            std::env::temp_dir().join(format!("cryo_test_success_marker_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&output_dir);
+        let (provider, mock) = Provider::mocked();
+        let source = Source {
+            inner_request_size: 10,
+            max_concurrent_chunks: Some(1),
+            ..Source::from_provider(provider)
+        };
+        let schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &None,
+                None,
+                None,
+            )
+            .unwrap();
+        let query = |partitions: Vec<u64>| Query {
+            datatypes: vec![MetaDatatype::Scalar(Datatype::Logs)],
+            schemas: HashMap::from([(Datatype::Logs, schema.clone())]),
+            partitions: partitions
+                .into_iter()
+                .map(|start| Partition {
+                    block_numbers: Some(vec![BlockChunk::Range(start, start + 9)]),
+                    ..Default::default()
+                })
+                .collect(),
+            partitioned_by: vec![Dim::BlockNumber],
+            ..Default::default()
+        };
+        let sink = FileOutput {
+            output_dir: output_dir.clone(),
+            success_marker: true,
+            ..Default::default()
+        };
+        let env = ExecutionEnvBuilder::new().verbose(0).report(false).build();
+        let marker = output_dir.join(SUCCESS_MARKER);
+
+        // complete run
+        mock.push(Vec::<Log>::new()).unwrap();
+        mock.push(Vec::<Log>::new()).unwrap();
+        let summary = freeze(&query(vec![0, 10]), &source, &sink, &env).await.unwrap().unwrap();
+        assert_eq!(summary.completed.len(), 2);
+        assert!(marker.exists());
+        assert_eq!(std::fs::read(&marker).unwrap().len(), 0);
+
+        // incomplete run, no responses are queued so its new partition errors, and the marker of
+        // the earlier run is removed
+        let summary = freeze(&query(vec![0, 10, 20]), &source, &sink, &env).await.unwrap().unwrap();
+        assert_eq!(summary.skipped.len(), 2);
+        assert_eq!(summary.errored.len(), 1);
+        assert!(!marker.exists());
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_post_run_hook_receives_report_path_and_status() {
+        let output_dir =
+            std::env::temp_dir().join(format!("cryo_test_post_run_hook_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&output_dir);
+        std::fs::create_dir_all(&output_dir).unwrap();
+        let report_path = output_dir.join("report.json");
+        let hook = |command: &str, always: bool, strict: bool| PostRunHook {
+            command: command.to_string(),
+            always,
+            strict,
+        };
+        let record = "echo \"$1 $CRYO_RUN_STATUS\" >> \"$CRYO_OUTPUT_DIR/hook.txt\"";
+        let recorded = || std::fs::read_to_string(output_dir.join("hook.txt")).unwrap_or_default();
+
+        // complete run
+        let mut summary = FreezeSummary::default();
+        run_post_run_hook(
+            &hook(record, false, false),
+            &output_dir,
+            Some(&report_path),
+            &mut summary,
+        );
+        assert_eq!(recorded(), format!("{} complete\n", report_path.display()));
+        assert!(summary.errored.is_empty());
+
+        // incomplete runs only run hooks that always run, without a report the path is empty
+        let incomplete =
+            || FreezeSummary { errored: vec![(None, err("chunk failed"))], ..Default::default() };
+        run_post_run_hook(&hook(record, false, false), &output_dir, None, &mut incomplete());
+        run_post_run_hook(&hook(record, true, false), &output_dir, None, &mut incomplete());
+        assert_eq!(recorded(), format!("{} complete\n incomplete\n", report_path.display()));
+
+        // a failing hook fails the run only when strict
+        let mut summary = FreezeSummary::default();
+        run_post_run_hook(&hook("exit 3", false, false), &output_dir, None, &mut summary);
+        assert!(summary.errored.is_empty());
+        run_post_run_hook(&hook("exit 3", false, true), &output_dir, None, &mut summary);
+        assert_eq!(summary.errored.len(), 1);
+        assert!(summary.errored[0].1.to_string().contains("exited with status 3"));
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
 }
@@ -0,0 +1,288 @@
+use crate::{
+    err, BlockChunk, ChunkData, CollectError, Datatype, Dim, FileOutput, FreezeSummary, Partition,
+    Query,
+};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+/// restrict partitions of query to the blocks missing from the existing files of sink, returning
+/// the restricted query and the inclusive block ranges of its gaps
+///
+/// each file covers the min to max block in its name, including blocks without rows, and a block
+/// is missing unless every datatype has a file covering it. each gap becomes its own partition
+pub fn fill_gaps(
+    query: &Query,
+    sink: &FileOutput,
+) -> Result<(Query, Vec<(u64, u64)>), CollectError> {
+    if query.partitioned_by != vec![Dim::BlockNumber] {
+        return Err(err("gap filling requires partitions of block ranges only"))
+    }
+    let files = list_files(&sink.output_dir)?;
+    let mut covered_by_datatype = Vec::new();
+    for meta_datatype in query.datatypes.iter() {
+        for datatype in meta_datatype.datatypes().into_iter() {
+            if query.schemas.contains_key(&datatype) {
+                covered_by_datatype.push(covered_block_ranges(&files, sink, datatype));
+            }
+        }
+    }
+
+    let mut partitions = Vec::new();
+    let mut gaps = Vec::new();
+    for partition in query.partitions.iter() {
+        let chunks = partition
+            .block_numbers
+            .as_ref()
+            .ok_or_else(|| err("gap filling requires partitions of block ranges"))?;
+        let mut partition_gaps = Vec::new();
+        for (start, end) in chunks.iter().flat_map(chunk_ranges) {
+            for covered in covered_by_datatype.iter() {
+                partition_gaps.extend(missing_block_ranges(start, end, covered));
+            }
+        }
+        for (start, end) in merge_ranges(partition_gaps) {
+            partitions.push(Partition {
+                label: None,
+                block_numbers: Some(vec![BlockChunk::Range(start, end)]),
+                ..partition.clone()
+            });
+            gaps.push((start, end));
+        }
+    }
+    Ok((Query { partitions, ..query.clone() }, gaps))
+}
+
+/// gaps whose partitions neither errored, went unstarted, nor missed unavailable blocks in the run
+/// summarized by summary
+pub(crate) fn completed_gaps(gaps: Vec<(u64, u64)>, summary: &FreezeSummary) -> Vec<(u64, u64)> {
+    let unfinished: Vec<(u64, u64)> = summary
+        .errored
+        .iter()
+        .filter_map(|(partition, _error)| partition.as_ref())
+        .chain(summary.budget_skipped.iter())
+        .chain(summary.abort_skipped.iter())
+        .chain(summary.unavailable.iter())
+        .filter_map(|partition| {
+            let chunks = partition.block_numbers.as_ref()?;
+            Some((chunks.min_value()?, chunks.max_value()?))
+        })
+        .collect();
+    gaps.into_iter().filter(|gap| !unfinished.contains(gap)).collect()
+}
+
+/// sub-ranges of the inclusive range start..=end not covered by any of the covered ranges
+fn missing_block_ranges(start: u64, end: u64, covered: &[(u64, u64)]) -> Vec<(u64, u64)> {
+    let mut covered = covered.to_vec();
+    covered.sort();
+    let mut missing = Vec::new();
+    let mut next = start;
+    for (covered_start, covered_end) in covered.into_iter() {
+        if covered_end < next {
+            continue
+        }
+        if covered_start > end {
+            break
+        }
+        if covered_start > next {
+            missing.push((next, covered_start - 1));
+        }
+        if covered_end >= end {
+            return missing
+        }
+        next = covered_end + 1;
+    }
+    missing.push((next, end));
+    missing
+}
+
+/// inclusive block ranges of the existing files of datatype, parsed from their names
+fn covered_block_ranges(
+    files: &[PathBuf],
+    sink: &FileOutput,
+    datatype: Datatype,
+) -> Vec<(u64, u64)> {
+    block_range_files(files, sink, datatype).into_iter().map(|(range, _)| range).collect()
+}
+
+/// inclusive block ranges of the existing files of each datatype of query
+///
+/// ranges are parsed from file names, so files are found whatever width their block numbers are
+/// zero-padded to
+pub(crate) fn existing_block_ranges(
+    query: &Query,
+    sink: &FileOutput,
+) -> Result<HashSet<(Datatype, (u64, u64))>, CollectError> {
+    let files = list_files(&sink.output_dir)?;
+    let mut existing = HashSet::new();
+    for meta_datatype in query.datatypes.iter() {
+        for datatype in meta_datatype.datatypes().into_iter() {
+            for range in covered_block_ranges(&files, sink, datatype) {
+                existing.insert((datatype, range));
+            }
+        }
+    }
+    Ok(existing)
+}
+
+/// files of datatype among files with the inclusive block range parsed from each name
+pub(crate) fn block_range_files(
+    files: &[PathBuf],
+    sink: &FileOutput,
+    datatype: Datatype,
+) -> Vec<((u64, u64), PathBuf)> {
+    let prefix = match &sink.suffix {
+        Some(suffix) => format!("{}__{}__{}__", sink.prefix, datatype.name(), suffix),
+        None => format!("{}__{}__", sink.prefix, datatype.name()),
+    };
+    let extension = format!(".{}", sink.output_format().extension());
+    files
+        .iter()
+        .filter_map(|path| {
+            let name = path.file_name()?.to_str()?;
+            let label = name.strip_prefix(prefix.as_str())?.strip_suffix(extension.as_str())?;
+            let (start, end) = label.split_once("_to_")?;
+            Some(((start.parse().ok()?, end.parse().ok()?), path.clone()))
+        })
+        .collect()
+}
+
+/// files within dir and its subdirectories, excluding the .cryo directory of reports and temp files
+fn list_files(dir: &Path) -> Result<Vec<PathBuf>, CollectError> {
+    let mut files = Vec::new();
+    if !dir.exists() {
+        return Ok(files)
+    }
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries = std::fs::read_dir(&dir).map_err(|_| err("could not read output dir"))?;
+        for entry in entries {
+            let path = entry.map_err(|_| err("could not read output dir"))?.path();
+            if path.is_dir() {
+                if path.file_name().map_or(true, |name| name != ".cryo") {
+                    dirs.push(path);
+                }
+            } else {
+                files.push(path);
+            }
+        }
+    }
+    Ok(files)
+}
+
+fn chunk_ranges(chunk: &BlockChunk) -> Vec<(u64, u64)> {
+    match chunk {
+        BlockChunk::Range(start, end) => vec![(*start, *end)],
+        BlockChunk::Numbers(numbers) => numbers.iter().map(|n| (*n, *n)).collect(),
+    }
+}
+
+/// merge overlapping and adjacent inclusive ranges
+fn merge_ranges(mut ranges: Vec<(u64, u64)>) -> Vec<(u64, u64)> {
+    ranges.sort();
+    let mut merged: Vec<(u64, u64)> = Vec::new();
+    for (start, end) in ranges.into_iter() {
+        match merged.last_mut() {
+            Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ColumnEncoding, MetaDatatype, U256Type};
+    use std::collections::HashMap;
+
+    fn get_query(partitions: Vec<Partition>) -> Query {
+        let schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &None,
+                None,
+                None,
+            )
+            .unwrap();
+        Query {
+            datatypes: vec![MetaDatatype::Scalar(Datatype::Logs)],
+            schemas: HashMap::from([(Datatype::Logs, schema)]),
+            partitions,
+            partitioned_by: vec![Dim::BlockNumber],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_missing_block_ranges() {
+        assert_eq!(missing_block_ranges(0, 99, &[]), vec![(0, 99)]);
+        assert!(missing_block_ranges(0, 99, &[(0, 99)]).is_empty());
+        assert_eq!(missing_block_ranges(10, 19, &[(0, 9), (20, 29)]), vec![(10, 19)]);
+        assert_eq!(
+            missing_block_ranges(0, 99, &[(50, 59), (0, 9), (55, 69), (95, 120)]),
+            vec![(10, 49), (70, 94)]
+        );
+    }
+
+    #[test]
+    fn test_fill_gaps_collects_only_missing_ranges() {
+        let output_dir =
+            std::env::temp_dir().join(format!("cryo_test_fill_gaps_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&output_dir);
+        let sink =
+            FileOutput { output_dir: output_dir.clone(), fill_gaps: true, ..Default::default() };
+
+        // an earlier run chunked by 100 blocks errored on 100-199, files of other datatypes,
+        // in-progress files, and reports do not count as covering blocks
+        std::fs::create_dir_all(output_dir.join(".cryo/reports")).unwrap();
+        for name in [
+            "ethereum__logs__00000000_to_00000099.parquet",
+            "ethereum__logs__00000200_to_00000299.parquet",
+            "ethereum__logs__00000300_to_00000349._tmp",
+            "ethereum__blocks__00000300_to_00000399.parquet",
+            ".cryo/reports/ethereum__logs__00000350_to_00000399.parquet",
+        ] {
+            std::fs::write(output_dir.join(name), "").unwrap();
+        }
+
+        // the resumed run uses chunks of 250 blocks
+        let partitions = vec![
+            Partition {
+                block_numbers: Some(vec![BlockChunk::Range(0, 249)]),
+                ..Default::default()
+            },
+            Partition {
+                block_numbers: Some(vec![BlockChunk::Range(250, 399)]),
+                ..Default::default()
+            },
+        ];
+        let (gap_query, gaps) = fill_gaps(&get_query(partitions), &sink).unwrap();
+        assert_eq!(gaps, vec![(100, 199), (300, 399)]);
+        let ranges: Vec<Vec<(u64, u64)>> = gap_query
+            .partitions
+            .iter()
+            .map(|partition| {
+                partition.block_numbers.iter().flatten().flat_map(chunk_ranges).collect()
+            })
+            .collect();
+        assert_eq!(ranges, vec![vec![(100, 199)], vec![(300, 399)]]);
+
+        // once the gaps are written nothing is left to collect
+        for name in [
+            "ethereum__logs__00000100_to_00000199.parquet",
+            "ethereum__logs__00000300_to_00000399.parquet",
+        ] {
+            std::fs::write(output_dir.join(name), "").unwrap();
+        }
+        let (gap_query, gaps) = fill_gaps(&get_query(gap_query.partitions), &sink).unwrap();
+        assert!(gaps.is_empty());
+        assert!(gap_query.partitions.is_empty());
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+}
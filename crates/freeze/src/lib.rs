@@ -7,14 +7,37 @@
     attr(deny(warnings, rust_2018_idioms), allow(dead_code, unused_variables))
 ))]
 
+mod block_index;
 mod collect;
+mod compact;
+mod compare;
 mod datasets;
+mod decode;
+mod factory;
 mod freeze;
+mod gaps;
 mod multi_datasets;
+mod provenance;
+mod replay;
+mod row_ids;
+mod schema_only;
+mod single_file;
+mod stdout;
 mod types;
 
+pub use block_index::{
+    block_index_path, lookup_block_file, read_block_index, BlockIndex, BlockIndexEntry,
+};
 pub use collect::collect;
+pub use compact::{compact_files, CompactionSummary, DEFAULT_COMPACT_TARGET_BYTES};
+pub use compare::compare_logs;
 pub use datasets::*;
-pub use freeze::freeze;
+pub use decode::decode_logs;
+pub use factory::{LogFactory, DEFAULT_FACTORY_BLOCKS_PER_REQUEST};
+pub use freeze::{freeze, SUCCESS_MARKER};
+pub use gaps::fill_gaps;
 pub use multi_datasets::*;
+pub use provenance::{read_file_filter, FileFilter, FILE_FILTER_KEY};
+pub use replay::{read_log_responses, replay_logs, LogResponseDump};
+pub use schema_only::write_schema_files;
 pub use types::*;
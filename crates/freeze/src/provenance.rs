@@ -0,0 +1,240 @@
+use crate::{
+    dataframes, err, AddressChunk, ChunkData, CollectError, Datatype, FileOutput, Partition, Query,
+};
+use std::path::Path;
+
+/// key of the parquet metadata entry holding the filter of a file
+pub const FILE_FILTER_KEY: &str = "cryo_filter";
+
+/// filter that produced an output file, embedded in its parquet metadata
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct FileFilter {
+    /// datatype of file
+    pub datatype: String,
+    /// first and last block requested, inclusive
+    pub block_range: Option<(u64, u64)>,
+    /// addresses requested
+    pub addresses: Option<Vec<String>>,
+    /// contracts requested
+    pub contracts: Option<Vec<String>>,
+    /// topic0s requested
+    pub topic0s: Option<Vec<String>>,
+    /// topic1s requested
+    pub topic1s: Option<Vec<String>>,
+    /// topic2s requested
+    pub topic2s: Option<Vec<String>>,
+    /// topic3s requested
+    pub topic3s: Option<Vec<String>>,
+    /// topic matched at any of positions 1-3
+    pub any_topic: Option<String>,
+    /// topic0s whose logs were dropped after fetching
+    pub excluded_topic0s: Option<Vec<String>>,
+}
+
+impl FileFilter {
+    /// filter of the file of datatype holding the rows of partitions
+    pub fn new(query: &Query, partitions: &[Partition], datatype: Datatype) -> Self {
+        let block_numbers: Vec<u64> = partitions
+            .iter()
+            .filter_map(|partition| partition.block_numbers.as_ref())
+            .flat_map(|block_numbers| {
+                block_numbers.min_value().into_iter().chain(block_numbers.max_value())
+            })
+            .collect();
+        let block_range = match (block_numbers.iter().min(), block_numbers.iter().max()) {
+            (Some(start), Some(end)) => Some((*start, *end)),
+            _ => None,
+        };
+        let excluded_topic0s = query.excluded_topic0s.as_ref().map(|excluded| {
+            let mut topic0s: Vec<String> =
+                excluded.counts().into_keys().map(prefix_hex::encode).collect();
+            topic0s.sort();
+            topic0s
+        });
+        FileFilter {
+            datatype: datatype.name(),
+            block_range,
+            addresses: chunk_values(partitions, |partition| partition.addresses.as_ref())
+                .or_else(|| query.log_address_set.as_ref().map(|set| sorted_hex(set.addresses()))),
+            contracts: chunk_values(partitions, |partition| partition.contracts.as_ref()),
+            topic0s: chunk_values(partitions, |partition| partition.topic0s.as_ref())
+                .or_else(|| query.included_topic0s.as_ref().map(|set| sorted_hex(set.topic0s()))),
+            topic1s: chunk_values(partitions, |partition| partition.topic1s.as_ref()),
+            topic2s: chunk_values(partitions, |partition| partition.topic2s.as_ref()),
+            topic3s: chunk_values(partitions, |partition| partition.topic3s.as_ref()),
+            any_topic: query.any_topic.clone().map(prefix_hex::encode),
+            excluded_topic0s,
+        }
+    }
+}
+
+/// distinct hex values of a binary dimension across partitions, None if no partition sets it
+fn chunk_values<F>(partitions: &[Partition], get_chunks: F) -> Option<Vec<String>>
+where
+    F: Fn(&Partition) -> Option<&Vec<AddressChunk>>,
+{
+    let chunks: Vec<&Vec<AddressChunk>> = partitions.iter().filter_map(get_chunks).collect();
+    if chunks.is_empty() {
+        return None
+    }
+    let mut values: Vec<String> =
+        chunks.into_iter().flat_map(|chunks| chunks.values()).map(prefix_hex::encode).collect();
+    values.sort();
+    values.dedup();
+    Some(values)
+}
+
+/// sorted hex values of an address set or topic0 inclusion of query
+fn sorted_hex(values: &[Vec<u8>]) -> Vec<String> {
+    let mut values: Vec<String> = values.iter().map(prefix_hex::encode).collect();
+    values.sort();
+    values
+}
+
+/// parquet metadata entries of the file of datatype, empty unless the sink embeds filters
+pub(crate) fn filter_metadata(
+    query: &Query,
+    partitions: &[Partition],
+    datatype: Datatype,
+    sink: &FileOutput,
+) -> Result<Vec<(String, String)>, CollectError> {
+    if !sink.embed_filter {
+        return Ok(Vec::new())
+    }
+    metadata_entries(&FileFilter::new(query, partitions, datatype))
+}
+
+/// parquet metadata entries of a file holding only the rows of partitions within block_range,
+/// such as a bucket or day file of a partition, empty unless the sink embeds filters
+pub(crate) fn filter_metadata_within(
+    query: &Query,
+    partitions: &[Partition],
+    datatype: Datatype,
+    sink: &FileOutput,
+    block_range: (u64, u64),
+) -> Result<Vec<(String, String)>, CollectError> {
+    if !sink.embed_filter {
+        return Ok(Vec::new())
+    }
+    let filter = FileFilter::new(query, partitions, datatype);
+    let block_range = filter
+        .block_range
+        .map(|(start, end)| (start.max(block_range.0), end.min(block_range.1)))
+        .or(Some(block_range));
+    metadata_entries(&FileFilter { block_range, ..filter })
+}
+
+fn metadata_entries(filter: &FileFilter) -> Result<Vec<(String, String)>, CollectError> {
+    let serialized =
+        serde_json::to_string(filter).map_err(|_| err("could not serialize file filter"))?;
+    Ok(vec![(FILE_FILTER_KEY.to_string(), serialized)])
+}
+
+/// read filter embedded in a parquet file, None if the file was written without one
+pub fn read_file_filter(path: &Path) -> Result<Option<FileFilter>, CollectError> {
+    let metadata = dataframes::read_parquet_metadata(path)
+        .map_err(|_| err("could not read parquet metadata"))?;
+    metadata
+        .get(FILE_FILTER_KEY)
+        .map(|filter| serde_json::from_str(filter).map_err(|_| err("invalid file filter")))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BlockChunk, ColumnEncoding, Dim, MetaDatatype, TopicChunk, U256Type};
+    use polars::prelude::*;
+    use std::collections::HashMap;
+
+    fn get_query() -> Query {
+        let schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &None,
+                None,
+                None,
+            )
+            .unwrap();
+        Query {
+            datatypes: vec![MetaDatatype::Scalar(Datatype::Logs)],
+            schemas: HashMap::from([(Datatype::Logs, schema)]),
+            partitioned_by: vec![Dim::BlockNumber],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_filter_embedded_in_parquet_metadata() {
+        let output_dir =
+            std::env::temp_dir().join(format!("cryo_test_provenance_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&output_dir);
+        std::fs::create_dir_all(&output_dir).unwrap();
+        let sink =
+            FileOutput { output_dir: output_dir.clone(), embed_filter: true, ..Default::default() };
+        let address = vec![0xaa; 20];
+        let topic0 = vec![0xbb; 32];
+        let partitions = vec![
+            Partition {
+                block_numbers: Some(vec![BlockChunk::Range(100, 199)]),
+                addresses: Some(vec![AddressChunk::Values(vec![address.clone()])]),
+                topic0s: Some(vec![TopicChunk::Values(vec![topic0.clone()])]),
+                ..Default::default()
+            },
+            Partition {
+                block_numbers: Some(vec![BlockChunk::Range(200, 250)]),
+                addresses: Some(vec![AddressChunk::Values(vec![address.clone()])]),
+                ..Default::default()
+            },
+        ];
+        let metadata = filter_metadata(&get_query(), &partitions, Datatype::Logs, &sink).unwrap();
+
+        let path = output_dir.join("ethereum__logs__00000100_to_00000250.parquet");
+        let mut df = df!("block_number" => &[100u32, 250]).unwrap();
+        dataframes::df_to_file_with_metadata(&mut df, &path, &sink, &metadata).unwrap();
+
+        let filter = read_file_filter(&path).unwrap().unwrap();
+        assert_eq!(filter.datatype, "logs");
+        assert_eq!(filter.block_range, Some((100, 250)));
+        assert_eq!(filter.addresses, Some(vec![prefix_hex::encode(address.clone())]));
+        assert_eq!(filter.topic0s, Some(vec![prefix_hex::encode(topic0)]));
+        assert_eq!(filter.topic1s, None);
+
+        // file is still readable after its footer is rewritten
+        let read = ParquetReader::new(std::fs::File::open(&path).unwrap()).finish().unwrap();
+        assert!(read.equals(&df));
+
+        // embedding rewrites the footer in place, leaving the bytes before it unchanged
+        let before = std::fs::read(&path).unwrap();
+        let footer_len =
+            u32::from_le_bytes(before[before.len() - 8..before.len() - 4].try_into().unwrap());
+        let footer_start = before.len() - 8 - footer_len as usize;
+        let entries = vec![("other".to_string(), "value".to_string())];
+        dataframes::embed_parquet_metadata(&path, &entries).unwrap();
+        let after = std::fs::read(&path).unwrap();
+        assert_eq!(after[..footer_start], before[..footer_start]);
+        assert_eq!(read_file_filter(&path).unwrap().unwrap().block_range, Some((100, 250)));
+        assert_eq!(dataframes::read_parquet_metadata(&path).unwrap()["other"], "value");
+
+        // files holding part of the partitions, like bucket and day files, carry their own range
+        let bucket =
+            filter_metadata_within(&get_query(), &partitions, Datatype::Logs, &sink, (200, 299))
+                .unwrap();
+        let bucket_path = output_dir.join("bucket.parquet");
+        let mut bucket_df = df!("block_number" => &[250u32]).unwrap();
+        dataframes::df_to_file_with_metadata(&mut bucket_df, &bucket_path, &sink, &bucket).unwrap();
+        let filter = read_file_filter(&bucket_path).unwrap().unwrap();
+        assert_eq!(filter.block_range, Some((200, 250)));
+        assert_eq!(filter.addresses, Some(vec![prefix_hex::encode(address)]));
+
+        // files written without --embed-filter carry no filter
+        let plain_path = output_dir.join("plain.parquet");
+        dataframes::df_to_file(&mut df, &plain_path, &sink).unwrap();
+        assert_eq!(read_file_filter(&plain_path).unwrap(), None);
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+}
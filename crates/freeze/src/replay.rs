@@ -0,0 +1,247 @@
+use crate::{
+    dataframes, err, BlockChunk, CollectByBlock, CollectError, Datatype, FileOutput, LogContext,
+    Logs, Partition, Query, SchemaFunctions, ToDataFrames,
+};
+use ethers::prelude::*;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// columns filled from data fetched alongside logs, which dumps do not capture
+const CONTEXT_COLUMNS: [&str; 4] = ["tx_from", "tx_to", "block_timestamp", "base_fee_per_gas"];
+
+/// raw eth_getLogs response of one request, as dumped before transformation
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct LogResponseDump {
+    /// chain id of the source that returned the logs
+    pub chain_id: u64,
+    /// block range of the request
+    pub block_range: (u64, u64),
+    /// filter of the request
+    pub filter: Filter,
+    /// logs returned for filter
+    pub logs: Vec<Log>,
+}
+
+/// write raw response to dir, named by its block range and a digest of its filter so that
+/// requests sharing a block range but filtering different addresses or topics do not collide
+pub(crate) fn dump_log_response(dir: &Path, dump: &LogResponseDump) -> Result<(), CollectError> {
+    std::fs::create_dir_all(dir).map_err(|_| err("could not create log response dir"))?;
+    let filter =
+        serde_json::to_vec(&dump.filter).map_err(|_| err("could not serialize log filter"))?;
+    let digest: String =
+        ethers::utils::keccak256(filter)[..4].iter().map(|byte| format!("{:02x}", byte)).collect();
+    let (start, end) = dump.block_range;
+    let path = dir.join(format!("logs__{:0>8}_to_{:0>8}__{}.json", start, end, digest));
+    let serialized =
+        serde_json::to_vec(dump).map_err(|_| err("could not serialize log response"))?;
+    std::fs::write(path, serialized).map_err(|_| err("could not write log response"))
+}
+
+/// read log responses dumped to dir, ordered by block range
+pub fn read_log_responses(dir: &Path) -> Result<Vec<LogResponseDump>, CollectError> {
+    let entries = std::fs::read_dir(dir).map_err(|_| err("could not read log response dir"))?;
+    let mut dumps = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(|_| err("could not read log response dir"))?.path();
+        if path.extension().and_then(|extension| extension.to_str()) != Some("json") {
+            continue
+        }
+        let contents = std::fs::read(&path).map_err(|_| err("could not read log response file"))?;
+        let dump: LogResponseDump = serde_json::from_slice(&contents)
+            .map_err(|_| err(format!("invalid log response file: {:?}", path).as_str()))?;
+        dumps.push(dump);
+    }
+    if dumps.is_empty() {
+        return Err(err("no log responses found in dir"))
+    }
+    dumps.sort_by_key(|dump| dump.block_range);
+    Ok(dumps)
+}
+
+/// transform dumped log responses into output files without connecting to a node
+///
+/// logs are split by block into partitions of chunk_size blocks starting from the first dumped
+/// block, reproducing the files of the dumping run when it used the same chunk size, whatever
+/// block ranges its requests covered. every partition within a dumped block range is written,
+/// including partitions without logs
+pub fn replay_logs(
+    dumps: &[LogResponseDump],
+    query: &Query,
+    sink: &FileOutput,
+    chunk_size: u64,
+) -> Result<Vec<PathBuf>, CollectError> {
+    let schema = query.schemas.get_schema(&Datatype::Logs)?;
+    if let Some(column) = CONTEXT_COLUMNS.iter().find(|column| schema.has_column(column)) {
+        let message = format!("column {} requires data beyond logs and cannot be replayed", column);
+        return Err(err(message.as_str()))
+    }
+    let (first_block, last_block) =
+        match (dumps.first(), dumps.iter().map(|d| d.block_range.1).max()) {
+            (Some(first), Some(last_block)) => (first.block_range.0, last_block),
+            _ => return Err(err("no log responses to replay")),
+        };
+    let chunk_size = chunk_size.max(1);
+    let chunk_index = |block_number: u64| (block_number.saturating_sub(first_block)) / chunk_size;
+    let mut chunks: BTreeMap<u64, Vec<Vec<Log>>> = BTreeMap::new();
+    for dump in dumps.iter() {
+        let (start, end) = dump.block_range;
+        for index in chunk_index(start)..=chunk_index(end) {
+            chunks.entry(index).or_default();
+        }
+        let mut dump_chunks: BTreeMap<u64, Vec<Log>> = BTreeMap::new();
+        for log in dump.logs.iter() {
+            let block_number =
+                log.block_number.ok_or_else(|| err("dumped log has no block number"))?;
+            dump_chunks.entry(chunk_index(block_number.as_u64())).or_default().push(log.clone());
+        }
+        for (index, logs) in dump_chunks.into_iter() {
+            chunks.entry(index).or_default().push(logs);
+        }
+    }
+    let chain_id = dumps[0].chain_id;
+
+    let _temp_dir_cleanup = sink.temp_dir_cleanup();
+    let arc_query = Arc::new(query.clone());
+    let mut outputs = Vec::new();
+    for (index, chunk_logs) in chunks.into_iter() {
+        let start = first_block + index * chunk_size;
+        let end = (start + chunk_size - 1).min(last_block);
+        let partition = Partition {
+            block_numbers: Some(vec![BlockChunk::Range(start, end)]),
+            ..Default::default()
+        };
+        let path = sink.get_path(query, &partition, Datatype::Logs)?;
+        if path.exists() && !sink.overwrite {
+            return Err(err(format!("output file already exists: {:?}", path).as_str()))
+        }
+
+        let logs = crate::datasets::logs::merge_logs(chunk_logs);
+        let mut columns = Logs::default();
+        <Logs as CollectByBlock>::transform(
+            (logs, LogContext::default()),
+            &mut columns,
+            &arc_query,
+        )?;
+        let mut df = columns
+            .create_dfs(&query.schemas, chain_id)?
+            .remove(&Datatype::Logs)
+            .ok_or_else(|| err("missing logs dataframe"))?;
+        if let Some(address_cap) = &query.max_logs_per_address {
+            df = address_cap.apply(&df)?;
+        }
+        dataframes::drop_null_topic_columns(&mut df, schema)?;
+        dataframes::add_block_bucket_column(&mut df, schema)?;
+        dataframes::alias_columns(&mut df, schema)?;
+        dataframes::df_to_file(&mut df, &path, sink)
+            .map_err(|_| err("error writing replayed file"))?;
+        outputs.push(path);
+    }
+    Ok(outputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ColumnEncoding, Dim, MetaDatatype, U256Type};
+    use polars::prelude::{ParquetReader, SerReader};
+    use std::collections::HashMap;
+
+    fn get_query(columns: Option<Vec<String>>) -> Query {
+        let schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &columns,
+                &None,
+                &None,
+                None,
+                None,
+            )
+            .unwrap();
+        Query {
+            datatypes: vec![MetaDatatype::Scalar(Datatype::Logs)],
+            schemas: HashMap::from([(Datatype::Logs, schema)]),
+            partitioned_by: vec![Dim::BlockNumber],
+            ..Default::default()
+        }
+    }
+
+    fn dump(block_range: (u64, u64), address: u64, block_numbers: &[u64]) -> LogResponseDump {
+        let logs = block_numbers
+            .iter()
+            .map(|block_number| Log {
+                address: H160::from_low_u64_be(address),
+                block_number: Some(U64::from(*block_number)),
+                transaction_hash: Some(H256::from_low_u64_be(*block_number)),
+                transaction_index: Some(U64::from(0u64)),
+                log_index: Some(U256::from(address)),
+                ..Default::default()
+            })
+            .collect();
+        let filter = Filter::new()
+            .from_block(block_range.0)
+            .to_block(block_range.1)
+            .address(H160::from_low_u64_be(address));
+        LogResponseDump { chain_id: 1, block_range, filter, logs }
+    }
+
+    #[test]
+    fn test_replay_dumped_log_responses_offline() {
+        let output_dir =
+            std::env::temp_dir().join(format!("cryo_test_replay_logs_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&output_dir);
+        let dump_dir = output_dir.join(".cryo/reports/log_responses/run");
+
+        // two addresses filtered over the same block range do not collide
+        dump_log_response(&dump_dir, &dump((0, 9), 1, &[3, 7])).unwrap();
+        dump_log_response(&dump_dir, &dump((0, 9), 2, &[5])).unwrap();
+        dump_log_response(&dump_dir, &dump((10, 14), 1, &[12])).unwrap();
+        let dumps = read_log_responses(&dump_dir).unwrap();
+        assert_eq!(dumps.len(), 3);
+        assert_eq!(dumps[2].logs[0].block_number, Some(U64::from(12u64)));
+
+        let sink = FileOutput { output_dir: output_dir.clone(), ..Default::default() };
+        let outputs = replay_logs(&dumps, &get_query(None), &sink, 10).unwrap();
+        assert_eq!(
+            outputs,
+            vec![
+                output_dir.join("ethereum__logs__00000000_to_00000009.parquet"),
+                output_dir.join("ethereum__logs__00000010_to_00000014.parquet"),
+            ]
+        );
+        let df = ParquetReader::new(std::fs::File::open(&outputs[0]).unwrap()).finish().unwrap();
+        let block_numbers: Vec<_> =
+            df.column("block_number").unwrap().u32().unwrap().into_iter().collect();
+        assert_eq!(block_numbers, vec![Some(3), Some(5), Some(7)]);
+
+        // logs of a request spanning several chunks are split by block into the chunks
+        let wide_dir = output_dir.join("wide");
+        let wide_sink = FileOutput { output_dir: wide_dir.clone(), ..Default::default() };
+        let wide_dumps = vec![dump((0, 24), 1, &[3, 15, 16])];
+        let outputs = replay_logs(&wide_dumps, &get_query(None), &wide_sink, 10).unwrap();
+        assert_eq!(
+            outputs,
+            vec![
+                wide_dir.join("ethereum__logs__00000000_to_00000009.parquet"),
+                wide_dir.join("ethereum__logs__00000010_to_00000019.parquet"),
+                wide_dir.join("ethereum__logs__00000020_to_00000024.parquet"),
+            ]
+        );
+        let heights: Vec<usize> = outputs
+            .iter()
+            .map(|path| {
+                ParquetReader::new(std::fs::File::open(path).unwrap()).finish().unwrap().height()
+            })
+            .collect();
+        assert_eq!(heights, vec![1, 2, 0]);
+
+        // columns filled by fetching transactions or blocks cannot be replayed
+        let columns = Some(vec!["block_number".to_string(), "block_timestamp".to_string()]);
+        assert!(replay_logs(&dumps, &get_query(columns), &sink, 10).is_err());
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+}
@@ -0,0 +1,269 @@
+use crate::{ChunkData, CollectError, Datatype, MetaDatatype, Partition};
+use polars::prelude::*;
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::{Arc, Mutex},
+};
+use tokio::sync::Notify;
+
+/// name of the column of row ids added to output files
+pub(crate) const ROW_ID_COLUMN: &str = "row_id";
+
+/// hands out row ids that increase across the partitions of a run in block order
+///
+/// partitions take their turns in block order once collected, so the ids of each partition start
+/// after the rows of every partition before it, regardless of the order partitions finish in
+#[derive(Debug, Default)]
+pub(crate) struct RowIdSequence {
+    state: Mutex<RowIdState>,
+    turn_passed: Notify,
+}
+
+#[derive(Debug, Default)]
+struct RowIdState {
+    next_turn: usize,
+    passed: BTreeSet<usize>,
+    next_ids: HashMap<Datatype, u64>,
+}
+
+impl RowIdState {
+    fn advance(&mut self) {
+        self.next_turn += 1;
+        while self.passed.remove(&self.next_turn) {
+            self.next_turn += 1;
+        }
+    }
+}
+
+impl RowIdSequence {
+    /// wait until turn is the next turn to be taken
+    async fn wait(&self, turn: usize) {
+        loop {
+            let notified = self.turn_passed.notified();
+            if self.state.lock().unwrap_or_else(|e| e.into_inner()).next_turn == turn {
+                return
+            }
+            notified.await;
+        }
+    }
+
+    /// wait until turn is less than window turns after the next turn to be taken
+    async fn wait_within(&self, turn: usize, window: usize) {
+        loop {
+            let notified = self.turn_passed.notified();
+            let next_turn = self.state.lock().unwrap_or_else(|e| e.into_inner()).next_turn;
+            if turn < next_turn + window {
+                return
+            }
+            notified.await;
+        }
+    }
+
+    /// wait for turn, then reserve n_rows ids of each datatype, returning the first id of each
+    async fn take(&self, turn: usize, n_rows: &HashMap<Datatype, u64>) -> HashMap<Datatype, u64> {
+        loop {
+            let notified = self.turn_passed.notified();
+            if let Some(first_ids) = self.try_take(turn, n_rows) {
+                self.turn_passed.notify_waiters();
+                return first_ids
+            }
+            notified.await;
+        }
+    }
+
+    fn try_take(
+        &self,
+        turn: usize,
+        n_rows: &HashMap<Datatype, u64>,
+    ) -> Option<HashMap<Datatype, u64>> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.next_turn != turn {
+            return None
+        }
+        let mut first_ids = HashMap::new();
+        for (datatype, n) in n_rows.iter() {
+            let next_id = state.next_ids.entry(*datatype).or_default();
+            first_ids.insert(*datatype, *next_id);
+            *next_id += n;
+        }
+        state.advance();
+        Some(first_ids)
+    }
+
+    /// give up turn without rows, partitions after it no longer wait for it
+    fn pass(&self, turn: usize) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.next_turn == turn {
+            state.advance();
+        } else if turn > state.next_turn {
+            state.passed.insert(turn);
+        }
+        drop(state);
+        self.turn_passed.notify_waiters();
+    }
+}
+
+/// turn of a partition within a RowIdSequence
+///
+/// a turn dropped before it is taken, e.g. by a partition that errors or is cancelled, is passed
+/// without rows so that later partitions are not blocked
+pub(crate) struct RowIdTurn {
+    sequence: Arc<RowIdSequence>,
+    turn: usize,
+    taken: bool,
+}
+
+impl RowIdTurn {
+    /// wait for the partitions before this one, the turn stays next until it is taken or dropped
+    pub(crate) async fn wait(&self) {
+        self.sequence.wait(self.turn).await
+    }
+
+    /// wait until fewer than window partitions before this one have yet to take their turns, so
+    /// that partitions are not collected far ahead of the partitions they wait for
+    pub(crate) async fn wait_within(&self, window: usize) {
+        self.sequence.wait_within(self.turn, window).await
+    }
+
+    /// wait for the partitions before this one, then reserve ids for n_rows rows of each datatype
+    pub(crate) async fn take(mut self, n_rows: &HashMap<Datatype, u64>) -> HashMap<Datatype, u64> {
+        let first_ids = self.sequence.take(self.turn, n_rows).await;
+        self.taken = true;
+        first_ids
+    }
+}
+
+impl Drop for RowIdTurn {
+    fn drop(&mut self) {
+        if !self.taken {
+            self.sequence.pass(self.turn);
+        }
+    }
+}
+
+/// turns of partitions of each datatype, numbered in order of the first block of each partition
+///
+/// partitions without block numbers keep their order, each meta datatype has its own sequence
+pub(crate) fn row_id_turns(partitions: &[(&Partition, &MetaDatatype)]) -> Vec<RowIdTurn> {
+    let mut groups: HashMap<Vec<Datatype>, Vec<usize>> = HashMap::new();
+    for (i, (_, datatype)) in partitions.iter().enumerate() {
+        groups.entry(datatype.datatypes()).or_default().push(i);
+    }
+    let mut turns: Vec<Option<RowIdTurn>> = partitions.iter().map(|_| None).collect();
+    for mut members in groups.into_values() {
+        members.sort_by_key(|i| {
+            partitions[*i].0.block_numbers.as_ref().and_then(|chunks| chunks.min_value())
+        });
+        let sequence = Arc::new(RowIdSequence::default());
+        for (turn, i) in members.into_iter().enumerate() {
+            turns[i] = Some(RowIdTurn { sequence: sequence.clone(), turn, taken: false });
+        }
+    }
+    turns.into_iter().flatten().collect()
+}
+
+/// add a UInt64 row_id column of consecutive ids starting at first_id
+pub(crate) fn add_row_id_column(df: &mut DataFrame, first_id: u64) -> Result<(), CollectError> {
+    let row_ids: Vec<u64> = (first_id..first_id + df.height() as u64).collect();
+    df.with_column(Series::new(ROW_ID_COLUMN, row_ids))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BlockChunk;
+
+    #[tokio::test]
+    async fn test_row_ids_follow_block_order_not_completion_order() {
+        let partition = |start| Partition {
+            block_numbers: Some(vec![BlockChunk::Range(start, start + 9)]),
+            ..Default::default()
+        };
+        // partitions listed out of block order, as with a reversed or random chunk order
+        let partitions = [partition(20), partition(0), partition(10)];
+        let datatype = MetaDatatype::Scalar(Datatype::Logs);
+        let payloads: Vec<(&Partition, &MetaDatatype)> =
+            partitions.iter().map(|partition| (partition, &datatype)).collect();
+        let mut turns = row_id_turns(&payloads).into_iter();
+        let (turn_20, turn_0, turn_10) =
+            (turns.next().unwrap(), turns.next().unwrap(), turns.next().unwrap());
+
+        // later partitions finish first but wait for the partitions before them
+        let n_rows = |n: u64| HashMap::from([(Datatype::Logs, n)]);
+        let last = tokio::spawn(async move { turn_20.take(&n_rows(4)).await });
+        let middle = tokio::spawn(async move { turn_10.take(&n_rows(2)).await });
+        tokio::task::yield_now().await;
+        assert!(!last.is_finished() && !middle.is_finished());
+        let first = turn_0.take(&n_rows(3)).await;
+
+        let first_id = |ids: HashMap<Datatype, u64>| ids[&Datatype::Logs];
+        assert_eq!(first_id(first), 0);
+        assert_eq!(first_id(middle.await.unwrap()), 3);
+        assert_eq!(first_id(last.await.unwrap()), 5);
+
+        // ids are contiguous across the partitions of the run
+        let mut df = DataFrame::new(vec![Series::new("block_number", vec![10u32, 11])]).unwrap();
+        add_row_id_column(&mut df, 3).unwrap();
+        assert!(df.column(ROW_ID_COLUMN).unwrap().equals(&Series::new(ROW_ID_COLUMN, [3u64, 4])));
+    }
+
+    #[tokio::test]
+    async fn test_dropped_row_id_turns_do_not_block_later_partitions() {
+        let partition = |start| Partition {
+            block_numbers: Some(vec![BlockChunk::Range(start, start + 9)]),
+            ..Default::default()
+        };
+        let partitions = [partition(0), partition(10), partition(20)];
+        let datatype = MetaDatatype::Scalar(Datatype::Blocks);
+        let payloads: Vec<(&Partition, &MetaDatatype)> =
+            partitions.iter().map(|partition| (partition, &datatype)).collect();
+        let mut turns = row_id_turns(&payloads).into_iter();
+        let (turn_0, turn_10, turn_20) =
+            (turns.next().unwrap(), turns.next().unwrap(), turns.next().unwrap());
+
+        // the middle partition errors before its predecessor is numbered
+        drop(turn_10);
+        let n_rows = HashMap::from([(Datatype::Blocks, 10)]);
+        assert_eq!(turn_0.take(&n_rows).await[&Datatype::Blocks], 0);
+        assert_eq!(turn_20.take(&n_rows).await[&Datatype::Blocks], 10);
+    }
+
+    #[tokio::test]
+    async fn test_partitions_wait_within_a_window_of_turns() {
+        let partitions: Vec<Partition> = (0..4u64)
+            .map(|i| Partition {
+                block_numbers: Some(vec![BlockChunk::Range(i * 10, i * 10 + 9)]),
+                ..Default::default()
+            })
+            .collect();
+        let datatype = MetaDatatype::Scalar(Datatype::Logs);
+        let payloads: Vec<(&Partition, &MetaDatatype)> =
+            partitions.iter().map(|partition| (partition, &datatype)).collect();
+        let mut turns = row_id_turns(&payloads).into_iter();
+        let turn_0 = turns.next().unwrap();
+        let turn_10 = turns.next().unwrap();
+        let turn_20 = turns.next().unwrap();
+        let turn_30 = turns.next().unwrap();
+
+        // with a window of two turns, the third and fourth partitions wait to start
+        turn_0.wait_within(2).await;
+        turn_10.wait_within(2).await;
+        let third = tokio::spawn(async move {
+            turn_20.wait_within(2).await;
+            turn_20
+        });
+        tokio::task::yield_now().await;
+        assert!(!third.is_finished());
+
+        // each turn taken lets one more partition start
+        turn_0.take(&HashMap::new()).await;
+        let turn_20 = third.await.unwrap();
+        let fourth = tokio::spawn(async move { turn_30.wait_within(2).await });
+        tokio::task::yield_now().await;
+        assert!(!fourth.is_finished());
+        drop(turn_10);
+        fourth.await.unwrap();
+        drop(turn_20);
+    }
+}
@@ -0,0 +1,29 @@
+use crate::{dataframes, err, row_ids, CollectError, Datatype, FileOutput, SchemaFunctions, Table};
+use std::{collections::HashMap, path::PathBuf};
+
+/// write a zero-row file for each datatype, with the exact columns and dtypes of a real run
+pub fn write_schema_files(
+    datatypes: &[Datatype],
+    schemas: &HashMap<Datatype, Table>,
+    sink: &FileOutput,
+) -> Result<Vec<PathBuf>, CollectError> {
+    let _temp_dir_cleanup = sink.temp_dir_cleanup();
+    let mut outputs = Vec::new();
+    for datatype in datatypes.iter() {
+        let mut df = datatype.empty_df(schemas, 0)?;
+        let schema = schemas.get_schema(datatype)?;
+        if schema.row_id {
+            row_ids::add_row_id_column(&mut df, 0)?;
+        }
+        dataframes::add_block_bucket_column(&mut df, schema)?;
+        dataframes::alias_columns(&mut df, schema)?;
+        let path = sink.get_schema_path(*datatype)?;
+        if path.exists() && !sink.overwrite {
+            return Err(err(format!("output file already exists: {:?}", path).as_str()))
+        }
+        dataframes::df_to_file(&mut df, &path, sink)
+            .map_err(|_| err("error writing schema file"))?;
+        outputs.push(path);
+    }
+    Ok(outputs)
+}
@@ -0,0 +1,143 @@
+use crate::{
+    err, BlockChunk, ChunkData, CollectError, Datatype, Dim, FileFormat, FileOutput, Partition,
+    Query, SchemaFunctions, TempDirCleanup,
+};
+use polars::prelude::*;
+use std::{collections::HashMap, path::PathBuf};
+
+/// partition files of a single-file run, spooled to disk until every partition is collected
+///
+/// spooled files keep every column under its original name so that they can be merged, sorted,
+/// and written with the compaction and aliases of the real schemas
+pub(crate) struct SingleFileSpool {
+    /// query used to collect partitions into the spool
+    pub(crate) query: Query,
+    /// sink writing partition files into the spool
+    pub(crate) sink: FileOutput,
+    _cleanup: TempDirCleanup,
+}
+
+impl SingleFileSpool {
+    pub(crate) fn new(query: &Query, sink: &FileOutput) -> Result<Self, CollectError> {
+        if query.logs_per_block {
+            return Err(err("cannot write a single file with logs per block"))
+        }
+        let base = match &sink.temp_dir {
+            Some(temp_dir) => temp_dir.clone(),
+            None => sink.output_dir.join(".cryo/tmp"),
+        };
+        let spool_dir = FileOutput::run_temp_dir(&base);
+        let schemas = query
+            .schemas
+            .iter()
+            .map(|(datatype, schema)| {
+                let mut schema = schema.clone();
+                schema.compact_topics = false;
+                schema.column_aliases = None;
+                schema.block_bucket_size = None;
+                (*datatype, schema)
+            })
+            .collect();
+        let spool_sink = FileOutput {
+            output_dir: spool_dir.clone(),
+            subdirs: vec![],
+            overwrite: true,
+            fill_gaps: false,
+            format: FileFormat::Parquet,
+            custom_format: None,
+            parquet_statistics: false,
+            parquet_compression: ParquetCompression::Uncompressed,
+            column_decimals: None,
+            target_partition_bytes: None,
+            single_file: false,
+            stdout: false,
+            block_index: false,
+            embed_filter: false,
+            success_marker: false,
+            row_count_sidecar: false,
+            temp_dir: None,
+            signer: None,
+            ..sink.clone()
+        };
+        Ok(SingleFileSpool {
+            query: Query { schemas, ..query.clone() },
+            sink: spool_sink,
+            _cleanup: TempDirCleanup::new(spool_dir),
+        })
+    }
+
+    /// whether the output files of the run already exist and should not be overwritten
+    pub(crate) fn output_exists(
+        &self,
+        query: &Query,
+        sink: &FileOutput,
+    ) -> Result<bool, CollectError> {
+        let paths = single_file_paths(query, sink)?;
+        Ok(!sink.overwrite && paths.values().all(|path| path.exists()))
+    }
+
+    /// merge spooled files of partitions into one dataframe per output path of the run
+    ///
+    /// rows are sorted by the sort columns of each schema across all partitions, the merged
+    /// dataframes are held in memory only once collection has finished
+    pub(crate) fn merge(
+        &self,
+        query: &Query,
+        sink: &FileOutput,
+        partitions: &[Partition],
+    ) -> Result<Vec<(Datatype, PathBuf, DataFrame)>, CollectError> {
+        let mut merged = Vec::new();
+        for (datatype, path) in single_file_paths(query, sink)? {
+            let mut df: Option<DataFrame> = None;
+            for partition in partitions.iter() {
+                let spool_path =
+                    match self.sink.output_paths(&self.query, partition)?.remove(&datatype) {
+                        Some(spool_path) if spool_path.exists() => spool_path,
+                        _ => continue,
+                    };
+                let file = std::fs::File::open(&spool_path)
+                    .map_err(|_| err("could not open spooled partition file"))?;
+                let partition_df = ParquetReader::new(file).finish()?;
+                match &mut df {
+                    Some(df) => {
+                        df.vstack_mut(&partition_df)?;
+                    }
+                    None => df = Some(partition_df),
+                }
+            }
+            if let Some(mut df) = df {
+                df.rechunk();
+                let df = match &query.schemas.get_schema(&datatype)?.sort_columns {
+                    Some(sort_columns) => df.sort(sort_columns, false, false)?,
+                    None => df,
+                };
+                merged.push((datatype, path, df));
+            }
+        }
+        Ok(merged)
+    }
+}
+
+/// output paths of a single-file run, named by the overall block range of its partitions
+pub(crate) fn single_file_paths(
+    query: &Query,
+    sink: &FileOutput,
+) -> Result<HashMap<Datatype, PathBuf>, CollectError> {
+    let block_numbers: Vec<u64> = query
+        .partitions
+        .iter()
+        .filter_map(|partition| partition.block_numbers.as_ref())
+        .flat_map(|block_numbers| {
+            block_numbers.min_value().into_iter().chain(block_numbers.max_value())
+        })
+        .collect();
+    let partition = match (block_numbers.iter().min(), block_numbers.iter().max()) {
+        (Some(start), Some(end)) => Partition {
+            block_numbers: Some(vec![BlockChunk::Range(*start, *end)]),
+            ..Default::default()
+        },
+        _ => return Err(err("a single file can only be written for block ranges")),
+    };
+    let query = Query { partitioned_by: vec![Dim::BlockNumber], ..query.clone() };
+    sink.get_paths(&query, &partition, None)
+}
@@ -0,0 +1,201 @@
+use crate::{
+    dataframes, err, row_ids::RowIdTurn, CollectError, Datatype, FileOutput, Query, SchemaFunctions,
+};
+use polars::prelude::*;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+/// rows of a run streamed to stdout as its partitions are collected
+///
+/// partitions stream their rows in turns taken in block order, so the stream holds the rows of
+/// the run in block order whichever order partitions finish in. once a partition is not
+/// streamed, e.g. because it failed, the partitions after it are not streamed either, so that
+/// the stream never skips rows
+#[derive(Debug, Default)]
+pub(crate) struct StdoutStream {
+    state: Mutex<StreamState>,
+}
+
+#[derive(Debug, Default)]
+struct StreamState {
+    header_written: bool,
+    next_log_seq: u64,
+    stopped: bool,
+}
+
+impl StdoutStream {
+    /// turn of a partition to stream its rows
+    pub(crate) fn turn(self: &Arc<Self>, turn: RowIdTurn) -> StdoutTurn {
+        StdoutTurn { stream: self.clone(), turn: Some(turn) }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, StreamState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// write rows of dfs to stdout, returning the number of rows written
+    fn write(
+        &self,
+        dfs: HashMap<Datatype, DataFrame>,
+        query: &Query,
+        sink: &FileOutput,
+    ) -> Result<u64, CollectError> {
+        let mut state = self.lock();
+        if state.stopped {
+            return Err(err("not streamed to stdout because an earlier chunk did not complete"))
+        }
+        let mut n_rows = 0;
+        for (datatype, mut df) in dfs {
+            let schema = query.schemas.get_schema(&datatype)?;
+            if datatype == Datatype::Logs && schema.has_column("global_log_seq") {
+                crate::datasets::logs::number_global_log_seq(&mut df, state.next_log_seq)?;
+                state.next_log_seq += df.height() as u64;
+            }
+            dataframes::add_block_bucket_column(&mut df, schema)?;
+            dataframes::alias_columns(&mut df, schema)?;
+            dataframes::df_to_stdout(&df, sink, !state.header_written)
+                .map_err(|_| err("error writing to stdout"))?;
+            state.header_written = true;
+            n_rows += df.height() as u64;
+        }
+        Ok(n_rows)
+    }
+}
+
+/// turn of a partition within a StdoutStream
+///
+/// a turn dropped before its rows are streamed stops the stream before the turn passes on
+pub(crate) struct StdoutTurn {
+    stream: Arc<StdoutStream>,
+    turn: Option<RowIdTurn>,
+}
+
+impl StdoutTurn {
+    /// wait until fewer than window partitions before this one have yet to be streamed
+    pub(crate) async fn wait_within(&self, window: usize) {
+        if let Some(turn) = &self.turn {
+            turn.wait_within(window).await;
+        }
+    }
+
+    /// wait for the partitions before this one to be streamed or to stop the stream
+    pub(crate) async fn wait(&self) {
+        if let Some(turn) = &self.turn {
+            turn.wait().await;
+        }
+    }
+
+    /// wait for the partitions before this one, then stream the rows of dfs to stdout, returning
+    /// the number of rows written
+    pub(crate) async fn write(
+        mut self,
+        dfs: HashMap<Datatype, DataFrame>,
+        query: &Query,
+        sink: &FileOutput,
+    ) -> Result<u64, CollectError> {
+        self.wait().await;
+        let n_rows = self.stream.write(dfs, query, sink)?;
+        if let Some(turn) = self.turn.take() {
+            turn.take(&HashMap::new()).await;
+        }
+        Ok(n_rows)
+    }
+}
+
+impl Drop for StdoutTurn {
+    fn drop(&mut self) {
+        if self.turn.is_some() {
+            self.stream.lock().stopped = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BlockChunk, ColumnEncoding, FileFormat, MetaDatatype, Partition, U256Type};
+
+    /// set in the environment of the test process that streams to its stdout
+    const CHILD_ENV: &str = "CRYO_TEST_STDOUT_CHILD";
+
+    /// streams partitions that finish out of block order to the stdout of the process, run by
+    /// test_partitions_stream_to_stdout_in_block_order in a child process
+    #[tokio::test]
+    async fn stream_out_of_order_partitions_to_process_stdout() {
+        if std::env::var(CHILD_ENV).is_err() {
+            return
+        }
+        let columns = Some(vec!["block_number".to_string()]);
+        let schema = Datatype::Blocks
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Hex,
+                &None,
+                &None,
+                &columns,
+                None,
+                None,
+            )
+            .unwrap();
+        let query = Arc::new(Query {
+            datatypes: vec![MetaDatatype::Scalar(Datatype::Blocks)],
+            schemas: HashMap::from([(Datatype::Blocks, schema)]),
+            ..Default::default()
+        });
+        let sink =
+            Arc::new(FileOutput { format: FileFormat::Csv, stdout: true, ..Default::default() });
+        let partitions: Vec<Partition> = (0..4u64)
+            .map(|i| Partition {
+                block_numbers: Some(vec![BlockChunk::Range(i * 10, i * 10 + 9)]),
+                ..Default::default()
+            })
+            .collect();
+        let datatype = MetaDatatype::Scalar(Datatype::Blocks);
+        let pairs: Vec<_> = partitions.iter().map(|partition| (partition, &datatype)).collect();
+        let stream = Arc::new(StdoutStream::default());
+        let mut turns: Vec<_> =
+            crate::row_ids::row_id_turns(&pairs).into_iter().map(|t| stream.turn(t)).collect();
+
+        // partitions write in reverse block order, the third partition is never collected
+        let last = turns.pop().unwrap();
+        let missing = turns.pop().unwrap();
+        let mut handles = Vec::new();
+        for (i, turn) in turns.into_iter().enumerate().rev() {
+            let (query, sink) = (query.clone(), sink.clone());
+            let block_number = i as u32 * 10;
+            let df = df!("block_number" => [block_number, block_number + 1]).unwrap();
+            handles.push(tokio::spawn(async move {
+                turn.write(HashMap::from([(Datatype::Blocks, df)]), &query, &sink).await
+            }));
+            tokio::task::yield_now().await;
+        }
+        for handle in handles {
+            assert_eq!(handle.await.unwrap().unwrap(), 2);
+        }
+        drop(missing);
+        let df = df!("block_number" => [30u32]).unwrap();
+        assert!(last.write(HashMap::from([(Datatype::Blocks, df)]), &query, &sink).await.is_err());
+    }
+
+    #[test]
+    fn test_partitions_stream_to_stdout_in_block_order() {
+        let output = std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["stream_out_of_order_partitions_to_process_stdout", "--nocapture"])
+            .env(CHILD_ENV, "1")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+
+        // rows are interleaved with the output of the test harness
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let rows: Vec<&str> = stdout
+            .lines()
+            .filter(|line| {
+                *line == "block_number" || line.starts_with(|c: char| c.is_ascii_digit())
+            })
+            .collect();
+        assert_eq!(rows, vec!["block_number", "0", "1", "10", "11"]);
+    }
+}
@@ -8,4 +8,5 @@ pub use chunk::{
     AddressChunk, BlockChunk, CallDataChunk, Chunk, SlotChunk, TopicChunk, TransactionChunk,
 };
 pub use chunk_ops::{ChunkData, ChunkStats};
+pub use number_chunk::DEFAULT_BLOCK_LABEL_WIDTH;
 pub use subchunks::Subchunk;
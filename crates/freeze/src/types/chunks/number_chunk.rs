@@ -12,11 +12,14 @@ pub enum NumberChunk {
     Range(u64, u64),
 }
 
+/// default number of digits that block numbers are zero-padded to in file names
+pub const DEFAULT_BLOCK_LABEL_WIDTH: usize = 8;
+
 impl ChunkData for NumberChunk {
     type Inner = u64;
 
     fn format_item(value: Self::Inner) -> Result<String, ChunkError> {
-        Ok(format!("{:0>8}", value))
+        Ok(format!("{:0>width$}", value, width = DEFAULT_BLOCK_LABEL_WIDTH))
     }
 
     fn size(&self) -> u64 {
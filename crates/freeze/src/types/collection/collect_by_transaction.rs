@@ -1,4 +1,6 @@
-use super::collect_generic::{fetch_partition, join_partition_handles};
+use super::collect_generic::{
+    fetch_partition, join_partition_handles, IndexedResponse, OrderedReceiver,
+};
 use crate::{CollectError, Datatype, Params, Partition, Query, Source, ToDataFrames};
 use polars::prelude::*;
 use std::collections::HashMap;
@@ -47,10 +49,11 @@ pub trait CollectByTransaction: 'static + Send + Default + ToDataFrames {
 
     /// convert transaction-derived data to dataframe
     async fn transform_channel(
-        mut receiver: mpsc::Receiver<R<Self::Response>>,
+        receiver: mpsc::Receiver<IndexedResponse<Self::Response>>,
         query: &Arc<Query>,
     ) -> R<Self> {
         let mut columns = Self::default();
+        let mut receiver = OrderedReceiver::new(receiver);
         while let Some(message) = receiver.recv().await {
             match message {
                 Ok(message) => Self::transform(message, &mut columns, query)?,
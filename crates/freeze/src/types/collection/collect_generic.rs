@@ -1,9 +1,12 @@
 use crate::*;
 use futures::Future;
 use polars::prelude::*;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use tokio::{sync::mpsc, task};
 
+/// response of a request, tagged with the index of its param set within the partition
+pub type IndexedResponse<T> = (usize, Result<T, CollectError>);
+
 /// collect single partition
 pub async fn collect_partition(
     datatype: MetaDatatype,
@@ -26,7 +29,7 @@ pub async fn fetch_partition<F, Fut, T>(
     source: Arc<Source>,
     inner_request_size: Option<u64>,
     query: Arc<Query>,
-    sender: mpsc::Sender<Result<T, CollectError>>,
+    sender: mpsc::Sender<IndexedResponse<T>>,
 ) -> Result<Vec<tokio::task::JoinHandle<Result<(), CollectError>>>, CollectError>
 where
     F: Copy
@@ -38,13 +41,13 @@ where
     T: Send + 'static,
 {
     let mut handles = Vec::new();
-    for rpc_params in partition.param_sets(inner_request_size)?.into_iter() {
+    for (index, rpc_params) in partition.param_sets(inner_request_size)?.into_iter().enumerate() {
         let sender = sender.clone();
         let source = source.clone();
         let query = query.clone();
         let handle = task::spawn(async move {
             let result = f_request(rpc_params, source.clone(), query.clone()).await;
-            match sender.send(result).await {
+            match sender.send((index, result)).await {
                 Ok(_) => Ok(()),
                 Err(_) => Err(CollectError::CollectError("tokio mpsc send failure".to_string())),
             }
@@ -55,6 +58,41 @@ where
     Ok(handles)
 }
 
+/// receives responses and yields them in param set order, regardless of completion order
+///
+/// this keeps the row order of a partition identical across runs, even when output is not sorted
+pub(crate) struct OrderedReceiver<T> {
+    receiver: mpsc::Receiver<IndexedResponse<T>>,
+    next: usize,
+    pending: BTreeMap<usize, Result<T, CollectError>>,
+}
+
+impl<T> OrderedReceiver<T> {
+    pub(crate) fn new(receiver: mpsc::Receiver<IndexedResponse<T>>) -> Self {
+        OrderedReceiver { receiver, next: 0, pending: BTreeMap::new() }
+    }
+
+    pub(crate) async fn recv(&mut self) -> Option<Result<T, CollectError>> {
+        loop {
+            if let Some(response) = self.pending.remove(&self.next) {
+                self.next += 1;
+                return Some(response)
+            }
+            match self.receiver.recv().await {
+                Some((index, response)) => {
+                    self.pending.insert(index, response);
+                }
+                None => {
+                    // channel closed with gaps (e.g. a failed task), drain remaining in order
+                    let index = *self.pending.keys().next()?;
+                    self.next = index + 1;
+                    return self.pending.remove(&index)
+                }
+            }
+        }
+    }
+}
+
 pub(crate) async fn join_partition_handles(
     handles: Vec<tokio::task::JoinHandle<Result<(), CollectError>>>,
 ) -> Result<(), CollectError> {
@@ -68,3 +106,39 @@ pub(crate) async fn join_partition_handles(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn receive_out_of_order(n: usize) -> Vec<usize> {
+        let (sender, receiver) = mpsc::channel(1);
+        let mut handles = Vec::new();
+        for index in 0..n {
+            let sender = sender.clone();
+            handles.push(task::spawn(async move {
+                // later requests complete first
+                let delay = ((n - index) * 7 % 5) as u64;
+                tokio::time::sleep(std::time::Duration::from_millis(delay)).await;
+                sender.send((index, Ok(index))).await.unwrap();
+            }));
+        }
+        drop(sender);
+
+        let mut receiver = OrderedReceiver::new(receiver);
+        let mut received = Vec::new();
+        while let Some(response) = receiver.recv().await {
+            received.push(response.unwrap());
+        }
+        futures::future::join_all(handles).await;
+        received
+    }
+
+    #[tokio::test]
+    async fn test_ordered_receiver_is_reproducible() {
+        let first = receive_out_of_order(20).await;
+        let second = receive_out_of_order(20).await;
+        assert_eq!(first, (0..20).collect::<Vec<_>>());
+        assert_eq!(first, second);
+    }
+}
@@ -110,4 +110,58 @@ pub trait Dataset: Sync + Send {
     fn arg_aliases() -> Option<HashMap<Dim, Dim>> {
         None
     }
+
+    /// transform dataframe after it is created and sorted, before it is written
+    fn post_process(df: DataFrame, _schema: &Table) -> Result<DataFrame, CollectError> {
+        Ok(df)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use polars::prelude::*;
+    use std::collections::HashMap;
+
+    #[cryo_to_df::to_df(Datatype::Blocks)]
+    #[derive(Default)]
+    struct GasUsed {
+        n_rows: u64,
+        block_number: Vec<u32>,
+        gas_used: Vec<u64>,
+        chain_id: Vec<u64>,
+    }
+
+    impl Dataset for GasUsed {
+        fn post_process(mut df: DataFrame, _schema: &Table) -> Result<DataFrame, CollectError> {
+            let gas_used = df.column("gas_used").map_err(CollectError::PolarsError)?;
+            let gas_used = gas_used.cast(&DataType::Float64).map_err(CollectError::PolarsError)?;
+            let mut kgas_used = &gas_used / 1000.0;
+            kgas_used.rename("kgas_used");
+            df.with_column(kgas_used).map_err(CollectError::PolarsError)?;
+            Ok(df)
+        }
+    }
+
+    #[test]
+    fn test_post_process_adds_computed_column_after_sort() {
+        let columns = Some(vec!["block_number".to_string(), "gas_used".to_string()]);
+        let sort = Some(vec!["block_number".to_string()]);
+        let schema = Datatype::Blocks
+            .table_schema(&[], &ColumnEncoding::Binary, &None, &None, &columns, sort, None)
+            .unwrap();
+        let schemas: HashMap<Datatype, Table> = [(Datatype::Blocks, schema)].into_iter().collect();
+        let gas_used = GasUsed {
+            n_rows: 2,
+            block_number: vec![2, 1],
+            gas_used: vec![3000, 1500],
+            ..Default::default()
+        };
+
+        let df = gas_used.create_dfs(&schemas, 1).unwrap().remove(&Datatype::Blocks).unwrap();
+        assert_eq!(df.get_column_names(), vec!["block_number", "gas_used", "kgas_used"]);
+        let kgas_used: Vec<_> =
+            df.column("kgas_used").unwrap().f64().unwrap().into_iter().collect();
+        assert_eq!(kgas_used, vec![Some(1.5), Some(3.0)]);
+    }
 }
@@ -0,0 +1,157 @@
+use crate::{err, CollectError, Table};
+use polars::prelude::*;
+use std::collections::HashSet;
+
+/// rename columns of dataframe to the output names given by the column aliases of schema
+///
+/// every column is renamed in one pass, so that aliases may swap or chain names
+pub(crate) fn alias_columns(df: &mut DataFrame, schema: &Table) -> Result<(), CollectError> {
+    if schema.column_aliases.is_none() {
+        return Ok(())
+    }
+    let names: Vec<String> =
+        df.get_column_names().iter().map(|column| schema.output_column_name(column)).collect();
+    let mut unique = HashSet::new();
+    if let Some(name) = names.iter().find(|name| !unique.insert(*name)) {
+        return Err(err(&format!("column alias collides with column: {}", name)))
+    }
+    df.set_column_names(&names)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ColumnEncoding, Datatype, U256Type};
+
+    #[test]
+    fn test_alias_columns_renames_before_writing() {
+        let columns = Some(vec!["block_number".to_string(), "address".to_string()]);
+        let mut schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &columns,
+                None,
+                None,
+            )
+            .unwrap();
+        let aliases = vec![
+            ("address".to_string(), "emitter".to_string()),
+            ("transaction_hash".to_string(), "tx_hash".to_string()),
+        ];
+        schema.set_column_aliases(&aliases).unwrap();
+        assert_eq!(schema.output_name("address"), "emitter");
+        assert_eq!(schema.output_name("block_number"), "block_number");
+
+        let mut df = DataFrame::new(vec![
+            Series::new("block_number", vec![1u32, 2]),
+            Series::new("address", vec![vec![0u8; 20], vec![1u8; 20]]),
+        ])
+        .unwrap();
+        alias_columns(&mut df, &schema).unwrap();
+        assert_eq!(df.get_column_names(), vec!["block_number", "emitter"]);
+
+        // aliases must not collide with other output columns
+        let aliases = vec![("address".to_string(), "block_number".to_string())];
+        assert!(schema.set_column_aliases(&aliases).is_err());
+        let aliases = vec![
+            ("address".to_string(), "x".to_string()),
+            ("block_number".to_string(), "x".to_string()),
+        ];
+        assert!(schema.set_column_aliases(&aliases).is_err());
+    }
+
+    #[test]
+    fn test_aliases_swap_names_and_rename_u256_and_event_columns() {
+        let mut schema = Datatype::Erc20Transfers
+            .table_schema(
+                &[U256Type::Binary, U256Type::F64],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &None,
+                None,
+                None,
+            )
+            .unwrap();
+        let aliases = vec![
+            ("from_address".to_string(), "to_address".to_string()),
+            ("to_address".to_string(), "from_address".to_string()),
+            ("value".to_string(), "amount".to_string()),
+            ("event__wad".to_string(), "wad".to_string()),
+        ];
+        schema.set_column_aliases(&aliases).unwrap();
+
+        let mut df = DataFrame::new(vec![
+            Series::new("from_address", vec![vec![1u8; 20]]),
+            Series::new("to_address", vec![vec![2u8; 20]]),
+            Series::new("value_binary", vec![vec![3u8; 32]]),
+            Series::new("value_f64", vec![3.0f64]),
+            Series::new("event__wad_f64", vec![4.0f64]),
+        ])
+        .unwrap();
+        alias_columns(&mut df, &schema).unwrap();
+        assert_eq!(
+            df.get_column_names(),
+            vec!["to_address", "from_address", "amount_binary", "amount_f64", "wad_f64"]
+        );
+        assert!(df
+            .column("to_address")
+            .unwrap()
+            .equals(&Series::new("to_address", vec![vec![1u8; 20]])));
+        let output_names: Vec<String> =
+            schema.output_columns().into_iter().map(|(name, _)| name).collect();
+        assert!(output_names.contains(&"amount_f64".to_string()));
+
+        // an alias colliding with an expanded u256 column or an event alias is rejected
+        let aliases = vec![("block_number".to_string(), "value_f64".to_string())];
+        let mut unaliased = schema.clone();
+        assert!(unaliased.set_column_aliases(&[]).is_ok());
+        assert!(unaliased.set_column_aliases(&aliases).is_err());
+        let aliases = vec![
+            ("block_number".to_string(), "x".to_string()),
+            ("event__wad".to_string(), "x".to_string()),
+        ];
+        assert!(schema.set_column_aliases(&aliases).is_err());
+        assert_eq!(schema.output_name("value"), "amount");
+    }
+
+    #[test]
+    fn test_aliases_rename_added_row_id_and_bucket_columns() {
+        let columns = Some(vec!["block_number".to_string()]);
+        let mut schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &columns,
+                None,
+                None,
+            )
+            .unwrap();
+        schema.row_id = true;
+        schema.block_bucket_size = Some(10);
+        let aliases = vec![
+            ("row_id".to_string(), "id".to_string()),
+            ("block_bucket".to_string(), "bucket".to_string()),
+        ];
+        schema.set_column_aliases(&aliases).unwrap();
+        let output_names: Vec<String> =
+            schema.output_columns().into_iter().map(|(name, _)| name).collect();
+        assert_eq!(output_names, vec!["block_number", "id", "bucket"]);
+
+        let mut df = DataFrame::new(vec![Series::new("block_number", vec![1u32, 12])]).unwrap();
+        crate::row_ids::add_row_id_column(&mut df, 0).unwrap();
+        crate::dataframes::add_block_bucket_column(&mut df, &schema).unwrap();
+        alias_columns(&mut df, &schema).unwrap();
+        assert_eq!(df.get_column_names(), output_names);
+
+        // aliases of added columns must not collide either
+        let aliases = vec![("row_id".to_string(), "block_number".to_string())];
+        assert!(schema.set_column_aliases(&aliases).is_err());
+    }
+}
@@ -0,0 +1,193 @@
+use crate::{err, CollectError, Table};
+use polars::prelude::*;
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+
+/// name of the partition column stored in directory names rather than in files
+pub(crate) const BUCKET_COLUMN: &str = "block_number_bucket";
+
+/// name of the integer bucket column added by a block bucket size
+pub(crate) const BLOCK_BUCKET_COLUMN: &str = "block_bucket";
+
+/// add a block_bucket column of block_number / block_bucket_size, if schema has a bucket size
+///
+/// unlike hive buckets, which are stored in directory names, the bucket is stored in each file
+/// as an integer partition key
+pub(crate) fn add_block_bucket_column(
+    df: &mut DataFrame,
+    schema: &Table,
+) -> Result<(), CollectError> {
+    let bucket_size = match schema.block_bucket_size {
+        Some(0) => return Err(err("block bucket size must be greater than zero")),
+        Some(bucket_size) => bucket_size,
+        None => return Ok(()),
+    };
+    let block_numbers = df
+        .column("block_number")
+        .map_err(|_| err("block_bucket requires a block_number column"))?
+        .cast(&DataType::UInt64)?;
+    let buckets: Vec<Option<u32>> = block_numbers
+        .u64()?
+        .into_iter()
+        .map(|block_number| block_number.map(|block_number| (block_number / bucket_size) as u32))
+        .collect();
+    df.with_column(Series::new(BLOCK_BUCKET_COLUMN, buckets))?;
+    Ok(())
+}
+
+/// bucket of bucket_size blocks that contains block_number, block_number / bucket_size
+pub(crate) fn block_bucket(block_number: u64, bucket_size: u64) -> u64 {
+    block_number / bucket_size
+}
+
+/// split dataframe into one dataframe per block_number bucket, keyed by the bucket
+///
+/// rows are split by a block_number_bucket column of block_number / bucket_size, which is then
+/// dropped, since hive layouts store it in directory names rather than in files
+pub(crate) fn split_by_block_bucket(
+    df: &DataFrame,
+    bucket_size: u64,
+) -> Result<Vec<(u64, DataFrame)>, CollectError> {
+    if bucket_size == 0 {
+        return Err(err("bucket size must be greater than zero"))
+    }
+    let buckets = df
+        .column("block_number")
+        .map_err(|_| err("bucketing by block requires a block_number column"))?
+        .cast(&DataType::UInt64)?
+        .u64()?
+        .into_iter()
+        .map(|block_number| match block_number {
+            Some(block_number) => Ok(block_bucket(block_number, bucket_size)),
+            None => Err(err("cannot bucket rows with null block_number")),
+        })
+        .collect::<Result<Vec<u64>, CollectError>>()?;
+    let mut bucketed = df.clone();
+    bucketed.with_column(Series::new(BUCKET_COLUMN, &buckets))?;
+
+    let mut output = Vec::new();
+    for bucket in buckets.iter().collect::<BTreeSet<_>>() {
+        let mask = bucketed.column(BUCKET_COLUMN)?.equal(*bucket)?;
+        let bucket_df = bucketed.filter(&mask)?.drop(BUCKET_COLUMN)?;
+        output.push((*bucket, bucket_df));
+    }
+    Ok(output)
+}
+
+/// directory name of a block_number bucket, e.g. `block_number_bucket=1000000`
+pub(crate) fn bucket_dirname(bucket: u64) -> String {
+    format!("{}={}", BUCKET_COLUMN, bucket)
+}
+
+/// path of file within the directory of its bucket
+///
+/// if the file is already inside a bucket directory, that directory is replaced
+pub(crate) fn bucket_path(path: &Path, bucket: u64) -> Result<PathBuf, CollectError> {
+    let (parent, filename) = match (path.parent(), path.file_name()) {
+        (Some(parent), Some(filename)) => (parent, filename),
+        _ => return Err(err("invalid output path")),
+    };
+    let in_bucket_dir = parent
+        .file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name.starts_with(&format!("{}=", BUCKET_COLUMN)))
+        .unwrap_or(false);
+    let parent = match (in_bucket_dir, parent.parent()) {
+        (true, Some(grandparent)) => grandparent,
+        _ => parent,
+    };
+    let bucket_dir = parent.join(bucket_dirname(bucket));
+    std::fs::create_dir_all(&bucket_dir).map_err(|_| err("could not create bucket directory"))?;
+    Ok(bucket_dir.join(filename))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dataframes::df_to_file, ColumnEncoding, Datatype, FileOutput, U256Type};
+
+    #[test]
+    fn test_block_bucket_column() {
+        let columns = Some(vec!["block_number".to_string(), "log_index".to_string()]);
+        let mut schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &columns,
+                None,
+                None,
+            )
+            .unwrap();
+        let mut df = DataFrame::new(vec![
+            Series::new("block_number", vec![999u32, 1000, 1500, 2000]),
+            Series::new("log_index", vec![0u32, 0, 0, 0]),
+        ])
+        .unwrap();
+
+        // opt-in, no column without a bucket size
+        add_block_bucket_column(&mut df, &schema).unwrap();
+        assert!(df.column(BLOCK_BUCKET_COLUMN).is_err());
+
+        schema.block_bucket_size = Some(1000);
+        add_block_bucket_column(&mut df, &schema).unwrap();
+        let buckets = df.column(BLOCK_BUCKET_COLUMN).unwrap();
+        assert_eq!(buckets.dtype(), &DataType::UInt32);
+        assert!(buckets.equals(&Series::new(BLOCK_BUCKET_COLUMN, vec![0u32, 1, 1, 2])));
+
+        schema.block_bucket_size = Some(0);
+        assert!(add_block_bucket_column(&mut df, &schema).is_err());
+    }
+
+    #[test]
+    fn test_hive_layout_writes_bucket_directories() {
+        let output_dir =
+            std::env::temp_dir().join(format!("cryo_test_hive_buckets_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&output_dir);
+        std::fs::create_dir_all(&output_dir).unwrap();
+        let sink = FileOutput {
+            output_dir: output_dir.clone(),
+            overwrite: true,
+            hive_bucket_size: Some(1000),
+            ..Default::default()
+        };
+
+        let df = DataFrame::new(vec![
+            Series::new("block_number", vec![998u32, 999, 1000, 1001, 1002]),
+            Series::new("log_index", vec![0u32, 0, 0, 1, 0]),
+        ])
+        .unwrap();
+        let path =
+            output_dir.join(bucket_dirname(0)).join("ethereum__logs__00000998_to_00001002.parquet");
+        let buckets = split_by_block_bucket(&df, 1000).unwrap();
+        let keys: Vec<u64> = buckets.iter().map(|(bucket, _)| *bucket).collect();
+        assert_eq!(keys, vec![0, 1]);
+        assert!(buckets
+            .iter()
+            .all(|(_, bucket_df)| bucket_df.get_column_names() == df.get_column_names()));
+        for (bucket, mut bucket_df) in split_by_block_bucket(&df, 1000).unwrap() {
+            df_to_file(&mut bucket_df, &bucket_path(&path, bucket).unwrap(), &sink).unwrap();
+        }
+
+        let mut dirs: Vec<_> = std::fs::read_dir(&output_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        dirs.sort();
+        assert_eq!(dirs, vec!["block_number_bucket=0", "block_number_bucket=1"]);
+
+        let filename = "ethereum__logs__00000998_to_00001002.parquet";
+        let first = output_dir.join("block_number_bucket=0").join(filename);
+        let second = output_dir.join("block_number_bucket=1").join(filename);
+        let first = ParquetReader::new(std::fs::File::open(first).unwrap()).finish().unwrap();
+        let second = ParquetReader::new(std::fs::File::open(second).unwrap()).finish().unwrap();
+        assert_eq!(first.height(), 2);
+        assert_eq!(second.height(), 3);
+        assert!(first.column(BUCKET_COLUMN).is_err() && second.column(BUCKET_COLUMN).is_err());
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+}
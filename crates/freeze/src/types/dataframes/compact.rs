@@ -0,0 +1,88 @@
+use crate::{CollectError, Table};
+use polars::prelude::*;
+
+const TOPIC_COLUMNS: [&str; 7] = [
+    "topic0",
+    "topic1",
+    "topic1_is_address",
+    "topic2",
+    "topic2_is_address",
+    "topic3",
+    "topic3_is_address",
+];
+
+/// drop topic columns that are null in every row, if schema uses compact topics
+///
+/// empty dataframes keep all of their columns so that empty files retain the full schema
+pub(crate) fn drop_null_topic_columns(
+    df: &mut DataFrame,
+    schema: &Table,
+) -> Result<(), CollectError> {
+    if !schema.compact_topics || df.height() == 0 {
+        return Ok(())
+    }
+    for column in TOPIC_COLUMNS.iter() {
+        let all_null = match df.column(column) {
+            Ok(series) => series.null_count() == series.len(),
+            Err(_) => false,
+        };
+        if all_null {
+            let _ = df.drop_in_place(column)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ColumnEncoding, Datatype, U256Type};
+
+    #[test]
+    fn test_two_topic_logs_omit_topic2_and_topic3() {
+        let columns = Some(
+            ["block_number", "topic0", "topic1", "topic2", "topic3"]
+                .iter()
+                .map(|column| column.to_string())
+                .collect(),
+        );
+        let mut schema = Datatype::Logs
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &columns,
+                None,
+                None,
+            )
+            .unwrap();
+        let topic = Some(vec![1u8; 32]);
+        let df = DataFrame::new(vec![
+            Series::new("block_number", vec![1u64, 2]),
+            Series::new("topic0", vec![topic.clone(), topic.clone()]),
+            Series::new("topic1", vec![topic.clone(), topic]),
+            Series::new("topic2", vec![None::<Vec<u8>>, None]),
+            Series::new("topic3", vec![None::<Vec<u8>>, None]),
+        ])
+        .unwrap();
+
+        // fixed schema by default
+        let mut fixed = df.clone();
+        drop_null_topic_columns(&mut fixed, &schema).unwrap();
+        assert_eq!(
+            fixed.get_column_names(),
+            vec!["block_number", "topic0", "topic1", "topic2", "topic3"]
+        );
+
+        schema.compact_topics = true;
+        let mut compact = df.clone();
+        drop_null_topic_columns(&mut compact, &schema).unwrap();
+        assert_eq!(compact.get_column_names(), vec!["block_number", "topic0", "topic1"]);
+
+        // empty files keep the full schema
+        let mut empty = df.head(Some(0));
+        drop_null_topic_columns(&mut empty, &schema).unwrap();
+        assert_eq!(empty.width(), 5);
+    }
+}
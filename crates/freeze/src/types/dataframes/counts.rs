@@ -0,0 +1,77 @@
+use crate::FileError;
+use std::path::{Path, PathBuf};
+
+/// path of the row count sidecar written beside an output file
+pub fn row_count_path(path: &Path) -> PathBuf {
+    let mut count_path = path.as_os_str().to_owned();
+    count_path.push(".count");
+    PathBuf::from(count_path)
+}
+
+/// write number of rows of an output file to its row count sidecar, once the file is in place
+pub(crate) fn write_row_count(filename: &Path, n_rows: usize) -> Result<(), FileError> {
+    std::fs::write(row_count_path(filename), format!("{}\n", n_rows))
+        .map_err(|_e| FileError::FileWriteError)
+}
+
+/// number of rows of an output file, read from its row count sidecar
+pub fn read_row_count(path: &Path) -> Result<u64, FileError> {
+    let contents = std::fs::read_to_string(row_count_path(path))
+        .map_err(|_e| FileError::NoFilePathError("could not read row count file".to_string()))?;
+    contents
+        .trim()
+        .parse()
+        .map_err(|_e| FileError::NoFilePathError("invalid row count file".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dataframes::{df_to_file, BatchedParquetWriter},
+        FileOutput,
+    };
+    use polars::prelude::*;
+
+    #[test]
+    fn test_row_count_sidecar_beside_each_output() {
+        let output_dir =
+            std::env::temp_dir().join(format!("cryo_test_row_counts_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&output_dir);
+        std::fs::create_dir_all(&output_dir).unwrap();
+        let sink = FileOutput {
+            output_dir: output_dir.clone(),
+            overwrite: true,
+            row_count_sidecar: true,
+            ..Default::default()
+        };
+        let df = DataFrame::new(vec![Series::new("block_number", vec![1u32, 2, 3])]).unwrap();
+
+        let path = output_dir.join("ethereum__blocks__00000000_to_00000999.parquet");
+        df_to_file(&mut df.clone(), &path, &sink).unwrap();
+        assert_eq!(read_row_count(&path).unwrap(), 3);
+        assert_eq!(
+            std::fs::read_to_string(
+                output_dir.join("ethereum__blocks__00000000_to_00000999.parquet.count")
+            )
+            .unwrap(),
+            "3\n"
+        );
+
+        // files written in batches count the rows of every batch
+        let batched_path = output_dir.join("ethereum__logs__00000000_to_00000999.parquet");
+        let mut writer = BatchedParquetWriter::new(&batched_path, &df.schema(), &sink).unwrap();
+        writer.write_batch(&df).unwrap();
+        writer.write_batch(&df).unwrap();
+        writer.finish().unwrap();
+        assert_eq!(read_row_count(&batched_path).unwrap(), 6);
+
+        // sidecars are opt-in
+        let plain_path = output_dir.join("plain.parquet");
+        let plain_sink = FileOutput { row_count_sidecar: false, ..sink };
+        df_to_file(&mut df.clone(), &plain_path, &plain_sink).unwrap();
+        assert!(!row_count_path(&plain_path).exists());
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+}
@@ -0,0 +1,174 @@
+use crate::{err, CollectError};
+use chrono::{Days, NaiveDate};
+use polars::prelude::*;
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+
+/// columns checked, in order, for the block timestamps used to route rows to days
+const TIMESTAMP_COLUMNS: [&str; 2] = ["block_timestamp", "timestamp"];
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// first and last block of the rows of df, None if it has no rows or no block_number column
+pub(crate) fn rows_block_range(df: &DataFrame) -> Result<Option<(u64, u64)>, CollectError> {
+    let block_numbers = match df.column("block_number") {
+        Ok(block_numbers) => block_numbers.cast(&DataType::UInt64)?,
+        Err(_) => return Ok(None),
+    };
+    let block_numbers = block_numbers.u64()?;
+    Ok(block_numbers.min().zip(block_numbers.max()))
+}
+
+/// split dataframe into one dataframe per UTC calendar day of its block timestamps
+pub(crate) fn split_by_day(df: &DataFrame) -> Result<Vec<(String, DataFrame)>, CollectError> {
+    let timestamps = TIMESTAMP_COLUMNS
+        .iter()
+        .find_map(|column| df.column(column).ok())
+        .ok_or_else(|| err("splitting by day requires a block_timestamp or timestamp column"))?;
+    let days = timestamps
+        .u32()?
+        .into_iter()
+        .map(|timestamp| match timestamp {
+            Some(timestamp) => utc_day(timestamp),
+            None => Err(err("cannot split rows with null timestamp by day")),
+        })
+        .collect::<Result<Vec<String>, CollectError>>()?;
+
+    let mut output = Vec::new();
+    for day in days.iter().collect::<BTreeSet<_>>() {
+        let mask = Series::new("mask", days.iter().map(|d| d == day).collect::<Vec<bool>>());
+        output.push((day.clone(), df.filter(mask.bool()?)?));
+    }
+    Ok(output)
+}
+
+/// path of file within the `date=YYYY-MM-DD` subdirectory of its directory
+pub(crate) fn day_path(path: &Path, day: &str) -> Result<PathBuf, CollectError> {
+    let (parent, filename) = match (path.parent(), path.file_name()) {
+        (Some(parent), Some(filename)) => (parent, filename),
+        _ => return Err(err("invalid output path")),
+    };
+    let day_dir = parent.join(format!("date={}", day));
+    std::fs::create_dir_all(&day_dir).map_err(|_| err("could not create day directory"))?;
+    Ok(day_dir.join(filename))
+}
+
+/// path of manifest listing the day files written for path, within the .cryo dir of output_dir
+///
+/// day files cannot be predicted before collecting the timestamps of their rows, so runs record
+/// which were written once all of them are in place, including when a partition has no rows
+pub(crate) fn day_manifest_path(output_dir: &Path, path: &Path) -> PathBuf {
+    let relative = path.strip_prefix(output_dir).unwrap_or(path);
+    let mut manifest = output_dir.join(".cryo/days").join(relative).into_os_string();
+    manifest.push(".days");
+    PathBuf::from(manifest)
+}
+
+/// record the day files written for path, one path per line relative to output_dir
+pub(crate) fn write_day_manifest(
+    output_dir: &Path,
+    path: &Path,
+    day_paths: &[PathBuf],
+) -> Result<(), CollectError> {
+    let manifest = day_manifest_path(output_dir, path);
+    if let Some(parent) = manifest.parent() {
+        std::fs::create_dir_all(parent).map_err(|_| err("could not create day manifest dir"))?;
+    }
+    let contents: String = day_paths
+        .iter()
+        .map(|day_path| {
+            format!("{}\n", day_path.strip_prefix(output_dir).unwrap_or(day_path).display())
+        })
+        .collect();
+    let tmp_manifest = manifest.with_extension("days_tmp");
+    std::fs::write(&tmp_manifest, contents).map_err(|_| err("could not write day manifest"))?;
+    std::fs::rename(&tmp_manifest, &manifest).map_err(|_| err("could not write day manifest"))
+}
+
+/// whether an earlier run wrote all day files of path, according to its manifest
+pub(crate) fn day_files_exist(output_dir: &Path, path: &Path) -> bool {
+    match std::fs::read_to_string(day_manifest_path(output_dir, path)) {
+        Ok(contents) => contents.lines().all(|line| output_dir.join(line).exists()),
+        Err(_) => false,
+    }
+}
+
+/// UTC calendar day of a unix timestamp, formatted as YYYY-MM-DD
+fn utc_day(timestamp: u32) -> Result<String, CollectError> {
+    NaiveDate::from_ymd_opt(1970, 1, 1)
+        .and_then(|epoch| epoch.checked_add_days(Days::new(timestamp as u64 / SECONDS_PER_DAY)))
+        .map(|date| date.format("%Y-%m-%d").to_string())
+        .ok_or_else(|| err("could not convert timestamp to date"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dataframes::df_to_file, FileOutput};
+
+    #[test]
+    fn test_partition_spanning_day_boundary_writes_two_day_files() {
+        let output_dir =
+            std::env::temp_dir().join(format!("cryo_test_split_by_day_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&output_dir);
+        std::fs::create_dir_all(&output_dir).unwrap();
+        let sink = FileOutput {
+            output_dir: output_dir.clone(),
+            overwrite: true,
+            split_by_day: true,
+            ..Default::default()
+        };
+
+        // 2023-01-01 23:59:59 UTC and 2023-01-02 00:00:00 UTC
+        let df = DataFrame::new(vec![
+            Series::new("block_number", vec![100u32, 101, 102]),
+            Series::new("block_timestamp", vec![1672617599u32, 1672617599, 1672617600]),
+        ])
+        .unwrap();
+        let path = output_dir.join("ethereum__logs__00000100_to_00000102.parquet");
+        let days = split_by_day(&df).unwrap();
+        for (day, mut day_df) in days.into_iter() {
+            df_to_file(&mut day_df, &day_path(&path, &day).unwrap(), &sink).unwrap();
+        }
+
+        let first = output_dir.join("date=2023-01-01/ethereum__logs__00000100_to_00000102.parquet");
+        let second =
+            output_dir.join("date=2023-01-02/ethereum__logs__00000100_to_00000102.parquet");
+        let first = ParquetReader::new(std::fs::File::open(first).unwrap()).finish().unwrap();
+        let second = ParquetReader::new(std::fs::File::open(second).unwrap()).finish().unwrap();
+        assert_eq!(first.height(), 2);
+        assert_eq!(second.height(), 1);
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[test]
+    fn test_day_manifest_records_written_day_files() {
+        let output_dir =
+            std::env::temp_dir().join(format!("cryo_test_day_manifest_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&output_dir);
+        let path = output_dir.join("logs/ethereum__logs__00000100_to_00000102.parquet");
+        assert!(!day_files_exist(&output_dir, &path));
+
+        // a partition without rows writes no day files but is still recorded as written
+        let empty = output_dir.join("logs/ethereum__logs__00000103_to_00000105.parquet");
+        write_day_manifest(&output_dir, &empty, &[]).unwrap();
+        assert!(day_files_exist(&output_dir, &empty));
+
+        let day_file = day_path(&path, "2023-01-01").unwrap();
+        std::fs::write(&day_file, "").unwrap();
+        write_day_manifest(&output_dir, &path, &[day_file.clone()]).unwrap();
+        assert!(
+            day_manifest_path(&output_dir, &path).starts_with(output_dir.join(".cryo/days/logs"))
+        );
+        assert!(day_files_exist(&output_dir, &path));
+
+        // removing a day file means the partition must be collected again
+        std::fs::remove_file(&day_file).unwrap();
+        assert!(!day_files_exist(&output_dir, &path));
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+
+use ethers::prelude::U256;
+use polars::prelude::*;
+
+use crate::types::FileError;
+
+/// suffixes of the u256 representations that hold exact integers
+const EXACT_U256_SUFFIXES: [&str; 4] = ["_binary", "_string", "_u32", "_u64"];
+
+/// render columns of df as exact decimal strings scaled down by 10^decimals of their column,
+/// e.g. 1500000000000000000 with 18 decimals renders as 1.5
+///
+/// this is a presentation-only transform used by the text formats, parquet files keep the raw
+/// integers. the decimals of a column also apply to its exact u256 representations, float
+/// representations are left as they are since their precision is already lost
+pub(crate) fn scale_decimal_columns(
+    df: &DataFrame,
+    decimals: &HashMap<String, u8>,
+) -> Result<DataFrame, FileError> {
+    let mut scaled = df.clone();
+    for name in df.get_column_names() {
+        let n_decimals = match column_decimals(name, decimals) {
+            Some(n_decimals) => n_decimals,
+            None => continue,
+        };
+        let column = df.column(name).map_err(|_e| FileError::FileWriteError)?;
+        let series = scale_series(column, n_decimals)?;
+        scaled.replace(name, series).map_err(|_e| FileError::FileWriteError)?;
+    }
+    Ok(scaled)
+}
+
+fn column_decimals(name: &str, decimals: &HashMap<String, u8>) -> Option<u8> {
+    if let Some(n_decimals) = decimals.get(name) {
+        return Some(*n_decimals)
+    }
+    EXACT_U256_SUFFIXES
+        .iter()
+        .filter_map(|suffix| name.strip_suffix(suffix))
+        .find_map(|base| decimals.get(base).copied())
+}
+
+fn scale_series(series: &Series, decimals: u8) -> Result<Series, FileError> {
+    let integers: Vec<Option<String>> = match series.dtype() {
+        DataType::Utf8 => series
+            .utf8()
+            .map_err(|_e| FileError::FileWriteError)?
+            .into_iter()
+            .map(|value| value.map(parse_integer).transpose())
+            .collect::<Result<_, _>>()?,
+        DataType::Binary => series
+            .binary()
+            .map_err(|_e| FileError::FileWriteError)?
+            .into_iter()
+            .map(|value| {
+                value
+                    .map(|bytes| match bytes.len() {
+                        0..=32 => Ok(U256::from_big_endian(bytes).to_string()),
+                        _ => Err(FileError::FileWriteError),
+                    })
+                    .transpose()
+            })
+            .collect::<Result<_, _>>()?,
+        DataType::UInt32 | DataType::UInt64 => series
+            .cast(&DataType::UInt64)
+            .and_then(|series| Ok(series.u64()?.into_iter().collect::<Vec<_>>()))
+            .map_err(|_e| FileError::FileWriteError)?
+            .into_iter()
+            .map(|value| value.map(|value| value.to_string()))
+            .collect(),
+        _ => return Err(FileError::FileWriteError),
+    };
+    let scaled: Vec<Option<String>> = integers
+        .into_iter()
+        .map(|value| value.map(|value| scale_integer(&value, decimals)))
+        .collect();
+    Ok(Series::new(series.name(), scaled))
+}
+
+/// decimal digits of an integer given in decimal, with optional sign, or in 0x-prefixed hex
+fn parse_integer(value: &str) -> Result<String, FileError> {
+    if let Some(hex) = value.strip_prefix("0x") {
+        return U256::from_str_radix(hex, 16)
+            .map(|value| value.to_string())
+            .map_err(|_e| FileError::FileWriteError)
+    }
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(FileError::FileWriteError)
+    }
+    Ok(value.to_string())
+}
+
+/// place the decimal point of an integer decimals digits from the right, dropping trailing zeros
+/// of the fraction so that the result is exact without padding
+fn scale_integer(value: &str, decimals: u8) -> String {
+    let (sign, digits) = match value.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", value),
+    };
+    let decimals = decimals as usize;
+    let digits = format!("{:0>width$}", digits, width = decimals + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - decimals);
+    match fraction.trim_end_matches('0') {
+        "" => format!("{}{}", sign, whole),
+        fraction => format!("{}{}.{}", sign, whole, fraction),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CsvOutput, OutputFormat};
+
+    #[test]
+    fn test_scale_integer_is_exact() {
+        assert_eq!(scale_integer("1500000000000000000", 18), "1.5");
+        assert_eq!(scale_integer("1000000000000000000", 18), "1");
+        assert_eq!(scale_integer("1", 18), "0.000000000000000001");
+        assert_eq!(scale_integer("-2500", 3), "-2.5");
+        assert_eq!(scale_integer("0", 6), "0");
+        assert_eq!(scale_integer("42", 0), "42");
+
+        // values beyond f64 precision keep every digit
+        let max = U256::MAX.to_string();
+        assert_eq!(
+            scale_integer(&max, 18),
+            "115792089237316195423570985008687907853269984665640564039457.584007913129639935"
+        );
+    }
+
+    #[test]
+    fn test_decimals_render_only_in_text_output() {
+        let raw = U256::from_dec_str("1234500000000000000000").unwrap();
+        let mut bytes = [0u8; 32];
+        raw.to_big_endian(&mut bytes);
+        let df = DataFrame::new(vec![
+            Series::new("value_binary", vec![bytes.to_vec()]),
+            Series::new("value_string", vec![raw.to_string()]),
+            Series::new("value_f64", vec![1234.5f64]),
+            Series::new("gas_used", vec![21000u64]),
+            Series::new("amount_string", vec!["0x64".to_string()]),
+        ])
+        .unwrap();
+        let decimals = HashMap::from([
+            ("value".to_string(), 18),
+            ("gas_used".to_string(), 3),
+            ("amount".to_string(), 2),
+        ]);
+        let scaled = scale_decimal_columns(&df, &decimals).unwrap();
+        let rendered = |name: &str| scaled.column(name).unwrap().utf8().unwrap().get(0);
+        assert_eq!(rendered("value_binary"), Some("1234.5"));
+        assert_eq!(rendered("value_string"), Some("1234.5"));
+        assert_eq!(rendered("gas_used"), Some("21"));
+        assert_eq!(rendered("amount_string"), Some("1"));
+        assert!(scaled.column("value_f64").unwrap().equals(df.column("value_f64").unwrap()));
+
+        // the csv writer renders scaled values, the dataframe it writes keeps the raw values
+        let path =
+            std::env::temp_dir().join(format!("cryo_test_decimals_{}.csv", std::process::id()));
+        let mut text_df = df.drop("value_binary").unwrap();
+        CsvOutput { column_decimals: Some(decimals) }.write(&mut text_df, &path).unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(contents.lines().nth(1), Some("1234.5,1234.5,21,1"));
+        let raw_value = text_df.column("value_string").unwrap().utf8().unwrap().get(0);
+        assert_eq!(raw_value, Some("1234500000000000000000"));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
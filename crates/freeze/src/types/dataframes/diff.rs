@@ -0,0 +1,77 @@
+use crate::{err, CollectError};
+use polars::prelude::*;
+use std::collections::HashSet;
+
+type LogKey = (Option<u32>, Option<u32>);
+
+/// logs present in only one of two dataframes, keyed on block_number and log_index
+///
+/// an only_in column records which dataframe each log came from, primary or compare
+pub(crate) fn diff_logs(
+    primary: &DataFrame,
+    compare: &DataFrame,
+) -> Result<DataFrame, CollectError> {
+    let primary_keys = log_keys(primary)?;
+    let compare_keys = log_keys(compare)?;
+    let primary_only = rows_missing_from(primary, &primary_keys, &compare_keys, "primary")?;
+    let compare_only = rows_missing_from(compare, &compare_keys, &primary_keys, "compare")?;
+    Ok(primary_only.vstack(&compare_only)?)
+}
+
+fn log_keys(df: &DataFrame) -> Result<Vec<LogKey>, CollectError> {
+    let (block_numbers, log_indices) = match (df.column("block_number"), df.column("log_index")) {
+        (Ok(block_numbers), Ok(log_indices)) => (block_numbers.u32()?, log_indices.u32()?),
+        _ => return Err(err("comparing logs requires block_number and log_index columns")),
+    };
+    Ok(block_numbers.into_iter().zip(log_indices.into_iter()).collect())
+}
+
+/// rows of df whose keys are missing from other_keys, labeled with name
+fn rows_missing_from(
+    df: &DataFrame,
+    keys: &[LogKey],
+    other_keys: &[LogKey],
+    name: &str,
+) -> Result<DataFrame, CollectError> {
+    let other_keys: HashSet<&LogKey> = other_keys.iter().collect();
+    let mask: Vec<bool> = keys.iter().map(|key| !other_keys.contains(key)).collect();
+    let mask = Series::new("mask", mask);
+    let mut rows = df.filter(mask.bool()?)?;
+    let only_in = Series::new("only_in", vec![name; rows.height()]);
+    rows.with_column(only_in)?;
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn logs(keys: &[(u32, u32)]) -> DataFrame {
+        DataFrame::new(vec![
+            Series::new("block_number", keys.iter().map(|key| key.0).collect::<Vec<_>>()),
+            Series::new("log_index", keys.iter().map(|key| key.1).collect::<Vec<_>>()),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_diff_logs_reports_logs_missing_from_either_side() {
+        let primary = logs(&[(1, 0), (1, 1), (2, 0)]);
+        let compare = logs(&[(1, 0), (2, 0), (2, 1), (3, 0)]);
+        let diff = diff_logs(&primary, &compare).unwrap();
+        assert_eq!(diff.get_column_names(), vec!["block_number", "log_index", "only_in"]);
+
+        let block_numbers: Vec<_> =
+            diff.column("block_number").unwrap().u32().unwrap().into_iter().collect();
+        let log_indices: Vec<_> =
+            diff.column("log_index").unwrap().u32().unwrap().into_iter().collect();
+        let only_in = Series::new("only_in", ["primary", "compare", "compare"]);
+        assert_eq!(block_numbers, vec![Some(1), Some(2), Some(3)]);
+        assert_eq!(log_indices, vec![Some(1), Some(1), Some(0)]);
+        assert!(diff.column("only_in").unwrap().equals(&only_in));
+
+        // identical logs produce an empty diff
+        assert_eq!(diff_logs(&primary, &primary).unwrap().height(), 0);
+        assert!(diff_logs(&primary, &primary.select(["block_number"]).unwrap()).is_err());
+    }
+}
@@ -1,8 +1,105 @@
-use std::path::Path;
+use std::{
+    collections::HashMap,
+    io::Write,
+    path::{Path, PathBuf},
+};
 
 use polars::prelude::*;
 
-use crate::types::{FileError, FileOutput};
+use crate::types::{FileError, FileFormat, FileOutput};
+
+/// format used to serialize dataframes into output files
+///
+/// formats defined outside of cryo are used by setting them as the custom_format of FileOutput
+pub trait OutputFormat: Send + Sync + std::fmt::Debug {
+    /// file extension used by the format
+    fn extension(&self) -> &'static str;
+
+    /// write dataframe to path
+    fn write(&self, df: &mut DataFrame, path: &Path) -> Result<(), FileError>;
+}
+
+/// parquet output format
+#[derive(Debug)]
+pub struct ParquetOutput {
+    /// whether to write column statistics
+    pub statistics: bool,
+    /// compression algorithm and level
+    pub compression: ParquetCompression,
+    /// number of rows per row group
+    pub row_group_size: Option<usize>,
+}
+
+/// csv output format
+#[derive(Debug)]
+pub struct CsvOutput {
+    /// decimals that integer columns are scaled down by when rendered, keyed by column
+    pub column_decimals: Option<HashMap<String, u8>>,
+}
+
+/// json output format
+#[derive(Debug)]
+pub struct JsonOutput {
+    /// decimals that integer columns are scaled down by when rendered, keyed by column
+    pub column_decimals: Option<HashMap<String, u8>>,
+}
+
+impl OutputFormat for ParquetOutput {
+    fn extension(&self) -> &'static str {
+        "parquet"
+    }
+
+    fn write(&self, df: &mut DataFrame, path: &Path) -> Result<(), FileError> {
+        let file = std::fs::File::create(path).map_err(|_e| FileError::FileWriteError)?;
+        let result = ParquetWriter::new(file)
+            .with_statistics(self.statistics)
+            .with_compression(self.compression)
+            .with_row_group_size(self.row_group_size)
+            .finish(df);
+        match result {
+            Err(_e) => Err(FileError::FileWriteError),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl OutputFormat for CsvOutput {
+    fn extension(&self) -> &'static str {
+        "csv"
+    }
+
+    fn write(&self, df: &mut DataFrame, path: &Path) -> Result<(), FileError> {
+        let mut scaled = match &self.column_decimals {
+            Some(decimals) => super::scale_decimal_columns(df, decimals)?,
+            None => df.clone(),
+        };
+        let file = std::fs::File::create(path).map_err(|_e| FileError::FileWriteError)?;
+        let result = CsvWriter::new(file).finish(&mut scaled);
+        match result {
+            Err(_e) => Err(FileError::FileWriteError),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl OutputFormat for JsonOutput {
+    fn extension(&self) -> &'static str {
+        "json"
+    }
+
+    fn write(&self, df: &mut DataFrame, path: &Path) -> Result<(), FileError> {
+        let mut scaled = match &self.column_decimals {
+            Some(decimals) => super::scale_decimal_columns(df, decimals)?,
+            None => df.clone(),
+        };
+        let file = std::fs::File::create(path).map_err(|_e| FileError::FileWriteError)?;
+        let result = JsonWriter::new(file).with_json_format(JsonFormat::Json).finish(&mut scaled);
+        match result {
+            Err(_e) => Err(FileError::FileWriteError),
+            _ => Ok(()),
+        }
+    }
+}
 
 /// write polars dataframe to file
 pub(crate) fn df_to_file(
@@ -10,53 +107,220 @@ pub(crate) fn df_to_file(
     filename: &Path,
     file_output: &FileOutput,
 ) -> Result<(), FileError> {
-    let tmp_filename = filename.with_extension("_tmp");
-    let result = match filename.extension().and_then(|ex| ex.to_str()) {
-        Some("parquet") => df_to_parquet(df, &tmp_filename, file_output),
-        Some("csv") => df_to_csv(df, &tmp_filename),
-        Some("json") => df_to_json(df, &tmp_filename),
-        _ => return Err(FileError::FileWriteError),
-    };
-    match result {
-        Ok(()) => std::fs::rename(tmp_filename, filename).map_err(|_e| FileError::FileWriteError),
-        Err(_e) => Err(FileError::FileWriteError),
-    }
+    df_to_file_with_metadata(df, filename, file_output, &[])
 }
 
-/// write polars dataframe to parquet file
-fn df_to_parquet(
+/// write polars dataframe to file, adding metadata entries to parquet files before signing
+pub(crate) fn df_to_file_with_metadata(
     df: &mut DataFrame,
     filename: &Path,
     file_output: &FileOutput,
+    metadata: &[(String, String)],
+) -> Result<(), FileError> {
+    let tmp_filename =
+        file_output.get_temp_path(filename).map_err(|_e| FileError::FileWriteError)?;
+    file_output.output_format().write(df, &tmp_filename)?;
+    if !metadata.is_empty() && file_output.writes_parquet() {
+        super::embed_parquet_metadata(&tmp_filename, metadata)?;
+    }
+    if let Some(signer) = &file_output.signer {
+        super::sign_file(&tmp_filename, filename, signer)?;
+    }
+    move_into_place(&tmp_filename, filename)?;
+    if file_output.row_count_sidecar {
+        super::write_row_count(filename, df.height())?;
+    }
+    Ok(())
+}
+
+/// write polars dataframe to stdout as csv or json lines, with a csv header if header is set
+pub(crate) fn df_to_stdout(
+    df: &DataFrame,
+    file_output: &FileOutput,
+    header: bool,
 ) -> Result<(), FileError> {
-    let file = std::fs::File::create(filename).map_err(|_e| FileError::FileWriteError)?;
-    let result = ParquetWriter::new(file)
-        .with_statistics(file_output.parquet_statistics)
-        .with_compression(file_output.parquet_compression)
-        .with_row_group_size(file_output.row_group_size)
-        .finish(df);
-    match result {
-        Err(_e) => Err(FileError::FileWriteError),
-        _ => Ok(()),
-    }
-}
-
-/// write polars dataframe to csv file
-fn df_to_csv(df: &mut DataFrame, filename: &Path) -> Result<(), FileError> {
-    let file = std::fs::File::create(filename).map_err(|_e| FileError::FileWriteError)?;
-    let result = CsvWriter::new(file).finish(df);
-    match result {
-        Err(_e) => Err(FileError::FileWriteError),
-        _ => Ok(()),
-    }
-}
-
-/// write polars dataframe to json file
-fn df_to_json(df: &mut DataFrame, filename: &Path) -> Result<(), FileError> {
-    let file = std::fs::File::create(filename).map_err(|_e| FileError::FileWriteError)?;
-    let result = JsonWriter::new(file).with_json_format(JsonFormat::Json).finish(df);
-    match result {
-        Err(_e) => Err(FileError::FileWriteError),
-        _ => Ok(()),
+    let stdout = std::io::stdout();
+    let mut writer = std::io::BufWriter::new(stdout.lock());
+    df_to_writer(df, file_output, &mut writer, header)?;
+    writer.flush().map_err(|_e| FileError::FileWriteError)
+}
+
+/// write polars dataframe to writer as csv or json lines, one object per row
+///
+/// binary columns are hex encoded since neither format can hold raw bytes, the csv header is only
+/// written if header is set
+pub(crate) fn df_to_writer<W: Write>(
+    df: &DataFrame,
+    file_output: &FileOutput,
+    writer: W,
+    header: bool,
+) -> Result<(), FileError> {
+    let scaled = match &file_output.column_decimals {
+        Some(decimals) => super::scale_decimal_columns(df, decimals)?,
+        None => df.clone(),
+    };
+    let mut encoded = hex_encode_binary_columns(&scaled)?;
+    let result = match file_output.format {
+        FileFormat::Csv => CsvWriter::new(writer).include_header(header).finish(&mut encoded),
+        FileFormat::Json => {
+            JsonWriter::new(writer).with_json_format(JsonFormat::JsonLines).finish(&mut encoded)
+        }
+        FileFormat::Parquet => return Err(FileError::FileWriteError),
+    };
+    result.map_err(|_e| FileError::FileWriteError)
+}
+
+/// replace binary columns of df with 0x-prefixed hex strings
+fn hex_encode_binary_columns(df: &DataFrame) -> Result<DataFrame, FileError> {
+    let mut encoded = df.clone();
+    for column in df.get_columns().iter().filter(|column| column.dtype() == &DataType::Binary) {
+        let values: Vec<Option<String>> = column
+            .binary()
+            .map_err(|_e| FileError::FileWriteError)?
+            .into_iter()
+            .map(|value| value.map(prefix_hex::encode))
+            .collect();
+        encoded
+            .replace(column.name(), Series::new(column.name(), values))
+            .map_err(|_e| FileError::FileWriteError)?;
+    }
+    Ok(encoded)
+}
+
+/// rename fully written temp file to its output path
+fn move_into_place(tmp_filename: &Path, filename: &Path) -> Result<(), FileError> {
+    if std::fs::rename(tmp_filename, filename).is_ok() {
+        return Ok(())
+    }
+
+    // temp dir may be on another filesystem, copy beside output and rename from there
+    let sibling = filename.with_extension("_tmp");
+    let result = std::fs::copy(tmp_filename, &sibling)
+        .and_then(|_| std::fs::rename(&sibling, filename))
+        .map_err(|_e| FileError::FileWriteError);
+    let _ = std::fs::remove_file(tmp_filename);
+    result
+}
+
+/// parquet writer that appends dataframes as row groups, for outputs too large to build in memory
+///
+/// rows are written to a temp file that is moved to its output path by finish()
+pub(crate) struct BatchedParquetWriter {
+    writer: polars::io::parquet::BatchedWriter<std::fs::File>,
+    tmp_filename: PathBuf,
+    filename: PathBuf,
+    signer: Option<ethers::signers::LocalWallet>,
+    metadata: Vec<(String, String)>,
+    row_count_sidecar: bool,
+    n_rows: usize,
+}
+
+impl BatchedParquetWriter {
+    /// create writer for dataframes with schema
+    pub(crate) fn new(
+        filename: &Path,
+        schema: &Schema,
+        file_output: &FileOutput,
+    ) -> Result<Self, FileError> {
+        let tmp_filename =
+            file_output.get_temp_path(filename).map_err(|_e| FileError::FileWriteError)?;
+        let file = std::fs::File::create(&tmp_filename).map_err(|_e| FileError::FileWriteError)?;
+        let writer = ParquetWriter::new(file)
+            .with_statistics(file_output.parquet_statistics)
+            .with_compression(file_output.parquet_compression)
+            .with_row_group_size(file_output.row_group_size)
+            .batched(schema)
+            .map_err(|_e| FileError::FileWriteError)?;
+        Ok(BatchedParquetWriter {
+            writer,
+            tmp_filename,
+            filename: filename.to_path_buf(),
+            signer: file_output.signer.clone(),
+            metadata: Vec::new(),
+            row_count_sidecar: file_output.row_count_sidecar,
+            n_rows: 0,
+        })
+    }
+
+    /// add metadata entries to the file once it is finished
+    pub(crate) fn with_metadata(mut self, metadata: Vec<(String, String)>) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// append rows of dataframe
+    pub(crate) fn write_batch(&mut self, df: &DataFrame) -> Result<(), FileError> {
+        self.writer.write_batch(df).map_err(|_e| FileError::FileWriteError)?;
+        self.n_rows += df.height();
+        Ok(())
+    }
+
+    /// finish file and move it to its output path
+    pub(crate) fn finish(mut self) -> Result<(), FileError> {
+        self.writer.finish().map_err(|_e| FileError::FileWriteError)?;
+        if !self.metadata.is_empty() {
+            super::embed_parquet_metadata(&self.tmp_filename, &self.metadata)?;
+        }
+        if let Some(signer) = &self.signer {
+            super::sign_file(&self.tmp_filename, &self.filename, signer)?;
+        }
+        move_into_place(&self.tmp_filename, &self.filename)?;
+        if self.row_count_sidecar {
+            super::write_row_count(&self.filename, self.n_rows)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stdout_sink(format: FileFormat) -> FileOutput {
+        FileOutput { output_dir: PathBuf::from("."), format, stdout: true, ..Default::default() }
+    }
+
+    #[test]
+    fn test_stream_rows_with_hex_encoded_binary_columns() {
+        let df = DataFrame::new(vec![
+            Series::new("block_number", vec![100u32, 101]),
+            Series::new("address", vec![vec![0xaau8; 20], vec![0xbbu8; 20]]),
+            Series::new("topic1", vec![Some(vec![0x01u8, 0x02]), None]),
+        ])
+        .unwrap();
+        let address_a = format!("0x{}", "aa".repeat(20));
+        let address_b = format!("0x{}", "bb".repeat(20));
+
+        let mut stdout = Vec::new();
+        df_to_writer(&df, &stdout_sink(FileFormat::Csv), &mut stdout, true).unwrap();
+        let rows: Vec<String> =
+            String::from_utf8(stdout).unwrap().lines().map(String::from).collect();
+        assert_eq!(
+            rows,
+            vec![
+                "block_number,address,topic1".to_string(),
+                format!("100,{},0x0102", address_a),
+                format!("101,{},", address_b),
+            ]
+        );
+
+        let mut stdout = Vec::new();
+        df_to_writer(&df, &stdout_sink(FileFormat::Json), &mut stdout, true).unwrap();
+        let rows: Vec<serde_json::Value> = String::from_utf8(stdout)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                serde_json::json!({"block_number": 100, "address": address_a, "topic1": "0x0102"}),
+                serde_json::json!({"block_number": 101, "address": address_b, "topic1": null}),
+            ]
+        );
+
+        // the dataframe itself keeps its binary columns
+        assert_eq!(df.column("address").unwrap().dtype(), &DataType::Binary);
+        assert!(df_to_writer(&df, &stdout_sink(FileFormat::Parquet), Vec::new(), true).is_err());
     }
 }
@@ -0,0 +1,82 @@
+use crate::types::FileError;
+use parquet_format_safe::{
+    thrift::protocol::{TCompactInputProtocol, TCompactOutputProtocol},
+    FileMetaData, KeyValue,
+};
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+const PARQUET_MAGIC: &[u8; 4] = b"PAR1";
+
+/// add entries to the key-value metadata of a parquet file, replacing entries of the same key
+///
+/// only the footer is read and rewritten in place, column chunks keep their offsets since they
+/// precede it
+pub(crate) fn embed_parquet_metadata(
+    path: &Path,
+    entries: &[(String, String)],
+) -> Result<(), FileError> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .map_err(|_e| FileError::FileWriteError)?;
+    let (footer_start, mut metadata) =
+        read_footer(&mut file).map_err(|_e| FileError::FileWriteError)?;
+    let key_values = metadata.key_value_metadata.get_or_insert_with(Vec::new);
+    key_values.retain(|key_value| !entries.iter().any(|(key, _)| key == &key_value.key));
+    key_values.extend(entries.iter().map(|(key, value)| KeyValue::new(key.clone(), value.clone())));
+
+    let mut footer = Vec::new();
+    metadata
+        .write_to_out_protocol(&mut TCompactOutputProtocol::new(&mut footer))
+        .map_err(|_e| FileError::FileWriteError)?;
+    let footer_len = footer.len() as u32;
+    footer.extend_from_slice(&footer_len.to_le_bytes());
+    footer.extend_from_slice(PARQUET_MAGIC);
+    file.set_len(footer_start).map_err(|_e| FileError::FileWriteError)?;
+    file.seek(SeekFrom::Start(footer_start)).map_err(|_e| FileError::FileWriteError)?;
+    file.write_all(&footer).map_err(|_e| FileError::FileWriteError)
+}
+
+/// read the key-value metadata of a parquet file
+pub fn read_parquet_metadata(path: &Path) -> Result<HashMap<String, String>, FileError> {
+    let mut file = File::open(path)
+        .map_err(|_e| FileError::NoFilePathError("could not read file".to_string()))?;
+    let (_, metadata) = read_footer(&mut file)?;
+    Ok(metadata
+        .key_value_metadata
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|key_value| key_value.value.map(|value| (key_value.key, value)))
+        .collect())
+}
+
+/// decode footer of parquet file, returning its offset and contents
+fn read_footer<R: Read + Seek>(reader: &mut R) -> Result<(u64, FileMetaData), FileError> {
+    let invalid = || FileError::NoFilePathError("not a parquet file".to_string());
+    let n_bytes = reader.seek(SeekFrom::End(0)).map_err(|_e| invalid())?;
+    if n_bytes < 12 {
+        return Err(invalid())
+    }
+    let mut tail = [0u8; 8];
+    reader.seek(SeekFrom::Start(n_bytes - 8)).map_err(|_e| invalid())?;
+    reader.read_exact(&mut tail).map_err(|_e| invalid())?;
+    if &tail[4..] != PARQUET_MAGIC {
+        return Err(invalid())
+    }
+    let mut footer_len = [0u8; 4];
+    footer_len.copy_from_slice(&tail[..4]);
+    let footer_len = u32::from_le_bytes(footer_len) as u64;
+    let footer_start = (n_bytes - 8).checked_sub(footer_len).ok_or_else(invalid)?;
+    let mut footer = vec![0u8; footer_len as usize];
+    reader.seek(SeekFrom::Start(footer_start)).map_err(|_e| invalid())?;
+    reader.read_exact(&mut footer).map_err(|_e| invalid())?;
+    let mut protocol = TCompactInputProtocol::new(footer.as_slice(), footer.len() * 2 + 1024);
+    let metadata = FileMetaData::read_from_in_protocol(&mut protocol).map_err(|_e| invalid())?;
+    Ok((footer_start, metadata))
+}
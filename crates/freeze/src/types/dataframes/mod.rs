@@ -1,12 +1,43 @@
+mod aliases;
+mod buckets;
+mod compact;
+mod counts;
+mod days;
+mod decimals;
+mod diff;
 mod export;
+mod metadata;
 mod read;
+mod signing;
+mod sizes;
 mod sort;
+mod stats;
 mod u256s;
 
 #[macro_use]
 mod creation;
 
-pub(crate) use export::*;
+pub(crate) use aliases::alias_columns;
+pub(crate) use buckets::{
+    add_block_bucket_column, block_bucket, bucket_dirname, bucket_path, split_by_block_bucket,
+    BLOCK_BUCKET_COLUMN,
+};
+pub(crate) use compact::drop_null_topic_columns;
+pub(crate) use counts::write_row_count;
+pub use counts::{read_row_count, row_count_path};
+pub(crate) use days::{
+    day_files_exist, day_path, rows_block_range, split_by_day, write_day_manifest,
+};
+pub(crate) use decimals::scale_decimal_columns;
+pub(crate) use diff::diff_logs;
+pub(crate) use export::{df_to_file, df_to_file_with_metadata, df_to_stdout, BatchedParquetWriter};
+pub use export::{CsvOutput, JsonOutput, OutputFormat, ParquetOutput};
+pub(crate) use metadata::embed_parquet_metadata;
+pub use metadata::read_parquet_metadata;
 pub use read::*;
+pub(crate) use signing::sign_file;
+pub use signing::{signature_path, verify_file_signature};
+pub(crate) use sizes::{SizedBatch, SizedFile, SizedFiles};
 pub(crate) use sort::SortableDataFrame;
+pub use stats::*;
 pub use u256s::*;
@@ -0,0 +1,118 @@
+use crate::FileError;
+use ethers::{
+    signers::LocalWallet,
+    types::{Address, Signature},
+    utils::{hash_message, keccak256},
+};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// path of the detached signature written beside an output file
+pub fn signature_path(path: &Path) -> PathBuf {
+    let mut signature_path = path.as_os_str().to_owned();
+    signature_path.push(".sig");
+    PathBuf::from(signature_path)
+}
+
+/// write detached signature of file to its signature path, before the file is in place
+///
+/// the signature is an EIP-191 signature of the keccak256 digest of the file's bytes
+pub(crate) fn sign_file(
+    tmp_filename: &Path,
+    filename: &Path,
+    signer: &LocalWallet,
+) -> Result<(), FileError> {
+    let digest = file_digest(tmp_filename)?;
+    let signature =
+        signer.sign_hash(hash_message(digest)).map_err(|_e| FileError::FileWriteError)?;
+    std::fs::write(signature_path(filename), format!("0x{}", signature))
+        .map_err(|_e| FileError::FileWriteError)
+}
+
+/// whether the detached signature of file was made by address over the file's current bytes
+pub fn verify_file_signature(path: &Path, address: Address) -> Result<bool, FileError> {
+    let signature = std::fs::read_to_string(signature_path(path))
+        .map_err(|_e| FileError::NoFilePathError("could not read signature file".to_string()))?;
+    let signature = Signature::from_str(signature.trim())
+        .map_err(|_e| FileError::NoFilePathError("invalid signature file".to_string()))?;
+    let digest = file_digest(path)?;
+    Ok(signature.verify(&digest[..], address).is_ok())
+}
+
+fn file_digest(path: &Path) -> Result<[u8; 32], FileError> {
+    let bytes = std::fs::read(path)
+        .map_err(|_e| FileError::NoFilePathError("could not read file".to_string()))?;
+    Ok(keccak256(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dataframes::df_to_file, FileOutput};
+    use ethers::signers::Signer;
+    use polars::prelude::*;
+
+    #[test]
+    fn test_signed_file_verifies_until_modified() {
+        let output_dir =
+            std::env::temp_dir().join(format!("cryo_test_signing_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&output_dir);
+        std::fs::create_dir_all(&output_dir).unwrap();
+        let signer = LocalWallet::from_str(
+            "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318",
+        )
+        .unwrap();
+        let sink = FileOutput {
+            output_dir: output_dir.clone(),
+            overwrite: true,
+            signer: Some(signer.clone()),
+            ..Default::default()
+        };
+
+        let path = output_dir.join("ethereum__blocks__00000000_to_00000999.parquet");
+        let mut df = DataFrame::new(vec![Series::new("block_number", vec![1u32, 2])]).unwrap();
+        df_to_file(&mut df, &path, &sink).unwrap();
+        assert!(signature_path(&path).exists());
+        assert!(verify_file_signature(&path, signer.address()).unwrap());
+        assert!(!verify_file_signature(&path, Address::from_low_u64_be(1)).unwrap());
+
+        // any change to the file invalidates its signature
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes.push(0);
+        std::fs::write(&path, bytes).unwrap();
+        assert!(!verify_file_signature(&path, signer.address()).unwrap());
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[test]
+    fn test_signer_recovered_from_signature_and_file_bytes() {
+        let output_dir =
+            std::env::temp_dir().join(format!("cryo_test_signing_recover_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&output_dir);
+        std::fs::create_dir_all(&output_dir).unwrap();
+        let signer = LocalWallet::from_str(
+            "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318",
+        )
+        .unwrap();
+        let sink = FileOutput {
+            output_dir: output_dir.clone(),
+            overwrite: true,
+            signer: Some(signer.clone()),
+            ..Default::default()
+        };
+        let path = output_dir.join("ethereum__blocks__00000000_to_00000999.parquet");
+        let mut df = DataFrame::new(vec![Series::new("block_number", vec![1u32, 2])]).unwrap();
+        df_to_file(&mut df, &path, &sink).unwrap();
+
+        // the .sig is an EIP-191 signature of the keccak256 digest of the file's bytes
+        let signature = std::fs::read_to_string(signature_path(&path)).unwrap();
+        let signature = Signature::from_str(signature.trim()).unwrap();
+        let digest = keccak256(std::fs::read(&path).unwrap());
+        assert_eq!(signature.recover(hash_message(digest)).unwrap(), signer.address());
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+}
@@ -0,0 +1,403 @@
+use crate::{err, BlockChunk, ChunkData, CollectError, Datatype, Partition};
+use polars::prelude::*;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+};
+
+/// rows of a datatype and the partition of the inclusive block range of the file they fill
+pub(crate) type SizedFile = (Datatype, Partition, DataFrame);
+
+/// files that are full, and the partitions whose rows are all in these files or in files
+/// returned before, with their number of rows. the partitions are complete once the files are
+/// written
+#[derive(Debug, Default)]
+pub(crate) struct SizedBatch {
+    pub(crate) files: Vec<SizedFile>,
+    pub(crate) partitions: Vec<(Partition, u64)>,
+}
+
+/// rows of consecutive partitions, held until they fill files of about target_bytes each
+///
+/// partitions are pushed in block order, and each file covers the contiguous blocks of the
+/// partitions it spans, so that sparse partitions are merged into one file and dense partitions
+/// split into several. at most about target_bytes of rows of each datatype are held between
+/// partitions
+#[derive(Debug)]
+pub(crate) struct SizedFiles {
+    target_bytes: u64,
+    state: Mutex<SizeState>,
+}
+
+#[derive(Debug, Default)]
+struct SizeState {
+    buffers: HashMap<Datatype, SizeBuffer>,
+    /// pushed partitions with rows still held, with their last block and number of rows
+    pending: Vec<(Partition, u64, u64)>,
+}
+
+impl SizeState {
+    /// release the pending partitions that no buffer holds rows of
+    fn release(&mut self) -> Vec<(Partition, u64)> {
+        let held_from = self.buffers.values().map(|buffer| buffer.start).min();
+        let (released, pending) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|(_, end, _)| held_from.map_or(true, |start| *end < start));
+        self.pending = pending;
+        released.into_iter().map(|(partition, _, n_rows)| (partition, n_rows)).collect()
+    }
+}
+
+/// rows of the blocks start to end that do not yet fill a file
+#[derive(Debug)]
+struct SizeBuffer {
+    template: Partition,
+    start: u64,
+    end: u64,
+    dfs: Vec<DataFrame>,
+    bytes: u64,
+}
+
+impl SizeBuffer {
+    fn file(self, datatype: Datatype) -> Result<SizedFile, CollectError> {
+        let partition = range_partition(&self.template, self.start, self.end);
+        Ok((datatype, partition, concat_dfs(self.dfs)?))
+    }
+}
+
+impl SizedFiles {
+    pub(crate) fn new(target_bytes: u64) -> Result<Self, CollectError> {
+        if target_bytes == 0 {
+            return Err(err("target partition bytes must be greater than zero"))
+        }
+        Ok(SizedFiles { target_bytes, state: Mutex::new(SizeState::default()) })
+    }
+
+    /// add the collected rows of partition, returning the files that are full
+    ///
+    /// rows held from partitions that do not end right before partition, e.g. because the
+    /// partitions between them errored or were already written, are returned as a file first
+    pub(crate) fn push(
+        &self,
+        partition: &Partition,
+        dfs: &HashMap<Datatype, DataFrame>,
+    ) -> Result<SizedBatch, CollectError> {
+        let (start, end) = partition
+            .block_numbers
+            .as_ref()
+            .and_then(|chunks| Some((chunks.min_value()?, chunks.max_value()?)))
+            .ok_or_else(|| err("target partition bytes require partitions of block ranges"))?;
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let buffers = &mut state.buffers;
+        let mut files = Vec::new();
+        for (datatype, df) in dfs.iter() {
+            let buffer = match buffers.remove(datatype) {
+                Some(buffer) if buffer.end + 1 == start => Some(buffer),
+                Some(buffer) => {
+                    files.push(buffer.file(*datatype)?);
+                    None
+                }
+                None => None,
+            };
+            let mut buffer = buffer.unwrap_or_else(|| SizeBuffer {
+                template: partition.clone(),
+                start,
+                end,
+                dfs: Vec::new(),
+                bytes: 0,
+            });
+            buffer.end = end;
+            buffer.bytes += df.estimated_size() as u64;
+            buffer.dfs.push(df.clone());
+            if buffer.bytes < self.target_bytes {
+                buffers.insert(*datatype, buffer);
+                continue
+            }
+
+            let df = concat_dfs(std::mem::take(&mut buffer.dfs))?;
+            let (full, remainder) =
+                split_by_size(&df, buffer.start, buffer.end, self.target_bytes)?;
+            for ((range_start, range_end), range_df) in full.into_iter() {
+                let range = range_partition(&buffer.template, range_start, range_end);
+                files.push((*datatype, range, range_df));
+            }
+            if let Some(((range_start, _), range_df)) = remainder {
+                buffer.start = range_start;
+                buffer.bytes = range_df.estimated_size() as u64;
+                buffer.dfs = vec![range_df];
+                buffers.insert(*datatype, buffer);
+            }
+        }
+        let n_rows = dfs.values().map(|df| df.height() as u64).sum();
+        state.pending.push((partition.clone(), end, n_rows));
+        Ok(SizedBatch { files, partitions: state.release() })
+    }
+
+    /// files of the rows still held once every partition has been pushed
+    pub(crate) fn finish(&self) -> Result<SizedBatch, CollectError> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let files = state
+            .buffers
+            .drain()
+            .map(|(datatype, buffer)| buffer.file(datatype))
+            .collect::<Result<_, _>>()?;
+        Ok(SizedBatch { files, partitions: state.release() })
+    }
+
+    /// drop the rows still held, returning the partitions they belong to
+    pub(crate) fn abandon(&self) -> Vec<Partition> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.buffers.clear();
+        state.release().into_iter().map(|(partition, _)| partition).collect()
+    }
+}
+
+/// partition covering the inclusive block range start to end, otherwise like template
+fn range_partition(template: &Partition, start: u64, end: u64) -> Partition {
+    Partition {
+        label: None,
+        block_numbers: Some(vec![BlockChunk::Range(start, end)]),
+        ..template.clone()
+    }
+}
+
+/// stack dataframes of the same schema, dataframes without rows are only kept if all are empty
+fn concat_dfs(dfs: Vec<DataFrame>) -> Result<DataFrame, CollectError> {
+    let empty = dfs.first().map(|df| df.head(Some(0)));
+    let mut output: Option<DataFrame> = None;
+    for df in dfs.into_iter().filter(|df| df.height() > 0) {
+        match &mut output {
+            Some(output) => {
+                output.vstack_mut(&df)?;
+            }
+            None => output = Some(df),
+        }
+    }
+    output.or(empty).ok_or_else(|| err("no rows to write"))
+}
+
+/// full ranges and the remaining range of a split, each with its rows
+type SizeSplits = (Vec<((u64, u64), DataFrame)>, Option<((u64, u64), DataFrame)>);
+
+/// split rows of the blocks start to end into the ranges whose estimated size reaches
+/// target_bytes and the range of the remaining blocks, keyed by the inclusive block range of each
+///
+/// blocks are added to a range in block order until the estimated size of its rows reaches
+/// target_bytes, a row is estimated at the average in-memory size of the rows of df. the ranges
+/// tile start to end without gaps, including blocks without rows, so that file names still cover
+/// every block. the remainder is None if the last block completes a range
+fn split_by_size(
+    df: &DataFrame,
+    start: u64,
+    end: u64,
+    target_bytes: u64,
+) -> Result<SizeSplits, CollectError> {
+    if df.height() == 0 {
+        return Ok((Vec::new(), Some(((start, end), df.clone()))))
+    }
+    let block_numbers = df
+        .column("block_number")
+        .map_err(|_| err("splitting by size requires a block_number column"))?
+        .cast(&DataType::UInt64)?
+        .u64()?
+        .into_iter()
+        .map(|block_number| {
+            block_number.ok_or_else(|| err("cannot split rows with null block_number"))
+        })
+        .collect::<Result<Vec<u64>, CollectError>>()?;
+
+    // accumulate blocks into ranges by the estimated size of their rows
+    let row_bytes = df.estimated_size() as f64 / df.height() as f64;
+    let mut rows_per_block: BTreeMap<u64, u64> = BTreeMap::new();
+    for block_number in block_numbers.iter() {
+        *rows_per_block.entry(*block_number).or_default() += 1;
+    }
+    let mut ranges = Vec::new();
+    let mut range_start = start;
+    let mut range_bytes = 0.0;
+    for (block_number, n_rows) in rows_per_block.range(start..=end) {
+        range_bytes += *n_rows as f64 * row_bytes;
+        if range_bytes >= target_bytes as f64 {
+            ranges.push((range_start, *block_number));
+            range_start = block_number + 1;
+            range_bytes = 0.0;
+        }
+    }
+
+    let range_df = |range_start: u64, range_end: u64| -> Result<DataFrame, CollectError> {
+        let mask: Vec<bool> = block_numbers
+            .iter()
+            .map(|block_number| *block_number >= range_start && *block_number <= range_end)
+            .collect();
+        let mask = Series::new("mask", mask);
+        Ok(df.filter(mask.bool()?)?)
+    };
+    let mut full = Vec::new();
+    for (range_start, range_end) in ranges.into_iter() {
+        full.push(((range_start, range_end), range_df(range_start, range_end)?));
+    }
+    let remainder = if range_start <= end {
+        Some(((range_start, end), range_df(range_start, end)?))
+    } else {
+        None
+    };
+    Ok((full, remainder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// blocks 0-9 hold 40 rows each, blocks 10-19 hold 2 rows each
+    fn dense_then_sparse() -> DataFrame {
+        let block_numbers: Vec<u32> = (0..20u32)
+            .flat_map(|block_number| {
+                let n_rows = if block_number < 10 { 40 } else { 2 };
+                std::iter::repeat(block_number).take(n_rows)
+            })
+            .collect();
+        let log_indices: Vec<u32> = (0..block_numbers.len() as u32).collect();
+        df!("block_number" => block_numbers, "log_index" => log_indices).unwrap()
+    }
+
+    #[test]
+    fn test_dense_and_sparse_blocks_split_into_even_files() {
+        let df = dense_then_sparse();
+        let target_bytes = (df.estimated_size() * 100 / df.height()) as u64;
+
+        let (full, remainder) = split_by_size(&df, 0, 19, target_bytes).unwrap();
+        let mut splits = full;
+        splits.extend(remainder);
+        let ranges: Vec<(u64, u64)> = splits.iter().map(|(range, _)| *range).collect();
+        assert_eq!(ranges.first().unwrap().0, 0);
+        assert_eq!(ranges.last().unwrap().1, 19);
+        assert!(ranges.windows(2).all(|pair| pair[1].0 == pair[0].1 + 1));
+        let heights: Vec<usize> = splits.iter().map(|(_, df)| df.height()).collect();
+        assert_eq!(heights.iter().sum::<usize>(), df.height());
+
+        // splitting into ten block files would give files of 400 and 20 rows
+        let (min, max) = (heights.iter().min().unwrap(), heights.iter().max().unwrap());
+        assert!(heights.len() > 2);
+        assert!(*max <= 3 * *min, "uneven file sizes: {:?}", heights);
+
+        // rows of the last block count towards the last range
+        let sparse = df.filter(&df.column("block_number").unwrap().gt(9).unwrap()).unwrap();
+        let target_bytes = (sparse.estimated_size() / 2) as u64;
+        let (full, remainder) = split_by_size(&sparse, 10, 19, target_bytes).unwrap();
+        assert_eq!(full.last().map(|(range, _)| *range), Some((15, 19)));
+        assert!(remainder.is_none());
+
+        // an empty partition is held as one remaining range
+        let empty = df.head(Some(0));
+        let (full, remainder) = split_by_size(&empty, 0, 19, target_bytes).unwrap();
+        assert!(full.is_empty());
+        assert_eq!(remainder.map(|(range, _)| range), Some((0, 19)));
+    }
+
+    #[test]
+    fn test_sparse_partitions_are_merged_and_dense_partitions_split() {
+        let df = dense_then_sparse();
+        let target_bytes = (df.estimated_size() * 100 / df.height()) as u64;
+        let sized_files = SizedFiles::new(target_bytes).unwrap();
+
+        // one partition per block, as with a chunk size of 1
+        let mut files = Vec::new();
+        for block_number in 0..20u32 {
+            let partition = Partition {
+                block_numbers: Some(vec![BlockChunk::Range(
+                    block_number as u64,
+                    block_number as u64,
+                )]),
+                ..Default::default()
+            };
+            let mask = df.column("block_number").unwrap().equal(block_number).unwrap();
+            let dfs = HashMap::from([(Datatype::Logs, df.filter(&mask).unwrap())]);
+            files.extend(sized_files.push(&partition, &dfs).unwrap().files);
+        }
+        files.extend(sized_files.finish().unwrap().files);
+
+        let ranges: Vec<(u64, u64)> = files
+            .iter()
+            .map(|(_, partition, _)| {
+                let chunks = partition.block_numbers.as_ref().unwrap();
+                (chunks.min_value().unwrap(), chunks.max_value().unwrap())
+            })
+            .collect();
+        assert_eq!(ranges.first().unwrap().0, 0);
+        assert_eq!(ranges.last().unwrap().1, 19);
+        assert!(ranges.windows(2).all(|pair| pair[1].0 == pair[0].1 + 1));
+        let heights: Vec<usize> = files.iter().map(|(_, _, df)| df.height()).collect();
+        assert_eq!(heights.iter().sum::<usize>(), df.height());
+
+        // the sparse partitions share a file, instead of ten files of 2 rows
+        assert!(ranges.iter().filter(|(_, end)| *end >= 10).count() < 10);
+        let (min, max) = (heights.iter().min().unwrap(), heights.iter().max().unwrap());
+        assert!(*max <= 3 * *min, "uneven file sizes: {:?}", heights);
+    }
+
+    #[test]
+    fn test_rows_are_not_merged_across_missing_partitions() {
+        let df = dense_then_sparse();
+        let sized_files = SizedFiles::new(u64::MAX).unwrap();
+        for (start, end) in [(10, 12), (13, 15), (18, 19)] {
+            let partition = Partition {
+                block_numbers: Some(vec![BlockChunk::Range(start, end)]),
+                ..Default::default()
+            };
+            let column = df.column("block_number").unwrap();
+            let mask = column.gt_eq(start).unwrap() & column.lt_eq(end).unwrap();
+            let dfs = HashMap::from([(Datatype::Logs, df.filter(&mask).unwrap())]);
+            let files = sized_files.push(&partition, &dfs).unwrap().files;
+            if start == 18 {
+                let chunks = files[0].1.block_numbers.as_ref().unwrap();
+                assert_eq!((chunks.min_value(), chunks.max_value()), (Some(10), Some(15)));
+            } else {
+                assert!(files.is_empty());
+            }
+        }
+        let files = sized_files.finish().unwrap().files;
+        let chunks = files[0].1.block_numbers.as_ref().unwrap();
+        assert_eq!((chunks.min_value(), chunks.max_value()), (Some(18), Some(19)));
+    }
+
+    #[test]
+    fn test_partitions_are_released_once_their_rows_are_in_full_files() {
+        let df = dense_then_sparse();
+        let target_bytes = (df.estimated_size() * 100 / df.height()) as u64;
+        let sized_files = SizedFiles::new(target_bytes).unwrap();
+        let released = |batch: SizedBatch| -> Vec<u64> {
+            batch
+                .partitions
+                .iter()
+                .map(|(partition, _)| {
+                    partition.block_numbers.as_ref().unwrap().min_value().unwrap()
+                })
+                .collect()
+        };
+        let push = |start: u64, end: u64| {
+            let partition = Partition {
+                block_numbers: Some(vec![BlockChunk::Range(start, end)]),
+                ..Default::default()
+            };
+            let column = df.column("block_number").unwrap();
+            let mask = column.gt_eq(start).unwrap() & column.lt_eq(end).unwrap();
+            let dfs = HashMap::from([(Datatype::Logs, df.filter(&mask).unwrap())]);
+            sized_files.push(&partition, &dfs).unwrap()
+        };
+
+        // the rows of blocks 0-1 do not fill a file and stay held
+        let batch = push(0, 1);
+        assert!(batch.files.is_empty() && batch.partitions.is_empty());
+
+        // a partition after a gap flushes the held rows, releasing the partition they belong to,
+        // while the rows of its own last blocks stay held
+        let batch = push(5, 9);
+        assert!(batch.files.len() > 1);
+        assert_eq!(released(batch), vec![0]);
+
+        // held rows are released with the files of the last rows
+        let batch = sized_files.finish().unwrap();
+        assert_eq!(batch.partitions.iter().map(|(_, n_rows)| *n_rows).sum::<u64>(), 200);
+        assert_eq!(released(batch), vec![5]);
+    }
+}
@@ -0,0 +1,189 @@
+use crate::CollectError;
+use polars::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+
+/// min and max values of a column within a single output file
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct ColumnStats {
+    /// minimum value of column
+    pub min: ColumnValue,
+    /// maximum value of column
+    pub max: ColumnValue,
+}
+
+/// value of a sort column, binary values are 0x-prefixed hex strings
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, serde::Serialize)]
+#[serde(untagged)]
+pub enum ColumnValue {
+    /// value of an unsigned integer column
+    UInt(u64),
+    /// value of a signed integer column
+    Int(i64),
+    /// value of a string or binary column
+    String(String),
+}
+
+impl ColumnStats {
+    /// widen stats to also cover the values of other
+    pub fn merge(&mut self, other: ColumnStats) {
+        self.min = std::mem::replace(&mut self.min, ColumnValue::UInt(0)).min(other.min);
+        self.max = std::mem::replace(&mut self.max, ColumnValue::UInt(0)).max(other.max);
+    }
+}
+
+/// compute min/max of the given columns in dataframe, columns missing from the dataframe or
+/// without non-null values are skipped
+///
+/// integer, string and binary columns are supported, binary values are compared as bytes
+pub(crate) fn compute_column_stats(
+    df: &DataFrame,
+    columns: &[String],
+) -> Result<HashMap<String, ColumnStats>, CollectError> {
+    let mut stats = HashMap::new();
+    for column in columns.iter() {
+        let series = match df.column(column) {
+            Ok(series) => series,
+            Err(_) => continue,
+        };
+        let min_max = match series.dtype() {
+            DataType::UInt8 | DataType::UInt16 | DataType::UInt32 | DataType::UInt64 => {
+                let series = series.cast(&DataType::UInt64)?;
+                let ca = series.u64()?;
+                ca.min()
+                    .zip(ca.max())
+                    .map(|(min, max)| (ColumnValue::UInt(min), ColumnValue::UInt(max)))
+            }
+            DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64 => {
+                let series = series.cast(&DataType::Int64)?;
+                let ca = series.i64()?;
+                ca.min()
+                    .zip(ca.max())
+                    .map(|(min, max)| (ColumnValue::Int(min), ColumnValue::Int(max)))
+            }
+            DataType::Utf8 => {
+                let ca = series.utf8()?;
+                let min = ca.into_iter().flatten().min();
+                let max = ca.into_iter().flatten().max();
+                min.zip(max).map(|(min, max)| {
+                    (ColumnValue::String(min.to_string()), ColumnValue::String(max.to_string()))
+                })
+            }
+            DataType::Binary => {
+                let ca = series.binary()?;
+                let min = ca.into_iter().flatten().min();
+                let max = ca.into_iter().flatten().max();
+                min.zip(max).map(|(min, max)| {
+                    (
+                        ColumnValue::String(prefix_hex::encode(min)),
+                        ColumnValue::String(prefix_hex::encode(max)),
+                    )
+                })
+            }
+            dtype => {
+                let message =
+                    format!("cannot compute min/max of column {} of type {}", column, dtype);
+                return Err(CollectError::CollectError(message))
+            }
+        };
+        if let Some((min, max)) = min_max {
+            stats.insert(column.clone(), ColumnStats { min, max });
+        }
+    }
+    Ok(stats)
+}
+
+/// count number of rows per block_number
+///
+/// if the partition's block numbers are known, blocks without any rows are given a count of 0
+pub(crate) fn count_rows_per_block(
+    df: &DataFrame,
+    block_numbers: Option<&[u64]>,
+) -> Result<DataFrame, CollectError> {
+    let mut counts: BTreeMap<u32, u64> = BTreeMap::new();
+    if let Some(block_numbers) = block_numbers {
+        for block_number in block_numbers.iter() {
+            counts.insert(*block_number as u32, 0);
+        }
+    }
+    for block_number in df.column("block_number")?.u32()?.into_iter().flatten() {
+        *counts.entry(block_number).or_insert(0) += 1;
+    }
+
+    let (block_numbers, n_rows): (Vec<u32>, Vec<u64>) = counts.into_iter().unzip();
+    let columns = vec![Series::new("block_number", block_numbers), Series::new("n_logs", n_rows)];
+    Ok(DataFrame::new(columns)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_column_stats_of_sort_column_types() {
+        let df = df!(
+            "block_number" => [12u32, 10, 11],
+            "create_index" => [Some(3u64), None, Some(1)],
+            "delta" => [-5i64, 2, 0],
+            "symbol" => ["WETH", "DAI", "USDC"],
+            "address" => [vec![0x02u8, 0xff], vec![0x01, 0x00], vec![0x10]],
+            "empty" => [None::<u32>, None, None],
+        )
+        .unwrap();
+        let columns: Vec<String> =
+            ["block_number", "create_index", "delta", "symbol", "address", "empty", "missing"]
+                .into_iter()
+                .map(String::from)
+                .collect();
+        let stats = compute_column_stats(&df, &columns).unwrap();
+        let min_max = |column: &str| (stats[column].min.clone(), stats[column].max.clone());
+        assert_eq!(min_max("block_number"), (ColumnValue::UInt(10), ColumnValue::UInt(12)));
+        assert_eq!(min_max("create_index"), (ColumnValue::UInt(1), ColumnValue::UInt(3)));
+        assert_eq!(min_max("delta"), (ColumnValue::Int(-5), ColumnValue::Int(2)));
+        let strings = |min: &str, max: &str| {
+            (ColumnValue::String(min.to_string()), ColumnValue::String(max.to_string()))
+        };
+        assert_eq!(min_max("symbol"), strings("DAI", "WETH"));
+        assert_eq!(min_max("address"), strings("0x0100", "0x10"));
+
+        // columns without values are skipped
+        assert!(!stats.contains_key("empty") && !stats.contains_key("missing"));
+
+        // integers are reported as numbers, other values as strings
+        let json = serde_json::to_value(&stats["block_number"]).unwrap();
+        assert_eq!(json, serde_json::json!({"min": 10, "max": 12}));
+        let json = serde_json::to_value(&stats["address"]).unwrap();
+        assert_eq!(json, serde_json::json!({"min": "0x0100", "max": "0x10"}));
+
+        // stats of batches are merged
+        let mut merged = stats["block_number"].clone();
+        merged.merge(ColumnStats { min: ColumnValue::UInt(11), max: ColumnValue::UInt(20) });
+        assert_eq!(merged, ColumnStats { min: ColumnValue::UInt(10), max: ColumnValue::UInt(20) });
+
+        // other column types are rejected
+        let df = df!("gas_price" => [1.5f64]).unwrap();
+        assert!(compute_column_stats(&df, &["gas_price".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_count_rows_per_block() {
+        let df = DataFrame::new(vec![
+            Series::new("block_number", vec![10u32, 10, 12, 13, 13, 13]),
+            Series::new("log_index", vec![0u32, 1, 0, 0, 1, 2]),
+        ])
+        .unwrap();
+
+        let counts = count_rows_per_block(&df, Some(&[10, 11, 12, 13, 14])).unwrap();
+        let block_numbers: Vec<Option<u32>> =
+            counts.column("block_number").unwrap().u32().unwrap().into_iter().collect();
+        let n_logs: Vec<Option<u64>> =
+            counts.column("n_logs").unwrap().u64().unwrap().into_iter().collect();
+        assert_eq!(block_numbers, vec![Some(10), Some(11), Some(12), Some(13), Some(14)]);
+        assert_eq!(n_logs, vec![Some(2), Some(0), Some(1), Some(3), Some(0)]);
+        assert_eq!(n_logs.iter().flatten().sum::<u64>(), df.height() as u64);
+
+        let counts = count_rows_per_block(&df, None).unwrap();
+        let n_logs: Vec<Option<u64>> =
+            counts.column("n_logs").unwrap().u64().unwrap().into_iter().collect();
+        assert_eq!(n_logs, vec![Some(2), Some(1), Some(3)]);
+    }
+}
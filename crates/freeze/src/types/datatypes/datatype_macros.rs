@@ -62,6 +62,18 @@ macro_rules! define_datatypes {
                 }
             }
 
+            /// zero-row dataframe with the exact columns and dtypes that collecting datatype produces
+            pub fn empty_df(
+                &self,
+                schemas: &HashMap<Datatype, Table>,
+                chain_id: u64,
+            ) -> Result<DataFrame, CollectError> {
+                let mut dfs = match *self {
+                    $(Datatype::$datatype => $datatype::default().create_dfs(schemas, chain_id)?,)*
+                };
+                dfs.remove(self).ok_or_else(|| err("could not create empty dataframe"))
+            }
+
             /// whether to use block ranges instead of individual blocks
             pub fn use_block_ranges(&self) -> bool {
                 match *self {
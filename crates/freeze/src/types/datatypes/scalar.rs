@@ -62,6 +62,19 @@ impl Datatype {
     }
 }
 
+/// look up datatype by its name or one of its aliases, e.g. logs, events, or erc20_transfers
+///
+/// Datatype dispatches to the Dataset implementation of each datatype, so the column types,
+/// default columns, and parameters of the dataset are available on the result
+pub fn dataset_by_name(name: &str) -> Option<Datatype> {
+    Datatype::alias_map().ok()?.remove(name)
+}
+
+/// names of all datatypes, in the order they are defined
+pub fn dataset_names() -> Vec<String> {
+    Datatype::all().iter().map(|datatype| datatype.name()).collect()
+}
+
 impl std::str::FromStr for Datatype {
     type Err = ParseError;
 
@@ -71,3 +84,23 @@ impl std::str::FromStr for Datatype {
             .ok_or_else(|| ParseError::ParseError(format!("no datatype matches input: {}", s)))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dataset_by_name() {
+        let logs = dataset_by_name("logs").unwrap();
+        assert_eq!(logs, Datatype::Logs);
+        assert!(logs.column_types().contains_key("topic0"));
+        assert_eq!(dataset_by_name("events"), Some(Datatype::Logs));
+        assert_eq!(dataset_by_name("erc20_transfers"), Some(Datatype::Erc20Transfers));
+        assert_eq!(dataset_by_name("not_a_dataset"), None);
+
+        // every name resolves to its own datatype
+        for name in dataset_names() {
+            assert_eq!(dataset_by_name(&name).map(|datatype| datatype.name()), Some(name));
+        }
+    }
+}
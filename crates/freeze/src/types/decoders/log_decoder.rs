@@ -4,6 +4,43 @@ use ethers_core::abi::{AbiEncode, EventParam, HumanReadableParser, ParamType, Ra
 use polars::prelude::*;
 use std::collections::HashSet;
 
+/// prefix of decoded event columns, keeping them apart from base columns of the same name
+pub const DECODED_COLUMN_PREFIX: &str = "event__";
+
+/// name of the decoded column of an event parameter
+pub fn decoded_column_name(param_name: &str) -> String {
+    format!("{}{}", DECODED_COLUMN_PREFIX, param_name)
+}
+
+/// decoded params of a log as a JSON object keyed by param name, in the order of the event
+///
+/// integers are decimal strings so that 256 bit values keep their precision, addresses and bytes
+/// are 0x-prefixed hex, and arrays and tuples are JSON arrays
+pub fn decoded_params_json(params: &[abi::LogParam]) -> String {
+    let entries: Vec<String> = params
+        .iter()
+        .map(|param| {
+            let name = serde_json::Value::String(param.name.clone());
+            format!("{}:{}", name, token_json(&param.value))
+        })
+        .collect();
+    format!("{{{}}}", entries.join(","))
+}
+
+fn token_json(token: &Token) -> serde_json::Value {
+    match token {
+        Token::Address(a) => serde_json::Value::String(format!("{:?}", a)),
+        Token::FixedBytes(b) | Token::Bytes(b) => serde_json::Value::String(prefix_hex::encode(b)),
+        Token::Uint(i) => serde_json::Value::String(i.to_string()),
+        Token::Int(i) => serde_json::Value::String(I256::from_raw(*i).to_string()),
+        Token::Bool(b) => serde_json::Value::Bool(*b),
+        Token::String(s) => serde_json::Value::String(s.clone()),
+        Token::Array(tokens) | Token::FixedArray(tokens) | Token::Tuple(tokens) => {
+            serde_json::Value::Array(tokens.iter().map(token_json).collect())
+        }
+    }
+}
+
 /// container for log decoding context
 #[derive(Clone, Debug, PartialEq)]
 pub struct LogDecoder {
@@ -19,7 +56,7 @@ impl LogDecoder {
     /// ex: LogDecoder::new("event Transfer(address indexed from, address indexed to, uint256
     /// amount)".to_string())
     pub fn new(event_signature: String) -> Result<Self, String> {
-        match HumanReadableParser::parse_event(event_signature.as_str()) {
+        match parse_event_signature(event_signature.as_str()) {
             Ok(event) => Ok(Self { event, raw: event_signature.clone() }),
             Err(e) => {
                 let err = format!("incorrectly formatted event {} (expect something like event Transfer(address indexed from, address indexed to, uint256 amount) err: {}", event_signature, e);
@@ -34,6 +71,17 @@ impl LogDecoder {
         self.event.inputs.iter().map(|i| i.name.clone()).collect()
     }
 
+    /// which of the 4 topic slots of the event hold addresses, according to the event abi
+    pub fn address_topics(&self) -> [bool; 4] {
+        let mut address_topics = [false; 4];
+        let first_topic = if self.event.anonymous { 0 } else { 1 };
+        let indexed = self.event.inputs.iter().filter(|input| input.indexed);
+        for (slot, input) in address_topics.iter_mut().skip(first_topic).zip(indexed) {
+            *slot = input.kind == ParamType::Address;
+        }
+        address_topics
+    }
+
     /// converts from a log type to an abi token type
     /// this function assumes all logs are of the same type and skips fields if they don't match the
     /// passed event definition
@@ -75,6 +123,8 @@ impl LogDecoder {
         let mut i256s: Vec<I256> = vec![];
         let mut bytes: Vec<Vec<u8>> = vec![];
         let mut hexes: Vec<String> = vec![];
+        let mut fixed_bytes: Vec<Vec<u8>> = vec![];
+        let mut fixed_hexes: Vec<String> = vec![];
         let mut bools: Vec<bool> = vec![];
         let mut strings: Vec<String> = vec![];
         // TODO: support array & tuple types
@@ -88,16 +138,34 @@ impl LogDecoder {
             .collect::<Vec<EventParam>>();
         let param = param.first();
 
+        // bytesN args hold exactly N bytes, indexed dynamic args are stored as their 32 byte hash
+        let fixed_size = param.and_then(|param| match param.kind {
+            ParamType::FixedBytes(size) => Some(size),
+            _ if param.indexed => Some(32),
+            _ => None,
+        });
+
         for token in data {
             match token {
                 Token::Address(a) => match column_encoding {
                     ColumnEncoding::Binary => bytes.push(a.to_fixed_bytes().into()),
                     ColumnEncoding::Hex => hexes.push(format!("{:?}", a)),
                 },
-                Token::FixedBytes(b) => match column_encoding {
-                    ColumnEncoding::Binary => bytes.push(b),
-                    ColumnEncoding::Hex => hexes.push(b.encode_hex()),
-                },
+                Token::FixedBytes(b) => {
+                    if let Some(size) = fixed_size.filter(|size| *size != b.len()) {
+                        let message = format!(
+                            "could not parse column {}, expected {} bytes but got {}",
+                            name,
+                            size,
+                            b.len()
+                        );
+                        return Err(err(message.as_str()))
+                    }
+                    match column_encoding {
+                        ColumnEncoding::Binary => fixed_bytes.push(b),
+                        ColumnEncoding::Hex => fixed_hexes.push(b.encode_hex()),
+                    }
+                }
                 Token::Bytes(b) => match column_encoding {
                     ColumnEncoding::Binary => bytes.push(b),
                     ColumnEncoding::Hex => hexes.push(b.encode_hex()),
@@ -148,7 +216,7 @@ impl LogDecoder {
 
         // check each vector, see if it contains any values, if it does, check if it's the same
         // length as the input data and map to a series
-        let name = format!("event__{}", name);
+        let name = decoded_column_name(&name);
         if !ints.is_empty() {
             Ok(vec![Series::new(name.as_str(), ints)])
         } else if !i256s.is_empty() {
@@ -185,6 +253,25 @@ impl LogDecoder {
                 return Err(err(mixed_length_err))
             }
             Ok(vec![Series::new(name.as_str(), hexes)])
+        } else if !fixed_bytes.is_empty() {
+            if fixed_bytes.len() != chunk_len {
+                return Err(err(mixed_length_err))
+            }
+            match fixed_size {
+                // fixed-size args are stored as fixed-width arrays of their N bytes
+                Some(size) => {
+                    let rows: Vec<Series> =
+                        fixed_bytes.into_iter().map(|b| Series::new("", b)).collect();
+                    let dtype = DataType::Array(Box::new(DataType::UInt8), size);
+                    Ok(vec![Series::new(name.as_str(), rows).cast(&dtype)?])
+                }
+                None => Ok(vec![Series::new(name.as_str(), fixed_bytes)]),
+            }
+        } else if !fixed_hexes.is_empty() {
+            if fixed_hexes.len() != chunk_len {
+                return Err(err(mixed_length_err))
+            }
+            Ok(vec![Series::new(name.as_str(), fixed_hexes)])
         } else if !bools.is_empty() {
             if bools.len() != chunk_len {
                 return Err(err(mixed_length_err))
@@ -201,3 +288,267 @@ impl LogDecoder {
         }
     }
 }
+
+/// parse a human-readable event signature, the `event` keyword and parameter names are optional
+/// so that signatures like Transfer(address indexed,address indexed,uint256) are accepted.
+/// unnamed parameters are named by position (arg0, arg1, ...)
+fn parse_event_signature(signature: &str) -> Result<abi::Event, String> {
+    let signature = signature.trim();
+    if !signature.ends_with(')') && !signature.ends_with("anonymous") {
+        return Err("signature must end with a closing parenthesis".to_string())
+    }
+    let mut event = HumanReadableParser::parse_event(signature).map_err(|e| e.to_string())?;
+    if event.name.is_empty() {
+        return Err("signature is missing an event name".to_string())
+    }
+    let mut names = HashSet::new();
+    for (i, input) in event.inputs.iter_mut().enumerate() {
+        if input.name.is_empty() {
+            input.name = format!("arg{}", i);
+        }
+        if !names.insert(input.name.clone()) {
+            return Err(format!("duplicate parameter name {}", input.name))
+        }
+    }
+    Ok(event)
+}
+
+/// log decoders keyed by block range, for events whose abi changed over time (e.g. proxy upgrades)
+///
+/// ranges may not overlap, and every decoder must have the same parameter names and types so
+/// that all logs produce the same columns (indexing and parameter order may differ). logs in
+/// blocks outside of every range use the fallback decoder if there is one, otherwise they are
+/// dropped because they cannot be decoded
+#[derive(Clone, Debug, PartialEq)]
+pub struct RangedLogDecoder {
+    ranges: Vec<DecoderRange>,
+    fallback: Option<LogDecoder>,
+}
+
+/// decoder used for the logs of a block range
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecoderRange {
+    /// first block of range
+    pub start_block: u64,
+    /// end of range (exclusive), None means unbounded
+    pub end_block: Option<u64>,
+    /// decoder used within range
+    pub decoder: LogDecoder,
+}
+
+impl DecoderRange {
+    /// parse range from START:END=SIGNATURE, END is exclusive and may be omitted
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid event signature range: {}", spec);
+        let (range, signature) = spec.split_once('=').ok_or_else(invalid)?;
+        let (start_block, end_block) = range.split_once(':').ok_or_else(invalid)?;
+        let start_block = start_block.trim().parse::<u64>().map_err(|_| invalid())?;
+        let end_block = match end_block.trim() {
+            "" => None,
+            end_block => Some(end_block.parse::<u64>().map_err(|_| invalid())?),
+        };
+        if end_block.map_or(false, |end_block| end_block <= start_block) {
+            return Err(format!("empty event signature range: {}", spec))
+        }
+        let decoder = LogDecoder::new(signature.trim().to_string())?;
+        Ok(DecoderRange { start_block, end_block, decoder })
+    }
+
+    /// whether block is within range
+    pub fn contains(&self, block_number: u64) -> bool {
+        block_number >= self.start_block && self.end_block.map_or(true, |end| block_number < end)
+    }
+}
+
+impl RangedLogDecoder {
+    /// create decoder from ranges, failing if ranges overlap or produce different columns
+    pub fn new(
+        mut ranges: Vec<DecoderRange>,
+        fallback: Option<LogDecoder>,
+    ) -> Result<Self, String> {
+        if ranges.is_empty() {
+            return Err("at least one event signature range is required".to_string())
+        }
+        ranges.sort_by_key(|range| range.start_block);
+        for pair in ranges.windows(2) {
+            if pair[0].end_block.map_or(true, |end| end > pair[1].start_block) {
+                return Err(format!(
+                    "event signature ranges overlap: {} and {}",
+                    pair[0].decoder.raw, pair[1].decoder.raw
+                ))
+            }
+        }
+
+        let params = |decoder: &LogDecoder| {
+            let mut params: Vec<_> = decoder
+                .event
+                .inputs
+                .iter()
+                .map(|input| (input.name.clone(), input.kind.to_string()))
+                .collect();
+            params.sort();
+            params
+        };
+        let expected = params(&ranges[0].decoder);
+        for decoder in ranges.iter().map(|range| &range.decoder).chain(fallback.iter()) {
+            if params(decoder) != expected {
+                return Err(format!(
+                    "event signatures must have the same parameter names and types: {} and {}",
+                    ranges[0].decoder.raw, decoder.raw
+                ))
+            }
+        }
+
+        Ok(RangedLogDecoder { ranges, fallback })
+    }
+
+    /// decoder whose event defines the output columns
+    pub fn primary(&self) -> &LogDecoder {
+        &self.ranges[0].decoder
+    }
+
+    /// decoder to use for a log in block_number
+    pub fn decoder_at(&self, block_number: u64) -> Option<&LogDecoder> {
+        self.ranges
+            .iter()
+            .find(|range| range.contains(block_number))
+            .map(|range| &range.decoder)
+            .or(self.fallback.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_from_signature_list() {
+        let transfer =
+            LogDecoder::new("Transfer(address indexed,address indexed,uint256)".to_string())
+                .unwrap();
+        let approval = LogDecoder::new(
+            "event Approval(address indexed owner, address indexed spender, uint256 value)"
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(transfer.field_names(), vec!["arg0", "arg1", "arg2"]);
+        assert!(transfer.event.inputs[0].indexed && !transfer.event.inputs[2].indexed);
+
+        let (a, b) = (H160::from_low_u64_be(0xaaaa), H160::from_low_u64_be(0xbbbb));
+        let log = |decoder: &LogDecoder, amount: u64| Log {
+            topics: vec![decoder.event.signature(), H256::from(a), H256::from(b)],
+            data: ethers_core::abi::encode(&[Token::Uint(U256::from(amount))]).into(),
+            ..Default::default()
+        };
+        let decoded = transfer.event.parse_log(RawLog::from(log(&transfer, 5))).unwrap();
+        assert_eq!(transfer.event.name, "Transfer");
+        assert_eq!(decoded.params[1].name, "arg1");
+        assert_eq!(decoded.params[1].value, Token::Address(b));
+        assert_eq!(decoded.params[2].value, Token::Uint(U256::from(5u64)));
+
+        let decoded = approval.event.parse_log(RawLog::from(log(&approval, 7))).unwrap();
+        assert_eq!(approval.event.name, "Approval");
+        assert_eq!(decoded.params[0].name, "owner");
+        assert_eq!(decoded.params[2].value, Token::Uint(U256::from(7u64)));
+
+        // logs of other events do not decode
+        assert!(transfer.event.parse_log(RawLog::from(log(&approval, 1))).is_err());
+
+        // malformed signatures
+        let parse = |signature: &str| LogDecoder::new(signature.to_string());
+        assert!(parse("Transfer(address indexed,uint256").is_err());
+        assert!(parse("Transfer(adress indexed,uint256)").is_err());
+        assert!(parse("(address,uint256)").is_err());
+        assert!(parse("Transfer(address from, uint256 from)").is_err());
+    }
+
+    #[test]
+    fn test_fixed_and_dynamic_bytes_decoded_distinctly() {
+        let decoder =
+            LogDecoder::new("event Stored(bytes32 indexed key, bytes4 tag, bytes value)".into())
+                .unwrap();
+        let key = H256::from_low_u64_be(0xabcd);
+        let values: Vec<Vec<u8>> = vec![vec![1, 2, 3], vec![]];
+        let logs = values
+            .iter()
+            .map(|value| Log {
+                topics: vec![decoder.event.signature(), key],
+                data: ethers_core::abi::encode(&[
+                    Token::FixedBytes(vec![0xde, 0xad, 0xbe, 0xef]),
+                    Token::Bytes(value.clone()),
+                ])
+                .into(),
+                ..Default::default()
+            })
+            .collect();
+        let mut tokens = decoder.parse_log_from_event(logs);
+        let series = |name: &str, encoding: &ColumnEncoding| {
+            let data = tokens[name].clone();
+            decoder.make_series(name.to_string(), data, 2, &[U256Type::Binary], encoding).unwrap()
+        };
+
+        // fixed-size args are fixed-width arrays of exactly N bytes
+        let fixed_bytes = |column: Series, size: usize| {
+            assert_eq!(column.dtype(), &DataType::Array(Box::new(DataType::UInt8), size));
+            let column = column.cast(&DataType::List(Box::new(DataType::UInt8))).unwrap();
+            column.explode().unwrap().u8().unwrap().into_no_null_iter().collect::<Vec<u8>>()
+        };
+        let key_column = fixed_bytes(series("key", &ColumnEncoding::Binary).remove(0), 32);
+        assert_eq!(key_column, [key.as_bytes(), key.as_bytes()].concat());
+        let tag_column = fixed_bytes(series("tag", &ColumnEncoding::Binary).remove(0), 4);
+        assert_eq!(tag_column, [0xde, 0xad, 0xbe, 0xef, 0xde, 0xad, 0xbe, 0xef]);
+
+        // dynamic args are variable-length binary of their own length
+        let value_column = series("value", &ColumnEncoding::Binary).remove(0);
+        let value_column: Vec<_> = value_column.binary().unwrap().into_iter().collect();
+        assert_eq!(value_column, vec![Some(&[1u8, 2, 3][..]), Some(&[][..])]);
+
+        // hex columns keep their existing encoding
+        let tag_column = series("tag", &ColumnEncoding::Hex).remove(0);
+        let tag_hex = vec![0xdeu8, 0xad, 0xbe, 0xef].encode_hex();
+        assert!(tag_column.equals(&Series::new("event__tag", vec![tag_hex; 2])));
+        let value_column = series("value", &ColumnEncoding::Hex).remove(0);
+        let value_hexes: Vec<_> = values.iter().map(|value| value.clone().encode_hex()).collect();
+        assert!(value_column.equals(&Series::new("event__value", value_hexes)));
+
+        // fixed-size args of the wrong length are rejected
+        tokens.insert("tag".to_string(), vec![Token::FixedBytes(vec![0xde]); 2]);
+        let data = tokens["tag"].clone();
+        let result = decoder.make_series(
+            "tag".into(),
+            data,
+            2,
+            &[U256Type::Binary],
+            &ColumnEncoding::Binary,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_ranged_decoder_policy() {
+        let old = "0:100=event Transfer(address from, address to, uint256 value)";
+        let new = "100:=event Transfer(address indexed from, address indexed to, uint256 value)";
+        let ranges = vec![DecoderRange::parse(new).unwrap(), DecoderRange::parse(old).unwrap()];
+        let decoder = RangedLogDecoder::new(ranges, None).unwrap();
+        assert!(!decoder.decoder_at(99).unwrap().event.inputs[0].indexed);
+        assert!(decoder.decoder_at(100).unwrap().event.inputs[0].indexed);
+
+        // overlapping ranges are rejected
+        let overlapping = "50:=event Transfer(address indexed from, address to, uint256 value)";
+        let ranges =
+            vec![DecoderRange::parse(old).unwrap(), DecoderRange::parse(overlapping).unwrap()];
+        assert!(RangedLogDecoder::new(ranges, None).is_err());
+
+        // ranges producing different columns are rejected
+        let renamed = "100:=event Transfer(address from, address to, uint256 amount)";
+        let ranges = vec![DecoderRange::parse(old).unwrap(), DecoderRange::parse(renamed).unwrap()];
+        assert!(RangedLogDecoder::new(ranges, None).is_err());
+
+        // blocks outside every range use the fallback, if any
+        let ranges = vec![DecoderRange::parse(new).unwrap()];
+        assert!(RangedLogDecoder::new(ranges.clone(), None).unwrap().decoder_at(5).is_none());
+        let fallback = LogDecoder::new(old.split_once('=').unwrap().1.to_string()).unwrap();
+        let decoder = RangedLogDecoder::new(ranges, Some(fallback.clone())).unwrap();
+        assert_eq!(decoder.decoder_at(5), Some(&fallback));
+    }
+}
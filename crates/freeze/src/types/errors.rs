@@ -87,6 +87,21 @@ pub enum CollectError {
     RPCError(String),
 }
 
+impl CollectError {
+    /// code, message, and data of the JSON-RPC error object returned by the provider, verbatim
+    ///
+    /// only the error object of the response is kept, so request urls and headers are never
+    /// part of it
+    pub fn json_rpc_error(&self) -> Option<serde_json::Value> {
+        match self {
+            CollectError::ProviderError(e) => {
+                e.as_error_response().and_then(|e| serde_json::to_value(e).ok())
+            }
+            _ => None,
+        }
+    }
+}
+
 /// Error related to parsing
 #[derive(Error, Debug)]
 pub enum ParseError {
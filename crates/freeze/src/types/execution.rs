@@ -1,6 +1,18 @@
-use crate::CollectError;
+use crate::{err, CollectError};
 use indicatif::ProgressBar;
-use std::{path::PathBuf, sync::Arc, time::SystemTime};
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
+use tokio::{sync::Notify, time::Instant};
+
+/// number of execution environments built by this process, keeps run ids unique
+static N_RUNS: AtomicU64 = AtomicU64::new(0);
 
 /// configuration of execution environment
 #[derive(Clone)]
@@ -25,6 +37,29 @@ pub struct ExecutionEnv {
     pub t_end: Option<SystemTime>,
     /// report directory
     pub report_dir: Option<PathBuf>,
+    /// number of most recent reports to keep
+    pub report_retention: Option<usize>,
+    /// cadence at which the incomplete report is updated during the run
+    pub report_interval: Option<ReportInterval>,
+    /// limiter on number of blocks collected per second
+    pub block_rate_limiter: Option<Arc<BlockRateLimiter>>,
+    /// bound on bytes of dataframes collected but not yet written
+    pub in_flight_bytes: Option<Arc<InFlightBytes>>,
+    /// partitions flagged for taking much longer than the rolling mean of recent partitions
+    pub slow_partitions: Option<Arc<SlowPartitions>>,
+    /// bound on number of collected partitions written concurrently, independent of the number of
+    /// partitions collected concurrently
+    pub write_semaphore: Option<Arc<tokio::sync::Semaphore>>,
+    /// wall-clock budget after which no new partitions are started
+    pub max_duration: Option<Duration>,
+    /// streak of consecutive partition failures that aborts the run
+    pub failure_streak: Option<Arc<FailureStreak>>,
+    /// abort on the first partition failure and cancel in-flight partitions
+    pub fail_fast: bool,
+    /// id distinguishing runs that start at the same time, from process id and a counter
+    pub run_id: String,
+    /// command run once the run finishes
+    pub post_run_hook: Option<PostRunHook>,
 }
 
 impl ExecutionEnv {
@@ -37,6 +72,21 @@ impl ExecutionEnv {
     pub fn set_end_time(self) -> Self {
         ExecutionEnv { t_end: Some(SystemTime::now()), ..self }
     }
+
+    /// whether the wall-clock budget of the run, measured from t_start, has elapsed
+    pub fn time_budget_elapsed(&self) -> bool {
+        match self.max_duration {
+            Some(max_duration) => {
+                self.t_start.elapsed().map_or(false, |elapsed| elapsed >= max_duration)
+            }
+            None => false,
+        }
+    }
+
+    /// whether the run was aborted after too many consecutive partition failures
+    pub fn aborted(&self) -> bool {
+        self.failure_streak.as_ref().map_or(false, |streak| streak.tripped())
+    }
 }
 
 impl Default for ExecutionEnv {
@@ -67,6 +117,16 @@ pub struct ExecutionEnvBuilder {
     t_start: SystemTime,
     t_end: Option<SystemTime>,
     report_dir: Option<PathBuf>,
+    report_retention: Option<usize>,
+    report_interval: Option<ReportInterval>,
+    block_rate_limiter: Option<Arc<BlockRateLimiter>>,
+    in_flight_bytes: Option<Arc<InFlightBytes>>,
+    slow_partitions: Option<Arc<SlowPartitions>>,
+    write_semaphore: Option<Arc<tokio::sync::Semaphore>>,
+    max_duration: Option<Duration>,
+    failure_streak: Option<Arc<FailureStreak>>,
+    fail_fast: bool,
+    post_run_hook: Option<PostRunHook>,
 }
 
 impl Default for ExecutionEnvBuilder {
@@ -82,6 +142,16 @@ impl Default for ExecutionEnvBuilder {
             t_start: SystemTime::now(),
             t_end: None,
             report_dir: None,
+            report_retention: None,
+            report_interval: None,
+            block_rate_limiter: None,
+            in_flight_bytes: None,
+            slow_partitions: None,
+            write_semaphore: None,
+            max_duration: None,
+            failure_streak: None,
+            fail_fast: false,
+            post_run_hook: None,
         }
     }
 }
@@ -116,6 +186,73 @@ impl ExecutionEnvBuilder {
         self
     }
 
+    /// set number of most recent reports to keep
+    pub fn report_retention(mut self, report_retention: Option<usize>) -> Self {
+        self.report_retention = report_retention;
+        self
+    }
+
+    /// set maximum number of blocks collected per second
+    pub fn blocks_per_second(mut self, blocks_per_second: Option<u64>) -> Self {
+        self.block_rate_limiter =
+            blocks_per_second.map(|rate| Arc::new(BlockRateLimiter::new(rate)));
+        self
+    }
+
+    /// set maximum bytes of collected dataframes waiting to be written before new partitions pause
+    pub fn max_in_flight_bytes(mut self, max_bytes: Option<u64>) -> Self {
+        self.in_flight_bytes = max_bytes.map(|max| Arc::new(InFlightBytes::new(max)));
+        self
+    }
+
+    /// set cadence at which the incomplete report is updated during the run
+    pub fn report_interval(mut self, report_interval: Option<ReportInterval>) -> Self {
+        self.report_interval = report_interval;
+        self
+    }
+
+    /// warn about partitions taking longer than this multiple of the rolling mean of recent
+    /// partitions
+    pub fn warn_if_slow(mut self, threshold: Option<f64>) -> Self {
+        self.slow_partitions = threshold.map(|threshold| Arc::new(SlowPartitions::new(threshold)));
+        self
+    }
+
+    /// set number of collected partitions written concurrently, partitions waiting to be written
+    /// free their collection slot for the next partition
+    pub fn write_concurrency(mut self, write_concurrency: Option<usize>) -> Self {
+        self.write_semaphore =
+            write_concurrency.map(|n| Arc::new(tokio::sync::Semaphore::new(n.max(1))));
+        self
+    }
+
+    /// set wall-clock budget after which no new partitions are started
+    pub fn max_duration(mut self, max_duration: Option<Duration>) -> Self {
+        self.max_duration = max_duration;
+        self
+    }
+
+    /// set number of consecutive partition failures after which the run is aborted
+    pub fn max_consecutive_failures(mut self, max_failures: Option<u64>) -> Self {
+        self.failure_streak = max_failures.map(|max| Arc::new(FailureStreak::new(max)));
+        self
+    }
+
+    /// abort on the first partition failure, cancelling partitions that are still in flight
+    pub fn fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        if fail_fast {
+            self.failure_streak = Some(Arc::new(FailureStreak::new(1)));
+        }
+        self
+    }
+
+    /// set command run once the run finishes
+    pub fn post_run_hook(mut self, post_run_hook: Option<PostRunHook>) -> Self {
+        self.post_run_hook = post_run_hook;
+        self
+    }
+
     /// progress bar size
     pub fn bar(mut self, n: u64) -> Result<Self, CollectError> {
         self.bar = Some(new_bar(n)?);
@@ -147,6 +284,426 @@ impl ExecutionEnvBuilder {
             t_start: self.t_start,
             t_end: self.t_end,
             report_dir: self.report_dir,
+            report_retention: self.report_retention,
+            report_interval: self.report_interval,
+            block_rate_limiter: self.block_rate_limiter,
+            in_flight_bytes: self.in_flight_bytes,
+            slow_partitions: self.slow_partitions,
+            write_semaphore: self.write_semaphore,
+            max_duration: self.max_duration,
+            failure_streak: self.failure_streak,
+            fail_fast: self.fail_fast,
+            run_id: format!("{}_{}", std::process::id(), N_RUNS.fetch_add(1, Ordering::Relaxed)),
+            post_run_hook: self.post_run_hook,
+        }
+    }
+}
+
+/// cadence of report snapshots written while a run is in progress
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReportInterval {
+    /// snapshot after every n finished partitions
+    Partitions(u64),
+    /// snapshot once this much time has passed, checked as partitions finish
+    Duration(Duration),
+}
+
+impl ReportInterval {
+    /// whether a snapshot is due given the partitions finished and time elapsed since the last
+    pub fn is_due(&self, n_partitions: u64, elapsed: Duration) -> bool {
+        match self {
+            ReportInterval::Partitions(n) => n_partitions >= *n,
+            ReportInterval::Duration(interval) => elapsed >= *interval,
+        }
+    }
+}
+
+/// shell command run once a freeze finishes, e.g. to trigger downstream jobs
+///
+/// the command runs through the system shell with the privileges and environment of cryo, so it
+/// must come from a trusted source and should quote the report path it is given
+#[derive(Clone, Debug)]
+pub struct PostRunHook {
+    /// shell command
+    pub command: String,
+    /// also run after runs in which some chunks did not complete
+    pub always: bool,
+    /// count a failure of the command as an error of the run
+    pub strict: bool,
+}
+
+impl PostRunHook {
+    /// run command, passing the report path as its first argument
+    ///
+    /// the report path, output dir, and status of the run are also set as CRYO_REPORT_PATH,
+    /// CRYO_OUTPUT_DIR, and CRYO_RUN_STATUS, the report path is empty if no report was written
+    pub fn run(
+        &self,
+        output_dir: &Path,
+        report_path: Option<&Path>,
+        status: &str,
+    ) -> Result<(), CollectError> {
+        let report_path = report_path.map(|path| path.to_string_lossy().to_string());
+        let report_path = report_path.unwrap_or_default();
+        let mut command = if cfg!(windows) {
+            let mut command = std::process::Command::new("cmd");
+            command.arg("/C").arg(&self.command);
+            command
+        } else {
+            let mut command = std::process::Command::new("sh");
+            command.arg("-c").arg(&self.command).arg("cryo");
+            command
+        };
+        let exit_status = command
+            .arg(&report_path)
+            .env("CRYO_REPORT_PATH", &report_path)
+            .env("CRYO_OUTPUT_DIR", output_dir)
+            .env("CRYO_RUN_STATUS", status)
+            .status()
+            .map_err(|e| err(format!("could not run post-run hook: {}", e).as_str()))?;
+        match exit_status.code() {
+            Some(0) => Ok(()),
+            Some(code) => Err(err(format!("post-run hook exited with status {}", code).as_str())),
+            None => Err(err("post-run hook was terminated by a signal")),
+        }
+    }
+}
+
+/// counts consecutive partition failures, tripping once max_failures is reached
+///
+/// any success resets the count, so scattered failures never trip the streak
+#[derive(Debug, Default)]
+pub struct FailureStreak {
+    /// number of consecutive failures that trips the streak
+    pub max_failures: u64,
+    n_failures: AtomicU64,
+    tripped: AtomicBool,
+}
+
+impl FailureStreak {
+    /// create new streak
+    pub fn new(max_failures: u64) -> Self {
+        FailureStreak { max_failures, ..Default::default() }
+    }
+
+    /// record outcome of a partition, returning whether the streak is tripped
+    pub fn record(&self, success: bool) -> bool {
+        if success {
+            self.n_failures.store(0, Ordering::SeqCst);
+        } else if self.n_failures.fetch_add(1, Ordering::SeqCst) + 1 >= self.max_failures {
+            self.tripped.store(true, Ordering::SeqCst);
+        }
+        self.tripped()
+    }
+
+    /// trip the streak regardless of its count, returning whether this call tripped it
+    pub fn trip(&self) -> bool {
+        !self.tripped.swap(true, Ordering::SeqCst)
+    }
+
+    /// whether the streak has been tripped, once tripped it stays tripped
+    pub fn tripped(&self) -> bool {
+        self.tripped.load(Ordering::SeqCst)
+    }
+}
+
+/// token bucket over block counts, used to limit the rate at which blocks are collected
+#[derive(Debug)]
+pub struct BlockRateLimiter {
+    blocks_per_second: u64,
+    state: std::sync::Mutex<(f64, Instant)>,
+}
+
+impl BlockRateLimiter {
+    /// create limiter with an initially empty bucket
+    pub fn new(blocks_per_second: u64) -> Self {
+        let state = std::sync::Mutex::new((0.0, Instant::now()));
+        BlockRateLimiter { blocks_per_second, state }
+    }
+
+    /// wait until n_blocks can be consumed without exceeding the rate
+    ///
+    /// requests larger than one second of capacity are allowed but wait proportionally longer.
+    /// blocks are taken from the bucket before waiting, so waiters sleep concurrently, each until
+    /// the bucket has refilled past the blocks taken before it
+    pub async fn acquire(&self, n_blocks: u64) {
+        if self.blocks_per_second == 0 {
+            return
+        }
+        let rate = self.blocks_per_second as f64;
+        let tokens = {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            let (tokens, last) = *state;
+            let now = Instant::now();
+            let tokens = (tokens + now.duration_since(last).as_secs_f64() * rate).min(rate);
+            let tokens = tokens - n_blocks as f64;
+            *state = (tokens, now);
+            tokens
+        };
+        if tokens < 0.0 {
+            tokio::time::sleep(Duration::from_secs_f64(-tokens / rate)).await;
+        }
+    }
+}
+
+/// bound on the bytes of dataframes that are collected but not yet written, shared by the
+/// partitions of a run
+///
+/// partitions wait for the bound before collecting and reserve their bytes until written. since a
+/// partition's size is only known once collected, each of the concurrent chunks can overshoot the
+/// bound by one partition
+#[derive(Debug, Default)]
+pub struct InFlightBytes {
+    /// bytes at or above which new partitions wait before collecting
+    pub max_bytes: u64,
+    bytes: AtomicU64,
+    n_pauses: AtomicU64,
+    released: Notify,
+}
+
+impl InFlightBytes {
+    /// create new bound
+    pub fn new(max_bytes: u64) -> Self {
+        InFlightBytes { max_bytes, ..Default::default() }
+    }
+
+    /// wait until the bytes in flight drop below the bound
+    pub async fn wait(&self) {
+        let mut paused = false;
+        loop {
+            // register for wakeups before checking, so a release in between is not missed
+            let released = self.released.notified();
+            if self.bytes.load(Ordering::SeqCst) < self.max_bytes {
+                return
+            }
+            if !paused {
+                self.n_pauses.fetch_add(1, Ordering::SeqCst);
+                paused = true;
+            }
+            released.await;
+        }
+    }
+
+    /// hold n_bytes in flight until the reservation is dropped
+    pub fn reserve(&self, n_bytes: u64) -> InFlightReservation<'_> {
+        self.bytes.fetch_add(n_bytes, Ordering::SeqCst);
+        InFlightReservation { in_flight: self, n_bytes }
+    }
+
+    /// bytes currently in flight
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::SeqCst)
+    }
+
+    /// number of partitions that waited for the bound
+    pub fn n_pauses(&self) -> u64 {
+        self.n_pauses.load(Ordering::SeqCst)
+    }
+}
+
+/// bytes held in flight, released once dropped
+#[derive(Debug)]
+pub struct InFlightReservation<'a> {
+    in_flight: &'a InFlightBytes,
+    n_bytes: u64,
+}
+
+impl Drop for InFlightReservation<'_> {
+    fn drop(&mut self) {
+        self.in_flight.bytes.fetch_sub(self.n_bytes, Ordering::SeqCst);
+        self.in_flight.released.notify_waiters();
+    }
+}
+
+/// number of most recent partitions whose durations make up the rolling mean
+pub const SLOW_PARTITION_WINDOW: usize = 20;
+
+/// number of partitions that must finish before any partition is compared to the rolling mean
+const MIN_SLOW_PARTITION_SAMPLES: usize = 3;
+
+/// flags partitions whose collection takes longer than a multiple of the rolling mean duration of
+/// the partitions that finished before them
+#[derive(Debug)]
+pub struct SlowPartitions {
+    /// multiple of the rolling mean above which a partition is flagged
+    pub threshold: f64,
+    state: std::sync::Mutex<(VecDeque<Duration>, Vec<SlowPartition>)>,
+}
+
+/// partition flagged as slow
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct SlowPartition {
+    /// inclusive block ranges of partition
+    pub block_ranges: Vec<(u64, u64)>,
+    /// seconds taken to collect and write partition
+    pub seconds: f64,
+    /// rolling mean of the partitions before it, in seconds
+    pub mean_seconds: f64,
+}
+
+impl SlowPartitions {
+    /// create new tracker
+    pub fn new(threshold: f64) -> Self {
+        SlowPartitions { threshold, state: std::sync::Mutex::new((VecDeque::new(), Vec::new())) }
+    }
+
+    /// record duration of a finished partition, returning it if it was flagged as slow
+    pub fn record(
+        &self,
+        block_ranges: Vec<(u64, u64)>,
+        duration: Duration,
+    ) -> Option<SlowPartition> {
+        let mut state = self.state.lock().ok()?;
+        let (recent, flagged) = &mut *state;
+        let seconds = duration.as_secs_f64();
+        let slow = if recent.len() >= MIN_SLOW_PARTITION_SAMPLES {
+            let mean_seconds = recent.iter().map(|duration| duration.as_secs_f64()).sum::<f64>() /
+                recent.len() as f64;
+            (seconds > self.threshold * mean_seconds).then(|| SlowPartition {
+                block_ranges,
+                seconds,
+                mean_seconds,
+            })
+        } else {
+            None
+        };
+        if recent.len() == SLOW_PARTITION_WINDOW {
+            recent.pop_front();
+        }
+        recent.push_back(duration);
+        if let Some(slow) = &slow {
+            flagged.push(slow.clone());
+        }
+        slow
+    }
+
+    /// partitions flagged so far, in the order they finished
+    pub fn flagged(&self) -> Vec<SlowPartition> {
+        self.state.lock().map(|state| state.1.clone()).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_budget_elapsed() {
+        let env = ExecutionEnvBuilder::new().build();
+        assert!(!env.time_budget_elapsed());
+        let env = ExecutionEnvBuilder::new().max_duration(Some(Duration::from_secs(3600))).build();
+        assert!(!env.time_budget_elapsed());
+        let env = ExecutionEnvBuilder::new().max_duration(Some(Duration::ZERO)).build();
+        assert!(env.time_budget_elapsed());
+    }
+
+    #[test]
+    fn test_failure_streak_trips_only_on_consecutive_failures() {
+        // scattered failures are reset by successes
+        let streak = FailureStreak::new(3);
+        for success in [false, false, true, false, false, true, false] {
+            assert!(!streak.record(success));
+        }
+
+        // consecutive failures trip the streak, which then stays tripped
+        let streak = FailureStreak::new(3);
+        assert!(!streak.record(false));
+        assert!(!streak.record(false));
+        assert!(streak.record(false));
+        assert!(streak.record(true));
+
+        let env = ExecutionEnvBuilder::new().max_consecutive_failures(Some(1)).build();
+        assert!(!env.aborted());
+        env.failure_streak.as_ref().unwrap().record(false);
+        assert!(env.aborted());
+        assert!(!ExecutionEnvBuilder::new().build().aborted());
+    }
+
+    #[tokio::test]
+    async fn test_block_rate_limiter_stays_under_cap() {
+        let blocks_per_second = 20_000;
+        let limiter = Arc::new(BlockRateLimiter::new(blocks_per_second));
+        let t_start = Instant::now();
+        let mut tasks = Vec::new();
+        for _ in 0..20 {
+            let limiter = limiter.clone();
+            tasks.push(tokio::spawn(async move { limiter.acquire(500).await }));
+        }
+        for task in tasks {
+            task.await.unwrap();
+        }
+        let elapsed = t_start.elapsed().as_secs_f64();
+        let measured_rate = (20 * 500) as f64 / elapsed;
+        assert!(measured_rate <= blocks_per_second as f64, "rate {} over cap", measured_rate);
+    }
+
+    #[tokio::test]
+    async fn test_block_rate_limiter_waiters_sleep_concurrently() {
+        let limiter = Arc::new(BlockRateLimiter::new(10));
+        let t_start = Instant::now();
+        let sleeper = {
+            let limiter = limiter.clone();
+            tokio::spawn(async move { limiter.acquire(10).await })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // the bucket is not locked while the first waiter sleeps
+        assert!(!sleeper.is_finished());
+        assert!(limiter.state.try_lock().is_ok());
+
+        // later waiters still wait for the blocks taken before them
+        limiter.acquire(5).await;
+        assert!(t_start.elapsed() >= Duration::from_millis(1400));
+        sleeper.await.unwrap();
+        assert!(t_start.elapsed() < Duration::from_millis(1900));
+    }
+
+    #[tokio::test]
+    async fn test_in_flight_bytes_pause_collection_until_writes_drain() {
+        let in_flight = Arc::new(InFlightBytes::new(100));
+        in_flight.wait().await;
+        let reservation = in_flight.reserve(150);
+        assert_eq!(in_flight.bytes(), 150);
+
+        // a new partition waits while the collected bytes are unwritten
+        let waiter = {
+            let in_flight = in_flight.clone();
+            tokio::spawn(async move { in_flight.wait().await })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiter.is_finished());
+
+        // writing the collected partition releases the waiter
+        drop(reservation);
+        tokio::time::timeout(Duration::from_secs(5), waiter).await.unwrap().unwrap();
+        assert_eq!(in_flight.bytes(), 0);
+        assert_eq!(in_flight.n_pauses(), 1);
+    }
+
+    #[test]
+    fn test_slow_partitions_flagged_against_rolling_mean() {
+        let slow_partitions = SlowPartitions::new(3.0);
+        let record = |start: u64, seconds: u64| {
+            slow_partitions.record(vec![(start, start + 999)], Duration::from_secs(seconds))
+        };
+
+        // nothing is flagged until enough partitions have finished to form a mean
+        assert_eq!(record(0, 30), None);
+        for start in [1000, 2000, 3000] {
+            assert_eq!(record(start, 2), None);
+        }
+
+        // the mean of 30, 2, 2, 2 is 9s, so 25s is under 3x and 40s is over
+        assert_eq!(record(4000, 25), None);
+        let flagged = record(5000, 40).unwrap();
+        assert_eq!(flagged.block_ranges, vec![(5000, 5999)]);
+        assert_eq!(flagged.mean_seconds, 61.0 / 5.0);
+
+        // the mean only covers the most recent partitions
+        for start in 0..SLOW_PARTITION_WINDOW as u64 {
+            assert_eq!(record(10_000 + start * 1000, 1), None);
         }
+        assert!(record(50_000, 4).is_some());
+        assert_eq!(slow_partitions.flagged().len(), 2);
     }
 }
@@ -1,5 +1,20 @@
-use crate::{CollectError, Datatype, MetaDatatype, ParseError, Partition, Query};
-use std::{collections::HashMap, path::PathBuf};
+use crate::{
+    dataframes, BlockChunk, ChunkData, CollectError, CsvOutput, Datatype, Dim, JsonOutput,
+    MetaDatatype, OutputFormat, ParquetOutput, ParseError, Partition, Query,
+    DEFAULT_BLOCK_LABEL_WIDTH,
+};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// number of temp directories and temp files created by this process, keeps names unique
+static N_TEMP_NAMES: AtomicU64 = AtomicU64::new(0);
 
 /// Options for file output
 #[derive(Clone, Debug)]
@@ -14,14 +29,77 @@ pub struct FileOutput {
     pub subdirs: Vec<SubDir>,
     /// Whether to overwrite existing files or skip them
     pub overwrite: bool,
+    /// Collect only the blocks missing from existing files, whatever their chunking
+    pub fill_gaps: bool,
     /// File format to used for output files
     pub format: FileFormat,
+    /// Format defined outside of cryo used to write output files in place of format
+    pub custom_format: Option<Arc<dyn OutputFormat>>,
     /// Number of rows per parquet row group
     pub row_group_size: Option<usize>,
     /// Parquet statistics recording flag
     pub parquet_statistics: bool,
     /// Parquet compression options
     pub parquet_compression: polars::prelude::ParquetCompression,
+    /// Decimals that integer columns are scaled down by in csv and json output, keyed by column
+    pub column_decimals: Option<HashMap<String, u8>>,
+    /// Route rows into date=YYYY-MM-DD subdirectories by UTC day of block timestamp
+    pub split_by_day: bool,
+    /// Route rows into block_number_bucket=<n> subdirectories of this many blocks (Hive layout)
+    pub hive_bucket_size: Option<u64>,
+    /// Split the rows of each partition into files of sub-ranges of its blocks of about this size
+    pub target_partition_bytes: Option<u64>,
+    /// Merge the partitions of a run into one file per datatype, named by its overall block range
+    pub single_file: bool,
+    /// Stream the rows of the run to stdout as csv or json lines in block order, instead of
+    /// writing files
+    pub stdout: bool,
+    /// Write an index of the block ranges of each output file once the run completes
+    pub block_index: bool,
+    /// Write the filter that produced each parquet file into its key-value metadata
+    pub embed_filter: bool,
+    /// Write an empty _SUCCESS file into the output directory once a run completes fully
+    pub success_marker: bool,
+    /// Write a .count file holding the number of rows beside each output file
+    pub row_count_sidecar: bool,
+    /// Per-run directory for in-progress files, None writes them beside their output files
+    pub temp_dir: Option<PathBuf>,
+    /// Key used to write a detached signature beside each output file
+    pub signer: Option<ethers::signers::LocalWallet>,
+    /// Number of digits that block numbers are zero-padded to in file names
+    pub block_label_width: usize,
+}
+
+impl Default for FileOutput {
+    /// uncompressed parquet files of ethereum in the current directory, without extra outputs
+    fn default() -> Self {
+        FileOutput {
+            output_dir: PathBuf::new(),
+            prefix: "ethereum".to_string(),
+            suffix: None,
+            subdirs: vec![],
+            overwrite: false,
+            fill_gaps: false,
+            format: FileFormat::Parquet,
+            custom_format: None,
+            row_group_size: None,
+            parquet_statistics: true,
+            parquet_compression: polars::prelude::ParquetCompression::Uncompressed,
+            column_decimals: None,
+            split_by_day: false,
+            hive_bucket_size: None,
+            target_partition_bytes: None,
+            single_file: false,
+            stdout: false,
+            block_index: false,
+            embed_filter: false,
+            success_marker: false,
+            row_count_sidecar: false,
+            temp_dir: None,
+            signer: None,
+            block_label_width: DEFAULT_BLOCK_LABEL_WIDTH,
+        }
+    }
 }
 
 /// Possible item to use as subdirectory
@@ -36,7 +114,57 @@ pub enum SubDir {
 }
 
 impl FileOutput {
-    /// get output file paths
+    /// get output format used to write files, the custom format if there is one
+    pub fn output_format(&self) -> Arc<dyn OutputFormat> {
+        if let Some(custom_format) = &self.custom_format {
+            return custom_format.clone()
+        }
+        match self.format {
+            FileFormat::Parquet => Arc::new(ParquetOutput {
+                statistics: self.parquet_statistics,
+                compression: self.parquet_compression,
+                row_group_size: self.row_group_size,
+            }),
+            FileFormat::Csv => {
+                Arc::new(CsvOutput { column_decimals: self.column_decimals.clone() })
+            }
+            FileFormat::Json => {
+                Arc::new(JsonOutput { column_decimals: self.column_decimals.clone() })
+            }
+        }
+    }
+
+    /// whether files are written by cryo's own parquet format, which parquet-only features need
+    pub fn writes_parquet(&self) -> bool {
+        self.custom_format.is_none() && self.format == FileFormat::Parquet
+    }
+
+    /// predict output file paths of partition, without collecting data or creating directories
+    ///
+    /// returns a path for each datatype of query that has a schema, the same paths that `freeze`
+    /// writes and reports. with `split_by_day`, files are instead written within `date=YYYY-MM-DD`
+    /// subdirectories of the parent of each path and listed in a manifest under `.cryo/days`, and
+    /// with `target_partition_bytes` into files named by the block ranges that they cover, which
+    /// may span several partitions or part of one
+    pub fn output_paths(
+        &self,
+        query: &Query,
+        partition: &Partition,
+    ) -> Result<HashMap<Datatype, PathBuf>, CollectError> {
+        let (label, bucket) = self.partition_label(query, partition)?;
+        let mut paths = HashMap::new();
+        for meta_datatype in query.datatypes.iter() {
+            for datatype in meta_datatype.datatypes().into_iter() {
+                if query.schemas.contains_key(&datatype) {
+                    let path = self.labeled_path(datatype.name(), label.clone(), bucket);
+                    paths.insert(datatype, path);
+                }
+            }
+        }
+        Ok(paths)
+    }
+
+    /// get output file paths, creating their directories
     pub fn get_paths(
         &self,
         query: &Query,
@@ -66,23 +194,105 @@ impl FileOutput {
         partition: &Partition,
         datatype: Datatype,
     ) -> Result<PathBuf, CollectError> {
+        self.get_named_path(query, partition, datatype.name())
+    }
+
+    /// get output file path using a custom name in place of the datatype name
+    pub fn get_named_path(
+        &self,
+        query: &Query,
+        partition: &Partition,
+        name: String,
+    ) -> Result<PathBuf, CollectError> {
+        let (label, bucket) = self.partition_label(query, partition)?;
+        self.get_labeled_path(name, label, bucket)
+    }
+
+    /// label and hive bucket used in file paths of partition
+    fn partition_label(
+        &self,
+        query: &Query,
+        partition: &Partition,
+    ) -> Result<(String, Option<u64>), CollectError> {
+        let label = partition.label_with_width(&query.partitioned_by, self.block_label_width)?;
+        let bucket = match (self.hive_bucket_size, &partition.block_numbers) {
+            (Some(bucket_size), Some(block_numbers)) => block_numbers
+                .min_value()
+                .map(|first_block| dataframes::block_bucket(first_block, bucket_size)),
+            _ => None,
+        };
+        Ok((label, bucket))
+    }
+
+    /// create a unique per-run temp directory path within base, the directory is created lazily
+    ///
+    /// names combine process id, start time, and a process-wide counter so that concurrent runs
+    /// sharing a base directory never write to the same temp files
+    pub fn run_temp_dir(base: &Path) -> PathBuf {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |t| t.as_nanos());
+        let n = N_TEMP_NAMES.fetch_add(1, Ordering::Relaxed);
+        base.join(format!("run_{}_{}_{}", std::process::id(), nanos, n))
+    }
+
+    /// get path of in-progress file that is renamed to path once fully written
+    pub fn get_temp_path(&self, path: &Path) -> Result<PathBuf, CollectError> {
+        match &self.temp_dir {
+            Some(temp_dir) => {
+                std::fs::create_dir_all(temp_dir)
+                    .map_err(|_| ParseError::ParseError("could not create temp dir".to_string()))?;
+                let filename = path.file_name().and_then(|name| name.to_str()).unwrap_or("output");
+                let n = N_TEMP_NAMES.fetch_add(1, Ordering::Relaxed);
+                Ok(temp_dir.join(format!("{}_{}", n, filename)))
+            }
+            None => Ok(path.with_extension("_tmp")),
+        }
+    }
+
+    /// guard that removes the temp directory of the run when dropped, including on errors
+    pub fn temp_dir_cleanup(&self) -> TempDirCleanup {
+        TempDirCleanup { temp_dir: self.temp_dir.clone() }
+    }
+
+    /// get output file path of datatype for the inclusive block range start to end
+    pub(crate) fn block_range_path(
+        &self,
+        datatype: Datatype,
+        start: u64,
+        end: u64,
+    ) -> Result<PathBuf, CollectError> {
+        let partition = Partition {
+            block_numbers: Some(vec![BlockChunk::Range(start, end)]),
+            ..Default::default()
+        };
+        let label = partition.label_with_width(&[Dim::BlockNumber], self.block_label_width)?;
+        self.get_labeled_path(datatype.name(), label, None)
+    }
+
+    /// get path of zero-row file describing the schema of datatype
+    pub fn get_schema_path(&self, datatype: Datatype) -> Result<PathBuf, CollectError> {
+        self.get_labeled_path(datatype.name(), "schema".to_string(), None)
+    }
+
+    fn get_labeled_path(
+        &self,
+        name: String,
+        label: String,
+        bucket: Option<u64>,
+    ) -> Result<PathBuf, CollectError> {
+        let path = self.labeled_path(name, label, bucket);
+        if let Some(output_dir) = path.parent() {
+            std::fs::create_dir_all(output_dir)
+                .map_err(|_| ParseError::ParseError("could not create dir".to_string()))?;
+        }
+        Ok(path)
+    }
+
+    fn labeled_path(&self, name: String, label: String, bucket: Option<u64>) -> PathBuf {
+        let extension = self.output_format().extension();
         let filename = if let Some(suffix) = self.suffix.clone() {
-            format!(
-                "{}__{}__{}__{}.{}",
-                self.prefix.clone(),
-                datatype.name(),
-                suffix,
-                partition.label(&query.partitioned_by)?,
-                self.format.as_str(),
-            )
+            format!("{}__{}__{}__{}.{}", self.prefix.clone(), name, suffix, label, extension)
         } else {
-            format!(
-                "{}__{}__{}.{}",
-                self.prefix.clone(),
-                datatype.name(),
-                partition.label(&query.partitioned_by)?,
-                self.format.as_str(),
-            )
+            format!("{}__{}__{}.{}", self.prefix.clone(), name, label, extension)
         };
         let filename = std::path::Path::new(&filename).to_path_buf();
         let mut output_dir = std::path::Path::new(&self.output_dir).to_path_buf();
@@ -90,18 +300,39 @@ impl FileOutput {
             let subdir_str: String = match subdir {
                 SubDir::Network => self.prefix.clone(),
                 SubDir::Datatype => match &self.suffix {
-                    Some(suffix) => datatype.name() + "__" + suffix.as_str(),
-                    None => datatype.name(),
+                    Some(suffix) => name.clone() + "__" + suffix.as_str(),
+                    None => name.clone(),
                 },
                 SubDir::Custom(subdir_str) => subdir_str.to_string(),
             };
             output_dir = output_dir.join(std::path::Path::new(&subdir_str));
         }
+        if let Some(bucket) = bucket {
+            output_dir = output_dir.join(dataframes::bucket_dirname(bucket));
+        }
+        output_dir.join(filename)
+    }
+}
 
-        std::fs::create_dir_all(output_dir.clone())
-            .map_err(|_| ParseError::ParseError("could not create dir".to_string()))?;
+/// removes a run's temp directory when dropped
+pub struct TempDirCleanup {
+    temp_dir: Option<PathBuf>,
+}
 
-        Ok(output_dir.join(filename))
+impl TempDirCleanup {
+    /// guard that removes temp_dir when dropped
+    pub(crate) fn new(temp_dir: PathBuf) -> Self {
+        TempDirCleanup { temp_dir: Some(temp_dir) }
+    }
+}
+
+impl Drop for TempDirCleanup {
+    fn drop(&mut self) {
+        if let Some(temp_dir) = &self.temp_dir {
+            if temp_dir.exists() {
+                let _ = std::fs::remove_dir_all(temp_dir);
+            }
+        }
     }
 }
 
@@ -145,3 +376,187 @@ impl ColumnEncoding {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BlockChunk, ColumnEncoding, Dim, FileError, Table, U256Type};
+    use polars::prelude::*;
+
+    fn get_sink(output_dir: &Path, temp_dir: PathBuf) -> FileOutput {
+        FileOutput {
+            output_dir: output_dir.to_path_buf(),
+            overwrite: true,
+            format: FileFormat::Csv,
+            temp_dir: Some(temp_dir),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_padded_block_labels_sort_in_numeric_order() {
+        let output_dir = PathBuf::from("/data");
+        let query = get_query(&[Datatype::Logs]);
+        let partitions: Vec<Partition> = [(2, 3), (10, 11), (99_999_000, 99_999_999)]
+            .into_iter()
+            .chain([(100_000_000, 100_000_999), (1_000_000_000, 1_000_000_999)])
+            .map(|(start, end)| Partition {
+                block_numbers: Some(vec![BlockChunk::Range(start, end)]),
+                ..Default::default()
+            })
+            .collect();
+        let sink = FileOutput { block_label_width: 10, ..get_sink(&output_dir, PathBuf::new()) };
+        let paths: Vec<PathBuf> = partitions
+            .iter()
+            .map(|partition| sink.output_paths(&query, partition).unwrap()[&Datatype::Logs].clone())
+            .collect();
+        assert!(paths[0].ends_with("ethereum__logs__0000000002_to_0000000003.csv"));
+        let mut sorted = paths.clone();
+        sorted.sort();
+        assert_eq!(sorted, paths);
+
+        // the default width keeps names of blocks below 100M unchanged
+        let sink = get_sink(&output_dir, PathBuf::new());
+        let path = &sink.output_paths(&query, &partitions[1]).unwrap()[&Datatype::Logs];
+        assert!(path.ends_with("ethereum__logs__00000010_to_00000011.csv"));
+    }
+
+    #[test]
+    fn test_concurrent_runs_use_distinct_temp_files() {
+        let output_dir =
+            std::env::temp_dir().join(format!("cryo_test_temp_dirs_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&output_dir);
+        let temp_base = output_dir.join(".cryo/tmp");
+        let first = get_sink(&output_dir, FileOutput::run_temp_dir(&temp_base));
+        let second = get_sink(&output_dir, FileOutput::run_temp_dir(&temp_base));
+        assert_ne!(first.temp_dir, second.temp_dir);
+
+        // same output path never maps to the same temp file, even within one run
+        let path = output_dir.join("ethereum__logs__00000000_to_00000999.csv");
+        let first_temp = first.get_temp_path(&path).unwrap();
+        let second_temp = second.get_temp_path(&path).unwrap();
+        assert_ne!(first_temp, second_temp);
+        assert_ne!(first_temp, first.get_temp_path(&path).unwrap());
+        assert!(first_temp.starts_with(first.temp_dir.as_ref().unwrap()));
+
+        // written files land at their output path and temp dirs are removed when runs end
+        {
+            let _first_cleanup = first.temp_dir_cleanup();
+            let _second_cleanup = second.temp_dir_cleanup();
+            for (sink, name) in [(&first, "first.csv"), (&second, "second.csv")] {
+                let mut df =
+                    DataFrame::new(vec![Series::new("block_number", vec![1u32, 2])]).unwrap();
+                dataframes::df_to_file(&mut df, &output_dir.join(name), sink).unwrap();
+                assert!(output_dir.join(name).exists());
+            }
+            assert!(first.temp_dir.as_ref().unwrap().exists());
+        }
+        assert!(!first.temp_dir.as_ref().unwrap().exists());
+        assert!(!second.temp_dir.as_ref().unwrap().exists());
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    fn get_query(datatypes: &[Datatype]) -> Query {
+        let schemas: HashMap<Datatype, Table> = datatypes
+            .iter()
+            .map(|datatype| {
+                let schema = datatype
+                    .table_schema(
+                        &[U256Type::Binary],
+                        &ColumnEncoding::Binary,
+                        &None,
+                        &None,
+                        &None,
+                        None,
+                        None,
+                    )
+                    .unwrap();
+                (*datatype, schema)
+            })
+            .collect();
+        Query {
+            datatypes: datatypes.iter().map(|datatype| MetaDatatype::Scalar(*datatype)).collect(),
+            schemas,
+            partitioned_by: vec![Dim::BlockNumber],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_output_paths_predict_paths_without_creating_dirs() {
+        let output_dir =
+            std::env::temp_dir().join(format!("cryo_test_output_paths_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&output_dir);
+        let sink = FileOutput {
+            subdirs: vec![SubDir::Datatype],
+            hive_bucket_size: Some(1_000_000),
+            target_partition_bytes: None,
+            single_file: false,
+            stdout: false,
+            block_index: false,
+            embed_filter: false,
+            success_marker: false,
+            row_count_sidecar: false,
+            temp_dir: None,
+            ..get_sink(&output_dir, PathBuf::new())
+        };
+        let query = get_query(&[Datatype::Blocks, Datatype::Logs]);
+        let partition = Partition {
+            block_numbers: Some(vec![BlockChunk::Range(1_000_000, 1_000_999)]),
+            ..Default::default()
+        };
+
+        let predicted = sink.output_paths(&query, &partition).unwrap();
+        assert_eq!(predicted.len(), 2);
+        assert!(!output_dir.exists());
+        let logs_path = &predicted[&Datatype::Logs];
+        assert!(logs_path.starts_with(output_dir.join("logs")));
+        assert!(logs_path.to_str().unwrap().ends_with("ethereum__logs__01000000_to_01000999.csv"));
+
+        // paths match those used when writing
+        assert_eq!(sink.get_paths(&query, &partition, None).unwrap(), predicted);
+        assert!(logs_path.parent().unwrap().exists());
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    /// writes only the number of rows, standing in for a format defined outside of cryo
+    #[derive(Debug)]
+    struct RowCountOutput;
+
+    impl OutputFormat for RowCountOutput {
+        fn extension(&self) -> &'static str {
+            "rows"
+        }
+
+        fn write(&self, df: &mut DataFrame, path: &Path) -> Result<(), FileError> {
+            std::fs::write(path, df.height().to_string()).map_err(|_| FileError::FileWriteError)
+        }
+    }
+
+    #[test]
+    fn test_custom_format_names_and_writes_files() {
+        let output_dir =
+            std::env::temp_dir().join(format!("cryo_test_custom_format_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&output_dir);
+        let sink = FileOutput {
+            custom_format: Some(Arc::new(RowCountOutput)),
+            ..get_sink(&output_dir, output_dir.join(".cryo/tmp"))
+        };
+        assert!(!sink.writes_parquet());
+        let query = get_query(&[Datatype::Logs]);
+        let partition = Partition {
+            block_numbers: Some(vec![BlockChunk::Range(0, 999)]),
+            ..Default::default()
+        };
+
+        let path = sink.get_path(&query, &partition, Datatype::Logs).unwrap();
+        assert_eq!(path, output_dir.join("ethereum__logs__00000000_to_00000999.rows"));
+        let mut df = df!("block_number" => [1u32, 2, 3]).unwrap();
+        dataframes::df_to_file(&mut df, &path, &sink).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "3");
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+}
@@ -29,7 +29,10 @@ pub mod execution;
 
 /// report generation
 pub mod reports;
-pub use reports::CRYO_VERSION;
+pub use reports::{
+    load_report_retry, read_report_retry, write_multichain_report, ChainReport, LocalReportStorage,
+    ReportRetry, ReportStorage, CRYO_VERSION,
+};
 
 /// type specifications for dataframes
 #[macro_use]
@@ -51,15 +54,26 @@ pub mod summaries;
 
 pub use chunks::{
     AddressChunk, BlockChunk, CallDataChunk, Chunk, ChunkData, ChunkStats, SlotChunk, Subchunk,
-    TopicChunk, TransactionChunk,
+    TopicChunk, TransactionChunk, DEFAULT_BLOCK_LABEL_WIDTH,
 };
 pub use conversions::{bytes_to_u32, ToVecHex, ToVecU8};
 pub use dataframes::*;
 pub use datatypes::*;
-pub use files::{ColumnEncoding, FileFormat, FileOutput, SubDir};
-pub use queries::{Query, QueryLabels, TimeDimension};
-pub use schemas::{ColumnType, SchemaFunctions, Schemas, Table, U256Type};
-pub use sources::{Fetcher, RateLimiter, Source, SourceLabels};
+pub use files::{ColumnEncoding, FileFormat, FileOutput, SubDir, TempDirCleanup};
+pub use queries::{
+    AbiEventFilter, AddressLogCap, AddressLogCount, ComparisonOp, DecodedColumnCap,
+    EventNameFilter, LogAddressSet, LogBlockCache, LogBlockHeader, LogDataFilter, LogSample,
+    NullLogPolicy, NullLogs, Query, QueryLabels, TimeDimension, Topic0Exclusion, Topic0Inclusion,
+    TransactionIndexRange, DEFAULT_ADDRESSES_PER_REQUEST, DEFAULT_MAX_DECODED_COLUMNS,
+};
+pub use schemas::{
+    ColumnDescription, ColumnType, PerBlockLog, SchemaFunctions, Schemas, SqlDialect, Table,
+    TableDescription, U256Type,
+};
+pub use sources::{
+    ConcurrencyThrottle, Fetcher, LatencySummary, LatencyTracker, RateLimiter, RetryBudget, Source,
+    SourceLabels, SourceRetryPolicy, UnavailableLogRanges, DEFAULT_UNAVAILABLE_LOG_PATTERNS,
+};
 // pub(crate) use summaries::FreezeSummaryAgg;
 // pub use summaries::{FreezeChunkSummary, FreezeSummary};
 pub use summaries::{print_all_datasets, print_dataset_info, FreezeSummary};
@@ -67,7 +81,11 @@ pub use summaries::{print_all_datasets, print_dataset_info, FreezeSummary};
 pub use errors::{err, ChunkError, CollectError, FileError, FreezeError, ParseError, R};
 
 pub use collection::*;
-pub use execution::{ExecutionEnv, ExecutionEnvBuilder};
+pub use execution::{
+    BlockRateLimiter, ExecutionEnv, ExecutionEnvBuilder, FailureStreak, InFlightBytes,
+    InFlightReservation, PostRunHook, ReportInterval, SlowPartition, SlowPartitions,
+    SLOW_PARTITION_WINDOW,
+};
 
 pub use signatures::*;
 
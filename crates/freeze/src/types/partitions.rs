@@ -1,6 +1,6 @@
 use crate::{
     err, types::chunks::Subchunk, AddressChunk, BlockChunk, CallDataChunk, ChunkData, ChunkStats,
-    CollectError, Params, SlotChunk, TopicChunk, TransactionChunk,
+    CollectError, Params, SlotChunk, TopicChunk, TransactionChunk, DEFAULT_BLOCK_LABEL_WIDTH,
 };
 
 /// a dimension of chunking
@@ -238,6 +238,18 @@ macro_rules! label_partition {
     }};
 }
 
+/// name of block chunks with block numbers zero-padded to width digits
+fn block_chunks_to_name(
+    chunks: &Option<Vec<BlockChunk>>,
+    width: usize,
+) -> Result<String, CollectError> {
+    let chunks = chunks.as_ref().ok_or(err("partition chunks missing"))?;
+    match (chunks.min_value(), chunks.max_value()) {
+        (Some(min), Some(max)) => Ok(format!("{:0>width$}_to_{:0>width$}", min, max)),
+        _ => Err(CollectError::CollectError("could not determine name of chunk".to_string())),
+    }
+}
+
 fn chunks_to_name<T: ChunkData>(chunks: &Option<Vec<T>>) -> Result<String, CollectError> {
     chunks
         .as_ref()
@@ -249,6 +261,15 @@ fn chunks_to_name<T: ChunkData>(chunks: &Option<Vec<T>>) -> Result<String, Colle
 impl Partition {
     /// get label of partition
     pub fn label_pieces(&self, partitioned_by: &[Dim]) -> Result<Vec<String>, CollectError> {
+        self.label_pieces_with_width(partitioned_by, DEFAULT_BLOCK_LABEL_WIDTH)
+    }
+
+    /// get label of partition, block numbers are zero-padded to block_label_width digits
+    pub fn label_pieces_with_width(
+        &self,
+        partitioned_by: &[Dim],
+        block_label_width: usize,
+    ) -> Result<Vec<String>, CollectError> {
         let stored_pieces = self.label.clone().unwrap_or_else(|| vec![None; partitioned_by.len()]);
 
         if stored_pieces.len() != partitioned_by.len() {
@@ -262,7 +283,9 @@ impl Partition {
             let piece = match piece.clone() {
                 Some(x) => x,
                 None => match dim {
-                    Dim::BlockNumber => chunks_to_name(&self.block_numbers)?,
+                    Dim::BlockNumber => {
+                        block_chunks_to_name(&self.block_numbers, block_label_width)?
+                    }
                     Dim::TransactionHash => chunks_to_name(&self.transactions)?,
                     Dim::CallData => chunks_to_name(&self.call_datas)?,
                     Dim::Address => chunks_to_name(&self.addresses)?,
@@ -286,6 +309,15 @@ impl Partition {
         Ok(self.label_pieces(partitioned_by)?.join("__"))
     }
 
+    /// get label of partition, block numbers are zero-padded to block_label_width digits
+    pub fn label_with_width(
+        &self,
+        partitioned_by: &[Dim],
+        block_label_width: usize,
+    ) -> Result<String, CollectError> {
+        Ok(self.label_pieces_with_width(partitioned_by, block_label_width)?.join("__"))
+    }
+
     /// partition Partition along given partition dimensions
     pub fn partition(&self, partition_by: Vec<Dim>) -> Result<Vec<Partition>, CollectError> {
         let mut outputs = vec![self.clone()];
@@ -1,8 +1,20 @@
-use crate::{CollectError, Datatype, Dim, MetaDatatype, Partition, Table};
-use std::collections::{HashMap, HashSet};
+use crate::{
+    decoded_column_name, CollectError, Datatype, Dim, LogDecoder, MetaDatatype, ParseError,
+    Partition, Table,
+};
+use ethers::types::{Log, U256};
+use polars::prelude::*;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
 
 /// Query
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct Query {
     /// MetaDatatype
     pub datatypes: Vec<MetaDatatype>,
@@ -18,12 +30,46 @@ pub struct Query {
     pub exclude_failed: bool,
     /// Javascript tracer
     pub js_tracer: Option<String>,
+    /// Also output count of logs per block
+    pub logs_per_block: bool,
+    /// Cap on number of logs kept per address across the run
+    pub max_logs_per_address: Option<Arc<AddressLogCap>>,
+    /// Predicates on log data, logs must match all of them to be kept
+    pub log_data_filters: Option<Vec<LogDataFilter>>,
+    /// Range of transaction indices within each block whose logs are kept
+    pub transaction_index_range: Option<Arc<TransactionIndexRange>>,
+    /// Handling of logs missing block number, transaction hash, transaction index, or log index
+    pub null_logs: Arc<NullLogs>,
+    /// Topic0s requested together as one OR filter of each eth_getLogs request
+    pub included_topic0s: Option<Arc<Topic0Inclusion>>,
+    /// Topic0s whose logs are dropped after fetching
+    pub excluded_topic0s: Option<Arc<Topic0Exclusion>>,
+    /// Keep only logs matching the event of the log decoder
+    pub abi_events_only: Option<Arc<AbiEventFilter>>,
+    /// Names used to fill the event_name column of logs, keyed by topic0
+    pub topic0_labels: Option<HashMap<Vec<u8>, String>>,
+    /// Keep only logs whose event name matches a regex
+    pub event_name_filter: Option<Arc<EventNameFilter>>,
+    /// Keep a fixed fraction of logs, chosen by a seeded hash of their position
+    pub log_sample: Option<Arc<LogSample>>,
+    /// Topic matched at any of positions 1-3, collected with one filter per position
+    pub any_topic: Option<Vec<u8>>,
+    /// Addresses whose logs are collected, requested in chunks of addresses per call
+    pub log_address_set: Option<Arc<LogAddressSet>>,
+    /// Cap on number of distinct decoded event columns
+    pub decoded_column_cap: Arc<DecodedColumnCap>,
+    /// Write log partitions larger than this many rows in batches rather than all at once
+    pub log_batch_size: Option<usize>,
+    /// Headers of blocks fetched for log columns, shared across partitions
+    pub log_block_cache: Arc<LogBlockCache>,
+    /// Directory where raw eth_getLogs responses are dumped as json before transformation
+    pub log_response_dir: Option<PathBuf>,
     /// Labels (these are non-functional)
     pub labels: QueryLabels,
 }
 
 /// query labels (non-functional)
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct QueryLabels {
     /// align
     pub align: bool,
@@ -71,10 +117,709 @@ impl Query {
 }
 
 /// Time dimension for queries
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub enum TimeDimension {
     /// Blocks
+    #[default]
     Blocks,
     /// Transactions
     Transactions,
 }
+
+/// caps the number of logs kept for each address across all partitions of a run
+#[derive(Debug, Default)]
+pub struct AddressLogCap {
+    /// maximum number of logs kept per address
+    pub max_logs: u64,
+    counts: Mutex<HashMap<Vec<u8>, AddressLogCount>>,
+}
+
+/// number of logs kept and truncated for an address
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct AddressLogCount {
+    /// logs kept
+    pub kept: u64,
+    /// logs dropped because cap was reached
+    pub truncated: u64,
+}
+
+impl AddressLogCap {
+    /// create new cap
+    pub fn new(max_logs: u64) -> Self {
+        AddressLogCap { max_logs, ..Default::default() }
+    }
+
+    /// keep the rows of df in order until the address of each reaches the cap, counting the rows
+    /// kept and dropped
+    ///
+    /// df should be sorted, and partitions should apply the cap in block order, so that the same
+    /// logs are kept on every run
+    pub fn apply(&self, df: &DataFrame) -> Result<DataFrame, CollectError> {
+        let column = df.column("address").map_err(|_| {
+            CollectError::CollectError("max logs per address requires the address column".into())
+        })?;
+        let addresses: Vec<Option<Vec<u8>>> = match column.dtype() {
+            DataType::Utf8 => column
+                .utf8()?
+                .into_iter()
+                .map(|address| {
+                    address.map(|address| {
+                        prefix_hex::decode(address).unwrap_or_else(|_| address.as_bytes().to_vec())
+                    })
+                })
+                .collect(),
+            _ => column.binary()?.into_iter().map(|address| address.map(|a| a.to_vec())).collect(),
+        };
+
+        let mut counts = match self.counts.lock() {
+            Ok(counts) => counts,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let keep: Vec<bool> = addresses
+            .into_iter()
+            .map(|address| match address {
+                Some(address) => {
+                    let count = counts.entry(address).or_default();
+                    if count.kept < self.max_logs {
+                        count.kept += 1;
+                        true
+                    } else {
+                        count.truncated += 1;
+                        false
+                    }
+                }
+                None => true,
+            })
+            .collect();
+        drop(counts);
+        Ok(df.filter(&BooleanChunked::from_slice("keep", &keep))?)
+    }
+
+    /// per-address counts recorded so far
+    pub fn counts(&self) -> HashMap<Vec<u8>, AddressLogCount> {
+        match self.counts.lock() {
+            Ok(counts) => counts.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// total number of logs truncated across all addresses
+    pub fn n_truncated(&self) -> u64 {
+        self.counts().values().map(|count| count.truncated).sum()
+    }
+}
+
+/// range of transaction indices within a block, counting the logs kept and dropped by it
+#[derive(Debug, Default)]
+pub struct TransactionIndexRange {
+    /// first transaction index kept
+    pub start: u64,
+    /// transaction index at which logs stop being kept, exclusive, None keeps all later ones
+    pub end: Option<u64>,
+    n_kept: AtomicU64,
+    n_dropped: AtomicU64,
+}
+
+impl TransactionIndexRange {
+    /// parse range from START:END, END is exclusive and may be omitted
+    pub fn parse(spec: &str) -> Result<Self, ParseError> {
+        let invalid =
+            || ParseError::ParseError(format!("invalid transaction index range: {}", spec));
+        let (start, end) = spec.split_once(':').ok_or_else(invalid)?;
+        let start = start.trim().parse::<u64>().map_err(|_| invalid())?;
+        let end = match end.trim() {
+            "" => None,
+            end => Some(end.parse::<u64>().map_err(|_| invalid())?),
+        };
+        if end.map_or(false, |end| end <= start) {
+            let message = format!("empty transaction index range: {}", spec);
+            return Err(ParseError::ParseError(message))
+        }
+        Ok(TransactionIndexRange { start, end, ..Default::default() })
+    }
+
+    /// record a log of transaction index, returning whether the log should be kept
+    pub fn admit(&self, transaction_index: u64) -> bool {
+        let kept =
+            transaction_index >= self.start && self.end.map_or(true, |end| transaction_index < end);
+        let count = if kept { &self.n_kept } else { &self.n_dropped };
+        count.fetch_add(1, Ordering::Relaxed);
+        kept
+    }
+
+    /// number of logs kept so far
+    pub fn n_kept(&self) -> u64 {
+        self.n_kept.load(Ordering::Relaxed)
+    }
+
+    /// number of logs dropped so far
+    pub fn n_dropped(&self) -> u64 {
+        self.n_dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// topic0s whose logs are requested from the node, counting the logs it returned for them
+///
+/// the topic0s are matched node-side, so they compose with a Topic0Exclusion applied to the
+/// fetched logs: a log is kept if its topic0 is included and not excluded
+#[derive(Debug, Default)]
+pub struct Topic0Inclusion {
+    topic0s: Vec<Vec<u8>>,
+    n_matched: AtomicU64,
+}
+
+impl Topic0Inclusion {
+    /// create new inclusion of topic0s, keeping the first of repeated topic0s
+    pub fn new(topic0s: Vec<Vec<u8>>) -> Self {
+        let mut seen = HashSet::new();
+        let topic0s = topic0s.into_iter().filter(|topic0| seen.insert(topic0.clone())).collect();
+        Topic0Inclusion { topic0s, n_matched: AtomicU64::new(0) }
+    }
+
+    /// distinct included topic0s, in order of first appearance
+    pub fn topic0s(&self) -> &[Vec<u8>] {
+        &self.topic0s
+    }
+
+    /// whether the topic0 of log is included
+    pub fn includes(&self, log: &Log) -> bool {
+        log.topics.first().map_or(false, |topic0| {
+            self.topic0s.iter().any(|included| included.as_slice() == topic0.as_bytes())
+        })
+    }
+
+    /// record logs returned for the included topic0s
+    pub fn record(&self, n_logs: usize) {
+        self.n_matched.fetch_add(n_logs as u64, Ordering::Relaxed);
+    }
+
+    /// number of logs matched by the included topic0s so far, before client-side filters
+    pub fn n_matched(&self) -> u64 {
+        self.n_matched.load(Ordering::Relaxed)
+    }
+}
+
+/// topic0s whose logs are dropped after fetching, counting the logs excluded by each
+#[derive(Debug, Default)]
+pub struct Topic0Exclusion {
+    counts: HashMap<Vec<u8>, AtomicU64>,
+}
+
+impl Topic0Exclusion {
+    /// create new exclusion of topic0s
+    pub fn new(topic0s: Vec<Vec<u8>>) -> Self {
+        let counts = topic0s.into_iter().map(|topic0| (topic0, AtomicU64::new(0))).collect();
+        Topic0Exclusion { counts }
+    }
+
+    /// record a log, returning whether its topic0 is excluded
+    pub fn excludes(&self, log: &Log) -> bool {
+        match log.topics.first().and_then(|topic0| self.counts.get(topic0.as_bytes())) {
+            Some(count) => {
+                count.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// number of logs excluded so far, per excluded topic0
+    pub fn counts(&self) -> HashMap<Vec<u8>, u64> {
+        self.counts
+            .iter()
+            .map(|(topic0, count)| (topic0.clone(), count.load(Ordering::Relaxed)))
+            .collect()
+    }
+
+    /// whether topic0 is excluded
+    pub fn contains(&self, topic0: &[u8]) -> bool {
+        self.counts.contains_key(topic0)
+    }
+
+    /// number of logs excluded so far
+    pub fn n_excluded(&self) -> u64 {
+        self.counts.values().map(|count| count.load(Ordering::Relaxed)).sum()
+    }
+}
+
+/// default number of addresses of an address set filtered by each eth_getLogs request
+pub const DEFAULT_ADDRESSES_PER_REQUEST: usize = 1000;
+
+/// addresses whose logs are collected, too many for one eth_getLogs request
+///
+/// the addresses are split into chunks of chunk_size, each requested with its own filter
+#[derive(Debug, Default)]
+pub struct LogAddressSet {
+    /// maximum number of addresses per request
+    pub chunk_size: usize,
+    addresses: Vec<Vec<u8>>,
+    members: HashSet<Vec<u8>>,
+    requests: Mutex<Vec<((u64, u64), u64)>>,
+}
+
+impl LogAddressSet {
+    /// create new set of addresses, keeping the first of repeated addresses
+    pub fn new(addresses: Vec<Vec<u8>>, chunk_size: usize) -> Self {
+        let mut members = HashSet::new();
+        let addresses =
+            addresses.into_iter().filter(|address| members.insert(address.clone())).collect();
+        LogAddressSet { chunk_size, addresses, members, requests: Mutex::new(Vec::new()) }
+    }
+
+    /// distinct addresses of set, in order of first appearance
+    pub fn addresses(&self) -> &[Vec<u8>] {
+        &self.addresses
+    }
+
+    /// addresses of each request, at most chunk_size per request
+    pub fn chunks(&self) -> std::slice::Chunks<'_, Vec<u8>> {
+        self.addresses.chunks(self.chunk_size.max(1))
+    }
+
+    /// whether address belongs to set
+    pub fn contains(&self, address: &[u8]) -> bool {
+        self.members.contains(address)
+    }
+
+    /// record the number of eth_getLogs requests issued for the logs of block_range
+    pub fn record_requests(&self, block_range: (u64, u64), n_requests: u64) {
+        if let Ok(mut requests) = self.requests.lock() {
+            requests.push((block_range, n_requests));
+        }
+    }
+
+    /// number of eth_getLogs requests issued for block ranges within block_ranges
+    pub fn n_requests(&self, block_ranges: &[(u64, u64)]) -> u64 {
+        let requests = match self.requests.lock() {
+            Ok(requests) => requests,
+            Err(_) => return 0,
+        };
+        requests
+            .iter()
+            .filter(|((start, end), _)| {
+                block_ranges.iter().any(|(first, last)| first <= start && end <= last)
+            })
+            .map(|(_, n_requests)| n_requests)
+            .sum()
+    }
+}
+
+/// keeps only logs of the events of the log decoder, counting the logs skipped
+#[derive(Debug, Default)]
+pub struct AbiEventFilter {
+    n_skipped: AtomicU64,
+}
+
+impl AbiEventFilter {
+    /// record a log selected for decoder, returning whether it is a log of the decoder's event
+    ///
+    /// anonymous events have no topic0 to match, so their logs are always kept
+    pub fn admit(&self, log: &Log, decoder: &LogDecoder) -> bool {
+        if decoder.event.anonymous || log.topics.first() == Some(&decoder.event.signature()) {
+            return true
+        }
+        self.n_skipped.fetch_add(1, Ordering::Relaxed);
+        false
+    }
+
+    /// number of logs skipped so far
+    pub fn n_skipped(&self) -> u64 {
+        self.n_skipped.load(Ordering::Relaxed)
+    }
+}
+
+/// keeps only logs whose event name matches a regex, counting the logs kept and dropped
+///
+/// event names are given by the log decoder for logs it decodes and by the topic0 labels of the
+/// query otherwise, logs without a name never match
+#[derive(Debug)]
+pub struct EventNameFilter {
+    /// regex matched against event names
+    pub regex: regex::Regex,
+    n_kept: AtomicU64,
+    n_dropped: AtomicU64,
+}
+
+impl EventNameFilter {
+    /// create filter from regex pattern, the pattern must match the whole event name
+    pub fn new(pattern: &str) -> Result<Self, ParseError> {
+        let regex = regex::Regex::new(&format!("^(?:{})$", pattern)).map_err(|_| {
+            ParseError::ParseError(format!("invalid event name regex: {}", pattern))
+        })?;
+        Ok(EventNameFilter { regex, n_kept: AtomicU64::new(0), n_dropped: AtomicU64::new(0) })
+    }
+
+    /// record a log of event name, returning whether the log should be kept
+    pub fn admit(&self, event_name: Option<&str>) -> bool {
+        let kept = event_name.map_or(false, |name| self.regex.is_match(name));
+        let count = if kept { &self.n_kept } else { &self.n_dropped };
+        count.fetch_add(1, Ordering::Relaxed);
+        kept
+    }
+
+    /// number of logs kept so far
+    pub fn n_kept(&self) -> u64 {
+        self.n_kept.load(Ordering::Relaxed)
+    }
+
+    /// number of logs dropped so far
+    pub fn n_dropped(&self) -> u64 {
+        self.n_dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// keeps a fixed fraction of logs, chosen by hashing (seed, block_number, log_index)
+///
+/// whether a log is kept depends only on the seed and its position, so the same seed selects the
+/// same logs in every run regardless of partitioning
+#[derive(Debug)]
+pub struct LogSample {
+    /// fraction of logs to keep, within (0, 1]
+    pub fraction: f64,
+    /// seed of the hash selecting logs
+    pub seed: u64,
+    n_kept: AtomicU64,
+    n_dropped: AtomicU64,
+}
+
+impl LogSample {
+    /// create sample keeping fraction of logs
+    pub fn new(fraction: f64, seed: u64) -> Result<Self, ParseError> {
+        if fraction.is_nan() || fraction <= 0.0 || fraction > 1.0 {
+            let message = format!("sample fraction must be within (0, 1], got {}", fraction);
+            return Err(ParseError::ParseError(message))
+        }
+        Ok(LogSample { fraction, seed, n_kept: AtomicU64::new(0), n_dropped: AtomicU64::new(0) })
+    }
+
+    /// whether the log at block_number and log_index belongs to the sample
+    pub fn selects(&self, block_number: u64, log_index: u64) -> bool {
+        let key = [self.seed.to_be_bytes(), block_number.to_be_bytes(), log_index.to_be_bytes()];
+        let hash = ethers::utils::keccak256(key.concat());
+        let mut prefix = [0u8; 8];
+        prefix.copy_from_slice(&hash[..8]);
+        // top 53 bits of the hash as a uniform value within [0, 1)
+        let position = (u64::from_be_bytes(prefix) >> 11) as f64 / (1u64 << 53) as f64;
+        position < self.fraction
+    }
+
+    /// record a log, returning whether the log should be kept
+    pub fn admit(&self, block_number: u64, log_index: u64) -> bool {
+        let kept = self.selects(block_number, log_index);
+        let count = if kept { &self.n_kept } else { &self.n_dropped };
+        count.fetch_add(1, Ordering::Relaxed);
+        kept
+    }
+
+    /// number of logs kept so far
+    pub fn n_kept(&self) -> u64 {
+        self.n_kept.load(Ordering::Relaxed)
+    }
+
+    /// number of logs dropped so far
+    pub fn n_dropped(&self) -> u64 {
+        self.n_dropped.load(Ordering::Relaxed)
+    }
+
+    /// fraction of the logs seen so far that were kept, None if no log was seen
+    pub fn kept_fraction(&self) -> Option<f64> {
+        let n_seen = self.n_kept() + self.n_dropped();
+        (n_seen > 0).then(|| self.n_kept() as f64 / n_seen as f64)
+    }
+}
+
+/// how logs missing block number, transaction hash, transaction index, or log index are handled
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NullLogPolicy {
+    /// drop log and count it
+    #[default]
+    Drop,
+    /// fail the partition of log
+    Error,
+}
+
+/// applies a NullLogPolicy, counting the logs dropped across the run
+#[derive(Debug, Default)]
+pub struct NullLogs {
+    /// policy applied to logs with null fields
+    pub policy: NullLogPolicy,
+    n_dropped: AtomicU64,
+}
+
+impl NullLogs {
+    /// create new handler of logs with null fields
+    pub fn new(policy: NullLogPolicy) -> Self {
+        NullLogs { policy, ..Default::default() }
+    }
+
+    /// handle a log with null fields, failing if policy is Error
+    pub fn handle(&self, log: &Log) -> Result<(), CollectError> {
+        if self.policy == NullLogPolicy::Error {
+            let null_fields: Vec<&str> = [
+                ("block_number", log.block_number.is_none()),
+                ("transaction_hash", log.transaction_hash.is_none()),
+                ("transaction_index", log.transaction_index.is_none()),
+                ("log_index", log.log_index.is_none()),
+            ]
+            .into_iter()
+            .filter_map(|(name, is_null)| if is_null { Some(name) } else { None })
+            .collect();
+            let message = format!(
+                "provider returned log with null {} (block_number={:?}, transaction_hash={:?}, \
+                 log_index={:?})",
+                null_fields.join(", "),
+                log.block_number,
+                log.transaction_hash,
+                log.log_index,
+            );
+            return Err(CollectError::CollectError(message))
+        }
+        if self.n_dropped.fetch_add(1, Ordering::Relaxed) == 0 {
+            eprintln!(
+                "warning: dropping logs with null block_number, transaction_hash, \
+                 transaction_index, or log_index, use --null-log-policy error to fail instead"
+            );
+        }
+        Ok(())
+    }
+
+    /// number of logs dropped so far
+    pub fn n_dropped(&self) -> u64 {
+        self.n_dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// timestamp and base fee of a block, as needed by optional log columns
+pub type LogBlockHeader = (u32, Option<U256>);
+
+/// default maximum number of headers held by a LogBlockCache
+const DEFAULT_LOG_BLOCK_CACHE_SIZE: usize = 100_000;
+
+/// caches headers of blocks containing logs so that each block is fetched once per run
+///
+/// at most max_headers headers are held. partitions are mostly collected in increasing block
+/// order, so the headers of the lowest blocks are evicted first
+#[derive(Debug)]
+pub struct LogBlockCache {
+    headers: Mutex<BTreeMap<u64, LogBlockHeader>>,
+    max_headers: usize,
+}
+
+impl Default for LogBlockCache {
+    fn default() -> Self {
+        LogBlockCache::new(DEFAULT_LOG_BLOCK_CACHE_SIZE)
+    }
+}
+
+impl LogBlockCache {
+    /// create cache holding at most max_headers headers
+    pub fn new(max_headers: usize) -> Self {
+        LogBlockCache { headers: Mutex::new(BTreeMap::new()), max_headers: max_headers.max(1) }
+    }
+
+    /// cached header of block
+    pub fn get(&self, block_number: u64) -> Option<LogBlockHeader> {
+        match self.headers.lock() {
+            Ok(headers) => headers.get(&block_number).copied(),
+            Err(poisoned) => poisoned.into_inner().get(&block_number).copied(),
+        }
+    }
+
+    /// insert header of block
+    pub fn insert(&self, block_number: u64, header: LogBlockHeader) {
+        let mut headers = match self.headers.lock() {
+            Ok(headers) => headers,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        headers.insert(block_number, header);
+        while headers.len() > self.max_headers {
+            headers.pop_first();
+        }
+    }
+}
+
+/// default maximum number of distinct decoded event columns
+pub const DEFAULT_MAX_DECODED_COLUMNS: usize = 256;
+
+/// caps the number of distinct decoded event columns, further columns are dropped with a warning
+#[derive(Debug)]
+pub struct DecodedColumnCap {
+    /// maximum number of decoded columns
+    pub max_columns: usize,
+    dropped: Mutex<BTreeSet<String>>,
+}
+
+impl Default for DecodedColumnCap {
+    fn default() -> Self {
+        DecodedColumnCap::new(DEFAULT_MAX_DECODED_COLUMNS)
+    }
+}
+
+impl DecodedColumnCap {
+    /// create new cap
+    pub fn new(max_columns: usize) -> Self {
+        DecodedColumnCap { max_columns, dropped: Mutex::new(BTreeSet::new()) }
+    }
+
+    /// whether a new column can be added alongside n_columns existing columns
+    pub fn admit(&self, name: &str, n_columns: usize) -> bool {
+        if n_columns < self.max_columns {
+            return true
+        }
+        let mut dropped = match self.dropped.lock() {
+            Ok(dropped) => dropped,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if dropped.insert(name.to_string()) {
+            eprintln!(
+                "warning: dropping decoded column {}, exceeds --max-decoded-columns {}",
+                decoded_column_name(name),
+                self.max_columns
+            );
+        }
+        false
+    }
+
+    /// names of decoded columns dropped so far
+    pub fn dropped(&self) -> Vec<String> {
+        match self.dropped.lock() {
+            Ok(dropped) => dropped.iter().cloned().collect(),
+            Err(poisoned) => poisoned.into_inner().iter().cloned().collect(),
+        }
+    }
+}
+
+/// predicate comparing the 32-byte word at a byte offset of log data against a value
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogDataFilter {
+    /// byte offset of word within log data
+    pub offset: usize,
+    /// comparison operator
+    pub op: ComparisonOp,
+    /// value compared against, word is interpreted as big-endian uint256
+    pub value: U256,
+}
+
+/// comparison operator of a LogDataFilter
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ComparisonOp {
+    /// equal
+    Eq,
+    /// not equal
+    Ne,
+    /// greater than
+    Gt,
+    /// greater than or equal
+    Gte,
+    /// less than
+    Lt,
+    /// less than or equal
+    Lte,
+}
+
+impl LogDataFilter {
+    /// parse filter from OFFSET:OP:VALUE, e.g. 0:gt:1000 or 32:eq:0xabcd
+    pub fn parse(spec: &str) -> Result<Self, ParseError> {
+        let invalid = || ParseError::ParseError(format!("invalid log data filter: {}", spec));
+        let (offset, op, value) = match spec.split(':').collect::<Vec<_>>().as_slice() {
+            [offset, op, value] => (offset.to_string(), op.to_string(), value.to_string()),
+            _ => return Err(invalid()),
+        };
+        // the word at offset must end within the address space
+        let offset = offset.parse::<usize>().map_err(|_| invalid())?;
+        if offset.checked_add(32).is_none() {
+            return Err(invalid())
+        }
+        let op = match op.as_str() {
+            "eq" => ComparisonOp::Eq,
+            "ne" => ComparisonOp::Ne,
+            "gt" => ComparisonOp::Gt,
+            "gte" => ComparisonOp::Gte,
+            "lt" => ComparisonOp::Lt,
+            "lte" => ComparisonOp::Lte,
+            _ => return Err(invalid()),
+        };
+        let value = match value.strip_prefix("0x") {
+            Some(hex) => U256::from_str_radix(hex, 16).ok(),
+            None => U256::from_dec_str(&value).ok(),
+        };
+        let value = value.ok_or_else(invalid)?;
+        Ok(LogDataFilter { offset, op, value })
+    }
+
+    /// whether log data matches filter, data too short to contain the word never matches
+    pub fn matches(&self, data: &[u8]) -> bool {
+        let word = match self.offset.checked_add(32).and_then(|end| data.get(self.offset..end)) {
+            Some(word) => U256::from_big_endian(word),
+            None => return false,
+        };
+        match self.op {
+            ComparisonOp::Eq => word == self.value,
+            ComparisonOp::Ne => word != self.value,
+            ComparisonOp::Gt => word > self.value,
+            ComparisonOp::Gte => word >= self.value,
+            ComparisonOp::Lt => word < self.value,
+            ComparisonOp::Lte => word <= self.value,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_data_filter_rejects_offsets_past_the_address_space() {
+        assert!(LogDataFilter::parse(&format!("{}:eq:0", usize::MAX)).is_err());
+        assert!(LogDataFilter::parse(&format!("{}:eq:0", usize::MAX - 31)).is_err());
+        let filter = LogDataFilter::parse(&format!("{}:eq:0", usize::MAX - 32)).unwrap();
+        assert!(!filter.matches(&[0; 64]));
+
+        // filters built without parsing never match instead of overflowing
+        let filter =
+            LogDataFilter { offset: usize::MAX, op: ComparisonOp::Eq, value: U256::zero() };
+        assert!(!filter.matches(&[0; 64]));
+    }
+
+    #[test]
+    fn test_log_block_cache_evicts_lowest_blocks_beyond_its_size() {
+        let cache = LogBlockCache::new(2);
+        cache.insert(20, (20, None));
+        cache.insert(10, (10, None));
+        cache.insert(30, (30, Some(U256::from(7))));
+        assert_eq!(cache.get(10), None);
+        assert_eq!(cache.get(20), Some((20, None)));
+        assert_eq!(cache.get(30), Some((30, Some(U256::from(7)))));
+    }
+
+    #[test]
+    fn test_address_log_cap_keeps_first_rows_of_each_address() {
+        let cap = AddressLogCap::new(2);
+        let first = df!(
+            "block_number" => [1u32, 1, 2, 3],
+            "address" => vec![vec![1u8], vec![2u8], vec![1u8], vec![1u8]],
+        )
+        .unwrap();
+        let kept = cap.apply(&first).unwrap();
+        let block_numbers: Vec<Option<u32>> =
+            kept.column("block_number").unwrap().u32().unwrap().into_iter().collect();
+        assert_eq!(block_numbers, vec![Some(1), Some(1), Some(2)]);
+
+        // the cap carries over to later partitions, only rows that are kept count against it
+        let second =
+            df!("block_number" => [4u32, 5], "address" => vec![vec![2u8], vec![1u8]]).unwrap();
+        assert_eq!(cap.apply(&second).unwrap().height(), 1);
+        let counts = cap.counts();
+        assert_eq!((counts[&vec![1u8]].kept, counts[&vec![1u8]].truncated), (2, 2));
+        assert_eq!((counts[&vec![2u8]].kept, counts[&vec![2u8]].truncated), (2, 0));
+        assert_eq!(cap.n_truncated(), 2);
+
+        // hex encoded addresses are counted by their bytes
+        let hex = df!("address" => ["0x02"]).unwrap();
+        assert_eq!(cap.apply(&hex).unwrap().height(), 0);
+        assert_eq!(cap.counts()[&vec![2u8]].truncated, 1);
+
+        // the address column is required
+        assert!(cap.apply(&df!("block_number" => [1u32]).unwrap()).is_err());
+    }
+}
@@ -1,6 +1,11 @@
-use crate::{err, CollectError, ExecutionEnv, FileOutput, FreezeSummary, Query};
+use crate::{
+    err, row_count_path, AddressLogCount, BlockChunk, CollectError, ColumnStats, ExecutionEnv,
+    FileOutput, FreezeSummary, LatencySummary, Partition, Query, SlowPartition,
+};
 use chrono::{DateTime, Local};
+use ethers::signers::Signer;
 use std::{
+    collections::HashMap,
     fs::File,
     io::Write,
     path::{Path, PathBuf},
@@ -18,8 +23,148 @@ struct FreezeReport {
 #[derive(serde::Serialize, Debug)]
 struct SerializedFreezeSummary {
     completed_paths: Vec<PathBuf>,
+    row_count_paths: Option<Vec<PathBuf>>,
+    n_completed_with_rows: u64,
+    n_completed_empty: u64,
     errored_paths: Vec<PathBuf>,
+    errored_block_ranges: Vec<(u64, u64)>,
+    partition_errors: Vec<PartitionError>,
+    unavailable_block_ranges: Vec<(u64, u64)>,
+    unavailable_paths: Vec<PathBuf>,
     n_skipped: u64,
+    filled_gap_ranges: Vec<(u64, u64)>,
+    stopped_by_time_budget: bool,
+    budget_skipped_paths: Vec<PathBuf>,
+    aborted_after_consecutive_failures: bool,
+    abort_skipped_paths: Vec<PathBuf>,
+    column_stats: HashMap<PathBuf, HashMap<String, ColumnStats>>,
+    address_log_counts: Option<HashMap<String, AddressLogCount>>,
+    address_set_requests: Option<Vec<PartitionRequests>>,
+    transaction_index_range_counts: Option<TransactionIndexRangeCounts>,
+    n_null_logs_dropped: u64,
+    n_included_topic0_logs: Option<u64>,
+    excluded_topic0_counts: Option<HashMap<String, u64>>,
+    n_other_event_logs_skipped: Option<u64>,
+    event_name_counts: Option<EventNameCounts>,
+    log_sample_counts: Option<LogSampleCounts>,
+    dropped_decoded_columns: Vec<String>,
+    retries_used: Option<u64>,
+    throttle_events: Option<u64>,
+    in_flight_pauses: Option<u64>,
+    slow_partitions: Vec<SlowPartition>,
+    rpc_latency: Option<LatencySummary>,
+    signed_by: Option<String>,
+    encrypted: bool,
+}
+
+/// error of an errored partition
+#[derive(serde::Serialize, Debug)]
+struct PartitionError {
+    block_ranges: Vec<(u64, u64)>,
+    error: String,
+    rpc_error: Option<serde_json::Value>,
+}
+
+/// number of eth_getLogs requests issued for the address set within a completed partition
+#[derive(serde::Serialize, Debug)]
+struct PartitionRequests {
+    block_ranges: Vec<(u64, u64)>,
+    n_requests: u64,
+}
+
+/// number of logs kept and dropped by the event name regex of the run
+#[derive(serde::Serialize, Debug)]
+struct EventNameCounts {
+    kept: u64,
+    dropped: u64,
+}
+
+/// number of logs kept and dropped by the log sample of the run
+#[derive(serde::Serialize, Debug)]
+struct LogSampleCounts {
+    fraction: f64,
+    seed: u64,
+    kept: u64,
+    dropped: u64,
+    kept_fraction: Option<f64>,
+}
+
+/// number of logs kept and dropped by the transaction index range of the run
+#[derive(serde::Serialize, Debug)]
+struct TransactionIndexRangeCounts {
+    kept: u64,
+    dropped: u64,
+}
+
+/// summary of one chain within a multichain run
+#[derive(serde::Serialize, Debug, Clone, Default)]
+pub struct ChainReport {
+    /// network name of chain
+    pub network_name: String,
+    /// chain id reported by the chain's rpc, if it could be reached
+    pub chain_id: Option<u64>,
+    /// output directory of chain
+    pub output_dir: PathBuf,
+    /// number of chunks completed
+    pub n_completed: u64,
+    /// number of chunks errored
+    pub n_errored: u64,
+    /// number of chunks skipped
+    pub n_skipped: u64,
+    /// rows written
+    pub n_rows: u64,
+    /// error that stopped the chain, if any
+    pub error: Option<String>,
+}
+
+impl ChainReport {
+    /// create report of chain from its freeze result
+    pub fn new(
+        network_name: String,
+        chain_id: Option<u64>,
+        output_dir: PathBuf,
+        result: &Result<Option<FreezeSummary>, CollectError>,
+    ) -> Self {
+        let report = ChainReport { network_name, chain_id, output_dir, ..Default::default() };
+        match result {
+            Ok(Some(summary)) => ChainReport {
+                n_completed: summary.completed.len() as u64,
+                n_errored: summary.errored.len() as u64,
+                n_skipped: summary.skipped.len() as u64,
+                n_rows: summary.n_rows,
+                ..report
+            },
+            Ok(None) => report,
+            Err(e) => ChainReport { error: Some(e.to_string()), ..report },
+        }
+    }
+}
+
+#[derive(serde::Serialize, Debug)]
+struct MultichainReport<'a> {
+    cryo_version: String,
+    cli_command: Option<Vec<String>>,
+    chains: &'a [ChainReport],
+}
+
+/// write report aggregating the per-chain summaries of a multichain run
+///
+/// each chain also writes its own report within its output directory
+pub async fn write_multichain_report(
+    report_dir: &Path,
+    t_start: std::time::SystemTime,
+    cli_command: Option<Vec<String>>,
+    chains: &[ChainReport],
+) -> Result<PathBuf, CollectError> {
+    std::fs::create_dir_all(report_dir).map_err(|_| err("could not create report dir"))?;
+    let report = MultichainReport { cryo_version: CRYO_VERSION.to_string(), cli_command, chains };
+    let serialized =
+        serde_json::to_string(&report).map_err(|_| err("could not serialize report"))?;
+    let t_start: DateTime<Local> = t_start.into();
+    let filename = format!("multichain_{}.json", t_start.format("%Y-%m-%d_%H-%M-%S%.6f"));
+    let path = report_dir.join(filename);
+    store_report(&path, &serialized).await?;
+    Ok(path)
 }
 
 pub(crate) fn get_report_path(
@@ -35,9 +180,9 @@ pub(crate) fn get_report_path(
     std::fs::create_dir_all(&report_dir)
         .map_err(|_| CollectError::CollectError("could not create report dir".to_string()))?;
 
-    // create file name
+    // create file name, run id keeps names of runs started at the same time distinct
     let t_start: DateTime<Local> = env.t_start.into();
-    let timestamp: String = t_start.format("%Y-%m-%d_%H-%M-%S%.6f").to_string();
+    let timestamp = format!("{}_{}", t_start.format("%Y-%m-%d_%H-%M-%S%.6f"), env.run_id);
     let filename = if is_complete {
         timestamp + ".json"
     } else {
@@ -48,44 +193,149 @@ pub(crate) fn get_report_path(
     Ok(report_dir.join(filename))
 }
 
-pub(crate) fn write_report(
+pub(crate) async fn write_report(
     env: &ExecutionEnv,
     query: &Query,
     sink: &FileOutput,
     freeze_summary: Option<&FreezeSummary>,
 ) -> Result<PathBuf, CollectError> {
-    // determine version
-    let cryo_version = CRYO_VERSION.to_string();
+    let serialized = serialize_report(env, query, sink, freeze_summary)?;
+
+    // create path, runs stopped by their time budget or aborted are flushed as incomplete reports
+    let is_complete = freeze_summary
+        .map_or(false, |summary| summary.budget_skipped.is_empty() && !summary.aborted);
+    let path = get_report_path(env, sink, is_complete)?;
+
+    // save to file
+    store_report(&path, &serialized).await?;
+    if is_complete {
+        if let Some(report_dir) = path.parent() {
+            store_report(&report_dir.join("latest.json"), &serialized).await?;
+        }
+    }
+
+    // delete initial report
+    if is_complete {
+        let incomplete_path = get_report_path(env, sink, false)?;
+        std::fs::remove_file(incomplete_path)
+            .map_err(|_| err("could not delete initial report file"))?;
+    }
+
+    // prune old reports
+    if let (true, Some(retention)) = (is_complete, env.report_retention) {
+        if let Some(report_dir) = path.parent() {
+            prune_reports(report_dir, retention, &path)?;
+        }
+    }
+
+    Ok(path)
+}
+
+/// write summary of a run in progress to its incomplete report
+///
+/// the final report of the run replaces the snapshot once the run finishes
+pub(crate) async fn write_report_snapshot(
+    env: &ExecutionEnv,
+    query: &Query,
+    sink: &FileOutput,
+    freeze_summary: &FreezeSummary,
+) -> Result<PathBuf, CollectError> {
+    let serialized = serialize_report(env, query, sink, Some(freeze_summary))?;
+    let path = get_report_path(env, sink, false)?;
+    store_report(&path, &serialized).await?;
+    Ok(path)
+}
+
+fn serialize_report(
+    env: &ExecutionEnv,
+    query: &Query,
+    sink: &FileOutput,
+    freeze_summary: Option<&FreezeSummary>,
+) -> Result<String, CollectError> {
     let serialized_summary = match freeze_summary {
         Some(x) => Some(serialize_summary(x, query, sink)?),
         None => None,
     };
     let report = FreezeReport {
-        cryo_version,
+        cryo_version: CRYO_VERSION.to_string(),
         cli_command: env.cli_command.clone(),
         args: env.args.clone(),
         results: serialized_summary,
     };
-    let serialized = serde_json::to_string(&report)
-        .map_err(|_| CollectError::CollectError("could not serialize report".to_string()))?;
+    serde_json::to_string(&report)
+        .map_err(|_| CollectError::CollectError("could not serialize report".to_string()))
+}
 
-    // create path
-    let path = get_report_path(env, sink, freeze_summary.is_some())?;
+/// write contents of a report to a local path through the local report storage
+async fn store_report(path: &Path, contents: &str) -> Result<(), CollectError> {
+    let location = path.to_str().ok_or_else(|| err("report path is not valid utf-8"))?;
+    LocalReportStorage.write_report(location, contents).await
+}
 
-    // save to file
-    let mut file = File::create(&path)
-        .map_err(|_| CollectError::CollectError("could not create report file".to_string()))?;
-    file.write_all(serialized.as_bytes())
-        .map_err(|_| CollectError::CollectError("could not write report data".to_string()))?;
+/// write data to path by writing and fsyncing a temp file, then renaming it into place
+///
+/// a crash at any point leaves either the previous file or the complete new file at path
+fn write_atomic(path: &Path, data: &[u8]) -> Result<(), CollectError> {
+    let tmp_path = write_tmp(path, data)?;
+    std::fs::rename(&tmp_path, path).map_err(|_| err("could not move report file into place"))?;
+    if let Some(parent) = path.parent() {
+        if let Ok(dir) = File::open(parent) {
+            let _ = dir.sync_all();
+        }
+    }
+    Ok(())
+}
 
-    // delete initial report
-    if freeze_summary.is_some() {
-        let incomplete_path = get_report_path(env, sink, false)?;
-        std::fs::remove_file(incomplete_path)
-            .map_err(|_| err("could not delete initial report file"))?;
+fn write_tmp(path: &Path, data: &[u8]) -> Result<PathBuf, CollectError> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+    let mut file = File::create(&tmp_path).map_err(|_| err("could not create report file"))?;
+    file.write_all(data).map_err(|_| err("could not write report data"))?;
+    file.sync_all().map_err(|_| err("could not sync report data"))?;
+    Ok(tmp_path)
+}
+
+/// delete completed reports in report_dir beyond the newest `retention` reports
+///
+/// incomplete reports, multichain reports, `latest.json`, and `current` are never deleted
+pub(crate) fn prune_reports(
+    report_dir: &Path,
+    retention: usize,
+    current: &Path,
+) -> Result<Vec<PathBuf>, CollectError> {
+    let entries =
+        std::fs::read_dir(report_dir).map_err(|_| err("could not read report directory"))?;
+    let mut reports: Vec<PathBuf> = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(|_| err("could not read report directory"))?.path();
+        let filename = match path.file_name().and_then(|name| name.to_str()) {
+            Some(filename) => filename,
+            None => continue,
+        };
+        if path.is_file() &&
+            filename.ends_with(".json") &&
+            !filename.starts_with("incomplete_") &&
+            !filename.starts_with("multichain_") &&
+            filename != "latest.json"
+        {
+            reports.push(path);
+        }
     }
 
-    Ok(path)
+    // report filenames are fixed-width timestamps, so they sort chronologically
+    reports.sort();
+    reports.reverse();
+
+    let mut deleted = Vec::new();
+    for path in reports.into_iter().skip(retention) {
+        if path == current {
+            continue
+        }
+        std::fs::remove_file(&path).map_err(|_| err("could not delete old report file"))?;
+        deleted.push(path);
+    }
+    Ok(deleted)
 }
 
 fn serialize_summary(
@@ -93,11 +343,50 @@ fn serialize_summary(
     query: &Query,
     sink: &FileOutput,
 ) -> Result<SerializedFreezeSummary, CollectError> {
-    let completed_paths: Vec<PathBuf> = summary
-        .completed
+    // runs streaming to stdout write no files, partitions of single-file runs are only written
+    // once merged at the end of the run. other runs report the files their completed partitions
+    // wrote, which include files beside the datatype files and files routed to other paths
+    let completed_paths: Vec<PathBuf> = if sink.stdout {
+        Vec::new()
+    } else if sink.single_file {
+        let paths = crate::single_file::single_file_paths(query, sink)?;
+        paths.into_values().filter(|path| path.exists()).collect()
+    } else {
+        let mut paths: Vec<PathBuf> = summary.column_stats.keys().cloned().collect();
+        paths.sort();
+        paths
+    };
+
+    // sidecars of the completed files, written beside them as each file is moved into place
+    let row_count_paths = sink.row_count_sidecar.then(|| {
+        completed_paths
+            .iter()
+            .map(|path| row_count_path(path))
+            .filter(|path| path.exists())
+            .collect()
+    });
+
+    let errored_paths: Vec<PathBuf> = summary
+        .errored
+        .iter()
+        .filter_map(|(partition_option, _error)| {
+            partition_option.as_ref().map(|partition| {
+                sink.output_paths(query, partition)
+                    .map(|paths| paths.values().cloned().collect::<Vec<_>>())
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .flatten()
+        .collect();
+
+    // files of partitions missing unavailable blocks are not written, so that later runs, e.g.
+    // against an archive node or with --fill-gaps, collect them again
+    let unavailable_paths: Vec<PathBuf> = summary
+        .unavailable
         .iter()
         .map(|partition| {
-            sink.get_paths(query, partition, None)
+            sink.output_paths(query, partition)
                 .map(|paths| paths.values().cloned().collect::<Vec<_>>())
         })
         .collect::<Result<Vec<_>, _>>()?
@@ -105,26 +394,504 @@ fn serialize_summary(
         .flatten()
         .collect();
 
-    let errored_paths: Vec<PathBuf> = summary
-        .errored
+    let budget_skipped_paths: Vec<PathBuf> = summary
+        .budget_skipped
         .iter()
-        .filter_map(|(partition_option, _error)| {
-            partition_option.as_ref().map(|partition| {
-                sink.get_paths(query, partition, None)
-                    .map(|paths| paths.values().cloned().collect::<Vec<_>>())
-            })
+        .map(|partition| {
+            sink.output_paths(query, partition)
+                .map(|paths| paths.values().cloned().collect::<Vec<_>>())
         })
         .collect::<Result<Vec<_>, _>>()?
         .into_iter()
         .flatten()
         .collect();
 
+    let abort_skipped_paths: Vec<PathBuf> = summary
+        .abort_skipped
+        .iter()
+        .map(|partition| {
+            sink.output_paths(query, partition)
+                .map(|paths| paths.values().cloned().collect::<Vec<_>>())
+        })
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .flatten()
+        .collect();
+
+    let address_log_counts = query.max_logs_per_address.as_ref().map(|address_cap| {
+        address_cap
+            .counts()
+            .into_iter()
+            .map(|(address, count)| (prefix_hex::encode(address), count))
+            .collect()
+    });
+
+    let address_set_requests = query.log_address_set.as_ref().map(|address_set| {
+        summary
+            .completed
+            .iter()
+            .map(|partition| {
+                let block_ranges = block_ranges(partition);
+                let n_requests = address_set.n_requests(&block_ranges);
+                PartitionRequests { block_ranges, n_requests }
+            })
+            .collect()
+    });
+
+    let excluded_topic0_counts = query.excluded_topic0s.as_ref().map(|excluded| {
+        excluded
+            .counts()
+            .into_iter()
+            .map(|(topic0, count)| (prefix_hex::encode(topic0), count))
+            .collect()
+    });
+
+    let n_completed_empty = summary.completed_empty.len() as u64;
     Ok(SerializedFreezeSummary {
         completed_paths,
+        row_count_paths,
+        n_completed_with_rows: summary.completed.len() as u64 - n_completed_empty,
+        n_completed_empty,
         errored_paths,
+        errored_block_ranges: errored_block_ranges(summary),
+        partition_errors: partition_errors(summary),
+        unavailable_block_ranges: summary.unavailable_ranges.clone(),
+        unavailable_paths,
         n_skipped: summary.skipped.len() as u64,
+        filled_gap_ranges: summary.filled_gaps.clone(),
+        stopped_by_time_budget: !summary.budget_skipped.is_empty(),
+        budget_skipped_paths,
+        aborted_after_consecutive_failures: summary.aborted,
+        abort_skipped_paths,
+        column_stats: summary.column_stats.clone(),
+        address_log_counts,
+        address_set_requests,
+        transaction_index_range_counts: query.transaction_index_range.as_ref().map(|range| {
+            TransactionIndexRangeCounts { kept: range.n_kept(), dropped: range.n_dropped() }
+        }),
+        n_null_logs_dropped: query.null_logs.n_dropped(),
+        n_included_topic0_logs: query
+            .included_topic0s
+            .as_ref()
+            .map(|included| included.n_matched()),
+        excluded_topic0_counts,
+        n_other_event_logs_skipped: query.abi_events_only.as_ref().map(|filter| filter.n_skipped()),
+        event_name_counts: query
+            .event_name_filter
+            .as_ref()
+            .map(|filter| EventNameCounts { kept: filter.n_kept(), dropped: filter.n_dropped() }),
+        log_sample_counts: query.log_sample.as_ref().map(|sample| LogSampleCounts {
+            fraction: sample.fraction,
+            seed: sample.seed,
+            kept: sample.n_kept(),
+            dropped: sample.n_dropped(),
+            kept_fraction: sample.kept_fraction(),
+        }),
+        dropped_decoded_columns: query.decoded_column_cap.dropped(),
+        retries_used: summary.retries_used,
+        throttle_events: summary.throttle_events,
+        in_flight_pauses: summary.in_flight_pauses,
+        slow_partitions: summary.slow_partitions.clone(),
+        rpc_latency: summary.rpc_latency.clone(),
+        signed_by: sink.signer.as_ref().map(|signer| format!("{:?}", signer.address())),
+        // output files are signed at most, never encrypted
+        encrypted: false,
+    })
+}
+
+/// inclusive block ranges of errored partitions, one per block chunk
+fn errored_block_ranges(summary: &FreezeSummary) -> Vec<(u64, u64)> {
+    summary
+        .errored
+        .iter()
+        .flat_map(|(partition, _error)| partition.iter().flat_map(block_ranges))
+        .collect()
+}
+
+/// errors of errored partitions, with the JSON-RPC error object returned by the provider if any
+///
+/// urls in error messages are cut down to their host, since rpc urls often embed api keys
+fn partition_errors(summary: &FreezeSummary) -> Vec<PartitionError> {
+    summary
+        .errored
+        .iter()
+        .map(|(partition, error)| PartitionError {
+            block_ranges: partition.iter().flat_map(block_ranges).collect(),
+            error: redact_urls(&error.to_string()),
+            rpc_error: error.json_rpc_error(),
+        })
+        .collect()
+}
+
+/// replace the credentials, path, and query of urls in message
+fn redact_urls(message: &str) -> String {
+    let url = regex::Regex::new(
+        r"(?P<scheme>(?:https?|wss?)://)(?:[^/\s@()]*@)?(?P<host>[^/\s@()?#]+)(?P<rest>[^\s()]*)",
+    )
+    .expect("valid url regex");
+    url.replace_all(message, |captures: &regex::Captures<'_>| {
+        let redacted = if captures["rest"].is_empty() { "" } else { "/<redacted>" };
+        format!("{}{}{}", &captures["scheme"], &captures["host"], redacted)
     })
+    .to_string()
+}
+
+/// inclusive block ranges of partition, one per block chunk
+pub(crate) fn block_ranges(partition: &Partition) -> Vec<(u64, u64)> {
+    let mut ranges = Vec::new();
+    for chunk in partition.block_numbers.iter().flatten() {
+        match chunk {
+            BlockChunk::Range(start, end) => ranges.push((*start, *end)),
+            BlockChunk::Numbers(numbers) => ranges.extend(numbers.iter().map(|n| (*n, *n))),
+        }
+    }
+    ranges
+}
+
+/// errored partitions of a prior run, as read from its report
+#[derive(Debug, Clone)]
+pub struct ReportRetry {
+    /// args of the prior run, with rpc credentials redacted
+    pub args: Option<String>,
+    /// inclusive block ranges of partitions that errored
+    pub errored_block_ranges: Vec<(u64, u64)>,
+}
+
+#[derive(serde::Deserialize)]
+struct RetryReport {
+    cryo_version: String,
+    args: Option<String>,
+    results: Option<RetryResults>,
+}
+
+#[derive(serde::Deserialize)]
+struct RetryResults {
+    #[serde(default)]
+    errored_paths: Vec<PathBuf>,
+    #[serde(default)]
+    errored_block_ranges: Vec<(u64, u64)>,
+}
+
+/// storage that reports are read from and written to, locations are paths or urls
+#[async_trait::async_trait]
+pub trait ReportStorage: Send + Sync {
+    /// read contents of the report at location
+    async fn read_report(&self, location: &str) -> Result<String, CollectError>;
+
+    /// write contents of a report to location
+    async fn write_report(&self, location: &str, contents: &str) -> Result<(), CollectError>;
+}
+
+/// reports stored on the local filesystem, each write replaces the report atomically
+#[derive(Debug, Clone, Default)]
+pub struct LocalReportStorage;
+
+#[async_trait::async_trait]
+impl ReportStorage for LocalReportStorage {
+    async fn read_report(&self, location: &str) -> Result<String, CollectError> {
+        std::fs::read_to_string(location).map_err(|_| err("could not read report"))
+    }
+
+    async fn write_report(&self, location: &str, contents: &str) -> Result<(), CollectError> {
+        write_atomic(Path::new(location), contents.as_bytes())
+    }
+}
+
+/// read errored partitions of a prior run from its report in storage, for retrying them
+pub async fn load_report_retry(
+    storage: &dyn ReportStorage,
+    location: &str,
+) -> Result<ReportRetry, CollectError> {
+    parse_report_retry(&storage.read_report(location).await?)
+}
+
+/// read errored partitions of a prior run from its local report, for retrying them
+pub fn read_report_retry(path: &Path) -> Result<ReportRetry, CollectError> {
+    let contents = std::fs::read_to_string(path).map_err(|_| err("could not read report"))?;
+    parse_report_retry(&contents)
+}
+
+/// parse errored partitions of a prior run from the contents of its report
+///
+/// reports must come from the running cryo version, and errored partitions must be block ranges
+fn parse_report_retry(contents: &str) -> Result<ReportRetry, CollectError> {
+    let report: RetryReport =
+        serde_json::from_str(contents).map_err(|_| err("could not parse report"))?;
+    if report.cryo_version != CRYO_VERSION {
+        return Err(err(&format!(
+            "report was written by cryo {}, cannot retry with cryo {}",
+            report.cryo_version, CRYO_VERSION
+        )))
+    }
+    let results = report.results.ok_or_else(|| err("report has no results to retry"))?;
+    if !results.errored_paths.is_empty() && results.errored_block_ranges.is_empty() {
+        return Err(err("errored chunks of report are not block ranges"))
+    }
+    Ok(ReportRetry { args: report.args, errored_block_ranges: results.errored_block_ranges })
 }
 
 /// cryo version
 pub const CRYO_VERSION: &str = env!("GIT_DESCRIPTION");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_interrupted_before_rename_leaves_no_report() {
+        let report_dir =
+            std::env::temp_dir().join(format!("cryo_test_atomic_report_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&report_dir);
+        std::fs::create_dir_all(&report_dir).unwrap();
+        let path = report_dir.join("2023-01-01_00-00-00.000000.json");
+
+        // crash after writing temp file but before rename
+        let tmp_path = write_tmp(&path, b"{\"cryo_version\": ").unwrap();
+        assert!(tmp_path.exists());
+        assert!(!path.exists());
+        let removed = prune_reports(&report_dir, 0, &path).unwrap();
+        assert!(removed.is_empty());
+
+        // a subsequent complete write replaces the temp file
+        write_atomic(&path, b"{}").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{}");
+        assert!(!tmp_path.exists());
+
+        std::fs::remove_dir_all(&report_dir).unwrap();
+    }
+
+    #[test]
+    fn test_runs_started_together_get_distinct_report_paths() {
+        let report_dir =
+            std::env::temp_dir().join(format!("cryo_test_report_paths_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&report_dir);
+        let sink = FileOutput { output_dir: report_dir.clone(), ..Default::default() };
+        let t_start = std::time::SystemTime::now();
+        let env = || ExecutionEnv {
+            t_start,
+            report_dir: Some(report_dir.clone()),
+            ..crate::ExecutionEnvBuilder::new().build()
+        };
+        let (first, second) = (env(), env());
+
+        let first_path = get_report_path(&first, &sink, true).unwrap();
+        assert_ne!(first_path, get_report_path(&second, &sink, true).unwrap());
+        assert_eq!(first_path, get_report_path(&first, &sink, true).unwrap());
+
+        // names still start with the human-readable start time
+        let t_start: DateTime<Local> = t_start.into();
+        let filename = first_path.file_name().unwrap().to_str().unwrap().to_string();
+        assert!(filename.starts_with(&t_start.format("%Y-%m-%d_%H-%M-%S%.6f_").to_string()));
+
+        std::fs::remove_dir_all(&report_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_partition_errors_keep_json_rpc_error() {
+        use ethers::prelude::*;
+
+        let (provider, mock) = Provider::mocked();
+        let source = crate::Source { ..Source::from_provider(provider) };
+        let data = serde_json::json!({"blockNumber": "0x3e8"});
+        mock.push_response(MockResponse::Error(JsonRpcError {
+            code: -32000,
+            message: "header not found".to_string(),
+            data: Some(data.clone()),
+        }));
+        let filter = Filter::new().from_block(1000u64).to_block(1999u64);
+        let error = source.get_logs(&filter).await.unwrap_err();
+
+        let partition = crate::Partition {
+            block_numbers: Some(vec![BlockChunk::Range(1000, 1999)]),
+            ..Default::default()
+        };
+        let summary = FreezeSummary {
+            errored: vec![
+                (Some(partition), error),
+                (None, err("error sending request for url (https://user:pw@rpc.io/v2/KEY?x=1)")),
+            ],
+            ..Default::default()
+        };
+        let errors = partition_errors(&summary);
+        assert_eq!(errors[0].block_ranges, vec![(1000, 1999)]);
+        assert_eq!(
+            errors[0].rpc_error,
+            Some(serde_json::json!({"code": -32000, "message": "header not found", "data": data}))
+        );
+
+        // errors that are not from the provider have no rpc error and keep no credentials
+        assert_eq!(errors[1].rpc_error, None);
+        assert!(errors[1].error.contains("(https://rpc.io/<redacted>)"));
+        assert!(!errors[1].error.contains("KEY") && !errors[1].error.contains("pw"));
+    }
+
+    #[test]
+    fn test_report_records_signer_and_unencrypted_files() {
+        use std::str::FromStr;
+
+        let signer = ethers::signers::LocalWallet::from_str(
+            "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318",
+        )
+        .unwrap();
+        let sink = FileOutput { signer: Some(signer.clone()), ..Default::default() };
+        let summary = serialize_summary(&FreezeSummary::default(), &Query::default(), &sink);
+        let summary = serde_json::to_value(summary.unwrap()).unwrap();
+        assert_eq!(summary["signed_by"], serde_json::json!(format!("{:?}", signer.address())));
+        assert_eq!(summary["encrypted"], serde_json::json!(false));
+
+        let summary =
+            serialize_summary(&FreezeSummary::default(), &Query::default(), &Default::default());
+        let summary = serde_json::to_value(summary.unwrap()).unwrap();
+        assert_eq!(summary["signed_by"], serde_json::Value::Null);
+        assert_eq!(summary["encrypted"], serde_json::json!(false));
+    }
+
+    #[test]
+    fn test_report_retry_reads_errored_block_ranges() {
+        let report_dir =
+            std::env::temp_dir().join(format!("cryo_test_report_retry_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&report_dir);
+        std::fs::create_dir_all(&report_dir).unwrap();
+
+        let errored = |block_numbers| {
+            let partition = crate::Partition { block_numbers, ..Default::default() };
+            (Some(partition), err("rpc unreachable"))
+        };
+        let summary = FreezeSummary {
+            errored: vec![
+                errored(Some(vec![BlockChunk::Range(1000, 1999)])),
+                errored(Some(vec![BlockChunk::Numbers(vec![5000, 5002])])),
+                (None, err("error joining chunks")),
+            ],
+            ..Default::default()
+        };
+        let ranges = errored_block_ranges(&summary);
+        assert_eq!(ranges, vec![(1000, 1999), (5000, 5000), (5002, 5002)]);
+
+        let write = |name: &str, version: &str| {
+            let report = serde_json::json!({
+                "cryo_version": version,
+                "cli_command": null,
+                "args": "{}",
+                "results": {"errored_paths": ["a.parquet"], "errored_block_ranges": ranges},
+            });
+            let path = report_dir.join(name);
+            std::fs::write(&path, report.to_string()).unwrap();
+            path
+        };
+        let retry = read_report_retry(&write("current.json", CRYO_VERSION)).unwrap();
+        assert_eq!(retry.errored_block_ranges, ranges);
+        assert_eq!(retry.args.as_deref(), Some("{}"));
+        assert!(read_report_retry(&write("old.json", "0.0.0-old")).is_err());
+
+        std::fs::remove_dir_all(&report_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_local_report_storage_writes_and_reads_reports() {
+        let report_dir =
+            std::env::temp_dir().join(format!("cryo_test_local_storage_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&report_dir);
+        std::fs::create_dir_all(&report_dir).unwrap();
+        let path = report_dir.join("report.json");
+        let location = path.to_str().unwrap();
+        let report = |ranges: Vec<(u64, u64)>| {
+            serde_json::json!({
+                "cryo_version": CRYO_VERSION,
+                "cli_command": null,
+                "args": null,
+                "results": {"errored_paths": ["a.parquet"], "errored_block_ranges": ranges},
+            })
+            .to_string()
+        };
+
+        // writes leave only the report behind and replace earlier contents
+        let storage = LocalReportStorage;
+        storage.write_report(location, &report(vec![(0, 999)])).await.unwrap();
+        storage.write_report(location, &report(vec![(1000, 1999)])).await.unwrap();
+        let entries: Vec<_> = std::fs::read_dir(&report_dir).unwrap().collect();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(storage.read_report(location).await.unwrap(), report(vec![(1000, 1999)]));
+        let retry = load_report_retry(&storage, location).await.unwrap();
+        assert_eq!(retry.errored_block_ranges, vec![(1000, 1999)]);
+
+        let missing = report_dir.join("missing.json");
+        assert!(storage.read_report(missing.to_str().unwrap()).await.is_err());
+        let unwritable = report_dir.join("missing_dir").join("report.json");
+        assert!(storage.write_report(unwritable.to_str().unwrap(), "{}").await.is_err());
+
+        std::fs::remove_dir_all(&report_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_multichain_report_continues_past_failed_chain() {
+        let report_dir =
+            std::env::temp_dir().join(format!("cryo_test_multichain_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&report_dir);
+
+        let summary = FreezeSummary { n_rows: 10, ..Default::default() };
+        let chains = vec![
+            ChainReport::new(
+                "ethereum".to_string(),
+                Some(1),
+                "ethereum".into(),
+                &Ok(Some(summary)),
+            ),
+            ChainReport::new("base".to_string(), None, "base".into(), &Err(err("rpc unreachable"))),
+        ];
+        let path =
+            write_multichain_report(&report_dir, std::time::SystemTime::now(), None, &chains)
+                .await
+                .unwrap();
+
+        let report: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        let chains = report["chains"].as_array().unwrap();
+        assert_eq!(chains.len(), 2);
+        assert_eq!(chains[0]["n_rows"], 10);
+        assert!(chains[0]["error"].is_null());
+        assert_eq!(chains[1]["error"], "Collect failed: rpc unreachable");
+
+        std::fs::remove_dir_all(&report_dir).unwrap();
+    }
+
+    #[test]
+    fn test_prune_reports_retention() {
+        let report_dir =
+            std::env::temp_dir().join(format!("cryo_test_prune_reports_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&report_dir);
+        std::fs::create_dir_all(&report_dir).unwrap();
+
+        let names = [
+            "2023-01-01_00-00-00.000000.json",
+            "2023-01-02_00-00-00.000000.json",
+            "2023-01-03_00-00-00.000000.json",
+            "2023-01-04_00-00-00.000000.json",
+            "incomplete_2023-01-05_00-00-00.000000.json",
+            "latest.json",
+        ];
+        for name in names.iter() {
+            File::create(report_dir.join(name)).unwrap();
+        }
+
+        let current = report_dir.join("2023-01-04_00-00-00.000000.json");
+        prune_reports(&report_dir, 2, &current).unwrap();
+
+        let mut remaining: Vec<String> = std::fs::read_dir(&report_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        remaining.sort();
+        assert_eq!(
+            remaining,
+            vec![
+                "2023-01-03_00-00-00.000000.json",
+                "2023-01-04_00-00-00.000000.json",
+                "incomplete_2023-01-05_00-00-00.000000.json",
+                "latest.json",
+            ]
+        );
+
+        std::fs::remove_dir_all(&report_dir).unwrap();
+    }
+}
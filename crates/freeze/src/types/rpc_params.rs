@@ -30,6 +30,16 @@ pub struct Params {
     pub topic2: Option<Vec<u8>>,
     /// topic3
     pub topic3: Option<Vec<u8>>,
+    /// addresses, log filters match logs emitted by any of them or by address
+    pub addresses: Option<Vec<Vec<u8>>>,
+    /// topic0s, log filters match logs whose topic0 is any of them or topic0
+    pub topic0s: Option<Vec<Vec<u8>>>,
+    /// topic1s, log filters match logs whose topic1 is any of them or topic1
+    pub topic1s: Option<Vec<Vec<u8>>>,
+    /// topic2s, log filters match logs whose topic2 is any of them or topic2
+    pub topic2s: Option<Vec<Vec<u8>>>,
+    /// topic3s, log filters match logs whose topic3 is any of them or topic3
+    pub topic3s: Option<Vec<Vec<u8>>>,
 }
 
 impl Params {
@@ -93,20 +103,202 @@ impl Params {
     }
 
     /// log filter
+    ///
+    /// values at the same position are combined with OR, the address and each topic position are
+    /// combined with AND, following the eth_getLogs filter rules
     pub fn ethers_log_filter(&self) -> Result<Filter, CollectError> {
         let (start, end) = self.block_range()?;
         let block_option =
             FilterBlockOption::Range { from_block: Some(start.into()), to_block: Some(end.into()) };
+        let address = filter_values("address", 20, &self.address, &self.addresses)?
+            .map(|values| value_or_array(values.iter().map(|x| H160::from_slice(x)).collect()));
         let filter = Filter {
             block_option,
-            address: self.address.clone().map(|x| ValueOrArray::Value(H160::from_slice(&x))),
+            address,
             topics: [
-                self.topic0.clone().map(|x| ValueOrArray::Value(Some(H256::from_slice(&x)))),
-                self.topic1.clone().map(|x| ValueOrArray::Value(Some(H256::from_slice(&x)))),
-                self.topic2.clone().map(|x| ValueOrArray::Value(Some(H256::from_slice(&x)))),
-                self.topic3.clone().map(|x| ValueOrArray::Value(Some(H256::from_slice(&x)))),
+                topic_filter("topic0", &self.topic0, &self.topic0s)?,
+                topic_filter("topic1", &self.topic1, &self.topic1s)?,
+                topic_filter("topic2", &self.topic2, &self.topic2s)?,
+                topic_filter("topic3", &self.topic3, &self.topic3s)?,
             ],
         };
         Ok(filter)
     }
+
+    /// log filters, each with at most max_addresses of the addresses of params
+    ///
+    /// providers cap the number of addresses of each eth_getLogs filter, so larger sets of
+    /// addresses are split into multiple filters that differ only in their addresses
+    pub fn ethers_log_filters(&self, max_addresses: usize) -> Result<Vec<Filter>, CollectError> {
+        let filter = self.ethers_log_filter()?;
+        let addresses = match &filter.address {
+            Some(ValueOrArray::Array(addresses)) if addresses.len() > max_addresses.max(1) => {
+                addresses.clone()
+            }
+            _ => return Ok(vec![filter]),
+        };
+        let filters = addresses
+            .chunks(max_addresses.max(1))
+            .map(|chunk| Filter { address: Some(value_or_array(chunk.to_vec())), ..filter.clone() })
+            .collect();
+        Ok(filters)
+    }
+}
+
+/// distinct values accepted at one filter position, None if every value is accepted
+///
+/// an empty set of alternatives is an error rather than a wildcard, since eth_getLogs treats an
+/// empty array as matching every value, values of the wrong width are an error rather than a panic
+fn filter_values(
+    name: &str,
+    width: usize,
+    value: &Option<Vec<u8>>,
+    values: &Option<Vec<Vec<u8>>>,
+) -> Result<Option<Vec<Vec<u8>>>, CollectError> {
+    if values.as_ref().map_or(false, |values| values.is_empty()) {
+        return Err(err(format!("empty set of {} values given for log filter", name).as_str()))
+    }
+    let mut combined: Vec<Vec<u8>> = Vec::new();
+    for value in value.iter().chain(values.iter().flatten()) {
+        if value.len() != width {
+            let message = format!(
+                "invalid {} for log filter: {} is {} bytes, expected {}",
+                name,
+                prefix_hex::encode(value),
+                value.len(),
+                width
+            );
+            return Err(err(message.as_str()))
+        }
+        if !combined.contains(value) {
+            combined.push(value.clone());
+        }
+    }
+    Ok(if combined.is_empty() { None } else { Some(combined) })
+}
+
+fn topic_filter(
+    name: &str,
+    value: &Option<Vec<u8>>,
+    values: &Option<Vec<Vec<u8>>>,
+) -> Result<Option<Topic>, CollectError> {
+    Ok(filter_values(name, 32, value, values)?
+        .map(|values| value_or_array(values.iter().map(|x| Some(H256::from_slice(x))).collect())))
+}
+
+fn value_or_array<T>(mut values: Vec<T>) -> ValueOrArray<T> {
+    match values.len() {
+        1 => ValueOrArray::Value(values.remove(0)),
+        _ => ValueOrArray::Array(values),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn filter_json(params: Params) -> serde_json::Value {
+        let params = Params { block_range: Some((16, 31)), ..params };
+        serde_json::to_value(params.ethers_log_filter().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_log_filter_combines_values_per_json_rpc_rules() {
+        let (a, b) = (vec![0xaa; 20], vec![0xbb; 20]);
+        let (x, y, z) = (vec![0x11; 32], vec![0x22; 32], vec![0x33; 32]);
+
+        // single values are plain values, unconstrained trailing topics are omitted
+        let filter = filter_json(Params { address: Some(a.clone()), ..Default::default() });
+        let address = prefix_hex::encode(&a);
+        let expected =
+            json!({"fromBlock": "0x10", "toBlock": "0x1f", "address": address, "topics": []});
+        assert_eq!(filter, expected);
+
+        // (a OR b) AND topic0 == x
+        let filter = filter_json(Params {
+            addresses: Some(vec![a.clone(), b.clone()]),
+            topic0: Some(x.clone()),
+            ..Default::default()
+        });
+        assert_eq!(filter["address"], json!([prefix_hex::encode(&a), prefix_hex::encode(&b)]));
+        assert_eq!(filter["topics"], json!([prefix_hex::encode(&x)]));
+
+        // topic0 == x AND (topic2 == y OR topic2 == z), topic1 is a positional wildcard
+        let filter = filter_json(Params {
+            topic0: Some(x.clone()),
+            topic2s: Some(vec![y.clone(), z.clone()]),
+            ..Default::default()
+        });
+        assert!(filter.get("address").is_none());
+        assert_eq!(
+            filter["topics"],
+            json!([prefix_hex::encode(&x), null, [prefix_hex::encode(&y), prefix_hex::encode(&z)]])
+        );
+
+        // a single value and a set at the same position are combined with OR without duplicates
+        let filter = filter_json(Params {
+            address: Some(a.clone()),
+            addresses: Some(vec![b.clone(), a.clone()]),
+            topic3: Some(y.clone()),
+            topic3s: Some(vec![y.clone()]),
+            ..Default::default()
+        });
+        assert_eq!(filter["address"], json!([prefix_hex::encode(&a), prefix_hex::encode(&b)]));
+        assert_eq!(filter["topics"], json!([null, null, null, prefix_hex::encode(&y)]));
+
+        // an empty set would match every log
+        let params =
+            Params { block_range: Some((16, 31)), topic1s: Some(vec![]), ..Default::default() };
+        assert!(params.ethers_log_filter().is_err());
+    }
+
+    #[test]
+    fn test_log_filter_rejects_values_of_wrong_width() {
+        let params = |addresses: Vec<Vec<u8>>| Params {
+            block_range: Some((16, 31)),
+            addresses: Some(addresses),
+            ..Default::default()
+        };
+        assert!(params(vec![vec![0xaa; 20]]).ethers_log_filter().is_ok());
+        assert!(params(vec![vec![0xaa; 20], vec![0xaa; 19]]).ethers_log_filter().is_err());
+        assert!(params(vec![vec![0xaa; 32]]).ethers_log_filter().is_err());
+
+        let params = Params {
+            block_range: Some((16, 31)),
+            topic0: Some(vec![0x11; 20]),
+            ..Default::default()
+        };
+        assert!(params.ethers_log_filter().is_err());
+    }
+
+    #[test]
+    fn test_log_filters_split_addresses_beyond_cap() {
+        let addresses: Vec<Vec<u8>> = (1..=5u8).map(|i| vec![i; 20]).collect();
+        let params = Params {
+            block_range: Some((16, 31)),
+            addresses: Some(addresses.clone()),
+            topic0: Some(vec![0x11; 32]),
+            ..Default::default()
+        };
+        let filters: Vec<serde_json::Value> = params
+            .ethers_log_filters(2)
+            .unwrap()
+            .into_iter()
+            .map(|filter| serde_json::to_value(filter).unwrap())
+            .collect();
+        let hex = |i: usize| prefix_hex::encode(&addresses[i]);
+        assert_eq!(filters.len(), 3);
+        assert_eq!(filters[0]["address"], json!([hex(0), hex(1)]));
+        assert_eq!(filters[1]["address"], json!([hex(2), hex(3)]));
+        assert_eq!(filters[2]["address"], json!(hex(4)));
+        for filter in filters.iter() {
+            assert_eq!(filter["topics"], json!([prefix_hex::encode(vec![0x11; 32])]));
+            assert_eq!(filter["fromBlock"], json!("0x10"));
+        }
+
+        // addresses within the cap keep a single filter
+        assert_eq!(params.ethers_log_filters(5).unwrap().len(), 1);
+        assert_eq!(Params { addresses: None, ..params }.ethers_log_filters(2).unwrap().len(), 1);
+    }
 }
@@ -1,8 +1,12 @@
 /// types and functions related to schemas
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use crate::{err, CollectError, ColumnEncoding, Datatype, LogDecoder};
+use crate::{
+    dataframes::BLOCK_BUCKET_COLUMN, err, row_ids::ROW_ID_COLUMN, CollectError, ColumnEncoding,
+    Datatype, LogDecoder, ParseError, RangedLogDecoder, DECODED_COLUMN_PREFIX,
+};
 use indexmap::{IndexMap, IndexSet};
+use polars::prelude::DataType;
 use thiserror::Error;
 
 /// collection of schemas
@@ -39,6 +43,39 @@ pub struct Table {
 
     /// log decoder for table
     pub log_decoder: Option<LogDecoder>,
+
+    /// log decoders keyed by block range, takes precedence over log_decoder when decoding
+    pub log_decoder_ranges: Option<RangedLogDecoder>,
+
+    /// output names of columns keyed by column name, applied right before writing
+    pub column_aliases: Option<IndexMap<String, String>>,
+
+    /// drop topic columns that are null in every row of an output file
+    pub compact_topics: bool,
+
+    /// store topics absent from a log as 32 zero bytes rather than null
+    pub zero_hash_topics: bool,
+
+    /// keep raw log columns only, logs are never decoded even if a log decoder is set
+    pub skip_decoding: bool,
+
+    /// decode logs in parallel chunks of this many logs, None decodes them one at a time
+    pub decode_chunk_size: Option<usize>,
+
+    /// keep raw topic1-3 and data columns beside decoded event columns
+    pub keep_raw_log_columns: bool,
+
+    /// store decoded event params as JSON in a decoded column rather than one column per param
+    pub decoded_json: bool,
+
+    /// add a block_bucket column of block_number / block_bucket_size to each output file
+    pub block_bucket_size: Option<u64>,
+
+    /// keep only the first or last log of each block, by log_index
+    pub per_block_log: Option<PerBlockLog>,
+
+    /// add a row_id column numbering rows across all partitions of the run in block order
+    pub row_id: bool,
 }
 
 impl Table {
@@ -52,10 +89,305 @@ impl Table {
         self.columns.get(column).cloned()
     }
 
+    /// get decoder to use for a log in block_number
+    pub fn log_decoder_at(&self, block_number: u64) -> Option<&LogDecoder> {
+        match &self.log_decoder_ranges {
+            Some(ranges) => ranges.decoder_at(block_number),
+            None => self.log_decoder.as_ref(),
+        }
+    }
+
+    /// set output names of columns, aliases of columns neither in Table nor added to it, like
+    /// row_id and block_bucket, are ignored
+    ///
+    /// decoded event columns are aliased by their event__ names. output names must be unique,
+    /// columns are renamed in one pass so that aliases may swap or chain names
+    pub fn set_column_aliases(&mut self, aliases: &[(String, String)]) -> Result<(), SchemaError> {
+        let added_column = |column: &str| {
+            (self.row_id && column == ROW_ID_COLUMN) ||
+                (self.block_bucket_size.is_some() && column == BLOCK_BUCKET_COLUMN)
+        };
+        let aliases: IndexMap<String, String> = aliases
+            .iter()
+            .filter(|(column, _)| {
+                self.has_column(column) ||
+                    added_column(column) ||
+                    column.starts_with(DECODED_COLUMN_PREFIX)
+            })
+            .map(|(column, alias)| (column.clone(), alias.clone()))
+            .collect();
+        let event_aliases: Vec<String> = aliases
+            .iter()
+            .filter(|(column, _)| !self.has_column(column) && !added_column(column))
+            .map(|(_, alias)| alias.clone())
+            .collect();
+        let previous = std::mem::replace(
+            &mut self.column_aliases,
+            if aliases.is_empty() { None } else { Some(aliases) },
+        );
+        let mut output_names = HashSet::new();
+        let names = self.output_columns().into_iter().map(|(name, _)| name).chain(event_aliases);
+        for name in names.collect::<Vec<_>>().into_iter() {
+            if !output_names.insert(name.clone()) {
+                self.column_aliases = previous;
+                return Err(SchemaError::AliasCollision(name))
+            }
+        }
+        Ok(())
+    }
+
+    /// get name in output files of a column of a collected dataframe
+    ///
+    /// the columns that a u256 column is expanded into, including those of decoded event params,
+    /// are named by the alias of the u256 column followed by their suffix
+    pub fn output_column_name(&self, column: &str) -> String {
+        let aliases = match &self.column_aliases {
+            Some(aliases) => aliases,
+            None => return column.to_string(),
+        };
+        if let Some(alias) = aliases.get(column) {
+            return alias.clone()
+        }
+        for u256_type in U256Type::all().iter() {
+            let suffix = u256_type.suffix();
+            let alias = column.strip_suffix(suffix.as_str()).and_then(|base| aliases.get(base));
+            if let Some(alias) = alias {
+                return alias.clone() + &suffix
+            }
+        }
+        column.to_string()
+    }
+
+    /// get name of column in output files
+    pub fn output_name<'a>(&'a self, column: &'a str) -> &'a str {
+        match self.column_aliases.as_ref().and_then(|aliases| aliases.get(column)) {
+            Some(alias) => alias.as_str(),
+            None => column,
+        }
+    }
+
+    /// remove chain_id column, for single-chain outputs where it has the same value in every row
+    pub fn exclude_chain_id(&mut self) {
+        self.columns.shift_remove("chain_id");
+    }
+
+    /// store topic0-3 of logs as 0x-prefixed hex strings, other binary columns are unchanged
+    pub fn set_topics_as_hex(&mut self) {
+        for column in ["topic0", "topic1", "topic2", "topic3"] {
+            if let Some(column_type) = self.columns.get_mut(column) {
+                *column_type = ColumnType::Hex;
+            }
+        }
+    }
+
+    /// store decoded event params as JSON in a decoded column of logs, instead of one column per
+    /// param
+    pub fn set_decoded_json(&mut self) {
+        self.decoded_json = true;
+        if self.datatype == Datatype::Logs {
+            self.columns.insert("decoded".to_string(), ColumnType::String);
+        }
+    }
+
     /// get columns of Table
     pub fn columns(&self) -> Vec<&str> {
         self.columns.keys().map(|x| x.as_str()).collect()
     }
+
+    /// describe resolved columns, sort, and decoder of Table
+    pub fn describe(&self) -> TableDescription {
+        TableDescription {
+            datatype: self.datatype.name(),
+            columns: self
+                .columns
+                .iter()
+                .map(|(name, column_type)| ColumnDescription {
+                    name: name.clone(),
+                    column_type: column_type.as_str().to_string(),
+                })
+                .collect(),
+            sort: self.sort_columns.clone(),
+            has_decoder: self.log_decoder.is_some(),
+            event_signature: self.log_decoder.as_ref().map(|decoder| decoder.raw.clone()),
+        }
+    }
+
+    /// columns of output files with their types, u256 columns are expanded into one column per
+    /// u256 type and columns are given their output names
+    pub fn output_columns(&self) -> Vec<(String, ColumnType)> {
+        let mut columns = Vec::new();
+        for (name, column_type) in self.columns.iter() {
+            let name = self.output_name(name);
+            if *column_type == ColumnType::UInt256 {
+                for u256_type in self.u256_types.iter() {
+                    columns
+                        .push((name.to_string() + &u256_type.suffix(), u256_type.to_columntype()));
+                }
+            } else {
+                columns.push((name.to_string(), *column_type));
+            }
+        }
+        if self.row_id {
+            columns.push((self.output_name(ROW_ID_COLUMN).to_string(), ColumnType::UInt64));
+        }
+        if self.block_bucket_size.is_some() {
+            columns.push((self.output_name(BLOCK_BUCKET_COLUMN).to_string(), ColumnType::UInt32));
+        }
+        columns
+    }
+
+    /// CREATE TABLE statement of Table in SQL dialect, with the sort columns as a suggested
+    /// index, cluster, or order
+    ///
+    /// decoded event columns depend on the logs of each file, so they are not included
+    pub fn create_table_sql(&self, dialect: SqlDialect, table_name: &str) -> String {
+        let output_columns = self.output_columns();
+        let sort: Vec<String> = self
+            .sort_columns
+            .iter()
+            .flatten()
+            .map(|column| self.output_name(column).to_string())
+            .filter(|column| output_columns.iter().any(|(name, _)| name == column))
+            .collect();
+        let columns: Vec<String> = output_columns
+            .into_iter()
+            .map(|(name, column_type)| {
+                let sql_type = column_type.sql_type(dialect);
+                // clickhouse columns are not nullable unless declared so, sort keys cannot be
+                let sql_type = match dialect {
+                    SqlDialect::ClickHouse if !sort.contains(&name) => {
+                        format!("Nullable({})", sql_type)
+                    }
+                    _ => sql_type.to_string(),
+                };
+                format!("    {} {}", dialect.quote(&name), sql_type)
+            })
+            .collect();
+        let quoted_sort: Vec<String> = sort.iter().map(|column| dialect.quote(column)).collect();
+
+        let mut sql =
+            format!("CREATE TABLE {} (\n{}\n)", dialect.quote(table_name), columns.join(",\n"));
+        match dialect {
+            SqlDialect::Postgres => {
+                sql.push(';');
+                if !sort.is_empty() {
+                    sql.push_str(&format!(
+                        "\nCREATE INDEX {} ON {} ({});",
+                        dialect.quote(&format!("{}_sort_idx", table_name)),
+                        dialect.quote(table_name),
+                        quoted_sort.join(", ")
+                    ));
+                }
+            }
+            SqlDialect::BigQuery => {
+                // bigquery clusters by at most 4 columns
+                if !sort.is_empty() {
+                    let cluster: Vec<&str> =
+                        quoted_sort.iter().take(4).map(|column| column.as_str()).collect();
+                    sql.push_str(&format!("\nCLUSTER BY {}", cluster.join(", ")));
+                }
+                sql.push(';');
+            }
+            SqlDialect::ClickHouse => {
+                sql.push_str("\nENGINE = MergeTree");
+                if sort.is_empty() {
+                    sql.push_str("\nORDER BY tuple()");
+                } else {
+                    sql.push_str(&format!("\nORDER BY ({})", quoted_sort.join(", ")));
+                }
+                sql.push(';');
+            }
+        }
+        sql
+    }
+}
+
+/// SQL dialect of CREATE TABLE statements
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SqlDialect {
+    /// Postgres
+    Postgres,
+    /// BigQuery
+    BigQuery,
+    /// ClickHouse
+    ClickHouse,
+}
+
+impl SqlDialect {
+    /// quote identifier, so that names such as reserved words can be used as column names
+    fn quote(&self, identifier: &str) -> String {
+        match self {
+            SqlDialect::Postgres => format!("\"{}\"", identifier.replace('"', "\"\"")),
+            SqlDialect::BigQuery | SqlDialect::ClickHouse => {
+                format!("`{}`", identifier.replace('`', "\\`"))
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for SqlDialect {
+    type Err = ParseError;
+
+    fn from_str(name: &str) -> Result<SqlDialect, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "postgres" | "postgresql" => Ok(SqlDialect::Postgres),
+            "bigquery" => Ok(SqlDialect::BigQuery),
+            "clickhouse" => Ok(SqlDialect::ClickHouse),
+            _ => Err(ParseError::ParseError(format!(
+                "invalid sql dialect: {}, must be postgres, bigquery, or clickhouse",
+                name
+            ))),
+        }
+    }
+}
+
+/// which log of each block to keep
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PerBlockLog {
+    /// log with the lowest log_index of each block
+    First,
+    /// log with the highest log_index of each block
+    Last,
+}
+
+impl std::str::FromStr for PerBlockLog {
+    type Err = ParseError;
+
+    fn from_str(name: &str) -> Result<PerBlockLog, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "first" => Ok(PerBlockLog::First),
+            "last" => Ok(PerBlockLog::Last),
+            _ => Err(ParseError::ParseError(format!(
+                "invalid per block log: {}, must be first or last",
+                name
+            ))),
+        }
+    }
+}
+
+/// serializable description of a resolved Table
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct TableDescription {
+    /// name of datatype
+    pub datatype: String,
+    /// columns in output order
+    pub columns: Vec<ColumnDescription>,
+    /// sort order for rows
+    pub sort: Option<Vec<String>>,
+    /// whether table has a log decoder
+    pub has_decoder: bool,
+    /// event signature of log decoder
+    pub event_signature: Option<String>,
+}
+
+/// serializable description of a column
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct ColumnDescription {
+    /// name of column
+    pub name: String,
+    /// type of column
+    #[serde(rename = "type")]
+    pub column_type: String,
 }
 
 /// representation of a U256 datum
@@ -78,6 +410,19 @@ pub enum U256Type {
 }
 
 impl U256Type {
+    /// every representation of u256 values
+    pub fn all() -> [U256Type; 7] {
+        [
+            U256Type::Binary,
+            U256Type::String,
+            U256Type::F32,
+            U256Type::F64,
+            U256Type::U32,
+            U256Type::U64,
+            U256Type::Decimal128,
+        ]
+    }
+
     /// convert U256Type to Columntype
     pub fn to_columntype(&self) -> ColumnType {
         match self {
@@ -135,6 +480,24 @@ pub enum ColumnType {
 }
 
 impl ColumnType {
+    /// ColumnType of a column of an output file, hex columns are read back as strings and
+    /// fixed-size byte arrays of decoded bytesN args as binary
+    pub fn from_dtype(dtype: &DataType) -> Option<ColumnType> {
+        match dtype {
+            DataType::Boolean => Some(ColumnType::Boolean),
+            DataType::UInt32 => Some(ColumnType::UInt32),
+            DataType::UInt64 => Some(ColumnType::UInt64),
+            DataType::Int32 => Some(ColumnType::Int32),
+            DataType::Int64 => Some(ColumnType::Int64),
+            DataType::Float32 => Some(ColumnType::Float32),
+            DataType::Float64 => Some(ColumnType::Float64),
+            DataType::Utf8 => Some(ColumnType::String),
+            DataType::Binary => Some(ColumnType::Binary),
+            DataType::Array(inner, _) if **inner == DataType::UInt8 => Some(ColumnType::Binary),
+            _ => None,
+        }
+    }
+
     /// convert ColumnType to str
     pub fn as_str(&self) -> &'static str {
         match *self {
@@ -152,6 +515,55 @@ impl ColumnType {
             ColumnType::Hex => "hex",
         }
     }
+
+    /// SQL type of ColumnType in dialect, unsigned integers are widened where the dialect has
+    /// no unsigned type
+    pub fn sql_type(&self, dialect: SqlDialect) -> &'static str {
+        match dialect {
+            SqlDialect::Postgres => match *self {
+                ColumnType::Boolean => "BOOLEAN",
+                ColumnType::UInt32 => "BIGINT",
+                ColumnType::UInt64 => "NUMERIC(20)",
+                ColumnType::UInt256 => "NUMERIC(78)",
+                ColumnType::Int32 => "INTEGER",
+                ColumnType::Int64 => "BIGINT",
+                ColumnType::Float32 => "REAL",
+                ColumnType::Float64 => "DOUBLE PRECISION",
+                ColumnType::Decimal128 => "NUMERIC(38)",
+                ColumnType::String => "TEXT",
+                ColumnType::Binary => "BYTEA",
+                ColumnType::Hex => "TEXT",
+            },
+            SqlDialect::BigQuery => match *self {
+                ColumnType::Boolean => "BOOL",
+                ColumnType::UInt32 => "INT64",
+                ColumnType::UInt64 => "NUMERIC",
+                ColumnType::UInt256 => "BIGNUMERIC",
+                ColumnType::Int32 => "INT64",
+                ColumnType::Int64 => "INT64",
+                ColumnType::Float32 => "FLOAT64",
+                ColumnType::Float64 => "FLOAT64",
+                ColumnType::Decimal128 => "NUMERIC",
+                ColumnType::String => "STRING",
+                ColumnType::Binary => "BYTES",
+                ColumnType::Hex => "STRING",
+            },
+            SqlDialect::ClickHouse => match *self {
+                ColumnType::Boolean => "Bool",
+                ColumnType::UInt32 => "UInt32",
+                ColumnType::UInt64 => "UInt64",
+                ColumnType::UInt256 => "UInt256",
+                ColumnType::Int32 => "Int32",
+                ColumnType::Int64 => "Int64",
+                ColumnType::Float32 => "Float32",
+                ColumnType::Float64 => "Float64",
+                ColumnType::Decimal128 => "Decimal(38, 0)",
+                ColumnType::String => "String",
+                ColumnType::Binary => "String",
+                ColumnType::Hex => "String",
+            },
+        }
+    }
 }
 
 /// Error related to Schemas
@@ -160,6 +572,9 @@ pub enum SchemaError {
     /// Invalid column being operated on
     #[error("Invalid column")]
     InvalidColumn,
+    /// Column alias would give two output columns the same name
+    #[error("Column alias collides with column: {0}")]
+    AliasCollision(String),
 }
 
 impl Datatype {
@@ -201,6 +616,17 @@ impl Datatype {
             u256_types: u256_types.to_owned(),
             binary_type: binary_column_format.clone(),
             log_decoder,
+            log_decoder_ranges: None,
+            column_aliases: None,
+            compact_topics: false,
+            zero_hash_topics: false,
+            skip_decoding: false,
+            decode_chunk_size: None,
+            keep_raw_log_columns: false,
+            decoded_json: false,
+            block_bucket_size: None,
+            per_block_log: None,
+            row_id: false,
         };
         Ok(schema)
     }
@@ -262,6 +688,66 @@ mod tests {
         assert!(table.columns().contains(&"transactions_root"));
     }
 
+    #[test]
+    fn test_table_describe() {
+        let cols = Some(vec!["block_number".to_string(), "block_hash".to_string()]);
+        let sort = Some(vec!["block_number".to_string()]);
+        let table = Datatype::Blocks
+            .table_schema(&get_u256_types(), &ColumnEncoding::Hex, &None, &None, &cols, sort, None)
+            .unwrap();
+        let description = table.describe();
+        assert_eq!(description.datatype, "blocks");
+        let columns: Vec<(&str, &str)> = description
+            .columns
+            .iter()
+            .map(|column| (column.name.as_str(), column.column_type.as_str()))
+            .collect();
+        assert_eq!(columns, vec![("block_number", "uint32"), ("block_hash", "hex")]);
+        assert_eq!(description.sort, Some(vec!["block_number".to_string()]));
+        assert!(!description.has_decoder);
+    }
+
+    #[test]
+    fn test_create_table_sql() {
+        let cols = ["block_number", "transaction_hash", "value"];
+        let cols = Some(cols.iter().map(|column| column.to_string()).collect());
+        let sort = Some(vec!["block_number".to_string(), "transaction_index".to_string()]);
+        let table = Datatype::Transactions
+            .table_schema(
+                &[U256Type::Binary, U256Type::F64],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &cols,
+                sort,
+                None,
+            )
+            .unwrap();
+
+        // sort columns missing from the table are left out of the suggested index
+        assert_eq!(
+            table.create_table_sql(SqlDialect::Postgres, "transactions"),
+            "CREATE TABLE \"transactions\" (\n    \"block_number\" BIGINT,\n    \
+             \"transaction_hash\" BYTEA,\n    \"value_binary\" BYTEA,\n    \"value_f64\" DOUBLE \
+             PRECISION\n);\nCREATE INDEX \"transactions_sort_idx\" ON \"transactions\" \
+             (\"block_number\");"
+        );
+        assert_eq!(
+            table.create_table_sql(SqlDialect::BigQuery, "transactions"),
+            "CREATE TABLE `transactions` (\n    `block_number` INT64,\n    `transaction_hash` \
+             BYTES,\n    `value_binary` BYTES,\n    `value_f64` FLOAT64\n)\nCLUSTER BY \
+             `block_number`;"
+        );
+        assert_eq!(
+            table.create_table_sql(SqlDialect::ClickHouse, "transactions"),
+            "CREATE TABLE `transactions` (\n    `block_number` UInt32,\n    `transaction_hash` \
+             Nullable(String),\n    `value_binary` Nullable(String),\n    `value_f64` \
+             Nullable(Float64)\n)\nENGINE = MergeTree\nORDER BY (`block_number`);"
+        );
+        assert_eq!("ClickHouse".parse::<SqlDialect>().unwrap(), SqlDialect::ClickHouse);
+        assert!("mysql".parse::<SqlDialect>().is_err());
+    }
+
     #[test]
     fn test_table_schema_include_cols() {
         let inc_cols = Some(vec!["chain_id".to_string(), "receipts_root".to_string()]);
@@ -34,4 +34,22 @@ lazy_static::lazy_static! {
         prefix_hex::decode("0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef")
             .expect("Decoding failed"),
     );
+
+    /// event hash of EVENT_ERC721_APPROVAL_FOR_ALL
+    pub static ref EVENT_ERC721_APPROVAL_FOR_ALL: H256 = H256(
+        prefix_hex::decode("0x17307eab39ab6107e8899845ad3d59bd9653f200f220920489ca2b5937696c31")
+            .expect("Decoding failed"),
+    );
+
+    /// event hash of EVENT_ERC1155_TRANSFER_SINGLE
+    pub static ref EVENT_ERC1155_TRANSFER_SINGLE: H256 = H256(
+        prefix_hex::decode("0xc3d58168c5ae7397731d063d5bbf3d657854427343f4c083240f7aacaa2d0f62")
+            .expect("Decoding failed"),
+    );
+
+    /// event hash of EVENT_ERC1155_TRANSFER_BATCH
+    pub static ref EVENT_ERC1155_TRANSFER_BATCH: H256 = H256(
+        prefix_hex::decode("0x4a39dc06d4c0dbc64b70af90fd698a233a518aa5d07e595d983b8c0526c8f7fb")
+            .expect("Decoding failed"),
+    );
 }
@@ -1,4 +1,10 @@
-use std::sync::Arc;
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
 
 use ethers::prelude::*;
 use governor::{
@@ -33,6 +39,18 @@ pub struct Source {
     pub semaphore: Arc<Option<Semaphore>>,
     /// rate limiter for controlling request rate
     pub rate_limiter: Arc<Option<RateLimiter>>,
+    /// budget of retries shared across all requests of run
+    pub retry_budget: Option<Arc<RetryBudget>>,
+    /// throttle that reduces concurrency after rate limiting
+    pub concurrency_throttle: Option<Arc<ConcurrencyThrottle>>,
+    /// latencies of get_logs calls observed across the run
+    pub rpc_latency: Option<Arc<LatencyTracker>>,
+    /// extra fields added to the params of every eth_getLogs request, support depends on provider
+    pub log_filter_params: Option<serde_json::Map<String, serde_json::Value>>,
+    /// split eth_getLogs block ranges in half when the provider reports too many results
+    pub bisect_log_ranges: bool,
+    /// block ranges whose logs the provider no longer serves, recorded rather than failing
+    pub unavailable_log_ranges: Option<Arc<UnavailableLogRanges>>,
     /// Labels (these are non-functional)
     pub labels: SourceLabels,
 }
@@ -88,6 +106,28 @@ macro_rules! source_provider {
 }
 
 impl Source {
+    /// source of provider for chain 1, without limits on concurrency or request rate
+    ///
+    /// each log request covers one block, other options are off
+    pub fn from_provider(provider: impl Into<ProviderWrapper>) -> Source {
+        Source {
+            provider: provider.into(),
+            chain_id: 1,
+            inner_request_size: 1,
+            max_concurrent_chunks: None,
+            rpc_url: "".to_string(),
+            semaphore: Arc::new(None),
+            rate_limiter: Arc::new(None),
+            retry_budget: None,
+            concurrency_throttle: None,
+            rpc_latency: None,
+            log_filter_params: None,
+            bisect_log_ranges: false,
+            unavailable_log_ranges: None,
+            labels: SourceLabels::default(),
+        }
+    }
+
     /// Returns all receipts for a block.
     /// Tries to use `eth_getBlockReceipts` first, and falls back to `eth_getTransactionReceipt`
     pub async fn get_tx_receipts_in_block(
@@ -177,9 +217,16 @@ impl Source {
                 max_concurrent_requests: Some(DEFAULT_MAX_CONCURRENT_REQUESTS),
                 max_requests_per_second: Some(0),
                 max_retries: Some(DEFAULT_MAX_RETRIES),
+                max_total_retries: None,
                 initial_backoff: Some(DEFAULT_INTIAL_BACKOFF),
             },
             rate_limiter: rate_limiter.into(),
+            retry_budget: None,
+            concurrency_throttle: None,
+            rpc_latency: Some(Arc::new(LatencyTracker::default())),
+            log_filter_params: None,
+            bisect_log_ranges: false,
+            unavailable_log_ranges: None,
             semaphore: semaphore.into(),
         };
 
@@ -234,6 +281,341 @@ fn parse_rpc_url(rpc_url: Option<String>) -> String {
 //     }
 // }
 
+/// maximum number of retries shared across all requests of a run
+#[derive(Debug)]
+pub struct RetryBudget {
+    max_retries: u64,
+    used: AtomicU64,
+}
+
+impl RetryBudget {
+    /// create new budget
+    pub fn new(max_retries: u64) -> Self {
+        RetryBudget { max_retries, used: AtomicU64::new(0) }
+    }
+
+    /// consume one retry, returning false if budget is exhausted
+    pub fn try_consume(&self) -> bool {
+        self.used
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                (used < self.max_retries).then_some(used + 1)
+            })
+            .is_ok()
+    }
+
+    /// number of retries used so far
+    pub fn used(&self) -> u64 {
+        self.used.load(Ordering::SeqCst)
+    }
+}
+
+/// latency of a call at or below which it is counted in the first bucket
+const MIN_LATENCY_MICROS: f64 = 100.0;
+/// ratio between the upper bounds of consecutive latency buckets
+const LATENCY_BUCKET_GROWTH: f64 = 1.1;
+/// number of latency buckets, the last one holds every call slower than about two hours
+const N_LATENCY_BUCKETS: usize = 192;
+
+/// streaming estimate of rpc call latency quantiles
+///
+/// calls are counted in buckets whose bounds grow by 10%, so quantiles are estimated to within
+/// 10% using constant memory and a single atomic increment per call
+#[derive(Debug)]
+pub struct LatencyTracker {
+    buckets: Vec<AtomicU64>,
+}
+
+/// latency quantiles of the rpc calls of a run, in milliseconds
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct LatencySummary {
+    /// number of calls recorded
+    pub n_calls: u64,
+    /// median latency
+    pub p50_ms: f64,
+    /// 95th percentile latency
+    pub p95_ms: f64,
+    /// 99th percentile latency
+    pub p99_ms: f64,
+}
+
+impl Default for LatencyTracker {
+    fn default() -> Self {
+        LatencyTracker { buckets: (0..N_LATENCY_BUCKETS).map(|_| AtomicU64::new(0)).collect() }
+    }
+}
+
+impl LatencyTracker {
+    /// record latency of one call
+    pub fn record(&self, latency: Duration) {
+        let micros = latency.as_secs_f64() * 1e6;
+        let index = if micros <= MIN_LATENCY_MICROS {
+            0
+        } else {
+            ((micros / MIN_LATENCY_MICROS).ln() / LATENCY_BUCKET_GROWTH.ln()).ceil() as usize
+        };
+        self.buckets[index.min(N_LATENCY_BUCKETS - 1)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// number of calls recorded so far
+    pub fn n_calls(&self) -> u64 {
+        self.buckets.iter().map(|bucket| bucket.load(Ordering::Relaxed)).sum()
+    }
+
+    /// estimated latency at quantile in [0, 1], given as the upper bound of its bucket
+    pub fn quantile(&self, quantile: f64) -> Option<Duration> {
+        let counts: Vec<u64> =
+            self.buckets.iter().map(|bucket| bucket.load(Ordering::Relaxed)).collect();
+        let n_calls: u64 = counts.iter().sum();
+        if n_calls == 0 {
+            return None
+        }
+        let rank = ((quantile.clamp(0.0, 1.0) * n_calls as f64).ceil() as u64).max(1);
+        let mut cumulative = 0;
+        let index = counts
+            .iter()
+            .position(|count| {
+                cumulative += count;
+                cumulative >= rank
+            })
+            .unwrap_or(N_LATENCY_BUCKETS - 1);
+        let micros = MIN_LATENCY_MICROS * LATENCY_BUCKET_GROWTH.powi(index as i32);
+        Some(Duration::from_secs_f64(micros / 1e6))
+    }
+
+    /// p50, p95, and p99 of calls recorded so far, None if no calls were recorded
+    pub fn summary(&self) -> Option<LatencySummary> {
+        let ms = |quantile| self.quantile(quantile).map(|latency| latency.as_secs_f64() * 1e3);
+        Some(LatencySummary {
+            n_calls: self.n_calls(),
+            p50_ms: ms(0.50)?,
+            p95_ms: ms(0.95)?,
+            p99_ms: ms(0.99)?,
+        })
+    }
+}
+
+/// temporarily reduces request concurrency after the provider signals rate limiting
+///
+/// each rate limit event withholds one permit of the source semaphore for the cooldown window,
+/// at least one permit is always left available
+#[derive(Debug)]
+pub struct ConcurrencyThrottle {
+    semaphore: Arc<Option<Semaphore>>,
+    max_permits: u64,
+    cooldown: Duration,
+    withheld: AtomicU64,
+    n_events: AtomicU64,
+}
+
+impl ConcurrencyThrottle {
+    /// create throttle over semaphore that has max_permits permits
+    pub fn new(semaphore: Arc<Option<Semaphore>>, max_permits: u64, cooldown: Duration) -> Self {
+        ConcurrencyThrottle {
+            semaphore,
+            max_permits,
+            cooldown,
+            withheld: AtomicU64::new(0),
+            n_events: AtomicU64::new(0),
+        }
+    }
+
+    /// record a rate limit event and withhold one permit for the cooldown window
+    pub fn on_rate_limited(self: &Arc<Self>) {
+        self.n_events.fetch_add(1, Ordering::SeqCst);
+        let max_withheld = self.max_permits.saturating_sub(1);
+        let reserved = self.withheld.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |withheld| {
+            (withheld < max_withheld).then_some(withheld + 1)
+        });
+        if reserved.is_err() {
+            return
+        }
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                let throttle = self.clone();
+                handle.spawn(async move { throttle.withhold_permit().await });
+            }
+            Err(_) => {
+                self.withheld.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+    }
+
+    async fn withhold_permit(&self) {
+        if let Some(semaphore) = &*self.semaphore {
+            if let Ok(permit) = semaphore.acquire().await {
+                permit.forget();
+                tokio::time::sleep(self.cooldown).await;
+                semaphore.add_permits(1);
+            }
+        }
+        self.withheld.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// number of rate limit events recorded
+    pub fn n_events(&self) -> u64 {
+        self.n_events.load(Ordering::SeqCst)
+    }
+}
+
+/// retry policy of sources, retries rate limit and connectivity errors while the shared
+/// RetryBudget lasts and reports rate limit errors to the ConcurrencyThrottle
+///
+/// clients should not retry timeouts on their own, e.g. RetryClientBuilder::timeout_retries(0),
+/// so that every retry counts against the budget
+#[derive(Debug)]
+pub struct SourceRetryPolicy {
+    inner: HttpRateLimitRetryPolicy,
+    budget: Option<Arc<RetryBudget>>,
+    throttle: Option<Arc<ConcurrencyThrottle>>,
+}
+
+impl SourceRetryPolicy {
+    /// create new policy
+    pub fn new(
+        budget: Option<Arc<RetryBudget>>,
+        throttle: Option<Arc<ConcurrencyThrottle>>,
+    ) -> Self {
+        SourceRetryPolicy { inner: HttpRateLimitRetryPolicy, budget, throttle }
+    }
+}
+
+impl RetryPolicy<HttpClientError> for SourceRetryPolicy {
+    fn should_retry(&self, error: &HttpClientError) -> bool {
+        // providers report too many logs with codes that are also used for rate limits, but
+        // requesting the same range again cannot succeed
+        if is_log_overflow_client_error(error) {
+            return false
+        }
+        if let Some(throttle) = &self.throttle {
+            if is_rate_limit_error(error) {
+                throttle.on_rate_limited();
+            }
+        }
+        (self.inner.should_retry(error) || is_connectivity_error(error)) &&
+            self.budget.as_ref().map_or(true, |budget| budget.try_consume())
+    }
+
+    fn backoff_hint(&self, error: &HttpClientError) -> Option<Duration> {
+        self.inner.backoff_hint(error)
+    }
+}
+
+/// whether error is the provider signaling too many requests (HTTP 429 or equivalent)
+fn is_rate_limit_error(error: &HttpClientError) -> bool {
+    match error {
+        HttpClientError::ReqwestError(e) => e.status().map(|status| status.as_u16()) == Some(429),
+        HttpClientError::JsonRpcError(e) => {
+            e.code == 429 || e.code == -32005 || e.message.to_lowercase().contains("rate limit")
+        }
+        HttpClientError::SerdeJson { text, .. } => {
+            let text = text.to_lowercase();
+            text.contains("429") ||
+                text.contains("too many requests") ||
+                text.contains("rate limit")
+        }
+    }
+}
+
+/// whether error is the request timing out, failing to connect, or a server error (HTTP 5xx)
+fn is_connectivity_error(error: &HttpClientError) -> bool {
+    match error {
+        HttpClientError::ReqwestError(e) => {
+            e.is_timeout() ||
+                e.is_connect() ||
+                e.status().map_or(false, |status| status.is_server_error())
+        }
+        _ => false,
+    }
+}
+
+/// whether message is the provider refusing an eth_getLogs request for matching too many logs
+fn is_log_overflow_message(message: &str) -> bool {
+    let message = message.to_lowercase();
+    [
+        "query returned more than",
+        "response size exceeded",
+        "too many results",
+        "maximum block range",
+        "block range is too wide",
+    ]
+    .iter()
+    .any(|pattern| message.contains(pattern))
+}
+
+fn is_log_overflow_client_error(error: &HttpClientError) -> bool {
+    match error {
+        HttpClientError::ReqwestError(_) => false,
+        HttpClientError::JsonRpcError(e) => is_log_overflow_message(&e.message),
+        HttpClientError::SerdeJson { text, .. } => is_log_overflow_message(text),
+    }
+}
+
+/// whether error is the provider refusing an eth_getLogs request for matching too many logs
+pub(crate) fn is_log_overflow_error(error: &CollectError) -> bool {
+    match error {
+        CollectError::ProviderError(e) => {
+            e.as_error_response().map_or(false, |e| is_log_overflow_message(&e.message))
+        }
+        _ => false,
+    }
+}
+
+/// default error messages of providers that no longer serve the logs of old blocks
+pub const DEFAULT_UNAVAILABLE_LOG_PATTERNS: [&str; 3] =
+    ["missing trie node", "pruned", "historical state"];
+
+/// block ranges whose eth_getLogs requests failed because the provider pruned their blocks
+///
+/// a request fails as unavailable when its error message contains any of the patterns, the
+/// partition of its blocks is then reported as unavailable rather than failing or being written
+#[derive(Debug)]
+pub struct UnavailableLogRanges {
+    patterns: Vec<String>,
+    ranges: Mutex<Vec<(u64, u64)>>,
+}
+
+impl UnavailableLogRanges {
+    /// create new set of ranges, patterns are matched case-insensitively
+    pub fn new(patterns: Vec<String>) -> Self {
+        let patterns = patterns.into_iter().map(|pattern| pattern.to_lowercase()).collect();
+        UnavailableLogRanges { patterns, ranges: Mutex::new(Vec::new()) }
+    }
+
+    /// whether error is the provider reporting that the blocks of a request are unavailable
+    pub fn is_unavailable_error(&self, error: &CollectError) -> bool {
+        let message = match error {
+            CollectError::ProviderError(e) => match e.as_error_response() {
+                Some(response) => response.message.to_lowercase(),
+                None => return false,
+            },
+            _ => return false,
+        };
+        self.patterns.iter().any(|pattern| message.contains(pattern))
+    }
+
+    /// record inclusive block range as unavailable
+    pub fn record(&self, start: u64, end: u64) {
+        if let Ok(mut ranges) = self.ranges.lock() {
+            ranges.push((start, end));
+        }
+    }
+
+    /// whether any recorded range overlaps the inclusive block range start to end
+    pub fn overlaps(&self, start: u64, end: u64) -> bool {
+        self.ranges
+            .lock()
+            .map(|ranges| ranges.iter().any(|(s, e)| *s <= end && *e >= start))
+            .unwrap_or(false)
+    }
+
+    /// inclusive block ranges recorded so far, in block order
+    pub fn ranges(&self) -> Vec<(u64, u64)> {
+        let mut ranges = self.ranges.lock().map(|ranges| ranges.clone()).unwrap_or_default();
+        ranges.sort();
+        ranges
+    }
+}
+
 /// source labels (non-functional)
 #[derive(Clone, Debug, Default)]
 pub struct SourceLabels {
@@ -243,6 +625,8 @@ pub struct SourceLabels {
     pub max_requests_per_second: Option<u64>,
     /// Max retries
     pub max_retries: Option<u32>,
+    /// Max retries across all requests
+    pub max_total_retries: Option<u64>,
     /// Initial backoff
     pub initial_backoff: Option<u64>,
 }
@@ -263,9 +647,76 @@ type Result<T> = ::core::result::Result<T, CollectError>;
 // impl<P: JsonRpcClient> Fetcher<P> {
 impl Source {
     /// Returns an array (possibly empty) of logs that match the filter
+    ///
+    /// with bisect_log_ranges, block ranges that the provider reports as matching too many logs
+    /// are split in half until they succeed, single blocks that still match too many are an error.
+    /// each split re-requests the blocks of a failed request, so it counts against the retry budget
+    ///
+    /// with unavailable_log_ranges, block ranges that the provider reports as pruned are recorded
+    /// and contribute no logs, partitions overlapping them are then not written
     pub async fn get_logs(&self, filter: &Filter) -> Result<Vec<Log>> {
+        let mut pending = vec![filter.clone()];
+        let mut logs = Vec::new();
+        while let Some(filter) = pending.pop() {
+            match self.get_logs_once(&filter).await {
+                Ok(filter_logs) => logs.extend(filter_logs),
+                Err(e) if self.bisect_log_ranges && is_log_overflow_error(&e) => {
+                    if !self.retry_budget.as_ref().map_or(true, |budget| budget.try_consume()) {
+                        return Err(e)
+                    }
+                    let (start, end) = match filter_block_range(&filter) {
+                        Some((start, end)) if start < end => (start, end),
+                        _ => return Err(e),
+                    };
+                    // lower half is requested first so that logs stay in block order
+                    let middle = start + (end - start) / 2;
+                    pending.push(filter.clone().from_block(middle + 1).to_block(end));
+                    pending.push(filter.from_block(start).to_block(middle));
+                }
+                Err(e) => {
+                    let unavailable = self
+                        .unavailable_log_ranges
+                        .as_ref()
+                        .filter(|unavailable| unavailable.is_unavailable_error(&e));
+                    match (unavailable, filter_block_range(&filter)) {
+                        (Some(unavailable), Some((start, end))) => unavailable.record(start, end),
+                        _ => return Err(e),
+                    }
+                }
+            }
+        }
+        Ok(logs)
+    }
+
+    async fn get_logs_once(&self, filter: &Filter) -> Result<Vec<Log>> {
         let _permit = self.permit_request().await;
-        Self::map_err(source_provider!(self, get_logs(filter)).await)
+        let start = std::time::Instant::now();
+        let logs = self.request_logs(filter).await;
+        if let Some(rpc_latency) = &self.rpc_latency {
+            rpc_latency.record(start.elapsed());
+        }
+        logs
+    }
+
+    /// send eth_getLogs request, with extra filter params if any
+    ///
+    /// a null result is read as a response without logs, like an empty array, rather than as an
+    /// error, since providers differ in which of the two they return for ranges without logs
+    async fn request_logs(&self, filter: &Filter) -> Result<Vec<Log>> {
+        let no_extra_params = serde_json::Map::new();
+        let extra_params = self.log_filter_params.as_ref().unwrap_or(&no_extra_params);
+        let params = [log_filter_params(filter, extra_params)?];
+        let logs: ::core::result::Result<Option<Vec<Log>>, ProviderError> = match &self.provider {
+            ProviderWrapper::MockProvider(provider) => {
+                provider.request("eth_getLogs", params).await
+            }
+            ProviderWrapper::RetryClientHttp(provider) => {
+                provider.request("eth_getLogs", params).await
+            }
+            ProviderWrapper::WsClient(provider) => provider.request("eth_getLogs", params).await,
+            ProviderWrapper::IpcClient(provider) => provider.request("eth_getLogs", params).await,
+        };
+        Self::map_err(logs).map(Option::unwrap_or_default)
     }
 
     /// Replays all transactions in a block returning the requested traces for each transaction
@@ -489,6 +940,26 @@ impl Source {
 
     // extra helpers below
 
+    /// block in which contract was created, found by binary search over `eth_getCode`
+    ///
+    /// returns None if address has no code at the latest block, requires an archive node
+    pub async fn get_contract_creation_block(&self, address: H160) -> Result<Option<u64>> {
+        let latest = self.get_block_number().await?.as_u64();
+        if self.get_code(address, latest.into()).await?.is_empty() {
+            return Ok(None)
+        }
+        let (mut low, mut high) = (0, latest);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self.get_code(address, mid.into()).await?.is_empty() {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        Ok(Some(low))
+    }
+
     /// block number of transaction
     pub async fn get_transaction_block_number(&self, transaction_hash: Vec<u8>) -> Result<u32> {
         let block = self.get_transaction(H256::from_slice(&transaction_hash)).await?;
@@ -938,6 +1409,33 @@ impl Source {
 use crate::err;
 use std::collections::BTreeMap;
 
+/// inclusive block range of filter, None unless both ends are block numbers
+fn filter_block_range(filter: &Filter) -> Option<(u64, u64)> {
+    match filter.block_option {
+        FilterBlockOption::Range {
+            from_block: Some(BlockNumber::Number(start)),
+            to_block: Some(BlockNumber::Number(end)),
+        } => Some((start.as_u64(), end.as_u64())),
+        _ => None,
+    }
+}
+
+/// eth_getLogs params of filter with extra fields added, fields of the filter take precedence
+fn log_filter_params(
+    filter: &Filter,
+    extra_params: &serde_json::Map<String, serde_json::Value>,
+) -> Result<serde_json::Value> {
+    let mut params = serde_json::to_value(filter).map_err(|e| {
+        CollectError::CollectError(format!("could not serialize log filter: {}", e))
+    })?;
+    if let Some(params) = params.as_object_mut() {
+        for (key, value) in extra_params.iter() {
+            params.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+    }
+    Ok(params)
+}
+
 fn parse_geth_diff_object(
     map: ethers::utils::__serde_json::Map<String, ethers::utils::__serde_json::Value>,
 ) -> Result<DiffMode> {
@@ -948,3 +1446,285 @@ fn parse_geth_diff_object(
 
     Ok(DiffMode { pre, post })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rate_limit_error() -> HttpClientError {
+        HttpClientError::JsonRpcError(JsonRpcError {
+            code: 429,
+            message: "Too Many Requests".to_string(),
+            data: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_log_filter_params_added_to_get_logs_request() {
+        let (provider, mock) = Provider::mocked();
+        let extra_params = serde_json::json!({"limit": 1000, "fromBlock": "0x0"});
+        let source = Source {
+            log_filter_params: extra_params.as_object().cloned(),
+            ..Source::from_provider(provider)
+        };
+        mock.push(Vec::<Log>::new()).unwrap();
+
+        let filter = Filter::new().from_block(1u64).to_block(2u64);
+        assert!(source.get_logs(&filter).await.unwrap().is_empty());
+        let mut expected = serde_json::to_value(&filter).unwrap();
+        expected["limit"] = 1000.into();
+        assert_eq!(expected["fromBlock"], "0x1");
+        mock.assert_request("eth_getLogs", [expected]).unwrap();
+    }
+
+    fn mock_log_source(provider: Provider<MockProvider>, bisect_log_ranges: bool) -> Source {
+        Source { bisect_log_ranges, ..Source::from_provider(provider) }
+    }
+
+    fn rpc_error(code: i64, message: &str) -> MockResponse {
+        MockResponse::Error(JsonRpcError { code, message: message.to_string(), data: None })
+    }
+
+    fn overflow_error() -> MockResponse {
+        rpc_error(-32005, "query returned more than 10000 results")
+    }
+
+    fn block_filter(start: u64, end: u64) -> Filter {
+        Filter::new().from_block(start).to_block(end)
+    }
+
+    #[tokio::test]
+    async fn test_get_logs_reads_empty_and_null_results_as_no_logs() {
+        let (provider, mock) = Provider::mocked();
+        let source = mock_log_source(provider, true);
+        let filter = block_filter(1, 2);
+
+        // mock responses are served last in, first out
+        mock.push(serde_json::Value::Null).unwrap();
+        mock.push(Vec::<Log>::new()).unwrap();
+        assert!(source.get_logs(&filter).await.unwrap().is_empty());
+        assert!(source.get_logs(&filter).await.unwrap().is_empty());
+
+        // neither response is retried or split
+        let params = [serde_json::to_value(&filter).unwrap()];
+        mock.assert_request("eth_getLogs", &params).unwrap();
+        mock.assert_request("eth_getLogs", &params).unwrap();
+        assert!(mock.assert_request("eth_getLogs", &params).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_logs_bisects_ranges_with_too_many_results() {
+        let log = |block_number: u64| Log {
+            block_number: Some(U64::from(block_number)),
+            log_index: Some(U256::zero()),
+            ..Default::default()
+        };
+
+        // 0-3 overflows, then 0-1 and 2-3 are requested in block order
+        let (provider, mock) = Provider::mocked();
+        let source = mock_log_source(provider, true);
+        mock.push(vec![log(3)]).unwrap();
+        mock.push(serde_json::Value::Null).unwrap();
+        mock.push(vec![log(0)]).unwrap();
+        mock.push_response(overflow_error());
+        mock.push_response(overflow_error());
+        let logs = source.get_logs(&block_filter(0, 3)).await.unwrap();
+        assert_eq!(logs, vec![log(0), log(3)]);
+        for (start, end) in [(0, 3), (0, 1), (0, 0), (1, 1), (2, 3)] {
+            let params = [serde_json::to_value(block_filter(start, end)).unwrap()];
+            mock.assert_request("eth_getLogs", params).unwrap();
+        }
+
+        // single blocks cannot be split further
+        mock.push_response(overflow_error());
+        let error = source.get_logs(&block_filter(5, 5)).await.unwrap_err();
+        assert!(is_log_overflow_error(&error));
+
+        // other errors are not split
+        mock.push_response(rpc_error(-32000, "internal error"));
+        let error = source.get_logs(&block_filter(0, 3)).await.unwrap_err();
+        assert!(!is_log_overflow_error(&error));
+
+        // splits count against the retry budget, once it is used up overflow errors are returned
+        let (provider, mock) = Provider::mocked();
+        let budget = Arc::new(RetryBudget::new(1));
+        let source =
+            Source { retry_budget: Some(budget.clone()), ..mock_log_source(provider, true) };
+        mock.push_response(overflow_error());
+        mock.push(vec![log(0)]).unwrap();
+        mock.push_response(overflow_error());
+        let error = source.get_logs(&block_filter(0, 3)).await.unwrap_err();
+        assert!(is_log_overflow_error(&error));
+        assert_eq!(budget.used(), 1);
+        for (start, end) in [(0, 3), (0, 1), (2, 3)] {
+            let params = [serde_json::to_value(block_filter(start, end)).unwrap()];
+            mock.assert_request("eth_getLogs", params).unwrap();
+        }
+
+        // without bisection, overflow errors are returned as they are
+        let (provider, mock) = Provider::mocked();
+        let source = mock_log_source(provider, false);
+        mock.push_response(overflow_error());
+        let error = source.get_logs(&block_filter(0, 3)).await.unwrap_err();
+        assert!(is_log_overflow_error(&error));
+        assert!(!SourceRetryPolicy::new(None, None).should_retry(&HttpClientError::JsonRpcError(
+            JsonRpcError {
+                code: -32005,
+                message: "query returned more than 10000 results".to_string(),
+                data: None
+            }
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_get_logs_records_pruned_ranges_as_unavailable() {
+        let patterns = DEFAULT_UNAVAILABLE_LOG_PATTERNS.iter().map(|p| p.to_string()).collect();
+        let unavailable = Arc::new(UnavailableLogRanges::new(patterns));
+        let (provider, mock) = Provider::mocked();
+        let source = Source {
+            unavailable_log_ranges: Some(unavailable.clone()),
+            ..mock_log_source(provider, false)
+        };
+
+        // pruned ranges contribute no logs and are recorded rather than failing
+        mock.push_response(rpc_error(-32000, "missing trie node 3a4f (path )"));
+        mock.push_response(rpc_error(-32000, "History has been PRUNED for this block"));
+        assert!(source.get_logs(&block_filter(4, 7)).await.unwrap().is_empty());
+        assert!(source.get_logs(&block_filter(0, 3)).await.unwrap().is_empty());
+        assert_eq!(unavailable.ranges(), vec![(0, 3), (4, 7)]);
+
+        // other errors are still errors
+        mock.push_response(rpc_error(-32000, "internal error"));
+        let error = source.get_logs(&block_filter(8, 9)).await.unwrap_err();
+        assert!(!unavailable.is_unavailable_error(&error));
+        assert_eq!(unavailable.ranges().len(), 2);
+
+        // patterns are configurable
+        let custom = UnavailableLogRanges::new(vec!["Block Not Archived".to_string()]);
+        mock.push_response(rpc_error(-32000, "block not archived"));
+        let error = source.get_logs(&block_filter(8, 9)).await.unwrap_err();
+        assert!(custom.is_unavailable_error(&error));
+        assert!(!unavailable.is_unavailable_error(&error));
+    }
+
+    /// serve a single JSON-RPC request over a unix socket and return the request received
+    #[cfg(unix)]
+    fn serve_ipc_once(
+        listener: std::os::unix::net::UnixListener,
+        result: serde_json::Value,
+    ) -> std::thread::JoinHandle<serde_json::Value> {
+        use std::io::{Read, Write};
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 4096];
+            let request: serde_json::Value = loop {
+                let n = stream.read(&mut buffer).unwrap();
+                request.extend_from_slice(&buffer[..n]);
+                if let Ok(request) = serde_json::from_slice(&request) {
+                    break request
+                }
+                assert!(n > 0, "ipc connection closed before a full request was received");
+            };
+            let response =
+                serde_json::json!({"jsonrpc": "2.0", "id": request["id"], "result": result});
+            stream.write_all(&serde_json::to_vec(&response).unwrap()).unwrap();
+            request
+        })
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_get_logs_over_ipc() {
+        let path = std::env::temp_dir().join(format!("cryo_ipc_{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+        let log = Log {
+            address: H160::from_low_u64_be(1),
+            block_number: Some(U64::from(2u64)),
+            log_index: Some(U256::from(3u64)),
+            ..Default::default()
+        };
+        let server = serve_ipc_once(listener, serde_json::to_value(vec![&log]).unwrap());
+
+        let provider = Provider::connect_ipc(&path).await.unwrap();
+        let source = Source {
+            rpc_url: path.to_str().unwrap().to_string(),
+            ..Source::from_provider(provider)
+        };
+        let filter = Filter::new().from_block(2u64).to_block(2u64);
+        assert_eq!(source.get_logs(&filter).await.unwrap(), vec![log]);
+
+        let request = server.join().unwrap();
+        assert_eq!(request["method"], "eth_getLogs");
+        assert_eq!(request["params"], serde_json::json!([filter]));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_latency_quantiles_within_bucket_error() {
+        let tracker = LatencyTracker::default();
+        assert_eq!(tracker.summary(), None);
+        for millis in 1..=1000u64 {
+            tracker.record(Duration::from_millis(millis));
+        }
+        let summary = tracker.summary().unwrap();
+        assert_eq!(summary.n_calls, 1000);
+        for (estimate, actual) in
+            [(summary.p50_ms, 500.0), (summary.p95_ms, 950.0), (summary.p99_ms, 990.0)]
+        {
+            assert!(estimate >= actual && estimate <= actual * 1.1, "{} vs {}", estimate, actual);
+        }
+
+        // latencies outside of the bucket range are clamped rather than lost
+        tracker.record(Duration::ZERO);
+        tracker.record(Duration::from_secs(1_000_000));
+        assert_eq!(tracker.n_calls(), 1002);
+    }
+
+    #[tokio::test]
+    async fn test_get_logs_latency_recorded() {
+        let (provider, mock) = Provider::mocked();
+        let source = Source {
+            rpc_latency: Some(Arc::new(LatencyTracker::default())),
+            ..Source::from_provider(provider)
+        };
+        for _ in 0..3 {
+            mock.push(Vec::<Log>::new()).unwrap();
+        }
+        let filter = Filter::new().from_block(1u64).to_block(2u64);
+        for _ in 0..3 {
+            source.get_logs(&filter).await.unwrap();
+        }
+        let summary = source.rpc_latency.as_ref().unwrap().summary().unwrap();
+        assert_eq!(summary.n_calls, 3);
+        assert!(summary.p50_ms <= summary.p95_ms && summary.p95_ms <= summary.p99_ms);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limits_reduce_concurrency() {
+        let semaphore = Arc::new(Some(Semaphore::new(4)));
+        let cooldown = Duration::from_millis(200);
+        let throttle = Arc::new(ConcurrencyThrottle::new(semaphore.clone(), 4, cooldown));
+        let policy = SourceRetryPolicy::new(None, Some(throttle.clone()));
+        let available = || semaphore.as_ref().as_ref().unwrap().available_permits();
+
+        // two simulated 429s withhold two permits
+        assert!(policy.should_retry(&rate_limit_error()));
+        assert!(policy.should_retry(&rate_limit_error()));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(available(), 2);
+
+        // concurrency never drops below one permit
+        for _ in 0..10 {
+            policy.should_retry(&rate_limit_error());
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(available(), 1);
+        assert_eq!(throttle.n_events(), 12);
+
+        // permits are restored after cooldown
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(available(), 4);
+    }
+}
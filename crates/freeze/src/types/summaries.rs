@@ -5,8 +5,9 @@ use colored::Colorize;
 use thousands::Separable;
 
 use crate::{
-    chunks::chunk_ops::ValueToString, ChunkData, ChunkStats, CollectError, ColumnType, Datatype,
-    Dim, ExecutionEnv, FileOutput, MetaDatatype, MultiDatatype, Partition, Query, Source, Table,
+    chunks::chunk_ops::ValueToString, ChunkData, ChunkStats, CollectError, ColumnStats, ColumnType,
+    Datatype, Dim, ExecutionEnv, FileOutput, LatencySummary, MetaDatatype, MultiDatatype,
+    Partition, Query, SlowPartition, Source, Table,
 };
 use std::path::PathBuf;
 
@@ -22,12 +23,40 @@ const ERROR_B: u8 = 0;
 pub struct FreezeSummary {
     /// partitions completed
     pub completed: Vec<Partition>,
+    /// partitions completed without writing any rows, these are also in completed
+    pub completed_empty: Vec<Partition>,
     /// partitions skipped
     pub skipped: Vec<Partition>,
+    /// partitions not started because the time budget of the run elapsed
+    pub budget_skipped: Vec<Partition>,
+    /// partitions not started or cancelled because the run aborted after too many consecutive
+    /// failures, or after its first failure under fail-fast
+    pub abort_skipped: Vec<Partition>,
+    /// whether the run aborted after too many consecutive partition failures, or any under
+    /// fail-fast
+    pub aborted: bool,
     /// partitions errored
     pub errored: Vec<(Option<Partition>, CollectError)>,
     /// rows written
     pub n_rows: u64,
+    /// inclusive block ranges missing from existing files that the run collected
+    pub filled_gaps: Vec<(u64, u64)>,
+    /// min/max of sort columns for each written file
+    pub column_stats: HashMap<PathBuf, HashMap<String, ColumnStats>>,
+    /// retries used from the run's retry budget
+    pub retries_used: Option<u64>,
+    /// number of rate limit events that reduced concurrency
+    pub throttle_events: Option<u64>,
+    /// latency quantiles of get_logs calls
+    pub rpc_latency: Option<LatencySummary>,
+    /// number of partitions that waited for written bytes under --max-in-flight-bytes
+    pub in_flight_pauses: Option<u64>,
+    /// inclusive block ranges whose logs the provider no longer serves, these are not errors
+    pub unavailable_ranges: Vec<(u64, u64)>,
+    /// partitions not written because the provider no longer serves some of their blocks
+    pub unavailable: Vec<Partition>,
+    /// partitions that took much longer than the rolling mean of recent partitions
+    pub slow_partitions: Vec<SlowPartition>,
 }
 
 /// print all datasets
@@ -242,7 +271,7 @@ pub(crate) fn print_cryo_intro(
         (n_datatypes * query.partitions.len()).separate_with_commas()
     );
     print_bullet_indent("chunks to collect", chunk_text, 4);
-    print_bullet_indent("output format", sink.format.as_str(), 4);
+    print_bullet_indent("output format", sink.output_format().extension(), 4);
     print_bullet_indent("output dir", sink.output_dir.clone().to_string_lossy(), 4);
 
     // print report path
@@ -265,6 +294,9 @@ pub(crate) fn print_cryo_intro(
         None => print_bullet_indent("report file", "None", 4),
         Some(path) => print_bullet_indent("report file", path.to_str().unwrap_or("none"), 4),
     };
+    if let Some(dir) = &query.log_response_dir {
+        print_bullet_indent("log responses", dir.to_string_lossy(), 4);
+    }
 
     // print schemas
     print_schemas(&query.datatypes, &query.schemas)?;
@@ -479,6 +511,53 @@ pub(crate) fn print_cryo_conclusion(
         ),
         4,
     );
+    if !freeze_summary.filled_gaps.is_empty() {
+        let n_blocks: u64 =
+            freeze_summary.filled_gaps.iter().map(|(start, end)| end - start + 1).sum();
+        print_bullet_indent(
+            "gaps filled",
+            format!(
+                "{} ({} blocks)",
+                freeze_summary.filled_gaps.len().separate_with_commas(),
+                n_blocks.separate_with_commas()
+            ),
+            4,
+        );
+    }
+    if !freeze_summary.unavailable_ranges.is_empty() {
+        let n_blocks: u64 =
+            freeze_summary.unavailable_ranges.iter().map(|(start, end)| end - start + 1).sum();
+        print_bullet_indent(
+            "unavailable ranges",
+            format!(
+                "{} ({} blocks)",
+                freeze_summary.unavailable_ranges.len().separate_with_commas(),
+                n_blocks.separate_with_commas()
+            ),
+            4,
+        );
+    }
+    if !freeze_summary.unavailable.is_empty() {
+        print_bullet_indent(
+            "chunks not written (unavailable blocks)",
+            freeze_summary.unavailable.len().separate_with_commas(),
+            4,
+        );
+    }
+    if !freeze_summary.budget_skipped.is_empty() {
+        print_bullet_indent(
+            "chunks not started (time budget)",
+            freeze_summary.budget_skipped.len().separate_with_commas(),
+            4,
+        );
+    }
+    if !freeze_summary.abort_skipped.is_empty() {
+        print_bullet_indent(
+            "chunks not started or cancelled (aborted)",
+            freeze_summary.abort_skipped.len().separate_with_commas(),
+            4,
+        );
+    }
     print_bullet_indent(
         "chunks collected",
         format!(
@@ -491,6 +570,14 @@ pub(crate) fn print_cryo_conclusion(
         4,
     );
 
+    if !freeze_summary.completed_empty.is_empty() {
+        print_bullet_indent(
+            "chunks collected with no rows",
+            freeze_summary.completed_empty.len().separate_with_commas(),
+            4,
+        );
+    }
+
     print_chunks_speeds(
         freeze_summary.completed.clone(),
         &query.partitioned_by,
@@ -498,6 +585,109 @@ pub(crate) fn print_cryo_conclusion(
         query.datatypes.len() as u64,
     );
     print_bullet_indent("rows written", freeze_summary.n_rows.separate_with_commas(), 0);
+    if let Some(retries_used) = freeze_summary.retries_used {
+        print_bullet_indent("retries used", retries_used.separate_with_commas(), 0);
+    }
+    if let Some(throttle_events) = freeze_summary.throttle_events {
+        if throttle_events > 0 {
+            print_bullet_indent("throttle events", throttle_events.separate_with_commas(), 0);
+        }
+    }
+    if !freeze_summary.slow_partitions.is_empty() {
+        print_bullet_indent(
+            "slow chunks",
+            freeze_summary.slow_partitions.len().separate_with_commas(),
+            0,
+        );
+    }
+    if let Some(in_flight_pauses) = freeze_summary.in_flight_pauses {
+        if in_flight_pauses > 0 {
+            print_bullet_indent("in-flight pauses", in_flight_pauses.separate_with_commas(), 0);
+        }
+    }
+    if let Some(rpc_latency) = &freeze_summary.rpc_latency {
+        print_bullet_indent(
+            "get_logs latency",
+            format!(
+                "p50 {:.1}ms, p95 {:.1}ms, p99 {:.1}ms over {} calls",
+                rpc_latency.p50_ms,
+                rpc_latency.p95_ms,
+                rpc_latency.p99_ms,
+                rpc_latency.n_calls.separate_with_commas()
+            ),
+            0,
+        );
+    }
+    if let Some(address_cap) = &query.max_logs_per_address {
+        print_bullet_indent(
+            "logs truncated by address cap",
+            address_cap.n_truncated().separate_with_commas(),
+            0,
+        );
+    }
+    if let Some(transaction_index_range) = &query.transaction_index_range {
+        print_bullet_indent(
+            "logs kept by tx index range",
+            format!(
+                "{} ({} dropped)",
+                transaction_index_range.n_kept().separate_with_commas(),
+                transaction_index_range.n_dropped().separate_with_commas()
+            ),
+            0,
+        );
+    }
+    if query.null_logs.n_dropped() > 0 {
+        print_bullet_indent(
+            "logs dropped for null fields",
+            query.null_logs.n_dropped().separate_with_commas(),
+            0,
+        );
+    }
+    if let Some(abi_events_only) = &query.abi_events_only {
+        print_bullet_indent(
+            "logs skipped as other events",
+            abi_events_only.n_skipped().separate_with_commas(),
+            0,
+        );
+    }
+    if let Some(event_name_filter) = &query.event_name_filter {
+        print_bullet_indent(
+            "logs kept by event name",
+            event_name_filter.n_kept().separate_with_commas(),
+            0,
+        );
+    }
+    if let Some(log_sample) = &query.log_sample {
+        if let Some(kept_fraction) = log_sample.kept_fraction() {
+            print_bullet_indent(
+                "logs kept by sample",
+                format!(
+                    "{} ({:.2}%)",
+                    log_sample.n_kept().separate_with_commas(),
+                    100.0 * kept_fraction
+                ),
+                0,
+            );
+        }
+    }
+    if let Some(included_topic0s) = &query.included_topic0s {
+        print_bullet_indent(
+            "logs matched by included topic0",
+            included_topic0s.n_matched().separate_with_commas(),
+            0,
+        );
+    }
+    if let Some(excluded_topic0s) = &query.excluded_topic0s {
+        print_bullet_indent(
+            "logs dropped by excluded topic0",
+            excluded_topic0s.n_excluded().separate_with_commas(),
+            0,
+        );
+    }
+    let dropped_columns = query.decoded_column_cap.dropped();
+    if !dropped_columns.is_empty() {
+        print_bullet_indent("decoded columns dropped by cap", dropped_columns.join(", "), 0);
+    }
 }
 
 macro_rules! print_dim_speed {
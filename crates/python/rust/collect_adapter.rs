@@ -190,6 +190,7 @@ pub fn _collect(
             verbose,
             no_verbose,
             event_signature,
+            ..Default::default()
         };
         pyo3_asyncio::tokio::future_into_py(py, async move {
             match run_collect(args).await {
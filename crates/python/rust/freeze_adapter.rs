@@ -182,6 +182,7 @@ pub fn _freeze(
             verbose,
             no_verbose,
             event_signature,
+            ..Default::default()
         };
 
         pyo3_asyncio::tokio::future_into_py(py, async move {
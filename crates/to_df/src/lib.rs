@@ -63,7 +63,11 @@ pub fn to_df(attrs: TokenStream, input: TokenStream) -> TokenStream {
     let event_code = if has_event_cols {
         // Generate the tokens for the event processing code
         quote! {
-            let decoder = schema.log_decoder.clone();
+            let decoder = if schema.skip_decoding || schema.decoded_json {
+                None
+            } else {
+                schema.log_decoder.clone()
+            };
             let u256_types: Vec<_> = schema.u256_types.clone().into_iter().collect();
             if let Some(decoder) = decoder {
 
@@ -104,7 +108,7 @@ pub fn to_df(attrs: TokenStream, input: TokenStream) -> TokenStream {
                 let chunk_len = self.n_rows;
                 if self.event_cols.is_empty() {
                     for param in decoder.event.inputs.iter() {
-                        let name = "event__".to_string() + param.name.as_str();
+                        let name = decoded_column_name(&param.name);
                         let name = name.as_str();
                         match param.kind {
                             ParamType::Address => {
@@ -162,8 +166,19 @@ pub fn to_df(attrs: TokenStream, input: TokenStream) -> TokenStream {
                     }
                 }
 
-                let drop_names = vec!["topic1".to_string(), "topic2".to_string(), "topic3".to_string(), "data".to_string()];
-                cols.retain(|c| !drop_names.contains(&c.name().to_string()));
+                if !schema.keep_raw_log_columns {
+                    let drop_names = vec!["topic1".to_string(), "topic2".to_string(), "topic3".to_string(), "data".to_string()];
+                    cols.retain(|c| !drop_names.contains(&c.name().to_string()));
+                }
+
+                // base columns keep their names, a decoded column must never shadow one
+                let mut names = std::collections::HashSet::new();
+                for col in cols.iter() {
+                    if !names.insert(col.name().to_string()) {
+                        let message = format!("decoded column {} collides with another column", col.name());
+                        return Err(err(message.as_str()))
+                    }
+                }
             }
         }
     } else {
@@ -243,6 +258,7 @@ pub fn to_df(attrs: TokenStream, input: TokenStream) -> TokenStream {
                 #event_code
 
                 let df = DataFrame::new(cols).map_err(CollectError::PolarsError).sort_by_schema(schema)?;
+                let df = <#name as Dataset>::post_process(df, schema)?;
                 let mut output = std::collections::HashMap::new();
                 output.insert(datatype, df);
                 Ok(output)